@@ -5,10 +5,12 @@ mod clusters;
 mod image;
 mod ring_buffer;
 pub mod rle;
+mod shared_bytes;
 pub use self::{
     bitvec::BitVec,
     clusters::{ArrayCluster, Clusters},
     image::{Image, ImageRuns},
     ring_buffer::RingBuffer,
     rle::Run,
+    shared_bytes::SharedBytes,
 };
@@ -0,0 +1,65 @@
+use std::{
+    fs::File,
+    io,
+    ops::{Deref, Range},
+    path::Path,
+    sync::Arc,
+};
+
+use memmap2::Mmap;
+
+/// A cheaply clonable view into a shared byte buffer, either in memory or a
+/// memory mapped file. Lets sliced files index their layers without copying
+/// each one out of the file.
+#[derive(Clone)]
+pub struct SharedBytes {
+    buffer: Arc<dyn AsRef<[u8]> + Send + Sync>,
+    range: Range<usize>,
+}
+
+impl SharedBytes {
+    pub fn new(buffer: impl AsRef<[u8]> + Send + Sync + 'static) -> Self {
+        let range = 0..buffer.as_ref().len();
+        Self {
+            buffer: Arc::new(buffer),
+            range,
+        }
+    }
+
+    /// Memory maps a file, so its pages are only read from disk once they are
+    /// accessed. The file should not be modified while it is mapped.
+    pub fn map(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        Ok(Self::new(mmap))
+    }
+
+    /// Gets a view of a range of this view, sharing the same buffer.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.range.len());
+        Self {
+            buffer: self.buffer.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+}
+
+impl Deref for SharedBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &(*self.buffer).as_ref()[self.range.clone()]
+    }
+}
+
+impl Default for SharedBytes {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl From<Vec<u8>> for SharedBytes {
+    fn from(value: Vec<u8>) -> Self {
+        Self::new(value)
+    }
+}
@@ -2,10 +2,12 @@
 
 use std::{
     array,
+    error::Error,
+    fmt::{self, Display},
     ops::Index,
     sync::{
         Arc,
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
 };
 
@@ -25,6 +27,16 @@ pub struct CombinedProgress<const N: usize> {
     inner: [Progress; N],
 }
 
+/// Lets an operation running on another thread be stopped early.
+///
+/// Like [`Progress`], cloning will maintain references to the same value.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+/// Error returned by operations stopped with a [`CancellationToken`].
+#[derive(Debug)]
+pub struct Cancelled;
+
 impl Progress {
     pub fn new() -> Self {
         Self(Arc::new(ProgressInner {
@@ -80,6 +92,38 @@ impl Progress {
     }
 }
 
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns [`Cancelled`] if the operation has been cancelled, so it can
+    /// be bailed out of with `?`.
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            return Err(Cancelled);
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Operation cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
 impl<const N: usize> CombinedProgress<N> {
     pub fn new() -> Self {
         Self {
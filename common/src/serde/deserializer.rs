@@ -5,6 +5,8 @@ use std::{
     slice,
 };
 
+use crate::container::SharedBytes;
+
 #[rustfmt::skip]
 pub trait Deserializer {
     fn pos(&mut self) -> usize;
@@ -43,6 +45,7 @@ pub trait Deserializer {
 
 pub struct SliceDeserializer<'a> {
     buffer: &'a [u8],
+    shared: Option<&'a SharedBytes>,
     offset: usize,
 }
 
@@ -54,6 +57,17 @@ impl<'a> SliceDeserializer<'a> {
     pub fn new(data: &'a [u8]) -> Self {
         Self {
             buffer: data,
+            shared: None,
+            offset: 0,
+        }
+    }
+
+    /// Creates a deserializer that can hand out views into the buffer with
+    /// [`SliceDeserializer::read_shared`] instead of copying.
+    pub fn new_shared(data: &'a SharedBytes) -> Self {
+        Self {
+            buffer: data,
+            shared: Some(data),
             offset: 0,
         }
     }
@@ -63,6 +77,19 @@ impl<'a> SliceDeserializer<'a> {
         self.offset += length;
         value
     }
+
+    /// Reads bytes that can outlive the deserializer. These are only copied
+    /// if it wasn't created with [`SliceDeserializer::new_shared`].
+    pub fn read_shared(&mut self, length: usize) -> SharedBytes {
+        match self.shared {
+            Some(shared) => {
+                let value = shared.slice(self.offset..self.offset + length);
+                self.offset += length;
+                value
+            }
+            None => SharedBytes::from(self.read_slice(length).to_vec()),
+        }
+    }
 }
 
 impl<T: Read> ReaderDeserializer<T> {
@@ -116,10 +143,13 @@ impl<'a> Deserializer for SliceDeserializer<'a> {
         result
     }
 
+    /// Like reading past the end of a [`ReaderDeserializer`], this returns
+    /// fewer bytes than asked for instead of panicking.
     fn read_bytes(&mut self, length: usize) -> Cow<'_, [u8]> {
-        let value = &self.buffer[self.offset..self.offset + length];
-        self.offset += length;
-        Cow::Borrowed(value)
+        let start = self.offset.min(self.buffer.len());
+        let end = self.offset.saturating_add(length).min(self.buffer.len());
+        self.offset = self.offset.saturating_add(length);
+        Cow::Borrowed(&self.buffer[start..end])
     }
 
     fn is_eof(&mut self) -> bool {
@@ -1,8 +1,12 @@
 use std::{
-    io::{Seek, Write},
+    io::{self, Seek, SeekFrom, Write},
     iter::repeat_n,
 };
 
+/// Largest amount of data that can be written with
+/// [`WriterSerializer::execute_at`].
+const MAX_PATCH_SIZE: usize = 4096;
+
 #[rustfmt::skip]
 pub trait Serializer {
     fn pos(&mut self) -> usize;
@@ -11,6 +15,11 @@ pub trait Serializer {
     fn execute_at(&mut self, offset: usize, f: impl FnOnce(&mut SizedSerializer));
     fn view_mut(&mut self, offset: usize, size: usize) -> &mut [u8];
 
+    /// Overwrites previously written (or reserved) data at `offset`.
+    fn write_at(&mut self, offset: usize, data: &[u8]) {
+        self.execute_at(offset, |ser| ser.write_bytes(data));
+    }
+
     fn write_bool(&mut self, data: bool) { self.write_u8(data as u8); }
     fn write_u8(&mut self, data: u8) { self.write_bytes(&[data]); }
     fn write_u16_be(&mut self, data: u16) { self.write_bytes(&data.to_be_bytes()); }
@@ -34,8 +43,18 @@ pub struct DynamicSerializer {
     buffer: Vec<u8>,
 }
 
+/// Serializer that writes straight to a stream.
+///
+/// Data can be patched with [`Serializer::execute_at`] (up to a few KiB at a
+/// time) or [`Serializer::write_at`], but [`Serializer::view_mut`] is not
+/// supported.
+///
+/// Once writing to the stream fails, nothing more is written and the error is
+/// returned from [`WriterSerializer::finish`].
 pub struct WriterSerializer<T: Write + Seek> {
     stream: T,
+    position: usize,
+    error: Option<io::Error>,
 }
 
 impl<'a> SizedSerializer<'a> {
@@ -59,8 +78,49 @@ impl DynamicSerializer {
 }
 
 impl<T: Write + Seek> WriterSerializer<T> {
-    pub fn new(stream: T) -> Self {
-        Self { stream }
+    pub fn new(mut stream: T) -> Self {
+        let (position, error) = match stream.stream_position() {
+            Ok(position) => (position as usize, None),
+            Err(err) => (0, Some(err)),
+        };
+
+        Self {
+            stream,
+            position,
+            error,
+        }
+    }
+
+    /// Flushes the stream and gives it back, or returns the first error hit
+    /// while writing.
+    pub fn finish(mut self) -> io::Result<T> {
+        self.try_io(|stream| stream.flush());
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(self.stream),
+        }
+    }
+
+    /// Runs `f` on the stream unless an earlier operation has failed, keeping
+    /// its error if it does.
+    fn try_io(&mut self, f: impl FnOnce(&mut T) -> io::Result<()>) {
+        if self.error.is_none()
+            && let Err(err) = f(&mut self.stream)
+        {
+            self.error = Some(err);
+        }
+    }
+
+    /// Writes `data` at `offset`, returning to the end of the written data
+    /// afterwards.
+    fn patch(&mut self, offset: usize, data: &[u8]) {
+        let end = self.position as u64;
+        self.try_io(|stream| {
+            stream.seek(SeekFrom::Start(offset as u64))?;
+            stream.write_all(data)?;
+            stream.seek(SeekFrom::Start(end))?;
+            Ok(())
+        });
     }
 }
 
@@ -117,19 +177,34 @@ impl Serializer for DynamicSerializer {
 
 impl<T: Write + Seek> Serializer for WriterSerializer<T> {
     fn pos(&mut self) -> usize {
-        self.stream.stream_position().unwrap() as usize
+        self.position
     }
 
     fn write_bytes(&mut self, data: &[u8]) {
-        self.stream.write_all(data).unwrap();
+        self.try_io(|stream| stream.write_all(data));
+        self.position += data.len();
     }
 
-    fn reserve(&mut self, _length: usize) -> usize {
-        unimplemented!()
+    fn reserve(&mut self, length: usize) -> usize {
+        let start = self.position;
+        self.write_bytes(&vec![0; length]);
+        start
     }
 
-    fn execute_at(&mut self, _offset: usize, _f: impl FnOnce(&mut SizedSerializer)) {
-        unimplemented!()
+    fn execute_at(&mut self, offset: usize, f: impl FnOnce(&mut SizedSerializer)) {
+        // Patches are built in a scratch buffer, and only the bytes actually
+        // written are copied over to the stream.
+        let mut buffer = vec![0; (self.position - offset).min(MAX_PATCH_SIZE)];
+        let mut ser = SizedSerializer::new(&mut buffer);
+        f(&mut ser);
+
+        let length = ser.offset;
+        self.patch(offset, &buffer[..length]);
+    }
+
+    fn write_at(&mut self, offset: usize, data: &[u8]) {
+        assert!(offset + data.len() <= self.position);
+        self.patch(offset, data);
     }
 
     fn view_mut(&mut self, _offset: usize, _size: usize) -> &mut [u8] {
@@ -142,3 +217,34 @@ impl Default for DynamicSerializer {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::{Serializer, WriterSerializer};
+
+    #[test]
+    fn patch_stream() {
+        let mut buffer = [0; 8];
+        let mut ser = WriterSerializer::new(Cursor::new(&mut buffer[..]));
+        let header = ser.reserve(4);
+        ser.write_u32_le(2);
+        ser.execute_at(header, |ser| ser.write_u32_le(1));
+        ser.finish().unwrap();
+
+        assert_eq!(buffer, [1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn write_error() {
+        let mut buffer = [0; 4];
+        let mut ser = WriterSerializer::new(Cursor::new(&mut buffer[..]));
+        ser.write_u32_le(1);
+        ser.write_u32_le(2);
+        ser.write_at(0, &[3]);
+
+        assert!(ser.finish().is_err());
+        assert_eq!(buffer, [1, 0, 0, 0]);
+    }
+}
@@ -43,7 +43,7 @@ impl<const SIZE: usize> SizedString<SIZE> {
 
 impl<const SIZE: usize> Display for SizedString<SIZE> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let null = self.data.iter().position(|&x| x == 0).unwrap_or(32);
+        let null = self.data.iter().position(|&x| x == 0).unwrap_or(SIZE);
         f.write_str(&String::from_utf8_lossy(&self.data[..null]))
     }
 }
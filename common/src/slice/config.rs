@@ -1,18 +1,22 @@
 use std::borrow::Cow;
 
-use anyhow::Result;
+use anyhow::{Result, bail};
 use nalgebra::{Vector2, Vector3};
 
 use crate::{
     misc::lerp,
     serde::{Deserializer, SerdeExt, Serializer},
-    slice::format::SliceMode,
+    slice::{UniformityMask, format::SliceMode},
     units::{
         CentimetersPerSecond, CubicMilimeters, Milimeter, Milimeters, Minutes, Seconds,
         SquareMilimeters,
     },
 };
 
+/// Time the platform is held at the top for a pause, in formats that can't
+/// pause the print.
+pub const DEFAULT_PAUSE_HOLD: Seconds = Seconds::new(300.0);
+
 /// Configuration for slicing a model.
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
@@ -20,15 +24,38 @@ pub struct SliceConfig {
     pub mode: SliceMode,
     pub supersample: u8,
     pub exposure_remap: ExposureRemap,
+    /// Evens out the exposure across the display, if it's brighter in some
+    /// places than others. Only applied when raster slicing.
+    pub uniformity: Option<UniformityMask>,
 
     pub platform_resolution: Vector2<u32>,
     pub platform_size: Vector3<Milimeters>,
     pub slice_height: Milimeters,
+    pub compensation: Compensation,
 
     pub exposure_config: ExposureConfig,
     pub first_exposure_config: ExposureConfig,
     pub first_layers: u32,
     pub transition_layers: u32,
+    pub exposure_overrides: Vec<ExposureOverride>,
+    /// How long formats without a pause command (ctb and cbddlp) hold the
+    /// platform at the top for a pause. The print carries on by itself
+    /// afterwards.
+    pub pause_hold: Seconds,
+}
+
+/// Corrections to the size of the sliced layers, to counteract the resin
+/// shrinking or over curing. Only applied when raster slicing.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Compensation {
+    /// Scale along the x and y axes, around the center of the platform.
+    pub scale: Vector2<f32>,
+    /// Distance to move the contours of each layer outward. Negative values
+    /// move them inward.
+    pub offset: Milimeters,
+    /// Extra distance to grow holes by, on top of the contour offset.
+    pub hole_offset: Milimeters,
 }
 
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
@@ -39,18 +66,61 @@ pub struct ExposureRemap {
 }
 
 /// Layer exposure settings.
+///
+/// Lifts and retracts are done in two stages, the second lift stage starting
+/// where the first one ends, and likewise for retracts. Setting a second
+/// stage distance to zero disables it.
 #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
 pub struct ExposureConfig {
     pub exposure_time: Seconds,
+    /// Time to wait after retracting, before exposing the next layer.
     pub exposure_delay: Seconds,
     pub pwm: u8,
 
+    /// Time to wait after exposing, before lifting the platform.
+    pub rest_before_lift: Seconds,
+    /// Time to wait after lifting, before retracting the platform.
+    pub rest_after_lift: Seconds,
+
     pub lift_distance: Milimeters,
     pub lift_speed: CentimetersPerSecond,
+    pub second_lift_distance: Milimeters,
+    pub second_lift_speed: CentimetersPerSecond,
 
     pub retract_distance: Milimeters,
     pub retract_speed: CentimetersPerSecond,
+    pub second_retract_distance: Milimeters,
+    pub second_retract_speed: CentimetersPerSecond,
+}
+
+/// Replaces some exposure settings of the layers with their top within
+/// `start..=end`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ExposureOverride {
+    pub start: Milimeters,
+    pub end: Milimeters,
+    /// The fields of `exposure` used over the range, the rest are left as is.
+    pub fields: Vec<ExposureField>,
+    pub exposure: ExposureConfig,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum ExposureField {
+    ExposureTime,
+    ExposureDelay,
+    Pwm,
+    RestBeforeLift,
+    RestAfterLift,
+    LiftDistance,
+    LiftSpeed,
+    SecondLiftDistance,
+    SecondLiftSpeed,
+    RetractDistance,
+    RetractSpeed,
+    SecondRetractDistance,
+    SecondRetractSpeed,
 }
 
 impl SliceConfig {
@@ -65,6 +135,16 @@ impl SliceConfig {
         }
     }
 
+    /// Exposure settings of a layer with its top at `height`, including any
+    /// exposure overrides covering it. Later overrides take priority.
+    pub fn layer_exposure(&self, layer: u32, height: Milimeters) -> Cow<'_, ExposureConfig> {
+        let mut exposure = self.exposure_config(layer);
+        for range in (self.exposure_overrides.iter()).filter(|x| x.contains(height)) {
+            range.apply(exposure.to_mut());
+        }
+        exposure
+    }
+
     pub fn default_height(&self, layer: u32) -> Milimeters {
         self.slice_height * (layer + 1) as f32
     }
@@ -84,6 +164,9 @@ impl SliceConfig {
             .component_div(&self.platform_size.xy().map(|x| x.get::<Milimeter>()))
     }
 
+    /// Estimates the print time of a file with `layers` layers, from just the
+    /// config. Once the layers are sliced, [`super::print_time`] also counts
+    /// the settings that differ per layer.
     pub fn print_time(&self, layers: u32) -> Seconds {
         let exp = &self.exposure_config;
         let fexp = &self.first_exposure_config;
@@ -96,14 +179,8 @@ impl SliceConfig {
             .saturating_sub(first_layers)
             .saturating_sub(transition_layers);
 
-        let layer_time = exp.exposure_time
-            + exp.lift_distance / exp.lift_speed
-            + exp.retract_distance / exp.retract_speed
-            + exp.exposure_delay;
-        let bottom_layer_time = fexp.exposure_time
-            + fexp.lift_distance / fexp.lift_speed
-            + fexp.retract_distance / fexp.retract_speed
-            + fexp.exposure_delay;
+        let layer_time = exp.layer_time();
+        let bottom_layer_time = fexp.layer_time();
 
         regular_layers as f32 * layer_time
             + first_layers as f32 * bottom_layer_time
@@ -173,10 +250,22 @@ impl SliceConfig {
         self.platform_resolution.serialize(ser);
         self.platform_size.map(|x| x.raw()).serialize(ser);
         ser.write_f32_be(self.slice_height.raw());
+        self.compensation.serialize(ser);
         self.exposure_config.serialize(ser);
         self.first_exposure_config.serialize(ser);
         ser.write_u32_be(self.first_layers);
         ser.write_u32_be(self.transition_layers);
+
+        ser.write_u32_be(self.exposure_overrides.len() as u32);
+        for range in &self.exposure_overrides {
+            range.serialize(ser);
+        }
+
+        ser.write_bool(self.uniformity.is_some());
+        if let Some(uniformity) = &self.uniformity {
+            uniformity.serialize(ser);
+        }
+        ser.write_f32_be(self.pause_hold.raw());
     }
 
     pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Result<Self> {
@@ -195,14 +284,149 @@ impl SliceConfig {
             platform_resolution: Vector2::deserialize(des),
             platform_size: Vector3::deserialize(des).map(Milimeters::new),
             slice_height: Milimeters::new(des.read_f32_be()),
+            compensation: if version < 20 {
+                Compensation::default()
+            } else {
+                Compensation::deserialize(des)
+            },
             exposure_config: ExposureConfig::deserialize(des, version),
             first_exposure_config: ExposureConfig::deserialize(des, version),
             first_layers: des.read_u32_be(),
             transition_layers: des.read_u32_be(),
+            exposure_overrides: if version < 22 {
+                Vec::new()
+            } else {
+                (0..des.read_u32_be())
+                    .map(|_| ExposureOverride::deserialize(des, version))
+                    .collect::<Result<_>>()?
+            },
+            uniformity: if version < 24 || !des.read_bool() {
+                None
+            } else {
+                Some(UniformityMask::deserialize(des)?)
+            },
+            pause_hold: if version < 25 {
+                DEFAULT_PAUSE_HOLD
+            } else {
+                Seconds::new(des.read_f32_be())
+            },
+        })
+    }
+}
+
+impl ExposureOverride {
+    pub fn contains(&self, height: Milimeters) -> bool {
+        (self.start.raw()..=self.end.raw()).contains(&height.raw())
+    }
+
+    pub fn apply(&self, exposure: &mut ExposureConfig) {
+        for field in &self.fields {
+            field.copy(&self.exposure, exposure);
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        ser.write_f32_be(self.start.raw());
+        ser.write_f32_be(self.end.raw());
+        ser.write_u8(self.fields.len() as u8);
+        for field in &self.fields {
+            ser.write_u8(*field as u8);
+        }
+        self.exposure.serialize(ser);
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Result<Self> {
+        Ok(Self {
+            start: Milimeters::new(des.read_f32_be()),
+            end: Milimeters::new(des.read_f32_be()),
+            fields: (0..des.read_u8())
+                .map(|_| ExposureField::deserialize(des))
+                .collect::<Result<_>>()?,
+            exposure: ExposureConfig::deserialize(des, version),
         })
     }
 }
 
+impl ExposureField {
+    pub const ALL: [Self; 13] = [
+        Self::ExposureTime,
+        Self::ExposureDelay,
+        Self::Pwm,
+        Self::RestBeforeLift,
+        Self::RestAfterLift,
+        Self::LiftDistance,
+        Self::LiftSpeed,
+        Self::SecondLiftDistance,
+        Self::SecondLiftSpeed,
+        Self::RetractDistance,
+        Self::RetractSpeed,
+        Self::SecondRetractDistance,
+        Self::SecondRetractSpeed,
+    ];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::ExposureTime => "Exposure Time",
+            Self::ExposureDelay => "Exposure Delay",
+            Self::Pwm => "PWM",
+            Self::RestBeforeLift => "Rest Before Lift",
+            Self::RestAfterLift => "Rest After Lift",
+            Self::LiftDistance => "Lift Distance",
+            Self::LiftSpeed => "Lift Speed",
+            Self::SecondLiftDistance => "Second Lift Distance",
+            Self::SecondLiftSpeed => "Second Lift Speed",
+            Self::RetractDistance => "Retract Distance",
+            Self::RetractSpeed => "Retract Speed",
+            Self::SecondRetractDistance => "Second Retract Distance",
+            Self::SecondRetractSpeed => "Second Retract Speed",
+        }
+    }
+
+    /// Copies this field from one exposure config to another.
+    pub fn copy(&self, from: &ExposureConfig, to: &mut ExposureConfig) {
+        match self {
+            Self::ExposureTime => to.exposure_time = from.exposure_time,
+            Self::ExposureDelay => to.exposure_delay = from.exposure_delay,
+            Self::Pwm => to.pwm = from.pwm,
+            Self::RestBeforeLift => to.rest_before_lift = from.rest_before_lift,
+            Self::RestAfterLift => to.rest_after_lift = from.rest_after_lift,
+            Self::LiftDistance => to.lift_distance = from.lift_distance,
+            Self::LiftSpeed => to.lift_speed = from.lift_speed,
+            Self::SecondLiftDistance => to.second_lift_distance = from.second_lift_distance,
+            Self::SecondLiftSpeed => to.second_lift_speed = from.second_lift_speed,
+            Self::RetractDistance => to.retract_distance = from.retract_distance,
+            Self::RetractSpeed => to.retract_speed = from.retract_speed,
+            Self::SecondRetractDistance => {
+                to.second_retract_distance = from.second_retract_distance
+            }
+            Self::SecondRetractSpeed => to.second_retract_speed = from.second_retract_speed,
+        }
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Result<Self> {
+        match Self::ALL.get(des.read_u8() as usize) {
+            Some(field) => Ok(*field),
+            None => bail!("Invalid exposure field"),
+        }
+    }
+}
+
+impl Compensation {
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        self.scale.serialize(ser);
+        ser.write_f32_be(self.offset.raw());
+        ser.write_f32_be(self.hole_offset.raw());
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Self {
+        Self {
+            scale: Vector2::deserialize(des),
+            offset: Milimeters::new(des.read_f32_be()),
+            hole_offset: Milimeters::new(des.read_f32_be()),
+        }
+    }
+}
+
 impl ExposureConfig {
     pub fn serialize<T: Serializer>(&self, ser: &mut T) {
         ser.write_f32_be(self.exposure_time.raw());
@@ -212,10 +436,17 @@ impl ExposureConfig {
         ser.write_f32_be(self.lift_speed.raw());
         ser.write_f32_be(self.retract_distance.raw());
         ser.write_f32_be(self.retract_speed.raw());
+
+        ser.write_f32_be(self.rest_before_lift.raw());
+        ser.write_f32_be(self.rest_after_lift.raw());
+        ser.write_f32_be(self.second_lift_distance.raw());
+        ser.write_f32_be(self.second_lift_speed.raw());
+        ser.write_f32_be(self.second_retract_distance.raw());
+        ser.write_f32_be(self.second_retract_speed.raw());
     }
 
     pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
-        Self {
+        let mut out = Self {
             exposure_time: Seconds::new(des.read_f32_be()),
             exposure_delay: Seconds::new(if version < 7 { 0.0 } else { des.read_f32_be() }),
             pwm: if version < 3 { 255 } else { des.read_u8() },
@@ -225,7 +456,40 @@ impl ExposureConfig {
 
             retract_distance: Milimeters::new(des.read_f32_be()),
             retract_speed: CentimetersPerSecond::new(des.read_f32_be()),
+            ..Default::default()
+        };
+
+        if version >= 21 {
+            out.rest_before_lift = Seconds::new(des.read_f32_be());
+            out.rest_after_lift = Seconds::new(des.read_f32_be());
+            out.second_lift_distance = Milimeters::new(des.read_f32_be());
+            out.second_lift_speed = CentimetersPerSecond::new(des.read_f32_be());
+            out.second_retract_distance = Milimeters::new(des.read_f32_be());
+            out.second_retract_speed = CentimetersPerSecond::new(des.read_f32_be());
         }
+
+        out
+    }
+
+    /// Time taken to print one layer, including the exposure, all the
+    /// platform moves, and the rests between them.
+    pub fn layer_time(&self) -> Seconds {
+        let motion = |distance: Milimeters, speed: CentimetersPerSecond| {
+            if distance.raw() == 0.0 {
+                Seconds::new(0.0)
+            } else {
+                distance / speed
+            }
+        };
+
+        self.exposure_time
+            + self.rest_before_lift
+            + motion(self.lift_distance, self.lift_speed)
+            + motion(self.second_lift_distance, self.second_lift_speed)
+            + self.rest_after_lift
+            + motion(self.retract_distance, self.retract_speed)
+            + motion(self.second_retract_distance, self.second_retract_speed)
+            + self.exposure_delay
     }
 
     pub fn lerp(&self, other: &Self, t: f32) -> Self {
@@ -237,6 +501,17 @@ impl ExposureConfig {
             lift_speed: lerp(self.lift_speed, other.lift_speed, t),
             retract_distance: lerp(self.retract_distance, other.retract_distance, t),
             retract_speed: lerp(self.retract_speed, other.retract_speed, t),
+
+            rest_before_lift: lerp(self.rest_before_lift, other.rest_before_lift, t),
+            rest_after_lift: lerp(self.rest_after_lift, other.rest_after_lift, t),
+            second_lift_distance: lerp(self.second_lift_distance, other.second_lift_distance, t),
+            second_lift_speed: lerp(self.second_lift_speed, other.second_lift_speed, t),
+            second_retract_distance: lerp(
+                self.second_retract_distance,
+                other.second_retract_distance,
+                t,
+            ),
+            second_retract_speed: lerp(self.second_retract_speed, other.second_retract_speed, t),
         }
     }
 }
@@ -247,10 +522,12 @@ impl Default for SliceConfig {
             mode: SliceMode::Raster,
             supersample: 0,
             exposure_remap: Default::default(),
+            uniformity: None,
 
             platform_resolution: Vector2::new(11_520, 5_120),
             platform_size: Vector3::new(218.88, 122.904, 260.0).map(Milimeters::new),
             slice_height: Milimeters::new(0.05),
+            compensation: Compensation::default(),
             exposure_config: ExposureConfig {
                 exposure_time: Seconds::new(3.0),
                 ..Default::default()
@@ -261,6 +538,29 @@ impl Default for SliceConfig {
             },
             first_layers: 3,
             transition_layers: 10,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
+        }
+    }
+}
+
+impl Default for ExposureOverride {
+    fn default() -> Self {
+        Self {
+            start: Milimeters::new(0.0),
+            end: Milimeters::new(10.0),
+            fields: vec![ExposureField::ExposureTime],
+            exposure: ExposureConfig::default(),
+        }
+    }
+}
+
+impl Default for Compensation {
+    fn default() -> Self {
+        Self {
+            scale: Vector2::repeat(1.0),
+            offset: Milimeters::new(0.0),
+            hole_offset: Milimeters::new(0.0),
         }
     }
 }
@@ -282,11 +582,18 @@ impl Default for ExposureConfig {
             exposure_time: Seconds::new(3.0),
             pwm: 255,
 
+            rest_before_lift: Seconds::new(0.0),
+            rest_after_lift: Seconds::new(0.0),
+
             lift_distance: Milimeters::new(5.0),
             lift_speed: (Milimeters::new(330.0) / Minutes::new(1.0)).convert(),
+            second_lift_distance: Milimeters::new(0.0),
+            second_lift_speed: (Milimeters::new(330.0) / Minutes::new(1.0)).convert(),
 
             retract_distance: Milimeters::new(5.0),
             retract_speed: (Milimeters::new(330.0) / Minutes::new(1.0)).convert(),
+            second_retract_distance: Milimeters::new(0.0),
+            second_retract_speed: (Milimeters::new(330.0) / Minutes::new(1.0)).convert(),
         }
     }
 }
@@ -13,7 +13,11 @@ pub enum SliceMode {
 pub enum RasterFormat {
     Goo,
     Ctb,
+    CtbLegacy,
+    Cbddlp,
     NanoDLP,
+    Sl1,
+    Photon,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -54,21 +58,41 @@ impl SliceMode {
 }
 
 impl RasterFormat {
-    pub const ALL: [Self; 3] = [Self::Goo, Self::Ctb, Self::NanoDLP];
+    pub const ALL: [Self; 7] = [
+        Self::Goo,
+        Self::Ctb,
+        Self::CtbLegacy,
+        Self::Cbddlp,
+        Self::NanoDLP,
+        Self::Sl1,
+        Self::Photon,
+    ];
 
     pub fn name(&self) -> &str {
         match self {
             Self::Goo => "Elegoo",
             Self::Ctb => "Chitu Encrypted",
+            Self::CtbLegacy => "Chitu v4",
+            Self::Cbddlp => "Chitu CBDDLP",
             Self::NanoDLP => "NanoDLP",
+            Self::Sl1 => "Prusa SL1",
+            Self::Photon => "Anycubic Photon",
         }
     }
 
     pub fn extension(&self) -> &str {
+        self.extensions()[0]
+    }
+
+    /// All extensions used by the format, starting with the default one.
+    pub fn extensions(&self) -> &[&str] {
         match self {
-            Self::Goo => "goo",
-            Self::Ctb => "ctb",
-            Self::NanoDLP => "nanodlp",
+            Self::Goo => &["goo"],
+            Self::Ctb | Self::CtbLegacy => &["ctb"],
+            Self::Cbddlp => &["cbddlp", "photon"],
+            Self::NanoDLP => &["nanodlp"],
+            Self::Sl1 => &["sl1", "sl1s"],
+            Self::Photon => &["pwmx", "pwma", "pm3"],
         }
     }
 
@@ -76,7 +100,10 @@ impl RasterFormat {
         Some(match extension.to_lowercase().as_str() {
             "goo" => Self::Goo,
             "ctb" => Self::Ctb,
+            "cbddlp" | "photon" => Self::Cbddlp,
             "nanodlp" => Self::NanoDLP,
+            "sl1" | "sl1s" => Self::Sl1,
+            "pwmx" | "pwma" | "pm3" => Self::Photon,
             _ => return None,
         })
     }
@@ -92,18 +119,26 @@ impl VectorFormat {
     }
 
     pub fn extension(&self) -> &str {
+        self.extensions()[0]
+    }
+
+    pub fn extensions(&self) -> &[&str] {
         match self {
-            VectorFormat::Svg => "svg",
+            VectorFormat::Svg => &["svg"],
         }
     }
 }
 
 impl Format {
     pub const VECTOR: [Format; 1] = [Format::Vector(VectorFormat::Svg)];
-    pub const RASTER: [Format; 3] = [
+    pub const RASTER: [Format; 7] = [
         Format::Raster(RasterFormat::Ctb),
+        Format::Raster(RasterFormat::CtbLegacy),
+        Format::Raster(RasterFormat::Cbddlp),
         Format::Raster(RasterFormat::Goo),
         Format::Raster(RasterFormat::NanoDLP),
+        Format::Raster(RasterFormat::Photon),
+        Format::Raster(RasterFormat::Sl1),
     ];
 
     pub fn extension(&self) -> &str {
@@ -113,6 +148,13 @@ impl Format {
         }
     }
 
+    pub fn extensions(&self) -> &[&str] {
+        match self {
+            Format::Raster(format) => format.extensions(),
+            Format::Vector(format) => format.extensions(),
+        }
+    }
+
     pub fn name(&self) -> String {
         match self {
             Format::Raster(format) => format!("{} (.{})", format.name(), format.extension()),
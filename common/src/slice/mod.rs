@@ -1,22 +1,28 @@
 //! Simplified configuration for slicing a model.
 
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 
+use anyhow::Result;
 use image::RgbaImage;
 use nalgebra::{Vector2, Vector3};
 
 mod config;
 pub mod format;
 mod layer_iter;
-pub use config::{ExposureConfig, ExposureRemap, SliceConfig};
+mod uniformity;
+pub use config::{
+    Compensation, DEFAULT_PAUSE_HOLD, ExposureConfig, ExposureField, ExposureOverride,
+    ExposureRemap, SliceConfig,
+};
 pub use format::SliceMode;
 pub use layer_iter::SliceLayerIterator;
+pub use uniformity::UniformityMask;
 
 use crate::{
-    container::{Image, Run, rle},
+    container::{Image, Run, SharedBytes, rle},
     progress::Progress,
     serde::DynamicSerializer,
-    units::Milimeters,
+    units::{Milimeters, Seconds},
 };
 
 /// Boxed [`SlicedFile`].
@@ -41,6 +47,29 @@ pub trait SlicedFile {
     }
 }
 
+/// Shared [`SliceDecoder`].
+pub type DynSliceDecoder = Arc<dyn SliceDecoder + Send + Sync>;
+
+/// Sliced file decoder interface.
+///
+/// Implemented by all format File types that can be loaded back into the
+/// slicer. Layers are only decoded once their runs are requested through
+/// [`SlicedFile::runs`].
+pub trait SliceDecoder: SlicedFile {
+    fn decode(data: &SharedBytes) -> Result<Self>
+    where
+        Self: Sized;
+
+    fn slice_config(&self) -> SliceConfig;
+    fn previews(&self) -> Vec<RgbaImage>;
+    /// Position and exposure settings of a layer.
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig);
+    /// If printing pauses at a layer.
+    fn layer_pause(&self, _layer: usize) -> bool {
+        false
+    }
+}
+
 /// Layer encoder interface.
 ///
 /// Implemented by all format layer encoders.
@@ -55,6 +84,24 @@ pub trait EncodableLayer {
         exposure: &ExposureConfig,
         height: Milimeters,
     ) -> Self::Output;
+    /// Marks a finished layer as one to pause the print at. Formats that
+    /// can't pause leave it as is.
+    fn pause(_layer: &mut Self::Output, _config: &SliceConfig) {}
+}
+
+/// Streaming sliced file writer interface.
+///
+/// Implemented by the format file writers, which write each layer to a stream
+/// as soon as it's encoded so the whole file never needs to be kept in
+/// memory. Layers must be written in order.
+pub trait SliceWriter {
+    type Encoder: EncodableLayer;
+
+    fn write_layer(&mut self, layer: <Self::Encoder as EncodableLayer>::Output) -> Result<()>;
+    /// Writes everything that depends on the full set of layers, like the
+    /// layer tables and the material usage, which is derived from the total
+    /// number of exposed `voxels`.
+    fn finish(self, voxels: u64) -> Result<()>;
 }
 
 /// Format agnostic sliced file info.
@@ -67,32 +114,100 @@ pub struct SliceInfo {
 
 #[derive(Clone)]
 pub struct Layer {
-    pub data: Arc<Vec<Run>>,
-    pub area: u64,
+    pub data: LayerData,
+    /// Number of exposed pixels, only found once it's needed for layers left
+    /// encoded in a sliced file.
+    area: OnceLock<u64>,
     pub height: Milimeters,
 
     /// If this exposure is not derived directly from the slice config.
     pub unique_exposure: bool,
     pub exposure: ExposureConfig,
+    /// If printing should be paused at this layer, to swap the resin or
+    /// embed parts like magnets and nuts.
+    pub pause: bool,
 }
 
 impl Layer {
     pub fn new(data: Vec<Run>, height: Milimeters, exposure: ExposureConfig) -> Self {
-        let area = data
-            .iter()
-            .filter(|x| x.value > 0)
-            .fold(0, |acc, run| acc + run.length);
+        Self {
+            area: OnceLock::from(exposed_area(&data)),
+            data: data.into(),
+            height,
 
+            unique_exposure: false,
+            exposure,
+            pause: false,
+        }
+    }
+
+    /// Creates a layer that is left encoded in a sliced file, and only decoded
+    /// when its runs or area are needed.
+    pub fn encoded(file: &DynSliceDecoder, layer: usize) -> Self {
+        let (height, exposure) = file.layer_settings(layer);
         Self {
-            data: Arc::new(data),
-            area,
+            data: LayerData::Encoded(file.clone(), layer),
+            area: OnceLock::new(),
             height,
 
             unique_exposure: false,
             exposure,
+            pause: file.layer_pause(layer),
+        }
+    }
+
+    /// Number of exposed pixels, decoding the layer the first time if needed.
+    pub fn area(&self) -> u64 {
+        *self.area.get_or_init(|| exposed_area(&self.data.runs()))
+    }
+
+    /// Number of exposed pixels, if it's known without decoding the layer.
+    pub fn cached_area(&self) -> Option<u64> {
+        self.area.get().copied()
+    }
+
+    /// Replaces the runs of the layer, along with its area.
+    pub fn set_runs(&mut self, runs: Vec<Run>) {
+        self.area = OnceLock::from(exposed_area(&runs));
+        self.data = runs.into();
+    }
+}
+
+/// Time needed to print the layers, from the exposure settings of each one.
+pub fn print_time(layers: &[Layer]) -> Seconds {
+    (layers.iter()).fold(Seconds::new(0.0), |acc, x| acc + x.exposure.layer_time())
+}
+
+fn exposed_area(runs: &[Run]) -> u64 {
+    (runs.iter())
+        .filter(|x| x.value > 0)
+        .fold(0, |acc, run| acc + run.length)
+}
+
+/// Runs making up a layer.
+#[derive(Clone)]
+pub enum LayerData {
+    /// Runs kept in memory, like the output of the slicer.
+    Decoded(Arc<Vec<Run>>),
+    /// A layer of a loaded sliced file, decoded whenever its runs are needed.
+    Encoded(DynSliceDecoder, usize),
+}
+
+impl LayerData {
+    /// Gets the runs of the layer, decoding them if needed.
+    pub fn runs(&self) -> Arc<Vec<Run>> {
+        match self {
+            LayerData::Decoded(runs) => runs.clone(),
+            LayerData::Encoded(file, layer) => Arc::new(file.runs(*layer).collect()),
         }
     }
 }
 
+impl From<Vec<Run>> for LayerData {
+    fn from(value: Vec<Run>) -> Self {
+        Self::Decoded(Arc::new(value))
+    }
+}
+
 pub type Polygon = Vec<Vector2<f32>>;
 pub type VectorLayer = Vec<Polygon>;
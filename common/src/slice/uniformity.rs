@@ -0,0 +1,308 @@
+use anyhow::{Result, ensure};
+use image::{GrayImage, imageops::FilterType};
+use nalgebra::Vector2;
+
+use crate::{
+    container::Run,
+    misc::lerp,
+    serde::{Deserializer, SerdeExt, Serializer},
+};
+
+/// Measured brightness of a printer's display, over a grid spanning the
+/// platform. Brighter areas are dimmed to match the dimmest one, so every part
+/// of the platform cures the same.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct UniformityMask {
+    /// Number of columns and rows in the grid.
+    pub size: Vector2<u32>,
+    /// Relative brightness of each cell, row by row, in the same orientation
+    /// as the layer images.
+    pub intensity: Vec<f32>,
+}
+
+impl UniformityMask {
+    /// Most columns or rows a mask can have.
+    pub const MAX_SIZE: u32 = 32;
+
+    /// Creates a mask with every cell equally bright, which leaves the layers
+    /// unchanged.
+    pub fn new(size: Vector2<u32>) -> Self {
+        let size = size.map(|x| x.clamp(1, Self::MAX_SIZE));
+        Self {
+            size,
+            intensity: vec![1.0; (size.x * size.y) as usize],
+        }
+    }
+
+    /// Creates a mask from a grayscale image of the display's brightness,
+    /// scaled down to fit within the largest grid size.
+    pub fn from_image(image: &GrayImage) -> Self {
+        let scale = (Self::MAX_SIZE as f32 / image.width().max(image.height()) as f32).min(1.0);
+        let size = Vector2::new(image.width(), image.height())
+            .map(|x| ((x as f32 * scale).round() as u32).max(1));
+        let image = image::imageops::resize(image, size.x, size.y, FilterType::Triangle);
+
+        Self {
+            size,
+            intensity: (image.pixels())
+                .map(|x| x.0[0].max(1) as f32 / 255.0)
+                .collect(),
+        }
+    }
+
+    /// Resamples the mask to a grid with a different number of cells.
+    pub fn resize(&self, size: Vector2<u32>) -> Self {
+        let size = size.map(|x| x.clamp(1, Self::MAX_SIZE));
+        let cell = |i: u32, n: u32| (i as f32 + 0.5) / n as f32;
+        Self {
+            size,
+            intensity: (0..size.y)
+                .flat_map(|y| (0..size.x).map(move |x| Vector2::new(x, y)))
+                .map(|pos| {
+                    self.intensity_at(Vector2::new(cell(pos.x, size.x), cell(pos.y, size.y)))
+                })
+                .collect(),
+        }
+    }
+
+    /// Brightness at a position on the display, from (0, 0) in the first
+    /// corner to (1, 1) in the opposite one. Interpolated between the centers
+    /// of the cells.
+    pub fn intensity_at(&self, pos: Vector2<f32>) -> f32 {
+        let (row, t) = self.grid_position(pos.y, self.size.y);
+        let (a, b) = (self.row(row), self.row((row + 1).min(self.size.y - 1)));
+        let (column, s) = self.grid_position(pos.x, self.size.x);
+        let next = (column + 1).min(self.size.x - 1);
+        lerp(
+            lerp(a[column as usize], a[next as usize], s),
+            lerp(b[column as usize], b[next as usize], s),
+            t,
+        )
+    }
+
+    /// Scales the value of each pixel of a layer by the compensation at its
+    /// position on a display `platform` pixels in size. Like the exposure
+    /// remap, this is done after anti-aliasing.
+    pub fn apply(&self, runs: &[Run], platform: Vector2<u32>) -> Vec<Run> {
+        let min = (self.intensity.iter().copied())
+            .fold(f32::MAX, f32::min)
+            .max(f32::EPSILON);
+        let width = platform.x as u64;
+
+        let mut out = Vec::<Run>::with_capacity(runs.len());
+        let mut push = |length: u64, value: u8| match out.last_mut() {
+            Some(last) if last.value == value => last.length += length,
+            _ => out.push(Run::new(length, value)),
+        };
+
+        let mut row = RowCompensation::default();
+        let mut pos = 0;
+        for run in runs {
+            if run.value == 0 {
+                push(run.length, 0);
+                pos += run.length;
+                continue;
+            }
+
+            let (mut x, mut y) = (pos % width, pos / width);
+            let mut left = run.length;
+            while left > 0 {
+                if row.y != Some(y) {
+                    row = self.row_compensation(y, platform, min);
+                }
+
+                // The compensated value only changes a few times along a row,
+                // so the pixels where it does are found with a binary search.
+                // It's monotonic between the centers of the grid cells.
+                let end = x + left.min(width - x);
+                let mut start = x;
+                while start < end {
+                    let piece = row.piece_end(start).clamp(start + 1, end);
+                    let value = row.value(start, run.value);
+
+                    let mut next = piece;
+                    if row.value(piece - 1, run.value) != value {
+                        let (mut low, mut high) = (start + 1, piece - 1);
+                        while low < high {
+                            let mid = (low + high) / 2;
+                            if row.value(mid, run.value) == value {
+                                low = mid + 1;
+                            } else {
+                                high = mid;
+                            }
+                        }
+                        next = low;
+                    }
+
+                    push(next - start, value);
+                    start = next;
+                }
+
+                left -= end - x;
+                (x, y) = (0, y + 1);
+            }
+            pos += run.length;
+        }
+
+        out
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        self.size.serialize(ser);
+        for intensity in &self.intensity {
+            ser.write_f32_be(*intensity);
+        }
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Result<Self> {
+        let size = Vector2::<u32>::deserialize(des);
+        ensure!(
+            (1..=Self::MAX_SIZE).contains(&size.x) && (1..=Self::MAX_SIZE).contains(&size.y),
+            "Invalid uniformity mask size"
+        );
+        Ok(Self {
+            size,
+            intensity: (0..size.x * size.y).map(|_| des.read_f32_be()).collect(),
+        })
+    }
+
+    fn row_compensation(&self, y: u64, platform: Vector2<u32>, min: f32) -> RowCompensation {
+        let t = (y as f32 + 0.5) / platform.y as f32;
+        let mut columns = (0..self.size.x)
+            .map(|x| {
+                let x = (x as f32 + 0.5) / self.size.x as f32;
+                self.intensity_at(Vector2::new(x, t))
+            })
+            .collect::<Vec<_>>();
+        columns.push(columns[columns.len() - 1]);
+
+        RowCompensation {
+            y: Some(y),
+            columns,
+            scale: self.size.x as f32 / platform.x as f32,
+            min,
+        }
+    }
+
+    fn row(&self, row: u32) -> &[f32] {
+        let start = (row * self.size.x) as usize;
+        &self.intensity[start..start + self.size.x as usize]
+    }
+
+    /// Gets the cell at or before a position (0 to 1) along an axis with `n`
+    /// cells, and how far the position is toward the next cell center.
+    fn grid_position(&self, pos: f32, n: u32) -> (u32, f32) {
+        let pos = (pos * n as f32 - 0.5).clamp(0.0, (n - 1) as f32);
+        let cell = (pos.floor() as u32).min(n - 1);
+        (cell, pos - cell as f32)
+    }
+}
+
+/// Brightness at the center of each grid column, along a row of pixels.
+#[derive(Default)]
+struct RowCompensation {
+    y: Option<u64>,
+    columns: Vec<f32>,
+    /// Grid columns per pixel.
+    scale: f32,
+    /// Brightness of the dimmest cell.
+    min: f32,
+}
+
+impl RowCompensation {
+    /// Position of a pixel along the grid columns.
+    fn column(&self, x: u64) -> f32 {
+        (x as f32 + 0.5) * self.scale - 0.5
+    }
+
+    fn value(&self, x: u64, value: u8) -> u8 {
+        let last = (self.columns.len() - 2) as f32;
+        let column = self.column(x).clamp(0.0, last);
+        let (i, t) = (column as usize, column.fract());
+        let intensity = lerp(self.columns[i], self.columns[i + 1], t);
+        (value as f32 * self.min / intensity.max(f32::EPSILON)).round() as u8
+    }
+
+    /// Gets the first pixel after `x` past the next grid column center.
+    fn piece_end(&self, x: u64) -> u64 {
+        let column = self.column(x);
+        let last = (self.columns.len() - 2) as f32;
+        if column >= last {
+            return u64::MAX;
+        }
+
+        let next = column.max(-1.0).floor() + 1.0;
+        ((next + 0.5) / self.scale - 0.5).ceil() as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+
+    use super::UniformityMask;
+    use crate::container::Run;
+
+    fn pixels(runs: &[Run]) -> Vec<u8> {
+        (runs.iter())
+            .flat_map(|run| std::iter::repeat_n(run.value, run.length as usize))
+            .collect()
+    }
+
+    #[test]
+    fn uniform_mask_keeps_layer() {
+        let mask = UniformityMask::new(Vector2::new(4, 3));
+        let runs = [Run::new(10, 0), Run::new(50, 255), Run::new(40, 128)];
+        assert_eq!(mask.apply(&runs, Vector2::new(20, 5)), runs);
+    }
+
+    #[test]
+    fn matches_per_pixel() {
+        let platform = Vector2::new(37, 5);
+        let mask = UniformityMask {
+            size: Vector2::new(3, 2),
+            intensity: vec![1.0, 0.5, 0.8, 0.6, 1.0, 0.9],
+        };
+
+        // Runs of varying values, many of them wrapping onto the next row.
+        let mut runs = Vec::new();
+        let (mut seed, mut total) = (7_u64, 0);
+        while total < (platform.x * platform.y) as u64 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let length = (seed >> 33) % 50 + 1;
+            let length = length.min((platform.x * platform.y) as u64 - total);
+            runs.push(Run::new(
+                length,
+                [0, 255, 128, 40][(seed >> 20) as usize % 4],
+            ));
+            total += length;
+        }
+
+        let out = mask.apply(&runs, platform);
+        assert!(out.windows(2).all(|x| x[0].value != x[1].value));
+
+        let min = 0.5;
+        let expected = (pixels(&runs).into_iter().enumerate())
+            .map(|(i, value)| {
+                let (x, y) = (i as u64 % platform.x as u64, i as u64 / platform.x as u64);
+                let row = mask.row_compensation(y, platform, min);
+                if value == 0 { 0 } else { row.value(x, value) }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pixels(&out), expected);
+
+        // And close to the brightness interpolated straight from the mask.
+        for (i, (&value, &out)) in pixels(&runs).iter().zip(&pixels(&out)).enumerate() {
+            let (x, y) = (i as u32 % platform.x, i as u32 / platform.x);
+            let pos = Vector2::new(x, y).cast::<f32>().add_scalar(0.5);
+            let intensity = mask.intensity_at(pos.component_div(&platform.cast()));
+            let target = value as f32 * min / intensity;
+            assert!(
+                (out as f32 - target).abs() <= 1.0,
+                "{out} vs {target} at {x}, {y}"
+            );
+        }
+    }
+}
@@ -28,6 +28,7 @@ pub type Seconds = Time<Second>;
 pub type Minutes = Time<Minute>;
 
 pub type CentimetersPerSecond = Velocity<Centimeter, Second>;
+pub type MilimetersPerSecond = Velocity<Milimeter, Second>;
 pub type MilimetersPerMinute = Velocity<Milimeter, Minute>;
 
 pub trait Unit {
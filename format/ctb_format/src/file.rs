@@ -3,13 +3,16 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{Result, ensure};
+use anyhow::{Result, bail, ensure};
 
 use common::{
-    container::{Image, Run},
+    container::{Image, Run, SharedBytes},
     progress::Progress,
     serde::{Deserializer, DynamicSerializer, Serializer, SliceDeserializer},
-    slice::{ExposureConfig, SliceConfig, SliceInfo, SliceMode, SlicedFile},
+    slice::{
+        DEFAULT_PAUSE_HOLD, ExposureConfig, SliceConfig, SliceDecoder, SliceInfo, SliceMode,
+        SlicedFile,
+    },
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
 use image::imageops::FilterType;
@@ -19,18 +22,23 @@ use sha2::{Digest, Sha256};
 use crate::{
     LayerDecoder, LayerEncoder, Section,
     crypto::{decrypt, encrypt, encrypt_in_place},
-    layer::{Layer, LayerRef},
+    layer::{LAYER_REF_SIZE, Layer, LayerRef, first_retract_distance},
+    legacy::{CBDDLP_MAGIC, CTB_MAGIC},
     preview::PreviewImage,
+    read_string,
     resin::ResinParameters,
+    writer::LayerTable,
 };
 
+const MAGIC: u32 = 0x12FD0107;
 const FORMAT_VERSION: u32 = 5;
-const PAGE_SIZE: u64 = 1 << 32;
-const DEFAULT_XOR_KEY: u32 = 0x67;
+pub(crate) const PAGE_SIZE: u64 = 1 << 32;
+pub(crate) const DEFAULT_XOR_KEY: u32 = 0x67;
 const DISCLAIMER: &str = "Layout and record format for the ctb and cbddlp file types are the copyrighted programs or codes of CBD Technology (China) Inc..The Customer or User shall not in any manner reproduce, distribute, modify, decompile, disassemble, decrypt, extract, reverse engineer, lease, assign, or sublicense the said programs or codes.";
 
 /// A ChituBox file.
 pub struct File {
+    pub layout: Layout,
     pub layers: Vec<Layer>,
 
     // Misc
@@ -94,9 +102,45 @@ pub struct File {
     pub rest_time_after_lift_2: Seconds,
 }
 
+/// Version and type of a ChituBox file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Encrypted `.ctb` v5.
+    Encrypted,
+    /// Unencrypted `.ctb` v2 to v4, with XOR scrambled layers.
+    Ctb(u32),
+    /// `.cbddlp` v2 to v4, with one bit per pixel.
+    Cbddlp(u32),
+}
+
 impl File {
-    pub fn deserialize(main_des: &mut SliceDeserializer) -> Result<Self> {
-        assert_eq!(main_des.read_u32_le(), 0x12FD0107);
+    pub fn deserialize(des: &mut SliceDeserializer) -> Result<Self> {
+        match des.read_u32_le() {
+            MAGIC => Self::deserialize_encrypted(des),
+            magic @ (CTB_MAGIC | CBDDLP_MAGIC) => Self::deserialize_legacy(des, magic),
+            magic => bail!("Unknown ChituBox file magic {magic:#X}"),
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        let mut table = self.serialize_header(ser, self.layers.len());
+        for layer in &self.layers {
+            table.write_layer(ser, layer);
+        }
+        table.finish(ser);
+    }
+
+    /// Writes everything before the layer data, returning the table that
+    /// layers are then added to.
+    pub(crate) fn serialize_header<T: Serializer>(&self, ser: &mut T, layers: usize) -> LayerTable {
+        match self.layout {
+            Layout::Encrypted => self.serialize_encrypted(ser, layers),
+            Layout::Ctb(version) => self.serialize_legacy(ser, layers, false, version),
+            Layout::Cbddlp(version) => self.serialize_legacy(ser, layers, true, version),
+        }
+    }
+
+    fn deserialize_encrypted(main_des: &mut SliceDeserializer) -> Result<Self> {
         let settings = Section::deserialize_rev(main_des)?;
 
         main_des.advance_by(4);
@@ -104,21 +148,19 @@ impl File {
         ensure!(version == FORMAT_VERSION);
         let signature = Section::deserialize_rev(main_des)?;
 
-        main_des.jump_to(settings.offset as usize);
-        let bytes = decrypt(main_des.read_slice(settings.size as usize));
+        ensure!(settings.size % 16 == 0, "Invalid encrypted settings");
+        let bytes = decrypt(settings.read(main_des)?);
         let mut des = SliceDeserializer::new(&bytes);
 
         let checksum = des.read_u64_le();
         let hash = Sha256::digest(checksum.to_le_bytes());
-        let signature = main_des.execute_at(signature.offset as usize, |des| {
-            des.read_slice(signature.size as usize)
-        });
-        ensure!(&encrypt(&hash) == signature);
+        ensure!(encrypt(&hash) == signature.read(main_des)?);
 
         let layer_offset = des.read_u32_le();
         let layer_count;
 
         Ok(Self {
+            layout: Layout::Encrypted,
             checksum,
             size: Vector3::new(des.read_f32_le(), des.read_f32_le(), des.read_f32_le())
                 .map(Milimeters::new),
@@ -184,9 +226,7 @@ impl File {
             rest_time_after_lift: Seconds::new(des.read_f32_le()),
             machine_name: {
                 let section = Section::deserialize(&mut des)?;
-                let machine_name = main_des.execute_at(section.offset as usize, |des| {
-                    String::from_utf8_lossy(des.read_slice(section.size as usize))
-                });
+                let machine_name = read_string(main_des, section)?;
                 machine_name.trim_end_matches('\0').to_owned()
             },
             anti_alias_flag: des.read_u8(),
@@ -215,9 +255,7 @@ impl File {
             disclaimer: {
                 des.advance_by(4 * 4);
                 let section = Section::deserialize(&mut des)?;
-                main_des.execute_at(section.offset as usize, |des| {
-                    String::from_utf8_lossy(des.read_slice(section.size as usize)).into_owned()
-                })
+                read_string(main_des, section)?
             },
             resin_parameters: {
                 des.advance_by(4);
@@ -227,8 +265,8 @@ impl File {
         })
     }
 
-    pub fn serialize<T: Serializer>(&self, main_ser: &mut T) {
-        main_ser.write_u32_le(0x12FD0107);
+    fn serialize_encrypted<T: Serializer>(&self, main_ser: &mut T, layers: usize) -> LayerTable {
+        main_ser.write_u32_le(MAGIC);
         let settings_section = main_ser.reserve(8);
         main_ser.write_u32_le(0);
         main_ser.write_u32_le(FORMAT_VERSION);
@@ -258,7 +296,7 @@ impl File {
         ser.write_u32_le(self.bottom_layer_count);
         ser.write_u32_le(self.resolution.x);
         ser.write_u32_le(self.resolution.y);
-        ser.write_u32_le(self.layers.len() as u32);
+        ser.write_u32_le(layers as u32);
         let large_preview = ser.reserve(4);
         let small_preview = ser.reserve(4);
         ser.write_u32_le(self.print_time);
@@ -338,7 +376,7 @@ impl File {
         self.small_preview.serialize(main_ser);
 
         ser.execute_at(layer_offset, |ser| ser.write_u32_le(main_ser.pos() as u32));
-        let layer_refs = main_ser.reserve(16 * self.layers.len());
+        let layer_refs = main_ser.reserve(LAYER_REF_SIZE * layers);
 
         let mut settings_bytes = ser.into_inner();
         encrypt_in_place(&mut settings_bytes);
@@ -363,33 +401,45 @@ impl File {
         });
 
         main_ser.write_u32_le(0x6D4232B3);
+        LayerTable::new(self.layout, layer_refs, layers, 1, LAYER_REF_SIZE)
+    }
+}
 
-        for (i, layer) in self.layers.iter().enumerate() {
-            let cursor = main_ser.pos() as u64;
+impl LayerTable {
+    pub(crate) fn serialize_encrypted_layer<T: Serializer>(&mut self, ser: &mut T, layer: &Layer) {
+        let cursor = ser.pos() as u64;
 
-            let page_number = (cursor / PAGE_SIZE) as u32;
-            let refrence = LayerRef {
-                layer_offset: (cursor % PAGE_SIZE) as u32,
-                page_number,
-            };
-            main_ser.execute_at(layer_refs + i * 16, |ser| refrence.serialize(ser));
+        let page_number = (cursor / PAGE_SIZE) as u32;
+        let refrence = LayerRef {
+            layer_offset: (cursor % PAGE_SIZE) as u32,
+            page_number,
+        };
+        (self.entries).execute_at(self.next * LAYER_REF_SIZE, |ser| refrence.serialize(ser));
 
-            layer.serialize(main_ser, DEFAULT_XOR_KEY, page_number, i as u32);
-        }
+        layer.serialize(ser, DEFAULT_XOR_KEY, page_number, self.next as u32);
     }
 }
 
 impl File {
-    pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>) -> Self {
-        let layer_count = layers.len();
+    pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>, layout: Layout) -> Self {
+        let layer_count = layers.len() as u32;
+        Self {
+            layers,
+            ..Self::from_config(config, layer_count, layout)
+        }
+    }
 
+    /// Creates a file without any layers, with settings describing
+    /// `layer_count` layers.
+    pub(crate) fn from_config(config: &SliceConfig, layer_count: u32, layout: Layout) -> Self {
         let epoch = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
             .as_secs();
 
         Self {
-            layers,
+            layout,
+            layers: Vec::new(),
             checksum: 0,
             disclaimer: DISCLAIMER.into(),
             modified: (epoch / 60) as u32,
@@ -406,7 +456,7 @@ impl File {
             },
             total_height: config.slice_height * layer_count as f32,
             layer_height: config.slice_height,
-            last_layer_index: layer_count.saturating_sub(1) as u32,
+            last_layer_index: layer_count.saturating_sub(1),
             transition_layer_count: config.transition_layers,
             anti_alias_flag: 7,
             anti_alias_level: 0,
@@ -429,52 +479,84 @@ impl File {
             bottom_light_off_delay: Seconds::new(0.0),
             light_pwm: config.exposure_config.pwm as u16,
             bottom_light_pwm: config.first_exposure_config.pwm as u16,
-            bottom_lift_height_2: Milimeters::new(4.0), // make a setting
-            bottom_lift_speed_2: MilimetersPerMinute::new(320.0), // make a setting
-            lift_height_2: Milimeters::new(0.0),
-            lift_speed_2: MilimetersPerMinute::new(0.0),
-            retract_height_2: Milimeters::new(0.0),
-            retract_speed_2: MilimetersPerMinute::new(0.0),
-            rest_time_after_lift: Seconds::new(0.0),
+            bottom_lift_height_2: config.first_exposure_config.second_lift_distance,
+            bottom_lift_speed_2: config.first_exposure_config.second_lift_speed.convert(),
+            lift_height_2: config.exposure_config.second_lift_distance,
+            lift_speed_2: config.exposure_config.second_lift_speed.convert(),
+            retract_height_2: config.exposure_config.second_retract_distance,
+            retract_speed_2: config.exposure_config.second_retract_speed.convert(),
+            rest_time_after_lift: config.exposure_config.rest_after_lift,
             bottom_retract_speed: config.first_exposure_config.retract_speed.convert(),
-            bottom_retract_speed_2: MilimetersPerMinute::new(90.0), // make a setting
+            bottom_retract_speed_2: config.first_exposure_config.second_retract_speed.convert(),
             rest_time_after_retract_2: config.first_exposure_config.exposure_delay,
             rest_time_after_lift_3: Seconds::new(0.0),
-            rest_time_before_lift: Seconds::new(0.0),
-            bottom_retract_height_2: Milimeters::new(1.5),
+            rest_time_before_lift: config.exposure_config.rest_before_lift,
+            bottom_retract_height_2: config.first_exposure_config.second_retract_distance,
             rest_time_after_retract: config.exposure_config.exposure_delay,
             rest_time_after_lift_2: Seconds::new(0.0),
         }
     }
 
     pub fn into_slice_config(&self) -> SliceConfig {
+        // The header only has the rest times of the normal layers, so those of
+        // the bottom layers are taken from the settings of the first layer.
+        let bottom = (self.layers.first()).filter(|_| self.bottom_layer_count > 0);
+        let bottom_rests = bottom.map_or(
+            (self.rest_time_before_lift, self.rest_time_after_lift),
+            |x| (x.rest_time_before_lift, x.rest_time_after_lift),
+        );
+
         SliceConfig {
             mode: SliceMode::Raster,
             supersample: 0,
             exposure_remap: Default::default(),
+            uniformity: None,
             platform_resolution: self.resolution,
             platform_size: self.size,
             slice_height: self.layer_height,
+            compensation: Default::default(),
             exposure_config: ExposureConfig {
                 exposure_time: self.exposure_time,
                 exposure_delay: self.rest_time_after_retract,
                 pwm: self.light_pwm as u8,
+                rest_before_lift: self.rest_time_before_lift,
+                rest_after_lift: self.rest_time_after_lift,
                 lift_distance: self.lift_height,
                 lift_speed: self.lift_speed.convert(),
-                retract_distance: Milimeters::new(0.0),
+                second_lift_distance: self.lift_height_2,
+                second_lift_speed: self.lift_speed_2.convert(),
+                retract_distance: first_retract_distance(
+                    self.lift_height,
+                    self.lift_height_2,
+                    self.retract_height_2,
+                ),
                 retract_speed: self.retract_speed.convert(),
+                second_retract_distance: self.retract_height_2,
+                second_retract_speed: self.retract_speed_2.convert(),
             },
             first_exposure_config: ExposureConfig {
                 exposure_time: self.bottom_exposure_time,
                 exposure_delay: self.rest_time_after_retract_2,
                 pwm: self.bottom_light_pwm as u8,
-                lift_distance: self.lift_height_2,
-                lift_speed: self.lift_speed_2.convert(),
-                retract_distance: Milimeters::new(0.0),
-                retract_speed: self.retract_speed_2.convert(),
+                rest_before_lift: bottom_rests.0,
+                rest_after_lift: bottom_rests.1,
+                lift_distance: self.bottom_lift_height,
+                lift_speed: self.bottom_lift_speed.convert(),
+                second_lift_distance: self.bottom_lift_height_2,
+                second_lift_speed: self.bottom_lift_speed_2.convert(),
+                retract_distance: first_retract_distance(
+                    self.bottom_lift_height,
+                    self.bottom_lift_height_2,
+                    self.bottom_retract_height_2,
+                ),
+                retract_speed: self.bottom_retract_speed.convert(),
+                second_retract_distance: self.bottom_retract_height_2,
+                second_retract_speed: self.bottom_retract_speed_2.convert(),
             },
             first_layers: self.bottom_layer_count,
             transition_layers: self.transition_layer_count,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
         }
     }
 }
@@ -504,20 +586,42 @@ impl SlicedFile for File {
     }
 
     fn runs(&self, layer: usize) -> Box<dyn Iterator<Item = Run> + '_> {
-        let data = &self.layers[layer].data;
-        Box::new(LayerDecoder::new(data))
+        Box::new(LayerDecoder::new(self.layers[layer].data()))
     }
 
     fn overwrite_layer(&mut self, layer: usize, image: Image) {
         let mut encoder = LayerEncoder::default();
         (image.runs()).for_each(|run| encoder.add_run(run.length, run.value));
-        self.layers[layer].data = encoder.into_inner();
+        self.layers[layer].set_data(encoder.into_inner());
+    }
+}
+
+impl SliceDecoder for File {
+    fn decode(data: &SharedBytes) -> Result<Self> {
+        Self::deserialize(&mut SliceDeserializer::new_shared(data))
+    }
+
+    fn slice_config(&self) -> SliceConfig {
+        self.into_slice_config()
+    }
+
+    fn previews(&self) -> Vec<image::RgbaImage> {
+        vec![
+            self.large_preview.into_image(),
+            self.small_preview.into_image(),
+        ]
+    }
+
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig) {
+        let layer = &self.layers[layer];
+        (layer.position_z, layer.exposure_config())
     }
 }
 
 impl Debug for File {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("File")
+            .field("layout", &self.layout)
             .field("checksum", &self.checksum)
             .field("disclaimer", &self.disclaimer)
             .field("modified", &self.modified)
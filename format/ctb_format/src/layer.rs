@@ -1,14 +1,20 @@
-use std::fmt::Debug;
+use std::{borrow::Cow, fmt::Debug};
 
 use anyhow::{Result, ensure};
 
 use common::{
+    container::SharedBytes,
     serde::{Deserializer, Serializer, SliceDeserializer},
     slice::ExposureConfig,
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
 
-use crate::{LayerDecoder, Section, crypto::decrypt_in_place};
+use crate::{Section, crypto::decrypt_in_place};
+
+/// Size of a layer reference in the layer table.
+pub(crate) const LAYER_REF_SIZE: usize = 0x10;
+/// Size of the layer definition written before the layer data.
+const LAYER_DEF_SIZE: usize = 0x58;
 
 #[derive(Debug)]
 pub struct LayerRef {
@@ -32,7 +38,19 @@ pub struct Layer {
     pub rest_time_after_lift: Seconds,
     pub rest_time_after_retract: Seconds,
     pub light_pwm: f32,
-    pub data: Vec<u8>,
+    /// Layer data as it's stored in the file, read with [`Layer::data`].
+    pub(crate) raw: SharedBytes,
+    /// How `raw` is scrambled, only undone when the data is read.
+    pub(crate) scramble: Option<Scramble>,
+}
+
+/// Encryption and XOR scrambling of the layer data in a file.
+#[derive(Clone, Copy)]
+pub(crate) struct Scramble {
+    pub xor_key: u32,
+    pub layer: u32,
+    /// Offset and length of the encrypted part of the data.
+    pub encrypted: Option<(usize, usize)>,
 }
 
 impl LayerRef {
@@ -41,7 +59,7 @@ impl LayerRef {
             layer_offset: des.read_u32_le(),
             page_number: des.read_u32_le(),
         };
-        ensure!(des.read_u32_le() == 0x58);
+        ensure!(des.read_u32_le() == LAYER_DEF_SIZE as u32);
         des.advance_by(4);
         Ok(this)
     }
@@ -49,7 +67,7 @@ impl LayerRef {
     pub fn serialize<T: Serializer>(&self, ser: &mut T) {
         ser.write_u32_le(self.layer_offset);
         ser.write_u32_le(self.page_number);
-        ser.write_u32_le(0x58);
+        ser.write_u32_le(LAYER_DEF_SIZE as u32);
         ser.write_u32_le(0);
     }
 }
@@ -57,7 +75,7 @@ impl LayerRef {
 impl Layer {
     pub fn deserialize(des: &mut SliceDeserializer, xor_key: u32, layer: u32) -> Result<Self> {
         let table_size = des.read_u32_le();
-        ensure!(table_size == 0x58);
+        ensure!(table_size == LAYER_DEF_SIZE as u32);
 
         let position_z = Milimeters::new(des.read_f32_le());
         let exposure_time = Seconds::new(des.read_f32_le());
@@ -69,18 +87,21 @@ impl Layer {
         des.advance_by(4);
         let encrypted_data = Section::deserialize(des)?;
 
-        let mut data = des.execute_at(layer_offset as usize, |des| {
-            des.read_slice(layer_size as usize).to_vec()
+        ensure!(
+            (layer_offset as usize).saturating_add(layer_size as usize) <= des.size(),
+            "Layer data out of bounds"
+        );
+        let raw = des.execute_at(layer_offset as usize, |des| {
+            des.read_shared(layer_size as usize)
         });
 
-        if encrypted_data.size != 0 {
-            let (offset, length) = (encrypted_data.offset as usize, encrypted_data.size as usize);
-            decrypt_in_place(&mut data[offset..offset + length]);
-        }
-
-        if xor_key != 0 {
-            xor_cypher(&mut data, xor_key, layer);
-        }
+        let (offset, length) = (encrypted_data.offset as usize, encrypted_data.size as usize);
+        let encrypted = (length != 0).then_some((offset, length));
+        ensure!(
+            encrypted
+                .is_none_or(|_| offset.saturating_add(length) <= raw.len() && length % 16 == 0),
+            "Invalid encrypted layer data"
+        );
 
         Ok(Self {
             position_z,
@@ -97,10 +118,38 @@ impl Layer {
             rest_time_after_lift: Seconds::new(des.read_f32_le()),
             rest_time_after_retract: Seconds::new(des.read_f32_le()),
             light_pwm: des.read_f32_le(),
-            data,
+            raw,
+            scramble: (xor_key != 0 || encrypted.is_some()).then_some(Scramble {
+                xor_key,
+                layer,
+                encrypted,
+            }),
         })
     }
 
+    /// Gets the layer data, unscrambling it if needed.
+    pub fn data(&self) -> Cow<'_, [u8]> {
+        let Some(scramble) = self.scramble else {
+            return Cow::Borrowed(&self.raw);
+        };
+
+        let mut data = self.raw.to_vec();
+        if let Some((offset, length)) = scramble.encrypted {
+            decrypt_in_place(&mut data[offset..offset + length]);
+        }
+
+        if scramble.xor_key != 0 {
+            xor_cypher(&mut data, scramble.xor_key, scramble.layer);
+        }
+
+        Cow::Owned(data)
+    }
+
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.raw = data.into();
+        self.scramble = None;
+    }
+
     pub fn serialize<T: Serializer>(
         &self,
         ser: &mut T,
@@ -108,13 +157,21 @@ impl Layer {
         page_number: u32,
         layer: u32,
     ) {
-        ser.write_u32_le(0x58);
+        let mut data = self.data();
+        if xor_key != 0 {
+            xor_cypher(data.to_mut(), xor_key, layer);
+        }
+
+        // The layer data directly follows its definition.
+        let position = ser.pos() + LAYER_DEF_SIZE;
+
+        ser.write_u32_le(LAYER_DEF_SIZE as u32);
         ser.write_f32_le(self.position_z.raw());
         ser.write_f32_le(self.exposure_time.raw());
         ser.write_f32_le(self.light_off_delay.raw());
-        let layer_offset = ser.reserve(4);
+        ser.write_u32_le(position as u32);
         ser.write_u32_le(page_number);
-        ser.write_u32_le(self.data.len() as u32);
+        ser.write_u32_le(data.len() as u32);
         ser.write_u32_le(0);
         Section::new(0, 0).serialize(ser); // TODO: Not sure if the data can always just be left unencrypted
         ser.write_f32_le(self.lift_height.raw());
@@ -129,33 +186,43 @@ impl Layer {
         ser.write_f32_le(self.rest_time_after_retract.raw());
         ser.write_f32_le(self.light_pwm);
         ser.write_u32_le(0);
-
-        let position = ser.pos();
-        ser.execute_at(layer_offset, |ser| ser.write_u32_le(position as u32));
-        ser.write_bytes(&self.data);
-
-        if xor_key != 0 {
-            let buffer = ser.view_mut(position, self.data.len());
-            xor_cypher(buffer, xor_key, layer);
-        }
+        ser.write_bytes(&data);
     }
 
-    pub fn into_layer(&self) -> common::slice::Layer {
-        let data = LayerDecoder::new(&self.data).collect();
-        let exposure = ExposureConfig {
+    pub fn exposure_config(&self) -> ExposureConfig {
+        ExposureConfig {
             exposure_time: self.exposure_time,
             exposure_delay: self.rest_time_after_retract,
             pwm: self.light_pwm as u8,
+            rest_before_lift: self.rest_time_before_lift,
+            rest_after_lift: self.rest_time_after_lift,
             lift_distance: self.lift_height,
             lift_speed: self.lift_speed.convert(),
-            retract_distance: Milimeters::new(0.0),
+            second_lift_distance: self.lift_height_2,
+            second_lift_speed: self.lift_speed_2.convert(),
+            retract_distance: first_retract_distance(
+                self.lift_height,
+                self.lift_height_2,
+                self.retract_height_2,
+            ),
             retract_speed: self.retract_speed.convert(),
-        };
-        common::slice::Layer::new(data, self.position_z, exposure)
+            second_retract_distance: self.retract_height_2,
+            second_retract_speed: self.retract_speed_2.convert(),
+        }
     }
 }
 
-fn xor_cypher(data: &mut [u8], seed: u32, layer: u32) {
+/// The platform always retracts as far as it lifted, so only the second
+/// retract stage has its own distance. The first stage covers the rest.
+pub(crate) fn first_retract_distance(
+    lift: Milimeters,
+    second_lift: Milimeters,
+    second_retract: Milimeters,
+) -> Milimeters {
+    Milimeters::new((lift.raw() + second_lift.raw() - second_retract.raw()).max(0.0))
+}
+
+pub(crate) fn xor_cypher(data: &mut [u8], seed: u32, layer: u32) {
     let init = seed.wrapping_mul(0x2D83CDAC).wrapping_add(0xD8A83423);
     let mut key = layer
         .wrapping_mul(0x1E1530CD)
@@ -1,7 +1,9 @@
+use std::borrow::Cow;
+
 use common::{
     container::Run,
     slice::{EncodableLayer, ExposureConfig, SliceConfig},
-    units::{Milimeters, MilimetersPerMinute, Seconds},
+    units::{Milimeters, Seconds},
 };
 use nalgebra::Vector2;
 
@@ -9,13 +11,16 @@ use crate::layer::Layer;
 
 /// Decodes the internal layer format to a series of runs.
 pub struct LayerDecoder<'a> {
-    data: &'a [u8],
+    data: Cow<'a, [u8]>,
     offset: usize,
 }
 
 impl<'a> LayerDecoder<'a> {
-    pub fn new(data: &'a [u8]) -> Self {
-        Self { data, offset: 0 }
+    pub fn new(data: impl Into<Cow<'a, [u8]>>) -> Self {
+        Self {
+            data: data.into(),
+            offset: 0,
+        }
     }
 }
 
@@ -34,7 +39,14 @@ impl Iterator for LayerDecoder<'_> {
             value &= 0x7F;
             self.offset += 1;
 
-            let next = self.data[self.offset] as u64;
+            // Stop at truncated runs rather than reading past the end.
+            let next = *self.data.get(self.offset)? as u64;
+            let extra = (next as u8).leading_ones().min(4) as usize;
+            if self.offset + extra >= self.data.len() {
+                self.offset = self.data.len();
+                return None;
+            }
+
             if next & 0x80 == 0 {
                 length = next;
             } else if next & 0xC0 == 0x80 {
@@ -121,23 +133,40 @@ impl EncodableLayer for LayerEncoder {
         exposure: &ExposureConfig,
         height: Milimeters,
     ) -> Self::Output {
-        // note that retract_distance is not used...
+        // note that retract_distance is not used, the first retract stage
+        // always covers whatever distance the second stage doesn't.
         Layer {
             position_z: height,
             exposure_time: exposure.exposure_time,
             light_off_delay: Seconds::new(0.0),
             lift_height: exposure.lift_distance,
             lift_speed: exposure.lift_speed.convert(),
-            lift_height_2: Milimeters::new(0.0),
-            lift_speed_2: MilimetersPerMinute::new(0.0),
+            lift_height_2: exposure.second_lift_distance,
+            lift_speed_2: exposure.second_lift_speed.convert(),
             retract_speed: exposure.retract_speed.convert(),
-            retract_height_2: Milimeters::new(0.0),
-            retract_speed_2: MilimetersPerMinute::new(0.0),
-            rest_time_before_lift: Seconds::new(0.0),
-            rest_time_after_lift: Seconds::new(0.0),
+            retract_height_2: exposure.second_retract_distance,
+            retract_speed_2: exposure.second_retract_speed.convert(),
+            rest_time_before_lift: exposure.rest_before_lift,
+            rest_time_after_lift: exposure.rest_after_lift,
             rest_time_after_retract: exposure.exposure_delay,
             light_pwm: exposure.pwm as f32,
-            data: self.data,
+            raw: self.data.into(),
+            scramble: None,
         }
     }
+
+    fn pause(layer: &mut Self::Output, config: &SliceConfig) {
+        // ChituBox files have no pause flag, so a pause is only a long rest
+        // with the platform at the top, after which the print carries on. The
+        // platform returns as far as it lifts, so raising it to the top of the
+        // build volume leaves the next layer where it would have been.
+        let top = config.platform_size.z.raw() - layer.position_z.raw();
+        let lift = (top - layer.lift_height_2.raw()).max(layer.lift_height.raw());
+        layer.lift_height = Milimeters::new(lift);
+        let hold = config
+            .pause_hold
+            .raw()
+            .max(layer.rest_time_after_lift.raw());
+        layer.rest_time_after_lift = Seconds::new(hold);
+    }
 }
@@ -0,0 +1,652 @@
+//! Unencrypted ChituBox formats, `.ctb` v2 - v4 and `.cbddlp`.
+//!
+//! These share the same layout, with a plain header pointing to the print
+//! parameters, slicer info, and (for v4) an extra print parameter table. From
+//! v3 on, each layer's data is preceded by a table of its own print
+//! parameters. CTB layers use the same run length encoding as v5, scrambled
+//! with a per-layer XOR key, while CBDDLP layers are one bit per pixel with
+//! anti-aliasing done by storing multiple copies of each layer.
+
+use anyhow::{Result, ensure};
+
+use common::{
+    container::{Run, SharedBytes},
+    serde::{Deserializer, Serializer, SliceDeserializer},
+    units::{Milimeters, MilimetersPerMinute, Seconds},
+};
+use nalgebra::{Vector2, Vector3, Vector4};
+
+use crate::{
+    File, LayerDecoder, LayerEncoder, Layout, Section,
+    file::{DEFAULT_XOR_KEY, PAGE_SIZE},
+    layer::{Layer, Scramble, xor_cypher},
+    preview::PreviewImage,
+    read_string,
+    resin::ResinParameters,
+    writer::LayerTable,
+};
+
+pub(crate) const CTB_MAGIC: u32 = 0x12FD0086;
+pub(crate) const CBDDLP_MAGIC: u32 = 0x12FD0019;
+
+const PRINT_PARAMETERS_SIZE: usize = 0x3C;
+const SLICER_INFO_SIZE: usize = 0x4C;
+const EXTENDED_PARAMETERS_SIZE: usize = 0x5C;
+const HEADER_SIZE: usize = 0x70;
+const LAYER_DEF_SIZE: usize = 0x24;
+/// Size of the per-layer parameter table written before the layer data in
+/// v3 and later. It starts with a copy of the layer definition.
+const LAYER_DEF_EX_SIZE: usize = 0x54;
+/// Longest run that can be stored in a single byte of a CBDDLP layer.
+const MAX_BIT_RUN: u64 = 0x7D;
+
+/// Print parameters from the layer definition table.
+struct LayerDef {
+    position_z: Milimeters,
+    exposure_time: Seconds,
+    light_off_delay: Seconds,
+    address: u64,
+    size: usize,
+}
+
+impl File {
+    pub(crate) fn deserialize_legacy(des: &mut SliceDeserializer, magic: u32) -> Result<Self> {
+        ensure!(des.size() >= HEADER_SIZE, "File too short");
+        let version = des.read_u32_le();
+        ensure!(
+            (2..=4).contains(&version),
+            "Unsupported CTB version {version}"
+        );
+        let layout = match magic {
+            CBDDLP_MAGIC => Layout::Cbddlp(version),
+            _ => Layout::Ctb(version),
+        };
+
+        let size = Vector3::new(des.read_f32_le(), des.read_f32_le(), des.read_f32_le());
+        des.advance_by(4 * 2);
+        let total_height = Milimeters::new(des.read_f32_le());
+        let layer_height = Milimeters::new(des.read_f32_le());
+        let exposure_time = Seconds::new(des.read_f32_le());
+        let bottom_exposure_time = Seconds::new(des.read_f32_le());
+        let light_off_delay = Seconds::new(des.read_f32_le());
+        let bottom_layer_count = des.read_u32_le();
+        let resolution = Vector2::new(des.read_u32_le(), des.read_u32_le());
+        let large_preview = des.read_u32_le();
+        let layer_defs = des.read_u32_le() as usize;
+        let layer_count = des.read_u32_le() as usize;
+        let small_preview = des.read_u32_le();
+        let print_time = des.read_u32_le();
+        let projector_type = des.read_u32_le();
+        let print_parameters = Section::deserialize(des)?;
+        let anti_alias_level = des.read_u32_le().max(1);
+        let light_pwm = des.read_u16_le();
+        let bottom_light_pwm = des.read_u16_le();
+        let xor_key = des.read_u32_le();
+        let slicer_info = Section::deserialize(des)?;
+
+        let mut file = File {
+            layout,
+            layers: Vec::with_capacity(layer_count),
+            checksum: 0,
+            disclaimer: String::new(),
+            modified: 0,
+            size: size.map(Milimeters::new),
+            resolution,
+            machine_name: String::new(),
+            projector_type,
+            resin_parameters: ResinParameters {
+                resin_color: Vector4::zeros(),
+                machine_name: String::new(),
+                resin_type: String::new(),
+                resin_name: String::new(),
+                resin_density: 1.1,
+            },
+            total_height,
+            layer_height,
+            last_layer_index: layer_count.saturating_sub(1) as u32,
+            transition_layer_count: 0,
+            anti_alias_flag: 7,
+            anti_alias_level,
+            per_layer_settings: 0,
+            print_time,
+            material_milliliters: 0.0,
+            material_grams: 0.0,
+            material_cost: 0.0,
+            large_preview: read_preview(des, large_preview)?,
+            small_preview: read_preview(des, small_preview)?,
+            exposure_time,
+            bottom_exposure_time,
+            light_off_delay,
+            bottom_layer_count,
+            bottom_lift_height: Milimeters::new(0.0),
+            bottom_lift_speed: MilimetersPerMinute::new(0.0),
+            lift_height: Milimeters::new(0.0),
+            lift_speed: MilimetersPerMinute::new(0.0),
+            retract_speed: MilimetersPerMinute::new(0.0),
+            bottom_light_off_delay: light_off_delay,
+            light_pwm,
+            bottom_light_pwm,
+            bottom_lift_height_2: Milimeters::new(0.0),
+            bottom_lift_speed_2: MilimetersPerMinute::new(0.0),
+            lift_height_2: Milimeters::new(0.0),
+            lift_speed_2: MilimetersPerMinute::new(0.0),
+            retract_height_2: Milimeters::new(0.0),
+            retract_speed_2: MilimetersPerMinute::new(0.0),
+            rest_time_after_lift: Seconds::new(0.0),
+            bottom_retract_speed: MilimetersPerMinute::new(0.0),
+            bottom_retract_speed_2: MilimetersPerMinute::new(0.0),
+            rest_time_after_retract_2: Seconds::new(0.0),
+            rest_time_after_lift_3: Seconds::new(0.0),
+            rest_time_before_lift: Seconds::new(0.0),
+            bottom_retract_height_2: Milimeters::new(0.0),
+            rest_time_after_retract: Seconds::new(0.0),
+            rest_time_after_lift_2: Seconds::new(0.0),
+        };
+
+        let file_size = des.size();
+        let in_bounds = |offset: u32, size: usize| offset as usize + size <= file_size;
+        ensure!(
+            in_bounds(print_parameters.offset, PRINT_PARAMETERS_SIZE)
+                && in_bounds(slicer_info.offset, SLICER_INFO_SIZE),
+            "Print parameters out of bounds"
+        );
+
+        if print_parameters.offset != 0 {
+            des.jump_to(print_parameters.offset as usize);
+            file.deserialize_print_parameters(des);
+        }
+
+        let mut extended_parameters = 0;
+        if slicer_info.offset != 0 {
+            des.jump_to(slicer_info.offset as usize);
+            extended_parameters = file.deserialize_slicer_info(des)?;
+        }
+
+        if version >= 4 && extended_parameters != 0 {
+            ensure!(
+                in_bounds(extended_parameters, EXTENDED_PARAMETERS_SIZE),
+                "Print parameters out of bounds"
+            );
+            des.jump_to(extended_parameters as usize);
+            file.deserialize_extended_parameters(des)?;
+        }
+
+        // CBDDLP files store a full layer table for each anti-aliasing level.
+        let levels = match layout {
+            Layout::Cbddlp(_) => anti_alias_level as usize,
+            _ => 1,
+        };
+        let table_size = layer_count * levels * LAYER_DEF_SIZE;
+        ensure!(
+            layer_defs.saturating_add(table_size) <= des.size(),
+            "Layer table out of bounds"
+        );
+
+        for i in 0..layer_count {
+            let mut planes = Vec::with_capacity(levels);
+            for level in 0..levels {
+                let position = layer_defs + (level * layer_count + i) * LAYER_DEF_SIZE;
+                let def = des.execute_at(position, |des| LayerDef::deserialize(des));
+                ensure!(
+                    def.address.saturating_add(def.size as u64) <= des.size() as u64,
+                    "Layer data out of bounds"
+                );
+                planes.push(def);
+            }
+
+            let def = &planes[0];
+            let mut layer = file.default_layer(i as u32, def);
+            if version >= 3 && def.address >= LAYER_DEF_EX_SIZE as u64 {
+                let position = def.address as usize - LAYER_DEF_EX_SIZE;
+                des.execute_at(position, |des| deserialize_layer_ex(des, &mut layer));
+            }
+
+            let mut read = |def: &LayerDef| {
+                des.execute_at(def.address as usize, |des| des.read_shared(def.size))
+            };
+            match layout {
+                Layout::Cbddlp(_) => {
+                    let planes = planes.iter().map(read).collect::<Vec<_>>();
+                    layer.raw = decode_bit_planes(&planes).into();
+                }
+                _ => {
+                    layer.raw = read(def);
+                    layer.scramble = (xor_key != 0).then_some(Scramble {
+                        xor_key,
+                        layer: i as u32,
+                        encrypted: None,
+                    });
+                }
+            }
+
+            file.layers.push(layer);
+        }
+
+        Ok(file)
+    }
+
+    pub(crate) fn serialize_legacy<T: Serializer>(
+        &self,
+        ser: &mut T,
+        layers: usize,
+        cbddlp: bool,
+        version: u32,
+    ) -> LayerTable {
+        let (magic, levels, xor_key) = match cbddlp {
+            true => (CBDDLP_MAGIC, self.anti_alias_level.max(1), 0),
+            false => (CTB_MAGIC, 1, DEFAULT_XOR_KEY),
+        };
+
+        ser.write_u32_le(magic);
+        ser.write_u32_le(version);
+        ser.write_f32_le(self.size.x.raw());
+        ser.write_f32_le(self.size.y.raw());
+        ser.write_f32_le(self.size.z.raw());
+        ser.write_u32_le(0);
+        ser.write_u32_le(0);
+        ser.write_f32_le(self.total_height.raw());
+        ser.write_f32_le(self.layer_height.raw());
+        ser.write_f32_le(self.exposure_time.raw());
+        ser.write_f32_le(self.bottom_exposure_time.raw());
+        ser.write_f32_le(self.light_off_delay.raw());
+        ser.write_u32_le(self.bottom_layer_count);
+        ser.write_u32_le(self.resolution.x);
+        ser.write_u32_le(self.resolution.y);
+        let large_preview = ser.reserve(4);
+        let layer_defs = ser.reserve(4);
+        ser.write_u32_le(layers as u32);
+        let small_preview = ser.reserve(4);
+        ser.write_u32_le(self.print_time);
+        ser.write_u32_le(self.projector_type);
+        let print_parameters = ser.reserve(8);
+        ser.write_u32_le(levels);
+        ser.write_u16_le(self.light_pwm);
+        ser.write_u16_le(self.bottom_light_pwm);
+        ser.write_u32_le(xor_key);
+        let slicer_info = ser.reserve(8);
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(large_preview, |ser| ser.write_u32_le(pos));
+        self.large_preview.serialize(ser);
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(small_preview, |ser| ser.write_u32_le(pos));
+        self.small_preview.serialize(ser);
+
+        let pos = ser.pos();
+        ser.execute_at(print_parameters, |ser| {
+            Section::new(pos, PRINT_PARAMETERS_SIZE).serialize(ser)
+        });
+        self.serialize_print_parameters(ser);
+
+        let pos = ser.pos();
+        ser.execute_at(slicer_info, |ser| {
+            Section::new(pos, SLICER_INFO_SIZE).serialize(ser)
+        });
+        let extended_parameters = self.serialize_slicer_info(ser, version);
+
+        if version >= 4 {
+            let pos = ser.pos() as u32;
+            ser.execute_at(extended_parameters, |ser| ser.write_u32_le(pos));
+            self.serialize_extended_parameters(ser);
+        }
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(layer_defs, |ser| ser.write_u32_le(pos));
+        let table = ser.reserve(layers * levels as usize * LAYER_DEF_SIZE);
+        LayerTable::new(self.layout, table, layers, levels, LAYER_DEF_SIZE)
+    }
+
+    fn deserialize_print_parameters(&mut self, des: &mut SliceDeserializer) {
+        self.bottom_lift_height = Milimeters::new(des.read_f32_le());
+        self.bottom_lift_speed = MilimetersPerMinute::new(des.read_f32_le());
+        self.lift_height = Milimeters::new(des.read_f32_le());
+        self.lift_speed = MilimetersPerMinute::new(des.read_f32_le());
+        self.retract_speed = MilimetersPerMinute::new(des.read_f32_le());
+        self.material_milliliters = des.read_f32_le();
+        self.material_grams = des.read_f32_le();
+        self.material_cost = des.read_f32_le();
+        self.bottom_light_off_delay = Seconds::new(des.read_f32_le());
+        self.light_off_delay = Seconds::new(des.read_f32_le());
+        self.bottom_layer_count = des.read_u32_le();
+    }
+
+    fn serialize_print_parameters<T: Serializer>(&self, ser: &mut T) {
+        ser.write_f32_le(self.bottom_lift_height.raw());
+        ser.write_f32_le(self.bottom_lift_speed.raw());
+        ser.write_f32_le(self.lift_height.raw());
+        ser.write_f32_le(self.lift_speed.raw());
+        ser.write_f32_le(self.retract_speed.raw());
+        ser.write_f32_le(self.material_milliliters);
+        ser.write_f32_le(self.material_grams);
+        ser.write_f32_le(self.material_cost);
+        ser.write_f32_le(self.bottom_light_off_delay.raw());
+        ser.write_f32_le(self.light_off_delay.raw());
+        ser.write_u32_le(self.bottom_layer_count);
+        ser.reserve(4 * 4);
+    }
+
+    /// Returns the address of the v4 print parameters.
+    fn deserialize_slicer_info(&mut self, des: &mut SliceDeserializer) -> Result<u32> {
+        self.bottom_lift_height_2 = Milimeters::new(des.read_f32_le());
+        self.bottom_lift_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+        self.lift_height_2 = Milimeters::new(des.read_f32_le());
+        self.lift_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+        self.retract_height_2 = Milimeters::new(des.read_f32_le());
+        self.retract_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+        self.rest_time_after_lift = Seconds::new(des.read_f32_le());
+        let machine_name = Section::deserialize(des)?;
+        self.machine_name = read_string(des, machine_name)?
+            .trim_end_matches('\0')
+            .to_owned();
+        self.anti_alias_flag = des.read_u8();
+        des.advance_by(2);
+        self.per_layer_settings = des.read_u8();
+        self.modified = des.read_u32_le();
+        des.advance_by(4 * 2); // anti-aliasing level and software version
+        self.rest_time_after_retract = Seconds::new(des.read_f32_le());
+        self.rest_time_after_lift_2 = Seconds::new(des.read_f32_le());
+        self.transition_layer_count = des.read_u32_le();
+        Ok(des.read_u32_le())
+    }
+
+    /// Returns the position of the v4 print parameters address, which needs to
+    /// be filled in later.
+    fn serialize_slicer_info<T: Serializer>(&self, ser: &mut T, version: u32) -> usize {
+        ser.write_f32_le(self.bottom_lift_height_2.raw());
+        ser.write_f32_le(self.bottom_lift_speed_2.raw());
+        ser.write_f32_le(self.lift_height_2.raw());
+        ser.write_f32_le(self.lift_speed_2.raw());
+        ser.write_f32_le(self.retract_height_2.raw());
+        ser.write_f32_le(self.retract_speed_2.raw());
+        ser.write_f32_le(self.rest_time_after_lift.raw());
+        let machine_name = ser.reserve(8);
+        ser.write_u8(self.anti_alias_flag);
+        ser.write_u16_le(0);
+        ser.write_u8(match version {
+            2 => 0,
+            _ => self.per_layer_settings,
+        });
+        ser.write_u32_le(self.modified);
+        ser.write_u32_le(self.anti_alias_level);
+        ser.write_u32_le(0x01060300); // software version
+        ser.write_f32_le(self.rest_time_after_retract.raw());
+        ser.write_f32_le(self.rest_time_after_lift_2.raw());
+        ser.write_u32_le(self.transition_layer_count);
+        let extended_parameters = ser.reserve(4);
+        ser.reserve(4 * 2);
+
+        let mut machine_name_bytes = self.machine_name.as_bytes().to_vec();
+        machine_name_bytes.push(0);
+        let pos = ser.pos();
+        ser.execute_at(machine_name, |ser| {
+            Section::new(pos, machine_name_bytes.len()).serialize(ser)
+        });
+        ser.write_bytes(&machine_name_bytes);
+
+        extended_parameters
+    }
+
+    fn deserialize_extended_parameters(&mut self, des: &mut SliceDeserializer) -> Result<()> {
+        self.bottom_retract_speed = MilimetersPerMinute::new(des.read_f32_le());
+        self.bottom_retract_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+        des.advance_by(4 * 4);
+        self.rest_time_after_retract_2 = Seconds::new(des.read_f32_le());
+        self.rest_time_after_lift_3 = Seconds::new(des.read_f32_le());
+        self.rest_time_before_lift = Seconds::new(des.read_f32_le());
+        self.bottom_retract_height_2 = Milimeters::new(des.read_f32_le());
+        des.advance_by(4 * 3);
+        self.last_layer_index = des.read_u32_le();
+        des.advance_by(4 * 4);
+        let disclaimer = Section::deserialize(des)?;
+        self.disclaimer = read_string(des, disclaimer)?;
+        Ok(())
+    }
+
+    fn serialize_extended_parameters<T: Serializer>(&self, ser: &mut T) {
+        ser.write_f32_le(self.bottom_retract_speed.raw());
+        ser.write_f32_le(self.bottom_retract_speed_2.raw());
+        ser.write_u32_le(0);
+        ser.write_f32_le(4.0);
+        ser.write_u32_le(0);
+        ser.write_f32_le(4.0);
+        ser.write_f32_le(self.rest_time_after_retract_2.raw());
+        ser.write_f32_le(self.rest_time_after_lift_3.raw());
+        ser.write_f32_le(self.rest_time_before_lift.raw());
+        ser.write_f32_le(self.bottom_retract_height_2.raw());
+        ser.reserve(4 * 2);
+        ser.write_u32_le(4);
+        ser.write_u32_le(self.last_layer_index);
+        ser.reserve(4 * 4);
+        let disclaimer = ser.reserve(8);
+        ser.reserve(4 * 3);
+
+        let disclaimer_bytes = self.disclaimer.as_bytes();
+        let pos = ser.pos();
+        ser.execute_at(disclaimer, |ser| {
+            Section::new(pos, disclaimer_bytes.len()).serialize(ser)
+        });
+        ser.write_bytes(disclaimer_bytes);
+    }
+
+    /// Layers without their own parameter table use the global settings.
+    fn default_layer(&self, layer: u32, def: &LayerDef) -> Layer {
+        fn pick<T>(bottom: bool, bottom_value: T, value: T) -> T {
+            if bottom { bottom_value } else { value }
+        }
+
+        let bottom = layer < self.bottom_layer_count;
+        Layer {
+            position_z: def.position_z,
+            exposure_time: def.exposure_time,
+            light_off_delay: def.light_off_delay,
+            lift_height: pick(bottom, self.bottom_lift_height, self.lift_height),
+            lift_speed: pick(bottom, self.bottom_lift_speed, self.lift_speed),
+            lift_height_2: pick(bottom, self.bottom_lift_height_2, self.lift_height_2),
+            lift_speed_2: pick(bottom, self.bottom_lift_speed_2, self.lift_speed_2),
+            retract_speed: pick(bottom, self.bottom_retract_speed, self.retract_speed),
+            retract_height_2: pick(bottom, self.bottom_retract_height_2, self.retract_height_2),
+            retract_speed_2: pick(bottom, self.bottom_retract_speed_2, self.retract_speed_2),
+            rest_time_before_lift: self.rest_time_before_lift,
+            rest_time_after_lift: self.rest_time_after_lift,
+            rest_time_after_retract: pick(
+                bottom,
+                self.rest_time_after_retract_2,
+                self.rest_time_after_retract,
+            ),
+            light_pwm: pick(bottom, self.bottom_light_pwm, self.light_pwm) as f32,
+            raw: SharedBytes::default(),
+            scramble: None,
+        }
+    }
+}
+
+impl LayerTable {
+    /// Writes the data for each anti-aliasing level of a layer. The layer
+    /// table has all the first levels, followed by all the second levels and
+    /// so on, but the data is written one layer at a time.
+    pub(crate) fn serialize_legacy_layer<T: Serializer>(
+        &mut self,
+        ser: &mut T,
+        layer: &Layer,
+        cbddlp: bool,
+        version: u32,
+    ) {
+        let xor_key = if cbddlp { 0 } else { DEFAULT_XOR_KEY };
+        let layer_data = layer.data();
+
+        for level in 0..self.levels {
+            let mut data = match cbddlp {
+                true => encode_bit_plane(&layer_data, level, self.levels),
+                false => layer_data.to_vec(),
+            };
+
+            let layer_ex = if version >= 3 { LAYER_DEF_EX_SIZE } else { 0 };
+            let def = LayerDef {
+                position_z: layer.position_z,
+                exposure_time: layer.exposure_time,
+                light_off_delay: layer.light_off_delay,
+                address: (ser.pos() + layer_ex) as u64,
+                size: data.len(),
+            };
+
+            if version >= 3 {
+                def.serialize(ser, version);
+                serialize_layer_ex(ser, layer, data.len());
+            }
+
+            if xor_key != 0 {
+                xor_cypher(&mut data, xor_key, self.next as u32);
+            }
+            ser.write_bytes(&data);
+
+            let index = level as usize * self.layers + self.next;
+            (self.entries).execute_at(index * LAYER_DEF_SIZE, |ser| def.serialize(ser, version));
+        }
+    }
+}
+
+impl LayerDef {
+    fn deserialize(des: &mut SliceDeserializer) -> Self {
+        let position_z = Milimeters::new(des.read_f32_le());
+        let exposure_time = Seconds::new(des.read_f32_le());
+        let light_off_delay = Seconds::new(des.read_f32_le());
+        let address = des.read_u32_le() as u64;
+        let size = des.read_u32_le() as usize;
+        let page_number = des.read_u32_le() as u64;
+        des.advance_by(4 * 3);
+
+        Self {
+            position_z,
+            exposure_time,
+            light_off_delay,
+            address: page_number * PAGE_SIZE + address,
+            size,
+        }
+    }
+
+    fn serialize<T: Serializer>(&self, ser: &mut T, version: u32) {
+        ser.write_f32_le(self.position_z.raw());
+        ser.write_f32_le(self.exposure_time.raw());
+        ser.write_f32_le(self.light_off_delay.raw());
+        ser.write_u32_le((self.address % PAGE_SIZE) as u32);
+        ser.write_u32_le(self.size as u32);
+        ser.write_u32_le((self.address / PAGE_SIZE) as u32);
+        ser.write_u32_le(match version {
+            2 => 0,
+            _ => LAYER_DEF_EX_SIZE as u32,
+        });
+        ser.reserve(4 * 2);
+    }
+}
+
+fn deserialize_layer_ex(des: &mut SliceDeserializer, layer: &mut Layer) {
+    des.advance_by(LAYER_DEF_SIZE + 4);
+    layer.lift_height = Milimeters::new(des.read_f32_le());
+    layer.lift_speed = MilimetersPerMinute::new(des.read_f32_le());
+    layer.lift_height_2 = Milimeters::new(des.read_f32_le());
+    layer.lift_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+    layer.retract_speed = MilimetersPerMinute::new(des.read_f32_le());
+    layer.retract_height_2 = Milimeters::new(des.read_f32_le());
+    layer.retract_speed_2 = MilimetersPerMinute::new(des.read_f32_le());
+    layer.rest_time_before_lift = Seconds::new(des.read_f32_le());
+    layer.rest_time_after_lift = Seconds::new(des.read_f32_le());
+    layer.rest_time_after_retract = Seconds::new(des.read_f32_le());
+    layer.light_pwm = des.read_f32_le();
+}
+
+/// Writes the part of the per-layer parameter table after the copy of the
+/// layer definition.
+fn serialize_layer_ex<T: Serializer>(ser: &mut T, layer: &Layer, size: usize) {
+    ser.write_u32_le((LAYER_DEF_EX_SIZE + size) as u32);
+    ser.write_f32_le(layer.lift_height.raw());
+    ser.write_f32_le(layer.lift_speed.raw());
+    ser.write_f32_le(layer.lift_height_2.raw());
+    ser.write_f32_le(layer.lift_speed_2.raw());
+    ser.write_f32_le(layer.retract_speed.raw());
+    ser.write_f32_le(layer.retract_height_2.raw());
+    ser.write_f32_le(layer.retract_speed_2.raw());
+    ser.write_f32_le(layer.rest_time_before_lift.raw());
+    ser.write_f32_le(layer.rest_time_after_lift.raw());
+    ser.write_f32_le(layer.rest_time_after_retract.raw());
+    ser.write_f32_le(layer.light_pwm);
+}
+
+fn read_preview(des: &mut SliceDeserializer, address: u32) -> Result<PreviewImage> {
+    if address == 0 {
+        return Ok(PreviewImage::default());
+    }
+
+    des.execute_at(address as usize, |des| PreviewImage::deserialize(des))
+}
+
+/// Converts a layer to the CBDDLP format for one anti-aliasing level. Pixels
+/// are turned on in as many of the levels as their brightness calls for.
+fn encode_bit_plane(data: &[u8], level: u32, levels: u32) -> Vec<u8> {
+    // byte 0: abbbbbbb
+    // a => pixel is on
+    // b => run length
+    let mut out = Vec::new();
+    let mut push = |on: bool, mut length: u64| {
+        while length > 0 {
+            let run = length.min(MAX_BIT_RUN);
+            out.push(((on as u8) << 7) | run as u8);
+            length -= run;
+        }
+    };
+
+    let (mut on, mut length) = (false, 0);
+    for run in LayerDecoder::new(data) {
+        let run_on = (run.value as u32 * levels + 127) / 255 > level;
+        if run_on != on {
+            push(on, length);
+            (on, length) = (run_on, 0);
+        }
+        length += run.length;
+    }
+    push(on, length);
+
+    out
+}
+
+/// Combines the CBDDLP layers for each anti-aliasing level into a single
+/// layer, with the brightness of each pixel being the fraction of levels it
+/// is on in.
+fn decode_bit_planes(planes: &[SharedBytes]) -> Vec<u8> {
+    let levels = planes.len() as u32;
+    let mut decoders = (planes.iter())
+        .map(|x| x.iter().map(|&x| (x & 0x80 != 0, (x & 0x7F) as u64)))
+        .collect::<Vec<_>>();
+    let mut current = vec![(false, 0); planes.len()];
+
+    let mut encoder = LayerEncoder::default();
+    let mut pending = Run {
+        length: 0,
+        value: 0,
+    };
+
+    'outer: loop {
+        for (decoder, current) in decoders.iter_mut().zip(current.iter_mut()) {
+            while current.1 == 0 {
+                let Some(next) = decoder.next() else {
+                    break 'outer;
+                };
+                *current = next;
+            }
+        }
+
+        let length = current.iter().map(|x| x.1).min().unwrap_or_default();
+        let count = current.iter().filter(|x| x.0).count() as u32;
+        let value = (count * 255 / levels) as u8;
+        current.iter_mut().for_each(|x| x.1 -= length);
+
+        if value != pending.value {
+            encoder.add_run(pending.length, pending.value);
+            pending = Run { length: 0, value };
+        }
+        pending.length += length;
+    }
+
+    encoder.add_run(pending.length, pending.value);
+    encoder.into_inner()
+}
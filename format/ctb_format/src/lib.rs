@@ -1,4 +1,5 @@
-//! ChituBox encrypted format v5 (`.ctb`).
+//! ChituBox formats, encrypted v5 (`.ctb`) and the older unencrypted v2 - v4
+//! (`.ctb` and `.cbddlp`).
 //!
 //! ## References
 //!
@@ -6,7 +7,7 @@
 //!
 //! - [UV Tools](https://github.com/sn4k3/UVtools)
 
-use anyhow::Result;
+use anyhow::{Result, ensure};
 
 use common::serde::{Deserializer, Serializer, SliceDeserializer};
 
@@ -14,15 +15,18 @@ mod crypto;
 mod file;
 mod layer;
 mod layer_coding;
+mod legacy;
 mod preview;
 mod resin;
+mod writer;
 
 pub use crate::{
-    file::File,
+    file::{File, Layout},
     layer::Layer,
     layer_coding::{LayerDecoder, LayerEncoder},
     preview::PreviewImage,
     resin::ResinParameters,
+    writer::FileWriter,
 };
 
 #[derive(Debug)]
@@ -62,10 +66,19 @@ impl Section {
         ser.write_u32_le(self.size);
         ser.write_u32_le(self.offset);
     }
+
+    /// Reads the bytes of this section, failing if it extends past the end of
+    /// the file.
+    pub fn read<'a>(&self, des: &mut SliceDeserializer<'a>) -> Result<&'a [u8]> {
+        let (offset, size) = (self.offset as usize, self.size as usize);
+        ensure!(
+            offset.saturating_add(size) <= des.size(),
+            "Section out of bounds"
+        );
+        Ok(des.execute_at(offset, |des| des.read_slice(size)))
+    }
 }
 
-fn read_string(des: &mut SliceDeserializer, section: Section) -> String {
-    des.execute_at(section.offset as usize, |des| {
-        String::from_utf8_lossy(des.read_slice(section.size as usize)).into_owned()
-    })
+fn read_string(des: &mut SliceDeserializer, section: Section) -> Result<String> {
+    Ok(String::from_utf8_lossy(section.read(des)?).into_owned())
 }
@@ -66,7 +66,7 @@ fn main() -> Result<()> {
             );
             stdout().flush()?;
 
-            let decoder = LayerDecoder::new(&layer.data);
+            let decoder = LayerDecoder::new(layer.data());
 
             let mut ser = DynamicSerializer::new();
             let mut encoder = PngEncoder::new(&mut ser, ColorType::Grayscale, file.resolution);
@@ -3,7 +3,7 @@ use std::{
     iter::repeat_n,
 };
 
-use anyhow::Result;
+use anyhow::{Result, ensure};
 
 use common::serde::{Deserializer, Serializer, SliceDeserializer};
 use image::RgbaImage;
@@ -70,12 +70,7 @@ impl PreviewImage {
         let width = des.read_u32_le();
         let height = des.read_u32_le();
         let image = Section::deserialize(des)?;
-
-        des.jump_to(image.offset as usize);
-        PreviewImage::from_bytes(
-            des.read_slice(image.size as usize),
-            Vector2::new(width, height),
-        )
+        PreviewImage::from_bytes(image.read(des)?, Vector2::new(width, height))
     }
 
     pub(crate) fn serialize<T: Serializer>(&self, ser: &mut T) {
@@ -98,6 +93,7 @@ impl PreviewImage {
 
         let mut i = 0;
         while i < bytes.len() {
+            ensure!(i + 2 <= bytes.len(), "Truncated preview image");
             let run = u16::from_le_bytes([bytes[i], bytes[i + 1]]);
             i += 2;
 
@@ -107,6 +103,7 @@ impl PreviewImage {
 
             let mut count = 1;
             if run & 0x20 != 0 {
+                ensure!(i + 2 <= bytes.len(), "Truncated preview image");
                 count += (bytes[i + 1] as u16 & 0x0F) << 8 | bytes[i] as u16;
                 i += 2;
             }
@@ -35,9 +35,9 @@ impl ResinParameters {
 
         Ok(Self {
             resin_color: Vector4::new(color_r, color_g, color_b, color_a),
-            machine_name: read_string(des, machine_name),
-            resin_type: read_string(des, resin_type),
-            resin_name: read_string(des, resin_name),
+            machine_name: read_string(des, machine_name)?,
+            resin_type: read_string(des, resin_type)?,
+            resin_name: read_string(des, resin_name)?,
             resin_density,
         })
     }
@@ -0,0 +1,93 @@
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+
+use common::{
+    serde::{DynamicSerializer, Serializer, WriterSerializer},
+    slice::{SliceConfig, SliceWriter, SlicedFile},
+};
+use image::RgbaImage;
+
+use crate::{File, Layer, LayerEncoder, Layout};
+
+/// Writes a ChituBox file to a stream one layer at a time.
+pub struct FileWriter<T: Write + Seek> {
+    ser: WriterSerializer<T>,
+    table: LayerTable,
+}
+
+/// Layer table of a file being written. The entries are kept in memory as
+/// the layers are written, and filled in once all layers are done.
+pub(crate) struct LayerTable {
+    pub layout: Layout,
+    /// Position of the table in the file.
+    pub offset: usize,
+    pub layers: usize,
+    /// Number of anti-aliasing levels stored for each layer.
+    pub levels: u32,
+    /// Index of the next layer to be written.
+    pub next: usize,
+    pub entries: DynamicSerializer,
+}
+
+impl<T: Write + Seek> FileWriter<T> {
+    /// Writes the header for a file with `layers` layers.
+    pub fn new(
+        stream: T,
+        config: &SliceConfig,
+        layers: u32,
+        layout: Layout,
+        preview: &RgbaImage,
+    ) -> Self {
+        let mut file = File::from_config(config, layers, layout);
+        file.set_preview(preview);
+
+        let mut ser = WriterSerializer::new(stream);
+        let table = file.serialize_header(&mut ser, layers as usize);
+        Self { ser, table }
+    }
+}
+
+impl<T: Write + Seek> SliceWriter for FileWriter<T> {
+    type Encoder = LayerEncoder;
+
+    fn write_layer(&mut self, layer: Layer) -> Result<()> {
+        self.table.write_layer(&mut self.ser, &layer);
+        Ok(())
+    }
+
+    fn finish(mut self, _voxels: u64) -> Result<()> {
+        self.table.finish(&mut self.ser);
+        self.ser.finish()?;
+        Ok(())
+    }
+}
+
+impl LayerTable {
+    pub fn new(layout: Layout, offset: usize, layers: usize, levels: u32, size: usize) -> Self {
+        let mut entries = DynamicSerializer::new();
+        entries.reserve(layers * levels as usize * size);
+
+        Self {
+            layout,
+            offset,
+            layers,
+            levels,
+            next: 0,
+            entries,
+        }
+    }
+
+    pub fn write_layer<T: Serializer>(&mut self, ser: &mut T, layer: &Layer) {
+        match self.layout {
+            Layout::Encrypted => self.serialize_encrypted_layer(ser, layer),
+            Layout::Ctb(version) => self.serialize_legacy_layer(ser, layer, false, version),
+            Layout::Cbddlp(version) => self.serialize_legacy_layer(ser, layer, true, version),
+        }
+        self.next += 1;
+    }
+
+    pub fn finish<T: Serializer>(self, ser: &mut T) {
+        ser.write_at(self.offset, &self.entries.into_inner());
+    }
+}
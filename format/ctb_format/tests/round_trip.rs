@@ -0,0 +1,139 @@
+use common::{
+    container::{Run, SharedBytes},
+    progress::Progress,
+    serde::DynamicSerializer,
+    slice::{EncodableLayer, SliceConfig, SliceDecoder, SlicedFile},
+    units::Seconds,
+};
+use ctb_format::{File, LayerDecoder, LayerEncoder, Layout};
+use image::RgbaImage;
+use nalgebra::Vector2;
+
+fn config() -> SliceConfig {
+    SliceConfig {
+        platform_resolution: Vector2::new(64, 32),
+        ..Default::default()
+    }
+}
+
+/// A few rows of pixels with some gray in them, wrapping over row ends. The
+/// gray level survives both the 7-bit CTB runs and three CBDDLP bit planes.
+fn layer_runs(offset: u64) -> Vec<Run> {
+    vec![
+        Run::new(70 + offset, 0),
+        Run::new(90, 255),
+        Run::new(20, 85),
+        Run::new(64 * 32 - 180 - offset, 0),
+    ]
+}
+
+fn pixels(runs: impl IntoIterator<Item = Run>) -> Vec<u8> {
+    (runs.into_iter())
+        .flat_map(|run| std::iter::repeat_n(run.value, run.length as usize))
+        .collect()
+}
+
+fn encode(layout: Layout) -> Vec<u8> {
+    let config = config();
+    let layers = (0..3)
+        .map(|i| {
+            let mut encoder = LayerEncoder::new(config.platform_resolution);
+            layer_runs(i * 10)
+                .iter()
+                .for_each(|x| encoder.add_run(x.length, x.value));
+            encoder.finish(
+                &config,
+                &config.exposure_config,
+                config.default_height(i as u32),
+            )
+        })
+        .collect();
+
+    let mut file = File::from_layers(&config, layers, layout);
+    file.anti_alias_level = 3;
+    file.set_preview(&RgbaImage::new(4, 4));
+    let mut ser = DynamicSerializer::new();
+    SlicedFile::serialize(&file, &mut ser, Progress::new());
+    ser.into_inner()
+}
+
+const LAYOUTS: [Layout; 5] = [
+    Layout::Encrypted,
+    Layout::Ctb(3),
+    Layout::Ctb(4),
+    Layout::Cbddlp(2),
+    Layout::Cbddlp(4),
+];
+
+#[test]
+fn encode_decode() {
+    for layout in LAYOUTS {
+        let file = File::decode(&SharedBytes::new(encode(layout))).unwrap();
+        assert_eq!(file.info().layers, 3);
+        assert_eq!(file.info().resolution, config().platform_resolution);
+
+        for layer in 0..3 {
+            let expected = pixels(layer_runs(layer as u64 * 10));
+            assert_eq!(
+                pixels(file.runs(layer)),
+                expected,
+                "{layout:?} layer {layer}"
+            );
+        }
+
+        let (height, _) = file.layer_settings(2);
+        assert!((height.raw() - 0.15).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn truncated() {
+    for layout in LAYOUTS {
+        let bytes = encode(layout);
+        for len in [0, 4, 30, bytes.len() / 2, bytes.len() - 1] {
+            let bytes = SharedBytes::new(bytes[..len].to_vec());
+            assert!(File::decode(&bytes).is_err(), "{len} bytes");
+        }
+    }
+}
+
+#[test]
+fn truncated_runs() {
+    // A run of 255 with a two byte length, cut off after the first byte.
+    let runs = LayerDecoder::new(&[0x10, 0xFF, 0x80][..]).collect::<Vec<_>>();
+    assert_eq!(runs, [Run::new(1, 0x21)]);
+}
+
+#[test]
+fn bottom_rests() {
+    // Versions before 3 have no per layer settings to keep them in.
+    for layout in [Layout::Encrypted, Layout::Ctb(3), Layout::Cbddlp(4)] {
+        let mut config = config();
+        config.first_exposure_config.rest_before_lift = Seconds::new(2.0);
+        config.first_exposure_config.rest_after_lift = Seconds::new(3.0);
+        config.exposure_config.rest_after_lift = Seconds::new(1.0);
+
+        let layers = (0..3)
+            .map(|i| {
+                let mut encoder = LayerEncoder::new(config.platform_resolution);
+                encoder.add_run(64 * 32, 0);
+                let height = config.default_height(i);
+                encoder.finish(&config, &config.layer_exposure(i, height), height)
+            })
+            .collect();
+        let mut file = File::from_layers(&config, layers, layout);
+        file.set_preview(&RgbaImage::new(4, 4));
+        let mut ser = DynamicSerializer::new();
+        SlicedFile::serialize(&file, &mut ser, Progress::new());
+
+        let file = File::decode(&SharedBytes::new(ser.into_inner())).unwrap();
+        let decoded = file.slice_config();
+        assert_eq!(
+            decoded.first_exposure_config.rest_before_lift.raw(),
+            2.0,
+            "{layout:?}"
+        );
+        assert_eq!(decoded.first_exposure_config.rest_after_lift.raw(), 3.0);
+        assert_eq!(decoded.exposure_config.rest_after_lift.raw(), 1.0);
+    }
+}
@@ -1,4 +1,5 @@
 use common::{
+    container::SharedBytes,
     serde::SizedString,
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
@@ -90,7 +91,7 @@ impl Default for Layer {
             second_retract_distance: Milimeters::new(0.0),
             second_retract_speed: MilimetersPerMinute::new(0.0),
             light_pwm: 255,
-            data: Vec::new(),
+            data: SharedBytes::default(),
             checksum: 0,
         }
     }
@@ -131,7 +131,7 @@ impl EncodableLayer for LayerEncoder {
     ) -> Self::Output {
         Layer {
             checksum: self.checksum(),
-            data: self.data,
+            data: self.data.into(),
             layer_position_z: height,
 
             layer_exposure_time: exposure.exposure_time,
@@ -141,10 +141,20 @@ impl EncodableLayer for LayerEncoder {
             lift_speed: exposure.lift_speed.convert(),
             retract_distance: exposure.retract_distance,
             retract_speed: exposure.retract_speed.convert(),
+            before_lift_time: exposure.rest_before_lift,
+            after_lift_time: exposure.rest_after_lift,
+            second_lift_distance: exposure.second_lift_distance,
+            second_lift_speed: exposure.second_lift_speed.convert(),
+            second_retract_distance: exposure.second_retract_distance,
+            second_retract_speed: exposure.second_retract_speed.convert(),
             pause_position_z: slice_config.platform_size.z,
             ..Default::default()
         }
     }
+
+    fn pause(layer: &mut Self::Output, _config: &SliceConfig) {
+        layer.pause = true;
+    }
 }
 
 impl<'a> LayerDecoder<'a> {
@@ -2,11 +2,11 @@ use anyhow::{Result, ensure};
 
 use chrono::Local;
 use common::{
-    container::{Image, Run},
+    container::{Image, Run, SharedBytes},
     progress::Progress,
     serde::{DynamicSerializer, Serializer, SizedString, SliceDeserializer},
-    slice::{SliceConfig, SliceInfo, SlicedFile},
-    units::Second,
+    slice::{ExposureConfig, SliceConfig, SliceDecoder, SliceInfo, SlicedFile},
+    units::{Milimeters, Second},
 };
 use image::{RgbaImage, imageops::FilterType};
 use nalgebra::{Vector2, Vector3};
@@ -25,8 +25,19 @@ impl File {
     }
 
     pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>) -> Self {
-        let layer_count = layers.len() as u32;
+        let mut file = Self::from_config(config, layers.len() as u32);
+
+        // Printers use the global settings for every layer unless told
+        // otherwise, so only enable it if needed.
+        file.header.per_layer_settings =
+            (layers.iter().enumerate()).any(|(i, layer)| layer.overrides_config(config, i as u32));
+        file.layers = layers;
+        file
+    }
 
+    /// Creates a file without any layers, with a header describing
+    /// `layer_count` layers.
+    pub(crate) fn from_config(config: &SliceConfig, layer_count: u32) -> Self {
         let print_time = config.print_time(layer_count);
         let save_time = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
         Self::new(
@@ -50,6 +61,12 @@ impl File {
                 lift_speed: config.exposure_config.lift_speed.convert(),
                 retract_distance: config.exposure_config.retract_distance,
                 retract_speed: config.exposure_config.retract_speed.convert(),
+                before_lift_time: config.exposure_config.rest_before_lift,
+                after_lift_time: config.exposure_config.rest_after_lift,
+                second_lift_distance: config.exposure_config.second_lift_distance,
+                second_lift_speed: config.exposure_config.second_lift_speed.convert(),
+                second_retract_distance: config.exposure_config.second_retract_distance,
+                second_retract_speed: config.exposure_config.second_retract_speed.convert(),
 
                 bottom_exposure_time: config.first_exposure_config.exposure_time,
                 bottom_after_retract_time: config.first_exposure_config.exposure_delay,
@@ -58,11 +75,22 @@ impl File {
                 bottom_lift_speed: config.first_exposure_config.lift_speed.convert(),
                 bottom_retract_distance: config.first_exposure_config.retract_distance,
                 bottom_retract_speed: config.first_exposure_config.retract_speed.convert(),
+                bottom_before_lift_time: config.first_exposure_config.rest_before_lift,
+                bottom_after_lift_time: config.first_exposure_config.rest_after_lift,
+                bottom_second_lift_distance: config.first_exposure_config.second_lift_distance,
+                bottom_second_lift_speed: config.first_exposure_config.second_lift_speed.convert(),
+                bottom_second_retract_distance: config
+                    .first_exposure_config
+                    .second_retract_distance,
+                bottom_second_retract_speed: config
+                    .first_exposure_config
+                    .second_retract_speed
+                    .convert(),
 
                 file_time: SizedString::new(save_time.as_bytes()),
                 ..Default::default()
             },
-            layers,
+            Vec::new(),
         )
     }
 
@@ -122,6 +150,32 @@ impl SlicedFile for File {
 
         let layer = &mut self.layers[layer];
         layer.checksum = encoder.checksum();
-        layer.data = encoder.into_inner();
+        layer.data = encoder.into_inner().into();
+    }
+}
+
+impl SliceDecoder for File {
+    fn decode(data: &SharedBytes) -> Result<Self> {
+        Self::deserialize(&mut SliceDeserializer::new_shared(data))
+    }
+
+    fn slice_config(&self) -> SliceConfig {
+        self.header.into_slice_config()
+    }
+
+    fn previews(&self) -> Vec<RgbaImage> {
+        vec![
+            self.header.big_preview.into_image(),
+            self.header.small_preview.into_image(),
+        ]
+    }
+
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig) {
+        let layer = &self.layers[layer];
+        (layer.layer_position_z, layer.exposure_config())
+    }
+
+    fn layer_pause(&self, layer: usize) -> bool {
+        self.layers[layer].pause
     }
 }
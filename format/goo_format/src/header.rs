@@ -4,7 +4,7 @@ use anyhow::{Result, ensure};
 
 use common::{
     serde::{Deserializer, Serializer, SizedString, SliceDeserializer},
-    slice::{ExposureConfig, ExposureRemap, SliceConfig, SliceMode},
+    slice::{DEFAULT_PAUSE_HOLD, ExposureConfig, ExposureRemap, SliceConfig, SliceMode},
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
 use nalgebra::{Vector2, Vector3};
@@ -299,9 +299,11 @@ impl Header {
             mode: SliceMode::Raster,
             supersample: 0,
             exposure_remap: ExposureRemap::default(),
+            uniformity: None,
             platform_resolution: Vector2::new(self.x_resolution, self.y_resolution).cast(),
             platform_size: Vector3::new(self.x_size, self.y_size, self.x_size),
             slice_height: self.layer_thickness,
+            compensation: Default::default(),
             exposure_config: ExposureConfig {
                 exposure_time: self.exposure_time,
                 exposure_delay: self.after_retract_time,
@@ -310,6 +312,12 @@ impl Header {
                 lift_speed: self.lift_speed.convert(),
                 retract_distance: self.retract_distance,
                 retract_speed: self.retract_speed.convert(),
+                rest_before_lift: self.before_lift_time,
+                rest_after_lift: self.after_lift_time,
+                second_lift_distance: self.second_lift_distance,
+                second_lift_speed: self.second_lift_speed.convert(),
+                second_retract_distance: self.second_retract_distance,
+                second_retract_speed: self.second_retract_speed.convert(),
             },
             first_exposure_config: ExposureConfig {
                 exposure_time: self.bottom_exposure_time,
@@ -319,9 +327,17 @@ impl Header {
                 lift_speed: self.bottom_lift_speed.convert(),
                 retract_distance: self.bottom_retract_distance,
                 retract_speed: self.bottom_retract_speed.convert(),
+                rest_before_lift: self.bottom_before_lift_time,
+                rest_after_lift: self.bottom_after_lift_time,
+                second_lift_distance: self.bottom_second_lift_distance,
+                second_lift_speed: self.bottom_second_lift_speed.convert(),
+                second_retract_distance: self.bottom_second_retract_distance,
+                second_retract_speed: self.bottom_second_retract_speed.convert(),
             },
             first_layers: self.bottom_layers,
             transition_layers: self.transition_layers as u32,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
         }
     }
 }
@@ -1,12 +1,13 @@
 use anyhow::{Result, ensure};
 
 use common::{
+    container::SharedBytes,
     serde::{Deserializer, Serializer, SliceDeserializer},
-    slice::ExposureConfig,
+    slice::{EncodableLayer, ExposureConfig, SliceConfig},
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
 
-use crate::{DELIMITER, LayerDecoder};
+use crate::{DELIMITER, LayerEncoder};
 
 /// Layer data with it's print parameters.
 pub struct Layer {
@@ -46,7 +47,7 @@ pub struct Layer {
     /// Brightness of the light, 0-255.
     pub light_pwm: u8,
     /// The actual layer data.
-    pub data: Vec<u8>,
+    pub data: SharedBytes,
     /// Negative wrapping sum of all bytes in `data`.
     pub checksum: u8,
 }
@@ -101,7 +102,7 @@ impl Layer {
                 ensure!(des.read_slice(2) == DELIMITER);
                 let data_len = des.read_u32_be() as usize - 2;
                 ensure!(des.read_u8() == 0x55);
-                des.read_slice(data_len).to_vec() // ehhh its fiiiine (its not fine)
+                des.read_shared(data_len)
             },
             checksum: {
                 let checksum = des.read_u8();
@@ -111,9 +112,38 @@ impl Layer {
         })
     }
 
-    pub fn into_layer(&self) -> common::slice::Layer {
-        let data = LayerDecoder::new(&self.data).collect();
-        let exposure = ExposureConfig {
+    /// Checks if the layer pauses or its settings differ from the ones the
+    /// slice config gives that layer.
+    pub(crate) fn overrides_config(&self, config: &SliceConfig, layer: u32) -> bool {
+        let exposure = config.exposure_config(layer);
+        let height = config.default_height(layer);
+        let expected = LayerEncoder::default().finish(config, &exposure, height);
+        self.pause
+            || self.settings() != expected.settings()
+            || (self.layer_position_z.raw() - height.raw()).abs() > 1e-4
+    }
+
+    fn settings(&self) -> [f32; 14] {
+        [
+            self.layer_exposure_time.raw(),
+            self.layer_off_time.raw(),
+            self.before_lift_time.raw(),
+            self.after_lift_time.raw(),
+            self.after_retract_time.raw(),
+            self.lift_distance.raw(),
+            self.lift_speed.raw(),
+            self.second_lift_distance.raw(),
+            self.second_lift_speed.raw(),
+            self.retract_distance.raw(),
+            self.retract_speed.raw(),
+            self.second_retract_distance.raw(),
+            self.second_retract_speed.raw(),
+            self.light_pwm as f32,
+        ]
+    }
+
+    pub fn exposure_config(&self) -> ExposureConfig {
+        ExposureConfig {
             exposure_time: self.layer_exposure_time,
             exposure_delay: self.after_retract_time,
             pwm: self.light_pwm,
@@ -121,9 +151,13 @@ impl Layer {
             lift_speed: self.lift_speed.convert(),
             retract_distance: self.retract_distance,
             retract_speed: self.retract_speed.convert(),
-        };
-
-        common::slice::Layer::new(data, self.layer_position_z, exposure)
+            rest_before_lift: self.before_lift_time,
+            rest_after_lift: self.after_lift_time,
+            second_lift_distance: self.second_lift_distance,
+            second_lift_speed: self.second_lift_speed.convert(),
+            second_retract_distance: self.second_retract_distance,
+            second_retract_speed: self.second_retract_speed.convert(),
+        }
     }
 }
 
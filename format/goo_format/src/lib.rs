@@ -10,12 +10,14 @@ mod file;
 mod header;
 mod layer_content;
 mod preview_image;
+mod writer;
 
 pub use encoded_layer::{LayerDecoder, LayerEncoder};
 pub use file::File;
 pub use header::{ExposureDelayMode, Header};
 pub use layer_content::Layer;
 pub use preview_image::PreviewImage;
+pub use writer::FileWriter;
 
 const ENDING_STRING: &[u8] = &[
     0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x44, 0x4C, 0x50, 0x00,
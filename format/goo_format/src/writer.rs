@@ -0,0 +1,59 @@
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+
+use common::{
+    serde::{DynamicSerializer, Serializer, WriterSerializer},
+    slice::{SliceConfig, SliceWriter, SlicedFile},
+};
+use image::RgbaImage;
+
+use crate::{ENDING_STRING, File, Header, Layer, LayerEncoder};
+
+/// Writes a Goo file to a stream one layer at a time.
+pub struct FileWriter<T: Write + Seek> {
+    ser: WriterSerializer<T>,
+    config: SliceConfig,
+    /// Kept to be rewritten if any layer turns out to need its own settings.
+    header: Header,
+    layers: u32,
+}
+
+impl<T: Write + Seek> FileWriter<T> {
+    /// Writes the header for a file with `layers` layers.
+    pub fn new(stream: T, config: &SliceConfig, layers: u32, preview: &RgbaImage) -> Self {
+        let mut file = File::from_config(config, layers);
+        file.set_preview(preview);
+
+        let mut ser = WriterSerializer::new(stream);
+        file.header.serialize(&mut ser);
+        Self {
+            ser,
+            config: config.clone(),
+            header: file.header,
+            layers: 0,
+        }
+    }
+}
+
+impl<T: Write + Seek> SliceWriter for FileWriter<T> {
+    type Encoder = LayerEncoder;
+
+    fn write_layer(&mut self, layer: Layer) -> Result<()> {
+        self.header.per_layer_settings |= layer.overrides_config(&self.config, self.layers);
+        layer.serialize(&mut self.ser);
+        self.layers += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, _voxels: u64) -> Result<()> {
+        self.ser.write_bytes(ENDING_STRING);
+        if self.header.per_layer_settings {
+            let mut header = DynamicSerializer::new();
+            self.header.serialize(&mut header);
+            self.ser.write_at(0, &header.into_inner());
+        }
+        self.ser.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use common::serde::Deserializer;
+
+use crate::Format;
+
+/// How much of the start of the file to look at.
+const SNIFF_SIZE: usize = 1024;
+/// Line prefixes that only show up in OBJ files.
+const OBJ_KEYWORDS: [&str; 9] = ["v", "vt", "vn", "f", "o", "g", "s", "mtllib", "usemtl"];
+
+impl Format {
+    /// Guesses the format of a file from its contents. Leaves the deserializer
+    /// at the same position.
+    pub fn detect<T: Deserializer>(des: &mut T) -> Option<Self> {
+        let pos = des.pos();
+        let size = des.size().saturating_sub(pos);
+        let start = des.execute_at(pos, |des| des.read_bytes(size.min(SNIFF_SIZE)).into_owned());
+
+        if start.starts_with(b"PK\x03\x04") {
+            return Some(Format::ThreeMf);
+        }
+
+        if start.starts_with(b"ply") && matches!(start.get(3), Some(b'\n' | b'\r')) {
+            return Some(Format::Ply);
+        }
+
+        // Binary STLs can start with `solid` too, so check if the triangle
+        // count matches the file size before looking for an ASCII STL.
+        if let Some(count) = start.get(80..84) {
+            let count = u32::from_le_bytes(count.try_into().unwrap());
+            if size as u64 == 84 + 50 * count as u64 {
+                return Some(Format::Stl);
+            }
+        }
+
+        // Binary ones that don't quite match are left to the STL parser.
+        if start.trim_ascii_start().starts_with(b"solid") {
+            return Some(Format::Stl);
+        }
+
+        let text = String::from_utf8_lossy(&start);
+        if start.contains(&0) {
+            return None;
+        }
+
+        let obj = text.lines().any(|line| {
+            let keyword = line.split_whitespace().next();
+            keyword.is_some_and(|x| OBJ_KEYWORDS.contains(&x))
+        });
+        obj.then_some(Format::Obj)
+    }
+}
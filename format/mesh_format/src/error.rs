@@ -0,0 +1,93 @@
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+};
+
+use zip::result::ZipError;
+
+pub type Result<T> = std::result::Result<T, MeshError>;
+
+/// Everything that can go wrong loading or saving a mesh.
+#[derive(Debug)]
+pub enum MeshError {
+    /// The file contents didn't match any supported format.
+    UnknownFormat,
+    /// There is no writer for the requested file extension.
+    UnsupportedFormat(String),
+    /// The file ended before all the data its header promised.
+    UnexpectedEof,
+    /// A face references a vertex that doesn't exist.
+    InvalidIndex {
+        index: u32,
+        vertices: usize,
+    },
+    /// The file is structurally invalid.
+    Malformed(String),
+    Io(io::Error),
+    Zip(ZipError),
+}
+
+/// Like `anyhow::Context`, but for turning missing values into
+/// [`MeshError::Malformed`] errors.
+pub(crate) trait Context<T> {
+    fn context(self, message: &str) -> Result<T>;
+    fn with_context(self, message: impl FnOnce() -> String) -> Result<T>;
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context(self, message: &str) -> Result<T> {
+        self.ok_or_else(|| MeshError::Malformed(message.to_owned()))
+    }
+
+    fn with_context(self, message: impl FnOnce() -> String) -> Result<T> {
+        self.ok_or_else(|| MeshError::Malformed(message()))
+    }
+}
+
+/// Checks that every face only references existing vertices.
+pub(crate) fn check_indices(faces: &[[u32; 3]], vertices: usize) -> Result<()> {
+    match faces.iter().flatten().find(|&&x| x as usize >= vertices) {
+        Some(&index) => Err(MeshError::InvalidIndex { index, vertices }),
+        None => Ok(()),
+    }
+}
+
+impl Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::UnknownFormat => f.write_str("Unknown or unsupported mesh format"),
+            MeshError::UnsupportedFormat(format) => write!(f, "Can't save meshes as `{format}`"),
+            MeshError::UnexpectedEof => f.write_str("Unexpected end of file"),
+            MeshError::InvalidIndex { index, vertices } => write!(
+                f,
+                "Face references vertex {index}, but there are only {vertices} vertices"
+            ),
+            MeshError::Malformed(message) => f.write_str(message),
+            MeshError::Io(err) => err.fmt(f),
+            MeshError::Zip(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for MeshError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MeshError::Io(err) => Some(err),
+            MeshError::Zip(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MeshError {
+    fn from(err: io::Error) -> Self {
+        MeshError::Io(err)
+    }
+}
+
+impl From<ZipError> for MeshError {
+    fn from(err: ZipError) -> Self {
+        MeshError::Zip(err)
+    }
+}
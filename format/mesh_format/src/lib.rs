@@ -1,10 +1,30 @@
-use anyhow::Result;
-use common::{progress::Progress, serde::Deserializer};
-use nalgebra::Vector3;
+use common::{
+    progress::Progress,
+    serde::{Deserializer, Serializer},
+};
+use nalgebra::{Matrix4, Vector3};
 
+mod detect;
+mod error;
 mod obj;
+mod ply;
 mod stl;
+mod three_mf;
 mod util;
+mod xml;
+
+pub use error::{MeshError, Result};
+
+/// File extensions of the formats that meshes can be saved as.
+pub const SAVE_FORMATS: [&str; 3] = ["stl", "obj", "3mf"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Stl,
+    Obj,
+    Ply,
+    ThreeMf,
+}
 
 #[derive(Debug, Default)]
 pub struct Mesh {
@@ -12,19 +32,149 @@ pub struct Mesh {
     pub faces: Vec<[u32; 3]>,
 }
 
-pub fn load_mesh<T: Deserializer + Send>(
+/// One of the meshes in a file, along with where it was placed. Formats that
+/// only hold a single mesh will always return one object with an identity
+/// transform.
+#[derive(Debug)]
+pub struct Object {
+    pub name: Option<String>,
+    pub mesh: Mesh,
+    /// Transforms the mesh from object space into the build space of the file
+    /// (in millimeters).
+    pub transform: Matrix4<f32>,
+}
+
+/// Loads every object in the file and merges them into a single mesh with
+/// their transforms applied.
+pub fn load_mesh<T: Deserializer + Send>(des: T, format: &str, progress: Progress) -> Result<Mesh> {
+    let mut mesh = Mesh::default();
+    for object in load_objects(des, format, progress)? {
+        mesh.append(object.into_mesh());
+    }
+
+    Ok(mesh)
+}
+
+/// Loads every object in the file. The format is detected from the file's
+/// contents, the passed format (file extension) is only used if that fails.
+pub fn load_objects<T: Deserializer + Send>(
     mut des: T,
     format: &str,
     progress: Progress,
-) -> Result<Mesh> {
-    let format = format.to_ascii_lowercase();
+) -> Result<Vec<Object>> {
+    let format = (Format::detect(&mut des))
+        .or_else(|| Format::from_extension(format))
+        .ok_or(MeshError::UnknownFormat)?;
+
+    let objects = match format {
+        Format::Stl => stl::parse(&mut des, progress.clone()).map(Object::single),
+        Format::Obj => obj::parse(&mut des, progress.clone()).map(Object::single),
+        Format::Ply => ply::parse(&mut des, progress.clone()).map(Object::single),
+        Format::ThreeMf => three_mf::parse(&mut des, progress.clone()),
+    };
+
+    progress.set_finished();
+    objects
+}
+
+/// Writes the objects to a file. Only 3MF keeps them as separate objects,
+/// other formats get a single mesh with each object's transform applied.
+pub fn save_objects<T: Serializer>(
+    ser: &mut T,
+    objects: &[Object],
+    format: &str,
+    progress: Progress,
+) -> Result<()> {
+    let unsupported = || MeshError::UnsupportedFormat(format.to_owned());
+    let format = Format::from_extension(format).ok_or_else(unsupported)?;
+    progress.set_total(objects.iter().map(|x| x.mesh.faces.len() as u64).sum());
 
-    let mesh = match format.as_str() {
-        "stl" => stl::parse(&mut des, progress.clone()),
-        "obj" => obj::parse(&mut des, progress.clone()),
-        _ => panic!("Unsupported format: {}", format),
+    match format {
+        Format::Stl => stl::serialize(ser, objects, progress.clone()),
+        Format::Obj => obj::serialize(ser, objects, progress.clone()),
+        Format::ThreeMf => three_mf::serialize(ser, objects, progress.clone())?,
+        Format::Ply => return Err(unsupported()),
     };
 
     progress.set_finished();
-    mesh
+    Ok(())
+}
+
+impl Format {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "stl" => Format::Stl,
+            "obj" => Format::Obj,
+            "ply" => Format::Ply,
+            "3mf" => Format::ThreeMf,
+            _ => return None,
+        })
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Stl => "stl",
+            Format::Obj => "obj",
+            Format::Ply => "ply",
+            Format::ThreeMf => "3mf",
+        }
+    }
+}
+
+impl Mesh {
+    /// Adds all the faces of another mesh to this one.
+    pub fn append(&mut self, other: Mesh) {
+        if self.verts.is_empty() {
+            *self = other;
+            return;
+        }
+
+        let offset = self.verts.len() as u32;
+        self.verts.extend(other.verts);
+        (self.faces).extend(other.faces.into_iter().map(|x| x.map(|x| x + offset)));
+    }
+
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Mesh {
+        Mesh {
+            verts: (self.verts.iter())
+                .map(|x| (transform * x.push(1.0)).xyz())
+                .collect(),
+            faces: self.faces.clone(),
+        }
+    }
+}
+
+impl Object {
+    pub fn new(name: Option<String>, mesh: Mesh, transform: Matrix4<f32>) -> Self {
+        Self {
+            name,
+            mesh,
+            transform,
+        }
+    }
+
+    fn single(mesh: Mesh) -> Vec<Self> {
+        vec![Object {
+            name: None,
+            mesh,
+            transform: Matrix4::identity(),
+        }]
+    }
+
+    /// Iterates over the faces with the object's transform applied to each
+    /// vertex.
+    pub fn faces(&self) -> impl Iterator<Item = [Vector3<f32>; 3]> + '_ {
+        (self.mesh.faces.iter()).map(|face| {
+            face.map(|x| (self.transform * self.mesh.verts[x as usize].push(1.0)).xyz())
+        })
+    }
+
+    /// Applies the object's transform to its vertices.
+    pub fn into_mesh(self) -> Mesh {
+        if self.transform == Matrix4::identity() {
+            return self.mesh;
+        }
+
+        self.mesh.transformed(&self.transform)
+    }
 }
@@ -1,8 +1,16 @@
-use anyhow::{Context, Result};
-use common::{progress::Progress, serde::Deserializer};
+use std::fmt::Write;
+
+use common::{
+    progress::Progress,
+    serde::{Deserializer, Serializer},
+};
 use nalgebra::Vector3;
 
-use crate::{Mesh, util::tokenize};
+use crate::{
+    Mesh, Object, Result,
+    error::{Context, check_indices},
+    util::tokenize,
+};
 
 pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Mesh> {
     progress.set_total(des.size() as u64);
@@ -16,7 +24,8 @@ pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Mesh> {
                 mesh.verts.push(vert);
             }
             Some("f") => {
-                let face = next_face(parts).context("Invalid face")?;
+                let verts = mesh.verts.len();
+                let face = next_face(parts, verts).context("Invalid face")?;
                 mesh.faces.push(face);
             }
             _ => {}
@@ -24,9 +33,46 @@ pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Mesh> {
         Ok(())
     })?;
 
+    check_indices(&mesh.faces, mesh.verts.len())?;
     Ok(mesh)
 }
 
+pub fn serialize<T: Serializer>(ser: &mut T, objects: &[Object], progress: Progress) {
+    let mut out = String::from("# Exported by mslicer\n");
+    let mut offset = 1;
+
+    for (i, object) in objects.iter().enumerate() {
+        let name = (object.name.as_deref())
+            .map(|x| x.replace(['\n', '\r'], " "))
+            .unwrap_or_else(|| format!("Object {i}"));
+        writeln!(out, "o {name}").unwrap();
+
+        for vert in object.mesh.verts.iter() {
+            let vert = (object.transform * vert.push(1.0)).xyz();
+            writeln!(out, "v {} {} {}", vert.x, vert.y, vert.z).unwrap();
+            flush(ser, &mut out);
+        }
+
+        for [a, b, c] in object.mesh.faces.iter() {
+            progress.add_complete(1);
+            writeln!(out, "f {} {} {}", a + offset, b + offset, c + offset).unwrap();
+            flush(ser, &mut out);
+        }
+
+        offset += object.mesh.verts.len() as u32;
+    }
+
+    ser.write_bytes(out.as_bytes());
+}
+
+/// Writes out the buffered text once it gets big enough.
+fn flush<T: Serializer>(ser: &mut T, out: &mut String) {
+    if out.len() >= 64 * 1024 {
+        ser.write_bytes(out.as_bytes());
+        out.clear();
+    }
+}
+
 fn next_vertex<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<Vector3<f32>> {
     Some(Vector3::new(
         parts.next()?.parse().ok()?,
@@ -35,16 +81,19 @@ fn next_vertex<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<Vector3<f
     ))
 }
 
-fn next_face<'a>(mut parts: impl Iterator<Item = &'a str>) -> Option<[u32; 3]> {
-    fn next_idx<'a>(parts: &mut impl Iterator<Item = &'a str>) -> Option<u32> {
+/// Indices are one-based, or relative to the end of the vertex list if
+/// negative.
+fn next_face<'a>(mut parts: impl Iterator<Item = &'a str>, verts: usize) -> Option<[u32; 3]> {
+    let mut next_idx = || {
         let str = parts.next()?;
         let number = str.split_once('/').map(|x| x.0).unwrap_or(str);
-        Some(number.parse::<u32>().ok()? - 1)
-    }
+        let index = match number.parse::<i64>().ok()? {
+            0 => return None,
+            x if x < 0 => verts as i64 + x,
+            x => x - 1,
+        };
+        u32::try_from(index).ok()
+    };
 
-    Some([
-        next_idx(&mut parts)?,
-        next_idx(&mut parts)?,
-        next_idx(&mut parts)?,
-    ])
+    Some([next_idx()?, next_idx()?, next_idx()?])
 }
@@ -0,0 +1,414 @@
+//! Polygon File Format (`.ply`).
+//!
+//! A header describes a list of elements, each with a count and a list of
+//! scalar or list properties. This is followed by the body in either ASCII or
+//! little / big endian binary. Only the `vertex` element's `x`, `y`, and `z`
+//! properties and the `face` element's `vertex_indices` list are used, every
+//! other element and property is skipped. Polygon faces are triangulated as a
+//! fan.
+//!
+//! ```plain
+//! ply
+//! format ascii 1.0
+//! element vertex 8
+//! property float x
+//! property float y
+//! property float z
+//! element face 6
+//! property list uchar int vertex_indices
+//! end_header
+//! ```
+//!
+//! ## References
+//!
+//! - <https://paulbourke.net/dataformats/ply>
+
+use common::{progress::Progress, serde::Deserializer};
+use nalgebra::Vector3;
+
+use crate::{
+    Mesh, MeshError, Result,
+    error::{Context, check_indices},
+    util::{WHITESPACE, tokenize},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Type {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+enum Property {
+    Scalar(Type),
+    List { count: Type, item: Type },
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<(String, Property)>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+/// Builds the mesh from the rows of each element.
+struct Builder {
+    vertex: Option<(usize, [usize; 3])>,
+    face: Option<(usize, usize)>,
+    mesh: Mesh,
+}
+
+/// Reusable buffer for the values of a single element row. The values of
+/// property `n` are at `values[offsets[n]..offsets[n + 1]]`.
+#[derive(Default)]
+struct Row {
+    values: Vec<f64>,
+    offsets: Vec<usize>,
+}
+
+pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Mesh> {
+    let header = Header::parse(des)?;
+    let mut builder = Builder::new(&header)?;
+
+    match header.format {
+        Format::Ascii => ascii::parse(des, &header, &mut builder, progress)?,
+        _ => binary::parse(des, &header, &mut builder, progress)?,
+    }
+
+    builder.finish()
+}
+
+mod ascii {
+    use super::*;
+
+    pub fn parse<T: Deserializer>(
+        des: &mut T,
+        header: &Header,
+        builder: &mut Builder,
+        progress: Progress,
+    ) -> Result<()> {
+        progress.set_total(des.size() as u64);
+
+        let mut elements = (header.elements.iter().enumerate())
+            .filter(|(_, x)| x.count > 0 && !x.properties.is_empty());
+        let mut element = elements.next();
+        let (mut row, mut index, mut property) = (Row::default(), 0, 0);
+        let mut list_remaining = None;
+
+        tokenize(des, &WHITESPACE, progress, |token| {
+            let Some((element_idx, current)) = element else {
+                return Ok(());
+            };
+
+            let value =
+                (token.parse::<f64>().ok()).with_context(|| format!("Invalid value `{token}`"))?;
+
+            if list_remaining.is_none() {
+                row.offsets.push(row.values.len());
+            }
+
+            let property_done = match (&current.properties[property].1, list_remaining) {
+                (Property::Scalar(_), _) => {
+                    row.values.push(value);
+                    true
+                }
+                (Property::List { .. }, None) => {
+                    list_remaining = Some(value as usize);
+                    value as usize == 0
+                }
+                (Property::List { .. }, Some(remaining)) => {
+                    row.values.push(value);
+                    list_remaining = Some(remaining - 1);
+                    remaining == 1
+                }
+            };
+
+            if !property_done {
+                return Ok(());
+            }
+
+            list_remaining = None;
+            property += 1;
+            if property == current.properties.len() {
+                builder.row(element_idx, row.finish())?;
+                row.clear();
+                property = 0;
+                index += 1;
+
+                if index == current.count {
+                    element = elements.next();
+                    index = 0;
+                }
+            }
+
+            Ok(())
+        })?;
+
+        match element {
+            Some(_) => Err(MeshError::UnexpectedEof),
+            None => Ok(()),
+        }
+    }
+}
+
+mod binary {
+    use super::*;
+
+    pub fn parse<T: Deserializer>(
+        des: &mut T,
+        header: &Header,
+        builder: &mut Builder,
+        progress: Progress,
+    ) -> Result<()> {
+        let big_endian = header.format == Format::BinaryBigEndian;
+        progress.set_total(header.elements.iter().map(|x| x.count as u64).sum());
+
+        // Element counts come straight from the header, so make sure reads
+        // never go past the end of the file rather than trusting them.
+        let mut remaining = des.size().saturating_sub(des.pos());
+        let mut read = |des: &mut T, kind: Type| {
+            remaining = (remaining.checked_sub(kind.size())).ok_or(MeshError::UnexpectedEof)?;
+            Ok::<_, MeshError>(kind.read(des, big_endian))
+        };
+
+        let mut row = Row::default();
+        for (element_idx, element) in header.elements.iter().enumerate() {
+            for _ in 0..element.count {
+                progress.add_complete(1);
+
+                for (_name, property) in element.properties.iter() {
+                    row.offsets.push(row.values.len());
+                    match *property {
+                        Property::Scalar(kind) => row.values.push(read(des, kind)?),
+                        Property::List { count, item } => {
+                            let count = read(des, count)? as usize;
+                            for _ in 0..count {
+                                row.values.push(read(des, item)?);
+                            }
+                        }
+                    }
+                }
+
+                builder.row(element_idx, row.finish())?;
+                row.clear();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Header {
+    fn parse<T: Deserializer>(des: &mut T) -> Result<Self> {
+        if next_line(des)? != "ply" {
+            return Err(MeshError::Malformed("Missing PLY magic".into()));
+        }
+
+        let mut format = None;
+        let mut elements = Vec::<Element>::new();
+        loop {
+            let line = next_line(des)?;
+            let mut parts = line.split_whitespace();
+            match parts.next() {
+                Some("format") => {
+                    format = Some(match parts.next() {
+                        Some("ascii") => Format::Ascii,
+                        Some("binary_little_endian") => Format::BinaryLittleEndian,
+                        Some("binary_big_endian") => Format::BinaryBigEndian,
+                        x => {
+                            let format = x.unwrap_or_default();
+                            return Err(MeshError::Malformed(format!("Unknown format `{format}`")));
+                        }
+                    })
+                }
+                Some("element") => {
+                    let name = parts.next().context("Missing element name")?;
+                    let count = parts.next().and_then(|x| x.parse().ok());
+                    elements.push(Element {
+                        name: name.to_owned(),
+                        count: count.context("Invalid element count")?,
+                        properties: Vec::new(),
+                    });
+                }
+                Some("property") => {
+                    let element = elements.last_mut().context("Property before element")?;
+                    let property = match parts.next() {
+                        Some("list") => Property::List {
+                            count: Type::parse(parts.next())?,
+                            item: Type::parse(parts.next())?,
+                        },
+                        kind => Property::Scalar(Type::parse(kind)?),
+                    };
+                    let name = parts.next().context("Missing property name")?;
+                    element.properties.push((name.to_owned(), property));
+                }
+                Some("end_header") => break,
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            format: format.context("Missing format")?,
+            elements,
+        })
+    }
+}
+
+impl Type {
+    fn parse(name: Option<&str>) -> Result<Self> {
+        Ok(match name.context("Missing property type")? {
+            "char" | "int8" => Type::I8,
+            "uchar" | "uint8" => Type::U8,
+            "short" | "int16" => Type::I16,
+            "ushort" | "uint16" => Type::U16,
+            "int" | "int32" => Type::I32,
+            "uint" | "uint32" => Type::U32,
+            "float" | "float32" => Type::F32,
+            "double" | "float64" => Type::F64,
+            x => return Err(MeshError::Malformed(format!("Unknown property type `{x}`"))),
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Type::I8 | Type::U8 => 1,
+            Type::I16 | Type::U16 => 2,
+            Type::I32 | Type::U32 | Type::F32 => 4,
+            Type::F64 => 8,
+        }
+    }
+
+    fn read<T: Deserializer>(self, des: &mut T, big_endian: bool) -> f64 {
+        match (self, big_endian) {
+            (Type::I8, _) => des.read_u8() as i8 as f64,
+            (Type::U8, _) => des.read_u8() as f64,
+            (Type::I16, false) => des.read_i16_le() as f64,
+            (Type::I16, true) => des.read_i16_be() as f64,
+            (Type::U16, false) => des.read_u16_le() as f64,
+            (Type::U16, true) => des.read_u16_be() as f64,
+            (Type::I32, false) => des.read_i32_le() as f64,
+            (Type::I32, true) => des.read_i32_be() as f64,
+            (Type::U32, false) => des.read_u32_le() as f64,
+            (Type::U32, true) => des.read_u32_be() as f64,
+            (Type::F32, false) => des.read_f32_le() as f64,
+            (Type::F32, true) => des.read_f32_be() as f64,
+            (Type::F64, false) => des.read_f64_le(),
+            (Type::F64, true) => des.read_f64_be(),
+        }
+    }
+}
+
+impl Builder {
+    fn new(header: &Header) -> Result<Self> {
+        let find = |element: &Element, name: &str| {
+            (element.properties.iter()).position(|(property, _)| property == name)
+        };
+
+        let mut vertex = None;
+        let mut face = None;
+        for (i, element) in header.elements.iter().enumerate() {
+            match element.name.as_str() {
+                "vertex" => {
+                    let [x, y, z] = ["x", "y", "z"].map(|x| find(element, x));
+                    let axes = [x, y, z].into_iter().collect::<Option<Vec<_>>>();
+                    let axes = axes.context("Vertex element is missing a position")?;
+                    vertex = Some((i, [axes[0], axes[1], axes[2]]));
+                }
+                "face" => {
+                    let indices = find(element, "vertex_indices")
+                        .or_else(|| find(element, "vertex_index"))
+                        .context("Face element is missing vertex indices")?;
+                    face = Some((i, indices));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            vertex,
+            face,
+            mesh: Mesh::default(),
+        })
+    }
+
+    fn row(&mut self, element: usize, row: &Row) -> Result<()> {
+        if let Some((idx, [x, y, z])) = self.vertex
+            && idx == element
+        {
+            let [x, y, z] = [x, y, z].map(|x| row.get(x).first().copied().unwrap_or_default());
+            self.mesh.verts.push(Vector3::new(x, y, z).cast());
+        }
+
+        if let Some((idx, indices)) = self.face
+            && idx == element
+        {
+            let indices = (row.get(indices).iter())
+                .map(|&x| {
+                    let valid = x.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&x);
+                    valid
+                        .then_some(x as u32)
+                        .with_context(|| format!("Invalid vertex index `{x}`"))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for i in 1..indices.len().saturating_sub(1) {
+                (self.mesh.faces).push([indices[0], indices[i], indices[i + 1]]);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Mesh> {
+        check_indices(&self.mesh.faces, self.mesh.verts.len())?;
+        Ok(self.mesh)
+    }
+}
+
+impl Row {
+    fn finish(&mut self) -> &Self {
+        self.offsets.push(self.values.len());
+        self
+    }
+
+    fn get(&self, property: usize) -> &[f64] {
+        &self.values[self.offsets[property]..self.offsets[property + 1]]
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+        self.offsets.clear();
+    }
+}
+
+/// Reads one line of the header.
+fn next_line<T: Deserializer>(des: &mut T) -> Result<String> {
+    let mut line = Vec::new();
+    loop {
+        if des.is_eof() {
+            return Err(MeshError::UnexpectedEof);
+        }
+        match des.read_u8() {
+            b'\n' => break,
+            x => line.push(x),
+        }
+    }
+
+    let line = (String::from_utf8(line).ok()).context("Invalid header")?;
+    Ok(line.trim_end_matches('\r').to_owned())
+}
@@ -1,23 +1,40 @@
 use std::collections::HashMap;
 
-use anyhow::Result;
-use common::{progress::Progress, serde::Deserializer};
+use common::{
+    progress::Progress,
+    serde::{Deserializer, Serializer},
+};
 use nalgebra::Vector3;
 
 use crate::{
-    Mesh,
+    Mesh, MeshError, Object, Result,
     util::{WHITESPACE, tokenize},
 };
 
+const HEADER: &[u8] = b"Binary STL exported by mslicer";
+
 pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Mesh> {
-    des.advance_by(80);
-    let tri_count = des.read_u32_le();
-    let is_ascii = des.size() != 84 + 50 * tri_count as usize;
-    des.jump_to(0);
-
-    match is_ascii {
-        true => ascii::parse(des, progress),
-        false => binary::parse(des, progress),
+    let size = des.size();
+    let start = des.execute_at(0, |des| des.read_bytes(size.min(1024)).into_owned());
+    let is_ascii = start.trim_ascii_start().starts_with(b"solid") && !start.contains(&0);
+    if size < 84 {
+        return match is_ascii {
+            true => ascii::parse(des, progress),
+            false => Err(MeshError::UnexpectedEof),
+        };
+    }
+
+    // Some exporters start binary files with `solid` too, so only treat the
+    // file as ASCII if the triangle count doesn't match the file size. Text
+    // never has null bytes, which binary headers and counts usually do.
+    let tri_count = des.execute_at(80, |des| des.read_u32_le());
+    let binary_size = 84 + 50 * tri_count as u64;
+    if is_ascii && size as u64 != binary_size {
+        ascii::parse(des, progress)
+    } else if (size as u64) < binary_size {
+        Err(MeshError::UnexpectedEof)
+    } else {
+        binary::parse(des, progress)
     }
 }
 
@@ -56,6 +73,26 @@ mod binary {
 
         Ok(finish(verts, faces))
     }
+
+    pub fn serialize<T: Serializer>(ser: &mut T, objects: &[Object], progress: Progress) {
+        let mut header = [0; 80];
+        header[..HEADER.len()].copy_from_slice(HEADER);
+        ser.write_bytes(&header);
+
+        let tri_count = objects.iter().map(|x| x.mesh.faces.len()).sum::<usize>();
+        ser.write_u32_le(tri_count as u32);
+
+        for face in objects.iter().flat_map(Object::faces) {
+            progress.add_complete(1);
+
+            let normal = (face[1] - face[0]).cross(&(face[2] - face[0]));
+            let normal = normal.try_normalize(0.0).unwrap_or_default();
+            for vec in [normal, face[0], face[1], face[2]] {
+                vec.iter().for_each(|&x| ser.write_f32_le(x));
+            }
+            ser.write_u16_le(0);
+        }
+    }
 }
 
 /// ```plain
@@ -111,6 +148,8 @@ mod ascii {
     }
 }
 
+pub use binary::serialize;
+
 fn vert_idx(verts: &mut HashMap<Vector3<u32>, u32>, vert: Vector3<f32>) -> u32 {
     let size = verts.len() as u32;
     *verts.entry(vert.map(f32::to_bits)).or_insert(size)
@@ -0,0 +1,323 @@
+//! 3D Manufacturing Format (`.3mf`).
+//!
+//! A zip package holding an XML model with a list of mesh objects (which can be
+//! built out of other objects) and build items placing objects on the plate.
+//! Only the core specification is supported, materials, colors, and
+//! extensions are ignored.
+//!
+//! ## References
+//!
+//! - <https://github.com/3MFConsortium/spec_core/blob/master/3MF%20Core%20Specification.md>
+
+use std::{
+    collections::HashMap,
+    io::{BufWriter, Cursor, Read, Seek, Write},
+};
+
+use common::{
+    progress::Progress,
+    serde::{Deserializer, Serializer},
+};
+use nalgebra::{Matrix4, Vector3};
+use zip::{ZipArchive, ZipWriter, write::FileOptions};
+
+use crate::{
+    Mesh, MeshError, Object, Result,
+    error::{Context, check_indices},
+    xml::{self, Tag},
+};
+
+const DEFAULT_MODEL_PATH: &str = "3D/3dmodel.model";
+const MODEL_RELATIONSHIP: &str = "http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel";
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+  <Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+  <Default Extension="model" ContentType="application/vnd.ms-package.3dmanufacturing-3dmodel+xml"/>
+</Types>
+"#;
+const RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+  <Relationship Target="/3D/3dmodel.model" Id="rel0" Type="http://schemas.microsoft.com/3dmanufacturing/2013/01/3dmodel"/>
+</Relationships>
+"#;
+// Components can reference each other, so limit the depth to not overflow the
+// stack on (invalid) cyclic references.
+const MAX_COMPONENT_DEPTH: usize = 32;
+
+#[derive(Default)]
+struct Resource {
+    name: Option<String>,
+    mesh: Mesh,
+    components: Vec<(u32, Matrix4<f32>)>,
+}
+
+pub fn parse<T: Deserializer>(des: &mut T, progress: Progress) -> Result<Vec<Object>> {
+    let size = des.size();
+    let bytes = des.read_bytes(size);
+    let mut zip = ZipArchive::new(Cursor::new(bytes))?;
+
+    let path = model_path(&mut zip)?;
+    let mut model = String::new();
+    (zip.by_name(&path).ok())
+        .with_context(|| format!("Missing model part `{path}`"))?
+        .read_to_string(&mut model)?;
+
+    parse_model(&model, progress)
+}
+
+pub fn serialize<T: Serializer>(ser: &mut T, objects: &[Object], progress: Progress) -> Result<()> {
+    let mut bytes = Vec::new();
+    let mut zip = ZipWriter::new(Cursor::new(&mut bytes));
+
+    zip.start_file("[Content_Types].xml", FileOptions::DEFAULT)?;
+    zip.write_all(CONTENT_TYPES.as_bytes())?;
+    zip.start_file("_rels/.rels", FileOptions::DEFAULT)?;
+    zip.write_all(RELATIONSHIPS.as_bytes())?;
+
+    zip.start_file(DEFAULT_MODEL_PATH, FileOptions::DEFAULT)?;
+    // Lots of tiny writes, so buffer them before they get compressed.
+    let mut out = BufWriter::with_capacity(64 * 1024, &mut zip);
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<model unit="millimeter" xml:lang="en-US" xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">"#
+    )?;
+    writeln!(out, " <resources>")?;
+
+    for (i, object) in objects.iter().enumerate() {
+        let name = (object.name.as_deref())
+            .map(xml::escape)
+            .unwrap_or_default();
+        writeln!(
+            out,
+            r#"  <object id="{}" name="{name}" type="model">"#,
+            i + 1
+        )?;
+
+        writeln!(out, "   <mesh>\n    <vertices>")?;
+        for vert in object.mesh.verts.iter() {
+            let (x, y, z) = (vert.x, vert.y, vert.z);
+            writeln!(out, r#"     <vertex x="{x}" y="{y}" z="{z}"/>"#)?;
+        }
+
+        writeln!(out, "    </vertices>\n    <triangles>")?;
+        for [a, b, c] in object.mesh.faces.iter() {
+            progress.add_complete(1);
+            writeln!(out, r#"     <triangle v1="{a}" v2="{b}" v3="{c}"/>"#)?;
+        }
+
+        writeln!(out, "    </triangles>\n   </mesh>\n  </object>")?;
+    }
+
+    writeln!(out, " </resources>\n <build>")?;
+    for (i, object) in objects.iter().enumerate() {
+        let transform = write_transform(&object.transform);
+        writeln!(
+            out,
+            r#"  <item objectid="{}" transform="{transform}"/>"#,
+            i + 1
+        )?;
+    }
+    writeln!(out, " </build>\n</model>")?;
+    out.flush()?;
+    drop(out);
+
+    zip.finish()?;
+
+    ser.write_bytes(&bytes);
+    Ok(())
+}
+
+/// Finds the path of the root model part from the package relationships,
+/// falling back to the path used by basically every producer.
+fn model_path<T: Read + Seek>(zip: &mut ZipArchive<T>) -> Result<String> {
+    let Ok(mut rels) = zip.by_name("_rels/.rels") else {
+        return Ok(DEFAULT_MODEL_PATH.into());
+    };
+
+    let mut str = String::new();
+    rels.read_to_string(&mut str)?;
+
+    let mut path = None;
+    xml::tags(&str, |_, tag| {
+        if tag.name == "Relationship"
+            && tag.attr("Type").as_deref() == Some(MODEL_RELATIONSHIP)
+            && path.is_none()
+        {
+            let target = tag.attr("Target");
+            path = target.map(|x| x.trim_start_matches('/').to_owned());
+        }
+        Ok(())
+    })?;
+
+    Ok(path.unwrap_or_else(|| DEFAULT_MODEL_PATH.into()))
+}
+
+fn parse_model(model: &str, progress: Progress) -> Result<Vec<Object>> {
+    progress.set_total(model.len() as u64);
+
+    let mut scale = 1.0;
+    let mut resources = HashMap::<u32, Resource>::new();
+    let mut items = Vec::new();
+    let mut object = None;
+
+    xml::tags(model, |pos, tag| {
+        progress.set_complete(pos as u64);
+
+        if tag.name == "object" && tag.is_end() {
+            object = None;
+        }
+
+        if !tag.is_start() {
+            return Ok(());
+        }
+
+        match tag.name {
+            "model" => {
+                if let Some(unit) = tag.attr("unit") {
+                    scale = unit_scale(&unit).with_context(|| format!("Unknown unit `{unit}`"))?;
+                }
+            }
+            "object" => {
+                let id = attr::<u32>(&tag, "id")?;
+                let resource = Resource {
+                    name: tag.attr("name").map(|x| x.into_owned()),
+                    ..Default::default()
+                };
+                resources.insert(id, resource);
+                object = (tag.kind != xml::TagKind::Empty).then_some(id);
+            }
+            "vertex" => {
+                let resource = current(&mut resources, object)?;
+                let vertex = Vector3::new(attr(&tag, "x")?, attr(&tag, "y")?, attr(&tag, "z")?);
+                resource.mesh.verts.push(vertex);
+            }
+            "triangle" => {
+                let resource = current(&mut resources, object)?;
+                let face = [attr(&tag, "v1")?, attr(&tag, "v2")?, attr(&tag, "v3")?];
+                resource.mesh.faces.push(face);
+            }
+            "component" => {
+                let resource = current(&mut resources, object)?;
+                let component = (attr(&tag, "objectid")?, transform(&tag)?);
+                resource.components.push(component);
+            }
+            "item" => items.push((attr::<u32>(&tag, "objectid")?, transform(&tag)?)),
+            _ => {}
+        }
+
+        Ok(())
+    })?;
+
+    for resource in resources.values() {
+        check_indices(&resource.mesh.faces, resource.mesh.verts.len())?;
+    }
+
+    // Files without a build section are not valid, but we might as well just
+    // load every object in that case.
+    if items.is_empty() {
+        let mut ids = resources.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        items = ids.into_iter().map(|x| (x, Matrix4::identity())).collect();
+    }
+
+    let unit = Matrix4::new_scaling(scale);
+    let mut objects = Vec::new();
+    for (id, transform) in items {
+        let mut mesh = Mesh::default();
+        flatten(&resources, id, &Matrix4::identity(), &mut mesh, 0)?;
+        if mesh.faces.is_empty() {
+            continue;
+        }
+
+        objects.push(Object {
+            name: resources[&id].name.clone(),
+            mesh,
+            transform: unit * transform,
+        });
+    }
+
+    Ok(objects)
+}
+
+/// Collects the mesh of an object and all of its components into one mesh.
+fn flatten(
+    resources: &HashMap<u32, Resource>,
+    id: u32,
+    transform: &Matrix4<f32>,
+    out: &mut Mesh,
+    depth: usize,
+) -> Result<()> {
+    if depth > MAX_COMPONENT_DEPTH {
+        let message = "Components nested too deeply, likely a cyclic reference";
+        return Err(MeshError::Malformed(message.into()));
+    }
+
+    let resource = (resources.get(&id)).with_context(|| format!("Undefined object {id}"))?;
+    out.append(resource.mesh.transformed(transform));
+
+    for (child, child_transform) in resource.components.iter() {
+        let transform = transform * child_transform;
+        flatten(resources, *child, &transform, out, depth + 1)?;
+    }
+
+    Ok(())
+}
+
+fn current(resources: &mut HashMap<u32, Resource>, object: Option<u32>) -> Result<&mut Resource> {
+    object
+        .and_then(|id| resources.get_mut(&id))
+        .context("Mesh data outside of an object")
+}
+
+fn attr<T: std::str::FromStr>(tag: &Tag, key: &str) -> Result<T> {
+    let value =
+        (tag.attr(key)).with_context(|| format!("Missing attribute `{key}` on <{}>", tag.name))?;
+    (value.trim().parse().ok())
+        .with_context(|| format!("Invalid value `{value}` for `{key}` on <{}>", tag.name))
+}
+
+/// Parses the optional `transform` attribute. It is made up of 12 numbers
+/// defining a 4x3 matrix in row-major order, which is applied to row vectors
+/// (`[x y z 1] * M`).
+fn transform(tag: &Tag) -> Result<Matrix4<f32>> {
+    let Some(value) = tag.attr("transform") else {
+        return Ok(Matrix4::identity());
+    };
+
+    let m = (value.split_whitespace())
+        .map(|x| x.parse::<f32>().ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|x| x.len() == 12)
+        .with_context(|| format!("Invalid transform `{value}`"))?;
+
+    #[rustfmt::skip]
+    let out = Matrix4::new(
+        m[0], m[3], m[6], m[9],
+        m[1], m[4], m[7], m[10],
+        m[2], m[5], m[8], m[11],
+        0.0,  0.0,  0.0,  1.0,
+    );
+    Ok(out)
+}
+
+/// Inverse of [`transform`].
+fn write_transform(transform: &Matrix4<f32>) -> String {
+    (0..4)
+        .flat_map(|col| (0..3).map(move |row| transform[(row, col)].to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Millimeters per model unit.
+fn unit_scale(unit: &str) -> Option<f32> {
+    Some(match unit {
+        "micron" => 0.001,
+        "millimeter" => 1.0,
+        "centimeter" => 10.0,
+        "inch" => 25.4,
+        "foot" => 304.8,
+        "meter" => 1000.0,
+        _ => return None,
+    })
+}
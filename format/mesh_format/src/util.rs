@@ -1,6 +1,7 @@
-use anyhow::Result;
 use common::{progress::Progress, serde::Deserializer};
 
+use crate::Result;
+
 pub const WHITESPACE: [char; 4] = [' ', '\t', '\r', '\n'];
 
 pub fn tokenize<T: Deserializer>(
@@ -10,9 +11,10 @@ pub fn tokenize<T: Deserializer>(
     mut callback: impl FnMut(&str) -> Result<()>,
 ) -> Result<()> {
     let mut complete = 0;
-    let mut carry = String::new();
+    let mut carry = Vec::new();
     loop {
-        let next = des.read_bytes(8 * 1024);
+        let remaining = des.size().saturating_sub(des.pos());
+        let next = des.read_bytes(remaining.min(8 * 1024));
         if next.is_empty() && carry.is_empty() {
             break;
         }
@@ -20,10 +22,19 @@ pub fn tokenize<T: Deserializer>(
         complete += next.len() as u64;
         progress.set_complete(complete);
 
-        let str = carry + str::from_utf8(&next).unwrap();
-        let (str, new_carry) = str.rsplit_once(delimiter).unwrap_or(("", &str));
-        carry = new_carry.to_owned();
+        // Chunks can split multi-byte characters, so only decode up to the
+        // last delimiter and carry the rest over to the next chunk.
+        let is_eof = next.is_empty();
+        carry.extend_from_slice(&next);
+        let split = match is_eof {
+            true => carry.len(),
+            false => (carry.iter())
+                .rposition(|&x| x.is_ascii() && delimiter.contains(&(x as char)))
+                .map_or(0, |x| x + 1),
+        };
 
+        let chunk = carry.drain(..split).collect::<Vec<_>>();
+        let str = String::from_utf8_lossy(&chunk);
         for token in str.split(delimiter).filter(|x| !x.is_empty()) {
             callback(token)?;
         }
@@ -0,0 +1,220 @@
+//! Just enough of an XML tokenizer to read the 3MF core model, doesn't handle
+//! DTDs, CDATA sections, or namespaced lookups.
+
+use std::borrow::Cow;
+
+use crate::{MeshError, Result};
+
+pub struct Tag<'a> {
+    pub name: &'a str,
+    pub kind: TagKind,
+    attributes: &'a str,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TagKind {
+    /// `<name>`
+    Open,
+    /// `</name>`
+    Close,
+    /// `<name/>`
+    Empty,
+}
+
+/// Calls the callback for every element tag in the document along with the
+/// byte offset of the tag. Text content, comments, and processing instructions
+/// are skipped.
+pub fn tags<'a>(
+    xml: &'a str,
+    mut callback: impl FnMut(usize, Tag<'a>) -> Result<()>,
+) -> Result<()> {
+    let mut pos = 0;
+    while let Some(start) = xml[pos..].find('<') {
+        let start = pos + start;
+        let rest = &xml[start..];
+
+        if rest.starts_with("<!--") {
+            let Some(end) = rest.find("-->") else {
+                return Err(MeshError::Malformed("Unterminated comment".into()));
+            };
+            pos = start + end + 3;
+            continue;
+        }
+
+        let Some(end) = tag_end(rest) else {
+            return Err(MeshError::Malformed(format!(
+                "Unterminated tag at byte {start}"
+            )));
+        };
+        pos = start + end + 1;
+
+        let inner = &rest[1..end];
+        if inner.starts_with(['?', '!']) {
+            continue;
+        }
+
+        let (kind, inner) = if let Some(inner) = inner.strip_prefix('/') {
+            (TagKind::Close, inner)
+        } else if let Some(inner) = inner.strip_suffix('/') {
+            (TagKind::Empty, inner)
+        } else {
+            (TagKind::Open, inner)
+        };
+
+        let inner = inner.trim();
+        let split = inner.find(char::is_whitespace).unwrap_or(inner.len());
+        let (name, attributes) = inner.split_at(split);
+
+        callback(
+            start,
+            Tag {
+                name: local_name(name),
+                kind,
+                attributes,
+            },
+        )?;
+    }
+
+    Ok(())
+}
+
+impl<'a> Tag<'a> {
+    /// Gets an attribute value by its local name, with entities decoded.
+    pub fn attr(&self, key: &str) -> Option<Cow<'a, str>> {
+        let mut rest = self.attributes;
+        loop {
+            rest = rest.trim_start();
+            let (name, after) = rest.split_once('=')?;
+            let after = after.trim_start();
+            let quote = after.chars().next().filter(|x| matches!(x, '"' | '\''))?;
+            let after = &after[quote.len_utf8()..];
+            let end = after.find(quote)?;
+
+            if local_name(name.trim()) == key {
+                return Some(unescape(&after[..end]));
+            }
+
+            rest = &after[end + quote.len_utf8()..];
+        }
+    }
+
+    pub fn is_start(&self) -> bool {
+        matches!(self.kind, TagKind::Open | TagKind::Empty)
+    }
+
+    pub fn is_end(&self) -> bool {
+        matches!(self.kind, TagKind::Close | TagKind::Empty)
+    }
+}
+
+/// Finds the closing `>` of the tag starting at the beginning of `str`,
+/// ignoring any within quoted attribute values.
+fn tag_end(str: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, chr) in str.char_indices() {
+        match (quote, chr) {
+            (None, '"' | '\'') => quote = Some(chr),
+            (Some(q), _) if q == chr => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit_once(':').map(|x| x.1).unwrap_or(name)
+}
+
+pub fn unescape(str: &str) -> Cow<'_, str> {
+    if !str.contains('&') {
+        return Cow::Borrowed(str);
+    }
+
+    let mut out = String::with_capacity(str.len());
+    let mut rest = str;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let chr = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => numeric_entity(entity),
+        };
+
+        match chr {
+            Some(chr) => {
+                out.push(chr);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+/// Escapes a string for use in an attribute value.
+pub fn escape(str: &str) -> String {
+    let mut out = String::with_capacity(str.len());
+    for chr in str.chars() {
+        match chr {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            chr => out.push(chr),
+        }
+    }
+    out
+}
+
+/// Decodes character references like `&#65;` or `&#x41;`.
+fn numeric_entity(entity: &str) -> Option<char> {
+    let code = match entity.strip_prefix("#x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => entity.strip_prefix('#')?.parse().ok()?,
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tag, TagKind};
+
+    fn tag(attributes: &str) -> Tag<'_> {
+        Tag {
+            name: "vertex",
+            kind: TagKind::Empty,
+            attributes,
+        }
+    }
+
+    #[test]
+    fn attributes() {
+        let tag = tag(r#" x="1.5" p:y = '2' name="a &amp; b&#x41;""#);
+        assert_eq!(tag.attr("x").as_deref(), Some("1.5"));
+        assert_eq!(tag.attr("y").as_deref(), Some("2"));
+        assert_eq!(tag.attr("name").as_deref(), Some("a & bA"));
+        assert_eq!(tag.attr("z"), None);
+    }
+
+    #[test]
+    fn unquoted_attributes() {
+        assert_eq!(tag("a=é b=\"1\"").attr("b"), None);
+        assert_eq!(tag("a=1").attr("a"), None);
+        assert_eq!(tag("a=\"é").attr("a"), None);
+    }
+}
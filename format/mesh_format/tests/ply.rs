@@ -0,0 +1,99 @@
+use common::{progress::Progress, serde::SliceDeserializer};
+use mesh_format::{Mesh, MeshError, load_mesh};
+
+const HEADER: &str = "element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+element face 1
+property list uchar int vertex_indices
+end_header
+";
+
+const VERTS: [[f32; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [1.0, 0.0, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 0.5],
+];
+
+fn load(bytes: &[u8]) -> mesh_format::Result<Mesh> {
+    load_mesh(SliceDeserializer::new(bytes), "ply", Progress::new())
+}
+
+fn ascii(face: &str) -> Vec<u8> {
+    let mut out = format!("ply\nformat ascii 1.0\ncomment made by hand\n{HEADER}");
+    for [x, y, z] in VERTS {
+        out.push_str(&format!("{x} {y} {z} 255\n"));
+    }
+    out.push_str(face);
+    out.into_bytes()
+}
+
+fn binary(big_endian: bool) -> Vec<u8> {
+    let format = if big_endian { "big" } else { "little" };
+    let mut out = format!("ply\nformat binary_{format}_endian 1.0\n{HEADER}").into_bytes();
+    let bytes = |x: [u8; 4]| match big_endian {
+        true => x,
+        false => [x[3], x[2], x[1], x[0]],
+    };
+
+    for vert in VERTS {
+        vert.iter().for_each(|x| out.extend(bytes(x.to_be_bytes())));
+        out.push(255);
+    }
+
+    out.push(4);
+    (0..4_i32).for_each(|x| out.extend(bytes(x.to_be_bytes())));
+    out
+}
+
+fn check(mesh: &Mesh) {
+    let verts = (mesh.verts.iter())
+        .map(|x| [x.x, x.y, x.z])
+        .collect::<Vec<_>>();
+    assert_eq!(verts, VERTS);
+    // The quad is triangulated as a fan.
+    assert_eq!(mesh.faces, [[0, 1, 2], [0, 2, 3]]);
+}
+
+#[test]
+fn ascii_quad() {
+    check(&load(&ascii("4 0 1 2 3\n")).unwrap());
+}
+
+#[test]
+fn binary_little_endian() {
+    check(&load(&binary(false)).unwrap());
+}
+
+#[test]
+fn binary_big_endian() {
+    check(&load(&binary(true)).unwrap());
+}
+
+#[test]
+fn invalid_indices() {
+    for face in ["3 0 1 -1\n", "3 0 1 1.5\n", "3 0 1 nan\n"] {
+        assert!(
+            matches!(load(&ascii(face)), Err(MeshError::Malformed(_))),
+            "{face}"
+        );
+    }
+
+    assert!(matches!(
+        load(&ascii("3 0 1 4\n")),
+        Err(MeshError::InvalidIndex { index: 4, .. })
+    ));
+}
+
+#[test]
+fn truncated() {
+    for bytes in [ascii("4 0 1 2 3\n"), binary(false), binary(true)] {
+        let body = bytes.len() - 10;
+        assert!(load(&bytes[..body]).is_err());
+        // Cut off in the middle of the header
+        assert!(load(&bytes[..30]).is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use common::{
+    progress::Progress,
+    serde::{DynamicSerializer, SliceDeserializer},
+};
+use mesh_format::{Mesh, Object, load_objects, save_objects};
+use nalgebra::{Matrix4, Vector3};
+
+fn tetrahedron() -> Mesh {
+    Mesh {
+        verts: vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(0.0, 10.0, 0.0),
+            Vector3::new(0.0, 0.0, 10.0),
+        ],
+        faces: vec![[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]],
+    }
+}
+
+fn objects() -> Vec<Object> {
+    vec![
+        Object::new(Some("first".into()), tetrahedron(), Matrix4::identity()),
+        Object::new(
+            Some("second & <more>".into()),
+            tetrahedron(),
+            Matrix4::new_translation(&Vector3::new(20.0, 5.0, 0.0)) * Matrix4::new_scaling(0.5),
+        ),
+    ]
+}
+
+fn save(objects: &[Object], format: &str) -> Vec<u8> {
+    let mut ser = DynamicSerializer::new();
+    save_objects(&mut ser, objects, format, Progress::new()).unwrap();
+    ser.into_inner()
+}
+
+fn load(bytes: &[u8], format: &str) -> mesh_format::Result<Vec<Object>> {
+    load_objects(SliceDeserializer::new(bytes), format, Progress::new())
+}
+
+/// Every face of every object in build space.
+fn faces(objects: &[Object]) -> Vec<[Vector3<f32>; 3]> {
+    objects.iter().flat_map(|x| x.faces()).collect()
+}
+
+fn assert_same(a: &[[Vector3<f32>; 3]], b: &[[Vector3<f32>; 3]]) {
+    assert_eq!(a.len(), b.len());
+    for (a, b) in a.iter().zip(b) {
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).magnitude() < 1e-4, "{a:?} != {b:?}");
+        }
+    }
+}
+
+#[test]
+fn stl_and_obj() {
+    for format in ["stl", "obj"] {
+        let loaded = load(&save(&objects(), format), format).unwrap();
+        // Only one mesh is kept, with the transforms applied.
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].mesh.verts.len(), 8, "{format}");
+        assert_same(&faces(&loaded), &faces(&objects()));
+    }
+}
+
+#[test]
+fn three_mf() {
+    let loaded = load(&save(&objects(), "3mf"), "3mf").unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].name.as_deref(), Some("first"));
+    assert_eq!(loaded[1].name.as_deref(), Some("second & <more>"));
+    assert_eq!(loaded[1].transform, objects()[1].transform);
+    assert_same(&faces(&loaded), &faces(&objects()));
+}
+
+#[test]
+fn unsupported_format() {
+    let mut ser = DynamicSerializer::new();
+    assert!(save_objects(&mut ser, &objects(), "ply", Progress::new()).is_err());
+}
+
+#[test]
+fn truncated() {
+    let bytes = save(&objects(), "stl");
+    for len in [40, 84, bytes.len() - 1] {
+        assert!(load(&bytes[..len], "stl").is_err(), "stl {len} bytes");
+    }
+
+    let bytes = save(&objects(), "3mf");
+    assert!(load(&bytes[..bytes.len() - 1], "3mf").is_err());
+}
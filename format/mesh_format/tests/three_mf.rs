@@ -0,0 +1,85 @@
+use std::io::{Cursor, Write};
+
+use common::{progress::Progress, serde::SliceDeserializer};
+use mesh_format::{MeshError, load_objects};
+use zip::{ZipWriter, write::FileOptions};
+
+const MODEL: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<model unit="centimeter" xmlns="http://schemas.microsoft.com/3dmanufacturing/core/2015/02">
+ <resources>
+  <object id="1" name="Tri &amp; Co" type="model">
+   <mesh>
+    <vertices>
+     <vertex x="0" y="0" z="0"/>
+     <vertex x="1" y="0" z="0"/>
+     <vertex x="0" y="1" z="0"/>
+    </vertices>
+    <triangles>
+     <triangle v1="0" v2="1" v3="2"/>
+    </triangles>
+   </mesh>
+  </object>
+  <object id="2" type="model">
+   <components>
+    <component objectid="1" transform="1 0 0 0 1 0 0 0 1 0 0 2"/>
+   </components>
+  </object>
+ </resources>
+ <build>
+  <item objectid="1"/>
+  <item objectid="2" transform="1 0 0 0 1 0 0 0 1 5 0 0"/>
+ </build>
+</model>
+"#;
+
+fn package(model: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut zip = ZipWriter::new(Cursor::new(&mut bytes));
+    zip.start_file("3D/3dmodel.model", FileOptions::DEFAULT)
+        .unwrap();
+    zip.write_all(model.as_bytes()).unwrap();
+    zip.finish().unwrap();
+    bytes
+}
+
+fn load(bytes: &[u8]) -> mesh_format::Result<Vec<mesh_format::Object>> {
+    load_objects(SliceDeserializer::new(bytes), "3mf", Progress::new())
+}
+
+#[test]
+fn build_items_and_components() {
+    let objects = load(&package(MODEL)).unwrap();
+    assert_eq!(objects.len(), 2);
+    assert_eq!(objects[0].name.as_deref(), Some("Tri & Co"));
+
+    // Units are in centimeters, and the component is moved up by 2 then the
+    // item over by 5.
+    let faces = objects[1].faces().collect::<Vec<_>>();
+    assert_eq!(faces.len(), 1);
+    let [a, b, _] = faces[0];
+    assert!((a - nalgebra::Vector3::new(50.0, 0.0, 20.0)).magnitude() < 1e-4);
+    assert!((b - nalgebra::Vector3::new(60.0, 0.0, 20.0)).magnitude() < 1e-4);
+}
+
+#[test]
+fn malformed_models() {
+    let out_of_range = MODEL.replace(r#"v3="2""#, r#"v3="3""#);
+    assert!(matches!(
+        load(&package(&out_of_range)),
+        Err(MeshError::InvalidIndex { index: 3, .. })
+    ));
+
+    let unquoted = MODEL.replace(r#"x="1""#, "x=é1");
+    assert!(load(&package(&unquoted)).is_err());
+
+    let cyclic = MODEL.replace(r#"objectid="1" transform"#, r#"objectid="2" transform"#);
+    assert!(load(&package(&cyclic)).is_err());
+}
+
+#[test]
+fn truncated() {
+    let bytes = package(MODEL);
+    for len in [0, 10, bytes.len() / 2, bytes.len() - 1] {
+        assert!(load(&bytes[..len]).is_err(), "{len} bytes");
+    }
+}
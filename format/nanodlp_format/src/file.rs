@@ -3,12 +3,16 @@ use std::{
     time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use common::{
-    container::{Image, Run},
+    container::{Image, Run, SharedBytes},
     progress::Progress,
     serde::{DynamicSerializer, Serializer},
-    slice::{ExposureConfig, SliceConfig, SliceInfo, SliceMode, SlicedFile},
+    slice::{
+        DEFAULT_PAUSE_HOLD, ExposureConfig, SliceConfig, SliceDecoder, SliceInfo, SliceMode,
+        SlicedFile,
+    },
+    units::Milimeters,
 };
 use image::{DynamicImage, RgbaImage};
 use nalgebra::{Vector2, Vector3};
@@ -38,9 +42,32 @@ pub struct File {
 
 impl File {
     pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>, voxels: u64) -> Self {
+        let stop_layers = (layers.iter().enumerate())
+            .filter_map(|(i, layer)| layer.pause.then_some(i))
+            .collect::<Vec<_>>();
         let (layers, layer_info): (Vec<_>, Vec<_>) =
             layers.into_iter().map(|x| (x.inner, x.info)).unzip();
 
+        let mut file = Self {
+            layers,
+            ..Self::from_info(config, layer_info, voxels)
+        };
+        file.set_stop_layers(&stop_layers);
+        file
+    }
+
+    /// Sets the layers to stop the print at, by their index.
+    pub fn set_stop_layers(&mut self, layers: &[usize]) {
+        // NanoDLP numbers layers starting from one.
+        self.plate.stop_layers = (layers.iter())
+            .map(|x| (x + 1).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+    }
+
+    /// Creates a file without any layer images, with metadata describing the
+    /// layers in `layer_info`.
+    pub(crate) fn from_info(config: &SliceConfig, layer_info: Vec<LayerInfo>, voxels: u64) -> Self {
         let pixel_size = Vector2::new(
             config.platform_size.x / config.platform_resolution.x as f32,
             config.platform_size.y / config.platform_resolution.y as f32,
@@ -55,7 +82,7 @@ impl File {
             plate: Plate {
                 processed: true,
                 total_solid_area: voxel_volume.convert() * voxels as f32,
-                layers_count: layers.len() as u32,
+                layers_count: layer_info.len() as u32,
                 x_min: min.x,
                 x_max: max.x,
                 y_min: min.y,
@@ -102,14 +129,28 @@ impl File {
             preview: Default::default(), // overwritten later
 
             layer_info,
-            layers,
+            layers: Vec::new(),
         }
     }
 
     pub fn serialize<T: Serializer>(&self, ser: &mut T, progress: Progress) -> Result<()> {
         let mut bytes = Vec::new();
         let mut zip = ZipWriter::new(Cursor::new(&mut bytes));
+        self.serialize_metadata(&mut zip)?;
 
+        progress.set_total(self.layers.len() as u64);
+        for (i, layer) in self.layers.iter().enumerate() {
+            progress.set_complete(i as u64);
+            serialize_layer(&mut zip, i, layer)?;
+        }
+
+        drop(zip);
+        ser.write_bytes(&bytes);
+        Ok(())
+    }
+
+    /// Writes everything but the layer images to the archive.
+    pub(crate) fn serialize_metadata<W: Write + Seek>(&self, zip: &mut ZipWriter<W>) -> Result<()> {
         fn serialize_file<W, T>(zip: &mut ZipWriter<W>, name: &str, value: &T) -> Result<()>
         where
             W: Write + Seek,
@@ -120,30 +161,20 @@ impl File {
             Ok(())
         }
 
-        serialize_file(&mut zip, "meta.json", &self.meta)?;
-        serialize_file(&mut zip, "info.json", &self.layer_info)?;
-        serialize_file(&mut zip, "plate.json", &self.plate)?;
-        serialize_file(&mut zip, "profile.json", &self.profile)?;
+        serialize_file(zip, "meta.json", &self.meta)?;
+        serialize_file(zip, "info.json", &self.layer_info)?;
+        serialize_file(zip, "plate.json", &self.plate)?;
+        serialize_file(zip, "profile.json", &self.profile)?;
 
         // The actual NanoDLP software seems to work with options.json, but this
         // is not currently recognized by UVTools like slicer.json is. Not sure
         // whats going on here, but to maximize compatibility Ill just output
         // both?
-        serialize_file(&mut zip, "options.json", &self.options)?;
-        serialize_file(&mut zip, "slicer.json", &self.options)?;
+        serialize_file(zip, "options.json", &self.options)?;
+        serialize_file(zip, "slicer.json", &self.options)?;
 
         zip.start_file("3d.png", FileOptions::DEFAULT)?;
         zip.write_all(&encode_png(&self.preview)?)?;
-
-        progress.set_total(self.layers.len() as u64);
-        for (i, layer) in self.layers.iter().enumerate() {
-            progress.set_complete(i as u64);
-            zip.start_file(format!("{}.png", i + 1), FileOptions::DEFAULT)?;
-            zip.write_all(layer)?;
-        }
-
-        drop(zip);
-        ser.write_bytes(&bytes);
         Ok(())
     }
 
@@ -168,7 +199,13 @@ impl File {
 
         let preview = decode_png(&read_to_bytes(zip.by_name("3d.png")?)?)?;
         let layers = (0..layer_info.len())
-            .map(|i| read_to_bytes(zip.by_name(&format!("{}.png", i + 1))?))
+            .map(|i| {
+                let layer = read_to_bytes(zip.by_name(&format!("{}.png", i + 1))?)?;
+                // Layers are decoded whenever their runs are needed, which
+                // can't fail, so make sure they can be decoded up front.
+                decode_png(&layer).with_context(|| format!("Layer {} is corrupt", i + 1))?;
+                Ok(layer)
+            })
             .collect::<Result<Vec<_>>>()?;
 
         Ok(File {
@@ -194,27 +231,19 @@ impl File {
             mode: SliceMode::Raster,
             supersample: 0,
             exposure_remap: Default::default(),
+            uniformity: None,
             platform_resolution: Vector2::new(self.options.p_width, self.options.p_height),
             platform_size,
             slice_height: self.profile.depth.convert(),
+            compensation: Default::default(),
             exposure_config: ExposureConfig::default(),
             first_exposure_config: ExposureConfig::default(),
             first_layers: 0,
             transition_layers: 0,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
         }
     }
-
-    pub fn into_layers(&self) -> impl Iterator<Item = common::slice::Layer> {
-        self.layers.iter().map(|layer| {
-            let data = LayerDecoder::new(layer).runs().collect();
-
-            common::slice::Layer::new(
-                data,
-                self.profile.depth.convert(),
-                ExposureConfig::default(),
-            )
-        })
-    }
 }
 
 impl SlicedFile for File {
@@ -236,7 +265,8 @@ impl SlicedFile for File {
     }
 
     fn runs(&self, layer: usize) -> Box<dyn Iterator<Item = Run> + '_> {
-        let decoder = LayerDecoder::new(&self.layers[layer]);
+        let decoder =
+            LayerDecoder::new(&self.layers[layer]).expect("Layers are checked when loaded");
         Box::new(decoder.runs().collect::<Vec<_>>().into_iter())
     }
 
@@ -245,3 +275,38 @@ impl SlicedFile for File {
         self.layers[layer] = encoder.image_data();
     }
 }
+
+impl SliceDecoder for File {
+    fn decode(data: &SharedBytes) -> Result<Self> {
+        Self::deserialize(Cursor::new(&**data))
+    }
+
+    fn slice_config(&self) -> SliceConfig {
+        self.into_slice_config()
+    }
+
+    fn previews(&self) -> Vec<RgbaImage> {
+        vec![self.preview.to_rgba8()]
+    }
+
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig) {
+        let height = self.profile.depth * (layer + 1) as f32;
+        (height.convert(), ExposureConfig::default())
+    }
+
+    fn layer_pause(&self, layer: usize) -> bool {
+        (self.plate.stop_layers.split(','))
+            .any(|x| x.trim().parse::<usize>().ok() == Some(layer + 1))
+    }
+}
+
+/// Writes the png encoded image of the layer at `index` to the archive.
+pub(crate) fn serialize_layer<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    index: usize,
+    layer: &[u8],
+) -> Result<()> {
+    zip.start_file(format!("{}.png", index + 1), FileOptions::DEFAULT)?;
+    zip.write_all(layer)?;
+    Ok(())
+}
@@ -1,3 +1,4 @@
+use anyhow::Result;
 use common::{
     container::{
         Clusters, Image, ImageRuns,
@@ -19,6 +20,8 @@ use crate::{decode_png, types::LayerInfo};
 pub struct Layer {
     pub inner: Vec<u8>, // png encoded
     pub info: LayerInfo,
+    /// If printing should be stopped at this layer.
+    pub pause: bool,
 }
 
 /// Encodes a series of runs into the internal layer format.
@@ -110,15 +113,20 @@ impl EncodableLayer for LayerEncoder {
                 area_count: islands.len() as u32,
             },
             inner: self.image_data(),
+            pause: false,
         }
     }
+
+    fn pause(layer: &mut Self::Output, _config: &SliceConfig) {
+        layer.pause = true;
+    }
 }
 
 impl LayerDecoder {
-    pub fn new(data: &[u8]) -> Self {
-        Self {
-            image: decode_png(data).unwrap().to_rgb8(),
-        }
+    pub fn new(data: &[u8]) -> Result<Self> {
+        Ok(Self {
+            image: decode_png(data)?.to_rgb8(),
+        })
     }
 
     pub fn runs(&self) -> impl Iterator<Item = Run> {
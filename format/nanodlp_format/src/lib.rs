@@ -16,9 +16,11 @@ use image::{DynamicImage, ImageFormat, codecs::png::PngDecoder};
 mod file;
 mod layer;
 mod types;
+mod writer;
 pub use crate::{
     file::File,
     layer::{Layer, LayerDecoder, LayerEncoder},
+    writer::FileWriter,
 };
 
 fn read_to_bytes<T: Read>(mut reader: T) -> Result<Vec<u8>> {
@@ -0,0 +1,55 @@
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+
+use common::slice::{SliceConfig, SliceWriter, SlicedFile};
+use image::RgbaImage;
+use zip::ZipWriter;
+
+use crate::{File, Layer, LayerEncoder, file::serialize_layer, types::LayerInfo};
+
+/// Writes a NanoDLP file to a stream one layer at a time. The metadata is
+/// written after the layers, once all the layer info is known.
+pub struct FileWriter<T: Write + Seek> {
+    zip: ZipWriter<T>,
+    config: SliceConfig,
+    preview: RgbaImage,
+    layer_info: Vec<LayerInfo>,
+    stop_layers: Vec<usize>,
+}
+
+impl<T: Write + Seek> FileWriter<T> {
+    pub fn new(stream: T, config: &SliceConfig, preview: &RgbaImage) -> Self {
+        Self {
+            zip: ZipWriter::new(stream),
+            config: config.clone(),
+            preview: preview.clone(),
+            layer_info: Vec::new(),
+            stop_layers: Vec::new(),
+        }
+    }
+}
+
+impl<T: Write + Seek> SliceWriter for FileWriter<T> {
+    type Encoder = LayerEncoder;
+
+    fn write_layer(&mut self, layer: Layer) -> Result<()> {
+        let index = self.layer_info.len();
+        serialize_layer(&mut self.zip, index, &layer.inner)?;
+        if layer.pause {
+            self.stop_layers.push(index);
+        }
+        self.layer_info.push(layer.info);
+        Ok(())
+    }
+
+    fn finish(mut self, voxels: u64) -> Result<()> {
+        let mut file = File::from_info(&self.config, self.layer_info, voxels);
+        file.set_stop_layers(&self.stop_layers);
+        file.set_preview(&self.preview);
+        file.serialize_metadata(&mut self.zip)?;
+
+        self.zip.finish()?.flush()?;
+        Ok(())
+    }
+}
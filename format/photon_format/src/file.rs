@@ -0,0 +1,262 @@
+use anyhow::{Result, ensure};
+
+use common::{
+    container::{Image, Run, SharedBytes},
+    progress::Progress,
+    serde::{Deserializer, DynamicSerializer, Serializer, SizedString, SliceDeserializer},
+    slice::{
+        DEFAULT_PAUSE_HOLD, ExposureConfig, SliceConfig, SliceDecoder, SliceInfo, SliceMode,
+        SlicedFile,
+    },
+    units::{Centimeter, Milimeters, Second},
+};
+use image::{RgbaImage, imageops::FilterType};
+
+use crate::{
+    FILE_MARK, Header, Layer, LayerDecoder, LayerEncoder, PreviewImage, Section,
+    layer::LAYER_DEF_SIZE, writer::LayerTable,
+};
+
+/// Version of the format written by [`File::serialize`].
+const FORMAT_VERSION: u32 = 1;
+/// Number of section addresses in the file mark of version 1 files.
+const AREA_COUNT: u32 = 4;
+/// Size of the file mark and section address table at the start of the file.
+const FILE_TABLE_SIZE: usize = 40;
+const LAYER_DEF_MARK: &str = "LAYERDEF";
+/// Dollar sign, used for the price.
+const CURRENCY: u32 = 0x24;
+
+/// A Photon Workshop file.
+#[derive(Debug)]
+pub struct File {
+    pub version: u32,
+    pub header: Header,
+    pub preview: PreviewImage,
+    pub layers: Vec<Layer>,
+}
+
+impl File {
+    pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>, voxels: u64) -> Self {
+        let mut file = Self::from_config(config, layers.len() as u32, voxels);
+
+        // Printers ignore the settings in the layer definitions unless told
+        // otherwise, so only enable it if needed.
+        file.header.per_layer_override =
+            (layers.iter().enumerate()).any(|(i, layer)| layer.overrides_config(config, i as u32));
+        file.layers = layers;
+        file
+    }
+
+    /// Creates a file without any layers, with a header describing
+    /// `layer_count` layers.
+    pub(crate) fn from_config(config: &SliceConfig, layer_count: u32, voxels: u64) -> Self {
+        let pixel_size = config.platform_size.x.raw() / config.platform_resolution.x as f32;
+
+        let exposure = &config.exposure_config;
+        let first_exposure = &config.first_exposure_config;
+        Self {
+            version: FORMAT_VERSION,
+            header: Header {
+                pixel_size: pixel_size * 1000.0,
+                layer_height: config.slice_height,
+                exposure_time: exposure.exposure_time,
+                light_off_delay: exposure.exposure_delay,
+                bottom_exposure_time: first_exposure.exposure_time,
+                bottom_layers: config.first_layers,
+                lift_height: exposure.lift_distance,
+                lift_speed: exposure.lift_speed.convert(),
+                retract_speed: exposure.retract_speed.convert(),
+                volume: (config.voxel_volume() * voxels as f32).convert::<Centimeter>(),
+                anti_aliasing: 1,
+                resolution: config.platform_resolution,
+                weight: 0.0,
+                price: 0.0,
+                price_currency: CURRENCY,
+                per_layer_override: false,
+                print_time: config.print_time(layer_count).get::<Second>() as u32,
+                transition_layers: config.transition_layers,
+            },
+            preview: PreviewImage::empty(),
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        let mut table = self.serialize_header(ser, self.layers.len());
+        for layer in &self.layers {
+            table.write_layer(ser, layer);
+        }
+        table.finish(ser);
+    }
+
+    /// Writes everything before the layer data, returning the table that
+    /// layers are then added to.
+    pub(crate) fn serialize_header<T: Serializer>(&self, ser: &mut T, layers: usize) -> LayerTable {
+        SizedString::<12>::new(FILE_MARK).serialize(ser);
+        ser.write_u32_le(FORMAT_VERSION);
+        ser.write_u32_le(AREA_COUNT);
+        let header = ser.reserve(4);
+        ser.write_u32_le(0); // software
+        let preview = ser.reserve(4);
+        ser.write_u32_le(0); // layer image color table
+        let layer_def = ser.reserve(4);
+        ser.write_u32_le(0); // extra
+        ser.write_u32_le(0); // machine
+        let layer_image = ser.reserve(4);
+
+        let header_pos = ser.pos();
+        ser.execute_at(header, |ser| ser.write_u32_le(header_pos as u32));
+        self.header.serialize(ser);
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(preview, |ser| ser.write_u32_le(pos));
+        self.preview.serialize(ser);
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(layer_def, |ser| ser.write_u32_le(pos));
+        Section::new(LAYER_DEF_MARK, 4 + LAYER_DEF_SIZE * layers).serialize(ser);
+        ser.write_u32_le(layers as u32);
+        let layer_defs = ser.reserve(LAYER_DEF_SIZE * layers);
+
+        let pos = ser.pos() as u32;
+        ser.execute_at(layer_image, |ser| ser.write_u32_le(pos));
+        LayerTable::new(header_pos, layer_defs)
+    }
+
+    pub fn deserialize(des: &mut SliceDeserializer) -> Result<Self> {
+        ensure!(des.size() >= FILE_TABLE_SIZE, "Not a Photon file");
+        let mark = SizedString::<12>::deserialize(des);
+        ensure!(
+            mark.to_string().as_bytes() == FILE_MARK,
+            "Not a Photon file"
+        );
+
+        let version = des.read_u32_le();
+        let _area_count = des.read_u32_le();
+        let header = des.read_u32_le() as usize;
+        des.advance_by(4);
+        let preview = des.read_u32_le() as usize;
+        des.advance_by(4);
+        let layer_def = des.read_u32_le() as usize;
+
+        ensure!(
+            [header, preview, layer_def].iter().all(|&x| x < des.size()),
+            "Section address out of bounds"
+        );
+
+        let header = des.execute_at(header, |des| Header::deserialize(des))?;
+        let preview = des.execute_at(preview, |des| PreviewImage::deserialize(des))?;
+
+        des.jump_to(layer_def);
+        let section = Section::deserialize(des, LAYER_DEF_MARK)?;
+        ensure!(section.length >= 4, "Layer definition table out of bounds");
+        let layer_count = des.read_u32_le() as usize;
+        ensure!(
+            layer_count.saturating_mul(LAYER_DEF_SIZE) <= des.size() - des.pos(),
+            "Layer definition table out of bounds"
+        );
+
+        let mut layers = Vec::with_capacity(layer_count);
+        let mut previous_z = Milimeters::new(0.0);
+        for _ in 0..layer_count {
+            let layer = Layer::deserialize(des, previous_z)?;
+            previous_z = layer.position_z;
+            layers.push(layer);
+        }
+
+        Ok(Self {
+            version,
+            header,
+            preview,
+            layers,
+        })
+    }
+
+    pub fn into_slice_config(&self) -> SliceConfig {
+        let header = &self.header;
+        let height = (self.layers.last()).map(|x| x.position_z.raw());
+        let exposure_config = |exposure_time| ExposureConfig {
+            exposure_time,
+            exposure_delay: header.light_off_delay,
+            pwm: 255,
+            lift_distance: header.lift_height,
+            lift_speed: header.lift_speed.convert(),
+            retract_distance: header.lift_height,
+            retract_speed: header.retract_speed.convert(),
+            ..Default::default()
+        };
+
+        SliceConfig {
+            mode: SliceMode::Raster,
+            supersample: 0,
+            exposure_remap: Default::default(),
+            uniformity: None,
+            platform_resolution: header.resolution,
+            platform_size: (header.platform_size())
+                .push(Milimeters::new(height.unwrap_or_default())),
+            slice_height: header.layer_height,
+            compensation: Default::default(),
+            exposure_config: exposure_config(header.exposure_time),
+            first_exposure_config: exposure_config(header.bottom_exposure_time),
+            first_layers: header.bottom_layers,
+            transition_layers: header.transition_layers,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
+        }
+    }
+}
+
+impl SlicedFile for File {
+    fn serialize(&self, ser: &mut DynamicSerializer, progress: Progress) {
+        self.serialize(ser);
+        progress.set_total(1);
+        progress.set_finished();
+    }
+
+    fn set_preview(&mut self, preview: &RgbaImage) {
+        self.preview = PreviewImage::from_image_scaled(preview, FilterType::Nearest);
+    }
+
+    fn info(&self) -> SliceInfo {
+        let height = (self.layers.last()).map(|x| x.position_z.raw());
+        SliceInfo {
+            layers: self.layers.len() as u32,
+            resolution: self.header.resolution,
+            size: (self.header.platform_size()).push(Milimeters::new(height.unwrap_or_default())),
+            bottom_layers: self.header.bottom_layers,
+        }
+    }
+
+    fn runs(&self, layer: usize) -> Box<dyn Iterator<Item = Run> + '_> {
+        Box::new(LayerDecoder::new(&self.layers[layer].data))
+    }
+
+    fn overwrite_layer(&mut self, layer: usize, image: Image) {
+        let mut encoder = LayerEncoder::new();
+        (image.runs()).for_each(|run| encoder.add_run(run.length, run.value));
+
+        let layer = &mut self.layers[layer];
+        layer.non_zero_pixels = encoder.non_zero_pixels() as u32;
+        layer.data = encoder.into_inner().into();
+    }
+}
+
+impl SliceDecoder for File {
+    fn decode(data: &SharedBytes) -> Result<Self> {
+        Self::deserialize(&mut SliceDeserializer::new_shared(data))
+    }
+
+    fn slice_config(&self) -> SliceConfig {
+        self.into_slice_config()
+    }
+
+    fn previews(&self) -> Vec<RgbaImage> {
+        vec![self.preview.into_image()]
+    }
+
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig) {
+        let layer = &self.layers[layer];
+        (layer.position_z, layer.exposure_config(&self.header))
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::{Result, ensure};
+
+use common::{
+    serde::{Deserializer, Serializer, SliceDeserializer},
+    units::{Milimeters, MilimetersPerSecond, Milliliters, Seconds},
+};
+use nalgebra::Vector2;
+
+use crate::Section;
+
+pub(crate) const HEADER_MARK: &str = "HEADER";
+const HEADER_LENGTH: usize = 80;
+
+/// Global print settings.
+#[derive(Debug, Clone)]
+pub struct Header {
+    /// Size of a single pixel, in micrometers.
+    pub pixel_size: f32,
+    /// Thickness of each layer.
+    pub layer_height: Milimeters,
+    pub exposure_time: Seconds,
+    /// Time to wait with the light off before exposing each layer.
+    pub light_off_delay: Seconds,
+    pub bottom_exposure_time: Seconds,
+    /// Number of bottom layers, stored as a float for some reason.
+    pub bottom_layers: u32,
+    pub lift_height: Milimeters,
+    pub lift_speed: MilimetersPerSecond,
+    pub retract_speed: MilimetersPerSecond,
+    /// Volume of resin used by the print.
+    pub volume: Milliliters,
+    /// Anti-aliasing level, 1 for none.
+    pub anti_aliasing: u32,
+    pub resolution: Vector2<u32>,
+    /// Weight of the resin used, in grams.
+    pub weight: f32,
+    pub price: f32,
+    /// Currency symbol of the price, `$` (0x24) or `¥` (0xA5).
+    pub price_currency: u32,
+    /// If the print settings stored in the layer definitions should be used
+    /// instead of the ones in this header.
+    pub per_layer_override: bool,
+    /// Estimated print time, in seconds.
+    pub print_time: u32,
+    pub transition_layers: u32,
+}
+
+impl Header {
+    pub fn deserialize(des: &mut SliceDeserializer) -> Result<Self> {
+        let section = Section::deserialize(des, HEADER_MARK)?;
+        ensure!(
+            section.length as usize >= HEADER_LENGTH,
+            "Header section is too short"
+        );
+
+        let this = Self {
+            pixel_size: des.read_f32_le(),
+            layer_height: Milimeters::new(des.read_f32_le()),
+            exposure_time: Seconds::new(des.read_f32_le()),
+            light_off_delay: Seconds::new(des.read_f32_le()),
+            bottom_exposure_time: Seconds::new(des.read_f32_le()),
+            bottom_layers: des.read_f32_le() as u32,
+            lift_height: Milimeters::new(des.read_f32_le()),
+            lift_speed: MilimetersPerSecond::new(des.read_f32_le()),
+            retract_speed: MilimetersPerSecond::new(des.read_f32_le()),
+            volume: Milliliters::new(des.read_f32_le()),
+            anti_aliasing: des.read_u32_le(),
+            resolution: Vector2::new(des.read_u32_le(), des.read_u32_le()),
+            weight: des.read_f32_le(),
+            price: des.read_f32_le(),
+            price_currency: des.read_u32_le(),
+            per_layer_override: des.read_u32_le() != 0,
+            print_time: des.read_u32_le(),
+            transition_layers: des.read_u32_le(),
+        };
+        des.advance_by(4);
+        Ok(this)
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        Section::new(HEADER_MARK, HEADER_LENGTH).serialize(ser);
+        ser.write_f32_le(self.pixel_size);
+        ser.write_f32_le(self.layer_height.raw());
+        ser.write_f32_le(self.exposure_time.raw());
+        ser.write_f32_le(self.light_off_delay.raw());
+        ser.write_f32_le(self.bottom_exposure_time.raw());
+        ser.write_f32_le(self.bottom_layers as f32);
+        ser.write_f32_le(self.lift_height.raw());
+        ser.write_f32_le(self.lift_speed.raw());
+        ser.write_f32_le(self.retract_speed.raw());
+        ser.write_f32_le(self.volume.raw());
+        ser.write_u32_le(self.anti_aliasing);
+        ser.write_u32_le(self.resolution.x);
+        ser.write_u32_le(self.resolution.y);
+        ser.write_f32_le(self.weight);
+        ser.write_f32_le(self.price);
+        ser.write_u32_le(self.price_currency);
+        ser.write_u32_le(self.per_layer_override as u32);
+        ser.write_u32_le(self.print_time);
+        ser.write_u32_le(self.transition_layers);
+        ser.write_u32_le(0);
+    }
+
+    /// Size of the display in the X and Y directions.
+    pub fn platform_size(&self) -> Vector2<Milimeters> {
+        (self.resolution).map(|x| Milimeters::new(x as f32 * self.pixel_size / 1000.0))
+    }
+}
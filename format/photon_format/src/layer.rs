@@ -0,0 +1,109 @@
+use std::fmt::{self, Debug};
+
+use anyhow::{Result, ensure};
+
+use common::{
+    container::SharedBytes,
+    serde::{Deserializer, Serializer, SliceDeserializer},
+    slice::{ExposureConfig, SliceConfig},
+    units::{Milimeters, MilimetersPerSecond, Seconds},
+};
+
+use crate::Header;
+
+/// Size of each entry in the layer definition table.
+pub(crate) const LAYER_DEF_SIZE: usize = 32;
+
+/// Layer data with it's print parameters.
+pub struct Layer {
+    pub position_z: Milimeters,
+    pub exposure_time: Seconds,
+    pub lift_height: Milimeters,
+    pub lift_speed: MilimetersPerSecond,
+    /// Number of pixels that are not fully black, used by the printer to
+    /// estimate resin usage.
+    pub non_zero_pixels: u32,
+    pub data: SharedBytes,
+}
+
+impl Layer {
+    /// Reads an entry of the layer definition table along with the layer data
+    /// it points to. The table only stores the thickness of each layer, so
+    /// the position of the previous layer is needed to place this one.
+    pub fn deserialize(des: &mut SliceDeserializer, previous_z: Milimeters) -> Result<Self> {
+        let address = des.read_u32_le() as usize;
+        let length = des.read_u32_le() as usize;
+        let lift_height = Milimeters::new(des.read_f32_le());
+        let lift_speed = MilimetersPerSecond::new(des.read_f32_le());
+        let exposure_time = Seconds::new(des.read_f32_le());
+        let layer_height = Milimeters::new(des.read_f32_le());
+        let non_zero_pixels = des.read_u32_le();
+        des.advance_by(4);
+
+        ensure!(
+            address.saturating_add(length) <= des.size(),
+            "Layer data out of bounds"
+        );
+        let data = des.execute_at(address, |des| des.read_shared(length));
+
+        Ok(Self {
+            position_z: previous_z + layer_height,
+            exposure_time,
+            lift_height,
+            lift_speed,
+            non_zero_pixels,
+            data,
+        })
+    }
+
+    /// Writes the layer definition table entry, the layer data is written
+    /// separately at `address`.
+    pub fn serialize<T: Serializer>(&self, ser: &mut T, address: usize, previous_z: Milimeters) {
+        ser.write_u32_le(address as u32);
+        ser.write_u32_le(self.data.len() as u32);
+        ser.write_f32_le(self.lift_height.raw());
+        ser.write_f32_le(self.lift_speed.raw());
+        ser.write_f32_le(self.exposure_time.raw());
+        ser.write_f32_le(self.position_z.raw() - previous_z.raw());
+        ser.write_u32_le(self.non_zero_pixels);
+        ser.write_u32_le(0);
+    }
+
+    /// Checks if the layer's settings differ from the ones the slice config
+    /// gives that layer.
+    pub(crate) fn overrides_config(&self, config: &SliceConfig, layer: u32) -> bool {
+        let exposure = config.exposure_config(layer);
+        let height = config.default_height(layer);
+        let lift_speed: MilimetersPerSecond = exposure.lift_speed.convert();
+        self.exposure_time.raw() != exposure.exposure_time.raw()
+            || self.lift_height.raw() != exposure.lift_distance.raw()
+            || self.lift_speed.raw() != lift_speed.raw()
+            || (self.position_z.raw() - height.raw()).abs() > 1e-4
+    }
+
+    /// Settings that aren't stored per layer are taken from the header.
+    pub fn exposure_config(&self, header: &Header) -> ExposureConfig {
+        ExposureConfig {
+            exposure_time: self.exposure_time,
+            exposure_delay: header.light_off_delay,
+            pwm: 255,
+            lift_distance: self.lift_height,
+            lift_speed: self.lift_speed.convert(),
+            retract_distance: self.lift_height,
+            retract_speed: header.retract_speed.convert(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Debug for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Layer")
+            .field("position_z", &self.position_z)
+            .field("exposure_time", &self.exposure_time)
+            .field("lift_height", &self.lift_height)
+            .field("lift_speed", &self.lift_speed)
+            .field("non_zero_pixels", &self.non_zero_pixels)
+            .finish()
+    }
+}
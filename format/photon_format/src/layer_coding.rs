@@ -0,0 +1,147 @@
+use common::{
+    container::Run,
+    slice::{EncodableLayer, ExposureConfig, SliceConfig},
+    units::Milimeters,
+};
+use nalgebra::Vector2;
+
+use crate::Layer;
+
+/// Longest run that can be stored in a single black or white chunk.
+const MAX_FULL_RUN: u64 = 0xFFF;
+/// Longest run that can be stored in a single grey chunk.
+const MAX_GREY_RUN: u64 = 0xF;
+
+/// Encodes a series of runs into the PW0 layer format.
+pub struct LayerEncoder {
+    data: Vec<u8>,
+    color: u8,
+    length: u64,
+    non_zero_pixels: u64,
+}
+
+/// Decodes the PW0 layer format to a series of runs.
+pub struct LayerDecoder<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl LayerEncoder {
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            color: 0,
+            length: 0,
+            non_zero_pixels: 0,
+        }
+    }
+
+    pub fn add_run(&mut self, length: u64, value: u8) {
+        // Only the top four bits of each pixel are stored, so runs that only
+        // differ in the lower bits are merged.
+        let color = value >> 4;
+        if color != 0 {
+            self.non_zero_pixels += length;
+        }
+
+        if color != self.color {
+            self.flush();
+            self.color = color;
+        }
+
+        self.length += length;
+    }
+
+    /// Number of pixels that are not fully black.
+    pub fn non_zero_pixels(&self) -> u64 {
+        self.non_zero_pixels
+    }
+
+    pub fn into_inner(mut self) -> Vec<u8> {
+        self.flush();
+        self.data
+    }
+
+    fn flush(&mut self) {
+        // byte 0: ccccllll
+        // c => the 4 bit color, 0x0 is black and 0xF is white
+        // l => the run length, or the top four bits of it for black and white
+        // byte 1: optional, the lower 8 bits of the run length for black and
+        //         white runs
+        let full = self.color == 0x0 || self.color == 0xF;
+        let max = if full { MAX_FULL_RUN } else { MAX_GREY_RUN };
+
+        while self.length > 0 {
+            let length = self.length.min(max);
+            self.length -= length;
+
+            if full {
+                self.data.push((self.color << 4) | (length >> 8) as u8);
+                self.data.push(length as u8);
+            } else {
+                self.data.push((self.color << 4) | length as u8);
+            }
+        }
+    }
+}
+
+impl EncodableLayer for LayerEncoder {
+    type Output = Layer;
+
+    fn new(_platform: Vector2<u32>) -> Self {
+        Self::default()
+    }
+
+    fn add_run(&mut self, length: u64, value: u8) {
+        self.add_run(length, value);
+    }
+
+    fn finish(
+        self,
+        _slice_config: &SliceConfig,
+        exposure: &ExposureConfig,
+        height: Milimeters,
+    ) -> Self::Output {
+        Layer {
+            position_z: height,
+            exposure_time: exposure.exposure_time,
+            lift_height: exposure.lift_distance,
+            lift_speed: exposure.lift_speed.convert(),
+            non_zero_pixels: self.non_zero_pixels() as u32,
+            data: self.into_inner().into(),
+        }
+    }
+}
+
+impl<'a> LayerDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, offset: 0 }
+    }
+}
+
+impl Iterator for LayerDecoder<'_> {
+    type Item = Run;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = *self.data.get(self.offset)?;
+        self.offset += 1;
+
+        let color = head >> 4;
+        let mut length = (head & 0x0F) as u64;
+        if color == 0x0 || color == 0xF {
+            length = (length << 8) | *self.data.get(self.offset)? as u64;
+            self.offset += 1;
+        }
+
+        Some(Run {
+            length,
+            value: color * 17,
+        })
+    }
+}
+
+impl Default for LayerEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
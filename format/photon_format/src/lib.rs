@@ -0,0 +1,74 @@
+//! Anycubic Photon Workshop format (`.pwmx`, `.pwma`, `.pm3`, ...).
+//!
+//! The file starts with a table of section addresses, followed by the
+//! sections themselves. Each section starts with a 12 byte name and its
+//! length. Only version 1 files are written, but newer versions are readable as
+//! they just add more sections.
+//!
+//! ## References
+//!
+//! - [UV Tools](https://github.com/sn4k3/UVtools)
+
+use anyhow::{Result, ensure};
+
+use common::serde::{Deserializer, Serializer, SizedString, SliceDeserializer};
+
+mod file;
+mod header;
+mod layer;
+mod layer_coding;
+mod preview;
+mod writer;
+
+pub use crate::{
+    file::File,
+    header::Header,
+    layer::Layer,
+    layer_coding::{LayerDecoder, LayerEncoder},
+    preview::PreviewImage,
+    writer::FileWriter,
+};
+
+const FILE_MARK: &[u8] = b"ANYCUBIC";
+const SECTION_MARK_SIZE: usize = 12;
+
+/// Name and length of a section.
+struct Section {
+    mark: SizedString<SECTION_MARK_SIZE>,
+    length: u32,
+}
+
+impl Section {
+    pub fn new(mark: &str, length: usize) -> Self {
+        Self {
+            mark: SizedString::new(mark.as_bytes()),
+            length: length as u32,
+        }
+    }
+
+    /// Reads a section header, making sure it has the expected name and that
+    /// the whole section is within the file.
+    pub fn deserialize(des: &mut SliceDeserializer, mark: &str) -> Result<Self> {
+        ensure!(
+            des.size().saturating_sub(des.pos()) >= SECTION_MARK_SIZE + 4,
+            "Section `{mark}` out of bounds"
+        );
+        let this = Self {
+            mark: SizedString::deserialize(des),
+            length: des.read_u32_le(),
+        };
+
+        let found = this.mark.to_string();
+        ensure!(found == mark, "Expected section `{mark}`, found `{found}`");
+        ensure!(
+            this.length as usize <= des.size() - des.pos(),
+            "Section `{mark}` out of bounds"
+        );
+        Ok(this)
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        self.mark.serialize(ser);
+        ser.write_u32_le(self.length);
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    fs,
+    io::{Write, stdout},
+    path::PathBuf,
+};
+
+use anyhow::Result;
+use clap::Parser;
+use common::{
+    container::rle::png::{ColorType, PngEncoder},
+    serde::{DynamicSerializer, SliceDeserializer},
+};
+use photon_format::{File, LayerDecoder};
+
+#[derive(Parser)]
+struct Args {
+    /// Path to the .pwmx, .pwma, or .pm3 file
+    input_file: PathBuf,
+
+    /// Path to output the preview image
+    #[clap(short, long)]
+    preview: Option<PathBuf>,
+
+    /// Path to output each layer as an image
+    #[clap(short, long)]
+    layers: Option<PathBuf>,
+
+    /// Do not print the header information
+    #[clap(short, long)]
+    no_header: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let raw = fs::read(&args.input_file)?;
+    let mut des = SliceDeserializer::new(&raw);
+    let file = File::deserialize(&mut des)?;
+
+    if !args.no_header {
+        println!("Version: {}", file.version);
+        println!("{:#?}", file.header);
+    }
+
+    if let Some(preview) = args.preview {
+        fs::create_dir_all(&preview)?;
+        file.preview
+            .into_image()
+            .save(preview.join("preview.png"))?;
+    }
+
+    if let Some(layers) = args.layers {
+        fs::create_dir_all(&layers)?;
+
+        println!("Exporting layers as images:\n");
+        let count = file.layers.len();
+        for (i, layer) in file.layers.iter().enumerate() {
+            print!(
+                "\r{}/{count} ({:.1}%)",
+                i + 1,
+                (1.0 + i as f32) / count as f32 * 100.0
+            );
+            stdout().flush()?;
+
+            let decoder = LayerDecoder::new(&layer.data);
+
+            let mut ser = DynamicSerializer::new();
+            let mut encoder =
+                PngEncoder::new(&mut ser, ColorType::Grayscale, file.header.resolution);
+            encoder.write_image_data(decoder.filter(|x| x.length > 0).collect());
+            encoder.write_end();
+
+            let path = layers.join(format!("layer_{i:03}.png"));
+            fs::write(path, ser.into_inner())?;
+        }
+    }
+
+    Ok(())
+}
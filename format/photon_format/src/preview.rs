@@ -0,0 +1,109 @@
+use std::fmt::{self, Debug};
+
+use anyhow::{Result, ensure};
+
+use common::serde::{Deserializer, Serializer, SliceDeserializer};
+use image::{RgbaImage, imageops::FilterType};
+use nalgebra::Vector2;
+
+use crate::Section;
+
+pub(crate) const PREVIEW_MARK: &str = "PREVIEW";
+const PREVIEW_SIZE: Vector2<u32> = Vector2::new(224, 168);
+
+/// RGB bitmap image.
+///
+/// Each pixel is two bytes: 0bRRRRRGGGGGGBBBBB.
+pub struct PreviewImage {
+    size: Vector2<u32>,
+    data: Vec<u16>,
+}
+
+impl PreviewImage {
+    pub fn empty() -> Self {
+        Self {
+            size: PREVIEW_SIZE,
+            data: vec![0; (PREVIEW_SIZE.x * PREVIEW_SIZE.y) as usize],
+        }
+    }
+
+    /// Scales the image to the size used by Photon Workshop.
+    pub fn from_image_scaled(image: &RgbaImage, filter: FilterType) -> Self {
+        let (width, height) = (PREVIEW_SIZE.x, PREVIEW_SIZE.y);
+        let image = image::imageops::resize(image, width, height, filter);
+
+        let data = (image.pixels())
+            .map(|color| {
+                let red = (color[0] as u16) >> 3;
+                let green = (color[1] as u16) >> 2;
+                let blue = (color[2] as u16) >> 3;
+                (red << 11) | (green << 5) | blue
+            })
+            .collect();
+
+        Self {
+            size: PREVIEW_SIZE,
+            data,
+        }
+    }
+
+    pub fn into_image(&self) -> RgbaImage {
+        let mut out = RgbaImage::new(self.size.x, self.size.y);
+
+        for (pixel, color) in out.pixels_mut().zip(self.data.iter()) {
+            let red = ((color >> 11) & 0x1F) as u32 * 255 / 31;
+            let green = ((color >> 5) & 0x3F) as u32 * 255 / 63;
+            let blue = (color & 0x1F) as u32 * 255 / 31;
+            *pixel = image::Rgba([red as u8, green as u8, blue as u8, 255]);
+        }
+
+        out
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        self.size
+    }
+
+    pub fn deserialize(des: &mut SliceDeserializer) -> Result<Self> {
+        let section = Section::deserialize(des, PREVIEW_MARK)?;
+        ensure!(section.length >= 12, "Preview image data is too short");
+        let width = des.read_u32_le();
+        des.advance_by(4); // 'x'
+        let height = des.read_u32_le();
+
+        let pixels = width as usize * height as usize;
+        ensure!(
+            section.length as usize >= 12 + pixels * 2 && des.size() - des.pos() >= pixels * 2,
+            "Preview image data is too short"
+        );
+
+        Ok(Self {
+            size: Vector2::new(width, height),
+            data: (0..pixels).map(|_| des.read_u16_le()).collect(),
+        })
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        Section::new(PREVIEW_MARK, 12 + self.data.len() * 2).serialize(ser);
+        ser.write_u32_le(self.size.x);
+        ser.write_u32_le(b'x' as u32);
+        ser.write_u32_le(self.size.y);
+        for pixel in self.data.iter() {
+            ser.write_u16_le(*pixel);
+        }
+    }
+}
+
+impl Default for PreviewImage {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Debug for PreviewImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PreviewImage")
+            .field("size", &self.size)
+            .finish()
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+
+use common::{
+    serde::{DynamicSerializer, Serializer, WriterSerializer},
+    slice::{SliceConfig, SliceWriter, SlicedFile},
+    units::Milimeters,
+};
+use image::RgbaImage;
+
+use crate::{File, Layer, LayerEncoder};
+
+/// Writes a Photon Workshop file to a stream one layer at a time.
+pub struct FileWriter<T: Write + Seek> {
+    ser: WriterSerializer<T>,
+    config: SliceConfig,
+    table: LayerTable,
+    per_layer_override: bool,
+}
+
+/// Layer definition table of a file being written. The entries are kept in
+/// memory as the layers are written, and filled in once all layers are done.
+pub(crate) struct LayerTable {
+    /// Position of the header, which holds values that are only known once
+    /// all layers have been written.
+    pub header: usize,
+    /// Position of the table in the file.
+    pub offset: usize,
+    /// Index of the next layer to be written.
+    pub next: usize,
+    pub previous_z: Milimeters,
+    pub entries: DynamicSerializer,
+}
+
+impl<T: Write + Seek> FileWriter<T> {
+    /// Writes the header for a file with `layers` layers. Its volume is
+    /// filled in once the file is finished.
+    pub fn new(stream: T, config: &SliceConfig, layers: u32, preview: &RgbaImage) -> Self {
+        let mut file = File::from_config(config, layers, 0);
+        file.set_preview(preview);
+
+        let mut ser = WriterSerializer::new(stream);
+        let table = file.serialize_header(&mut ser, layers as usize);
+        Self {
+            ser,
+            config: config.clone(),
+            table,
+            per_layer_override: false,
+        }
+    }
+}
+
+impl<T: Write + Seek> SliceWriter for FileWriter<T> {
+    type Encoder = LayerEncoder;
+
+    fn write_layer(&mut self, layer: Layer) -> Result<()> {
+        let index = self.table.next as u32;
+        self.per_layer_override |= layer.overrides_config(&self.config, index);
+        self.table.write_layer(&mut self.ser, &layer);
+        Ok(())
+    }
+
+    fn finish(mut self, voxels: u64) -> Result<()> {
+        let layers = self.table.next as u32;
+        let mut header = File::from_config(&self.config, layers, voxels).header;
+        header.per_layer_override = self.per_layer_override;
+
+        (self.ser).execute_at(self.table.header, |ser| header.serialize(ser));
+        self.table.finish(&mut self.ser);
+        self.ser.finish()?;
+        Ok(())
+    }
+}
+
+impl LayerTable {
+    pub fn new(header: usize, offset: usize) -> Self {
+        Self {
+            header,
+            offset,
+            next: 0,
+            previous_z: Milimeters::new(0.0),
+            entries: DynamicSerializer::new(),
+        }
+    }
+
+    pub fn write_layer<T: Serializer>(&mut self, ser: &mut T, layer: &Layer) {
+        let address = ser.pos();
+        ser.write_bytes(&layer.data);
+        layer.serialize(&mut self.entries, address, self.previous_z);
+
+        self.previous_z = layer.position_z;
+        self.next += 1;
+    }
+
+    pub fn finish<T: Serializer>(self, ser: &mut T) {
+        ser.write_at(self.offset, &self.entries.into_inner());
+    }
+}
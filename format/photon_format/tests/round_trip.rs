@@ -0,0 +1,81 @@
+use common::{
+    container::{Run, SharedBytes},
+    progress::Progress,
+    serde::DynamicSerializer,
+    slice::{EncodableLayer, SliceConfig, SliceDecoder, SlicedFile},
+};
+use image::RgbaImage;
+use nalgebra::Vector2;
+use photon_format::{File, LayerEncoder};
+
+fn config() -> SliceConfig {
+    SliceConfig {
+        platform_resolution: Vector2::new(100, 50),
+        ..Default::default()
+    }
+}
+
+/// A few rows of pixels with some gray in them, wrapping over row ends. The
+/// last black run is longer than fits in one chunk, and the gray one is a
+/// value that is kept exactly by the four bit colors.
+fn layer_runs(offset: u64) -> Vec<Run> {
+    vec![
+        Run::new(70 + offset, 0),
+        Run::new(90, 255),
+        Run::new(20, 136),
+        Run::new(100 * 50 - 180 - offset, 0),
+    ]
+}
+
+fn pixels(runs: impl IntoIterator<Item = Run>) -> Vec<u8> {
+    (runs.into_iter())
+        .flat_map(|run| std::iter::repeat_n(run.value, run.length as usize))
+        .collect()
+}
+
+fn encode() -> Vec<u8> {
+    let config = config();
+    let layers = (0..3)
+        .map(|i| {
+            let mut encoder = <LayerEncoder as EncodableLayer>::new(config.platform_resolution);
+            layer_runs(i * 10)
+                .iter()
+                .for_each(|x| encoder.add_run(x.length, x.value));
+            encoder.finish(
+                &config,
+                &config.exposure_config,
+                config.default_height(i as u32),
+            )
+        })
+        .collect();
+
+    let mut file = File::from_layers(&config, layers, 330);
+    file.set_preview(&RgbaImage::new(4, 4));
+    let mut ser = DynamicSerializer::new();
+    SlicedFile::serialize(&file, &mut ser, Progress::new());
+    ser.into_inner()
+}
+
+#[test]
+fn encode_decode() {
+    let file = File::decode(&SharedBytes::new(encode())).unwrap();
+    assert_eq!(file.info().layers, 3);
+    assert_eq!(file.info().resolution, config().platform_resolution);
+
+    for layer in 0..3 {
+        let expected = pixels(layer_runs(layer as u64 * 10));
+        assert_eq!(pixels(file.runs(layer)), expected, "layer {layer}");
+    }
+
+    let (height, _) = file.layer_settings(2);
+    assert!((height.raw() - 0.15).abs() < 1e-5);
+}
+
+#[test]
+fn truncated() {
+    let bytes = encode();
+    for len in [0, 4, 30, bytes.len() / 2, bytes.len() - 1] {
+        let bytes = SharedBytes::new(bytes[..len].to_vec());
+        assert!(File::decode(&bytes).is_err(), "{len} bytes");
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    cmp::Reverse,
+    io::{Cursor, Read, Seek, Write},
+};
+
+use anyhow::{Context, Result, ensure};
+use common::{
+    container::{Image, Run, SharedBytes},
+    progress::Progress,
+    serde::{DynamicSerializer, Serializer},
+    slice::{
+        DEFAULT_PAUSE_HOLD, ExposureConfig, SliceConfig, SliceDecoder, SliceInfo, SliceMode,
+        SlicedFile,
+    },
+    units::{Centimeter, Milimeters},
+};
+use image::{RgbaImage, imageops::FilterType};
+use nalgebra::{Vector2, Vector3};
+use zip::{ZipArchive, ZipWriter, write::FileOptions};
+
+use crate::{
+    Config, Display, Layer, LayerDecoder, LayerEncoder, decode_png, encode_png, ini::Ini,
+    read_to_bytes,
+};
+
+const JOB_DIR: &str = "mslicer";
+const THUMBNAIL_SIZES: [(u32, u32); 2] = [(800, 480), (400, 400)];
+
+/// SL1 file.
+pub struct File {
+    pub config: Config,
+    pub display: Display,
+    /// Previews shown on the printer, largest first.
+    pub thumbnails: Vec<RgbaImage>,
+
+    pub layers: Vec<Layer>,
+}
+
+impl File {
+    pub fn from_layers(config: &SliceConfig, layers: Vec<Layer>, voxels: u64) -> Self {
+        let layer_count = layers.len() as u32;
+        Self {
+            layers,
+            ..Self::from_config(config, layer_count, voxels)
+        }
+    }
+
+    /// Creates a file without any layers, with a config describing
+    /// `layer_count` layers.
+    pub(crate) fn from_config(config: &SliceConfig, layer_count: u32, voxels: u64) -> Self {
+        let mut file_config = Config::from_slice_config(config, layer_count, JOB_DIR.into());
+        file_config.used_material = (config.voxel_volume() * voxels as f32).convert::<Centimeter>();
+
+        Self {
+            config: file_config,
+            display: Display::from_slice_config(config),
+            thumbnails: Vec::new(), // overwritten later
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T, progress: Progress) -> Result<()> {
+        let mut bytes = Vec::new();
+        let mut zip = ZipWriter::new(Cursor::new(&mut bytes));
+        self.serialize_config(&mut zip)?;
+        self.serialize_thumbnails(&mut zip)?;
+
+        progress.set_total(self.layers.len() as u64);
+        for (i, layer) in self.layers.iter().enumerate() {
+            progress.set_complete(i as u64);
+            self.serialize_layer(&mut zip, i, layer)?;
+        }
+
+        drop(zip);
+        ser.write_bytes(&bytes);
+        Ok(())
+    }
+
+    pub(crate) fn serialize_config<W: Write + Seek>(&self, zip: &mut ZipWriter<W>) -> Result<()> {
+        zip.start_file("config.ini", FileOptions::DEFAULT)?;
+        zip.write_all(self.config.to_ini().to_string().as_bytes())?;
+        zip.start_file("prusaslicer.ini", FileOptions::DEFAULT)?;
+        zip.write_all(self.slicer_ini().to_string().as_bytes())?;
+        Ok(())
+    }
+
+    pub(crate) fn serialize_thumbnails<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+    ) -> Result<()> {
+        for thumbnail in self.thumbnails.iter() {
+            let (width, height) = thumbnail.dimensions();
+            zip.start_file(
+                format!("thumbnail/thumbnail{width}x{height}.png"),
+                FileOptions::DEFAULT,
+            )?;
+            zip.write_all(&encode_png(&thumbnail.clone().into())?)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn serialize_layer<W: Write + Seek>(
+        &self,
+        zip: &mut ZipWriter<W>,
+        index: usize,
+        layer: &Layer,
+    ) -> Result<()> {
+        // The layer images are already compressed, so compressing them again
+        // would just waste time.
+        let stored = FileOptions::DEFAULT.compression_method(zip::CompressionMethod::Stored);
+        zip.start_file(format!("{}{index:05}.png", self.config.job_dir), stored)?;
+        zip.write_all(&layer.inner)?;
+        Ok(())
+    }
+
+    pub fn deserialize<T: Read + Seek>(reader: T) -> Result<Self> {
+        let mut zip = ZipArchive::new(reader)?;
+
+        let config = read_ini(&mut zip, "config.ini")?.context("Missing config.ini")?;
+        let config = Config::from_ini(&config);
+        let display = read_ini(&mut zip, "prusaslicer.ini")?.unwrap_or_default();
+        let display = Display::from_ini(&display);
+
+        let mut thumbnails = Vec::new();
+        let mut layer_names = Vec::new();
+        for name in zip.file_names() {
+            if name.starts_with("thumbnail/") && name.ends_with(".png") {
+                thumbnails.push(name.to_owned());
+            } else if !name.contains('/')
+                && name.starts_with(&config.job_dir)
+                && name.ends_with(".png")
+            {
+                layer_names.push(name.to_owned());
+            }
+        }
+
+        layer_names.sort();
+        let mut layers = Vec::with_capacity(layer_names.len());
+        for name in layer_names {
+            let inner = read_to_bytes(zip.by_name(&name)?)?;
+            ensure!(
+                png_size(&inner) == Some(display.image_size()),
+                "Layer `{name}` doesn't match the display resolution"
+            );
+            // Layers are decoded whenever their runs are needed, which can't
+            // fail, so make sure they can be decoded up front.
+            decode_png(&inner).with_context(|| format!("Layer `{name}` is corrupt"))?;
+            layers.push(Layer { inner });
+        }
+
+        let mut thumbnails = (thumbnails.into_iter())
+            .map(|name| Ok(decode_png(&read_to_bytes(zip.by_name(&name)?)?)?.into_rgba8()))
+            .collect::<Result<Vec<_>>>()?;
+        thumbnails.sort_by_key(|x| Reverse(x.width() * x.height()));
+
+        Ok(Self {
+            config,
+            display,
+            thumbnails,
+            layers,
+        })
+    }
+
+    pub fn into_slice_config(&self) -> SliceConfig {
+        let exposure_config = |exposure_time| ExposureConfig {
+            exposure_time,
+            ..Default::default()
+        };
+
+        SliceConfig {
+            mode: SliceMode::Raster,
+            supersample: 0,
+            exposure_remap: Default::default(),
+            uniformity: None,
+            platform_resolution: self.display.resolution,
+            platform_size: self.display.size.push(self.display.max_print_height),
+            slice_height: self.config.layer_height,
+            compensation: Default::default(),
+            exposure_config: exposure_config(self.config.exposure_time),
+            first_exposure_config: exposure_config(self.config.first_exposure_time),
+            first_layers: self.config.fade_layers,
+            transition_layers: 0,
+            exposure_overrides: Vec::new(),
+            pause_hold: DEFAULT_PAUSE_HOLD,
+        }
+    }
+
+    /// Parts of the PrusaSlicer config needed to open the file in PrusaSlicer
+    /// or UVtools.
+    fn slicer_ini(&self) -> Ini {
+        let mut ini = Ini::default();
+        ini.set("printer_model", &self.config.printer_model);
+        ini.set("layer_height", self.config.layer_height.raw());
+        ini.set("exposure_time", self.config.exposure_time.raw());
+        ini.set(
+            "initial_exposure_time",
+            self.config.first_exposure_time.raw(),
+        );
+        ini.set("faded_layers", self.config.fade_layers);
+        self.display.write_ini(&mut ini);
+        ini
+    }
+}
+
+impl SlicedFile for File {
+    fn serialize(&self, ser: &mut DynamicSerializer, progress: Progress) {
+        self.serialize(ser, progress).unwrap();
+    }
+
+    fn set_preview(&mut self, preview: &RgbaImage) {
+        self.thumbnails = (THUMBNAIL_SIZES.iter())
+            .map(|&(width, height)| {
+                image::imageops::resize(preview, width, height, FilterType::Nearest)
+            })
+            .collect();
+    }
+
+    fn info(&self) -> SliceInfo {
+        let height = self.config.layer_height * self.layers.len() as f32;
+        SliceInfo {
+            layers: self.layers.len() as u32,
+            resolution: self.display.resolution,
+            size: Vector3::new(self.display.size.x, self.display.size.y, height),
+            bottom_layers: self.config.fade_layers,
+        }
+    }
+
+    fn runs(&self, layer: usize) -> Box<dyn Iterator<Item = Run> + '_> {
+        let decoder = LayerDecoder::new(&self.layers[layer].inner, &self.display)
+            .expect("Layers are checked when loaded");
+        Box::new(decoder.runs().collect::<Vec<_>>().into_iter())
+    }
+
+    fn overwrite_layer(&mut self, layer: usize, image: Image) {
+        let encoder = LayerEncoder::from_image(image);
+        self.layers[layer].inner = encoder.image_data(&self.display);
+    }
+}
+
+impl SliceDecoder for File {
+    fn decode(data: &SharedBytes) -> Result<Self> {
+        Self::deserialize(Cursor::new(&**data))
+    }
+
+    fn slice_config(&self) -> SliceConfig {
+        self.into_slice_config()
+    }
+
+    fn previews(&self) -> Vec<RgbaImage> {
+        self.thumbnails.clone()
+    }
+
+    fn layer_settings(&self, layer: usize) -> (Milimeters, ExposureConfig) {
+        let config = self.into_slice_config();
+        let exposure = config.exposure_config(layer as u32).into_owned();
+        (config.default_height(layer as u32), exposure)
+    }
+}
+
+fn read_ini<T: Read + Seek>(zip: &mut ZipArchive<T>, name: &str) -> Result<Option<Ini>> {
+    let Ok(file) = zip.by_name(name) else {
+        return Ok(None);
+    };
+
+    let str = String::from_utf8(read_to_bytes(file)?)?;
+    Ok(Some(Ini::parse(&str)))
+}
+
+/// Reads the image size from the PNG header.
+fn png_size(png: &[u8]) -> Option<Vector2<u32>> {
+    let size = png.get(16..24)?;
+    let [x, y] = [&size[..4], &size[4..]].map(|x| u32::from_be_bytes(x.try_into().unwrap()));
+    Some(Vector2::new(x, y))
+}
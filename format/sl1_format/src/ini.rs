@@ -0,0 +1,50 @@
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// Flat `key = value` config file, as used for both `config.ini` and
+/// `prusaslicer.ini`. Keys are kept in insertion order.
+#[derive(Default)]
+pub struct Ini {
+    entries: Vec<(String, String)>,
+}
+
+impl Ini {
+    pub fn parse(str: &str) -> Self {
+        let entries = (str.lines())
+            .map(str::trim)
+            .filter(|x| !x.is_empty() && !x.starts_with(['#', ';']))
+            .filter_map(|x| x.split_once('='))
+            .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        (self.entries.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn parse_value<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|x| x.parse().ok())
+    }
+
+    pub fn set(&mut self, key: &str, value: impl Display) {
+        let value = value.to_string();
+        match self.entries.iter_mut().find(|(k, _)| k == key) {
+            Some(entry) => entry.1 = value,
+            None => self.entries.push((key.to_owned(), value)),
+        }
+    }
+}
+
+impl Display for Ini {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in self.entries.iter() {
+            writeln!(f, "{key} = {value}")?;
+        }
+        Ok(())
+    }
+}
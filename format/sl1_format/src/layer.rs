@@ -0,0 +1,155 @@
+use anyhow::Result;
+use common::{
+    container::{
+        Image, ImageRuns,
+        rle::{
+            Run,
+            png::{ColorType, PngEncoder},
+        },
+    },
+    serde::DynamicSerializer,
+    slice::{EncodableLayer, ExposureConfig, SliceConfig},
+    units::Milimeters,
+};
+use nalgebra::Vector2;
+
+use crate::{
+    decode_png,
+    types::{Display, Orientation},
+};
+
+/// PNG encoded layer image.
+pub struct Layer {
+    pub inner: Vec<u8>,
+}
+
+/// Encodes a series of runs into a layer image.
+pub struct LayerEncoder {
+    platform: Vector2<u32>,
+    runs: Vec<Run>,
+}
+
+/// Decodes a layer image back into a series of runs.
+pub struct LayerDecoder {
+    image: Image,
+}
+
+impl LayerEncoder {
+    pub fn from_image(image: Image) -> Self {
+        let mut out = LayerEncoder::new(image.size.cast());
+        (image.runs()).for_each(|run| out.add_run(run.length, run.value));
+        out
+    }
+
+    pub fn image_data(self, display: &Display) -> Vec<u8> {
+        let size = display.image_size();
+        let runs = match display.is_identity() {
+            true => self.runs,
+            false => {
+                let layer = Image::from_decoder(self.platform.cast(), self.runs.into_iter());
+                display.layer_to_image(&layer).runs().collect()
+            }
+        };
+
+        let mut ser = DynamicSerializer::new();
+        let mut encoder = PngEncoder::new(&mut ser, ColorType::Grayscale, size);
+        encoder.write_image_data(runs);
+        encoder.write_end();
+        ser.into_inner()
+    }
+}
+
+impl EncodableLayer for LayerEncoder {
+    type Output = Layer;
+
+    fn new(platform: Vector2<u32>) -> Self {
+        Self {
+            platform,
+            runs: Vec::new(),
+        }
+    }
+
+    fn add_run(&mut self, length: u64, value: u8) {
+        self.runs.push(Run { length, value });
+    }
+
+    fn finish(
+        self,
+        config: &SliceConfig,
+        _exposure: &ExposureConfig,
+        _height: Milimeters,
+    ) -> Self::Output {
+        let display = Display::from_slice_config(config);
+        Layer {
+            inner: self.image_data(&display),
+        }
+    }
+}
+
+impl LayerDecoder {
+    pub fn new(data: &[u8], display: &Display) -> Result<Self> {
+        let image = decode_png(data)?.into_luma8();
+        let size = Vector2::new(image.width(), image.height()).cast();
+        let image = Image::from_raw(size, image.into_raw());
+
+        Ok(Self {
+            image: match display.is_identity() {
+                true => image,
+                false => display.image_to_layer(&image),
+            },
+        })
+    }
+
+    pub fn runs(&self) -> impl Iterator<Item = Run> {
+        ImageRuns::new(self.image.inner())
+    }
+
+    pub fn into_inner(self) -> Image {
+        self.image
+    }
+}
+
+impl Display {
+    /// If layers can be used as images without any transformation. PrusaSlicer
+    /// flips the y axis by default as image rows go top to bottom.
+    fn is_identity(&self) -> bool {
+        self.orientation == Orientation::Landscape && !self.mirror_x && self.mirror_y
+    }
+
+    /// Gets the image position of a pixel in the layer.
+    fn image_pos(&self, x: usize, y: usize) -> (usize, usize) {
+        let size = self.image_size().cast::<usize>();
+        let (mut x, mut y) = match self.orientation {
+            Orientation::Landscape => (x, y),
+            Orientation::Portrait => (y, x),
+        };
+
+        self.mirror_x.then(|| x = size.x - 1 - x);
+        (!self.mirror_y).then(|| y = size.y - 1 - y);
+        (x, y)
+    }
+
+    /// Converts a layer in platform space into the image shown on the display.
+    pub fn layer_to_image(&self, layer: &Image) -> Image {
+        let mut image = Image::blank(self.image_size().cast());
+        for y in 0..layer.size.y {
+            for x in 0..layer.size.x {
+                let (ix, iy) = self.image_pos(x, y);
+                image.set_pixel(ix, iy, layer.get_pixel(x, y));
+            }
+        }
+        image
+    }
+
+    /// Inverse of [`Display::layer_to_image`].
+    pub fn image_to_layer(&self, image: &Image) -> Image {
+        let mut layer = Image::blank(self.resolution.cast());
+        for y in 0..layer.size.y {
+            for x in 0..layer.size.x {
+                let (ix, iy) = self.image_pos(x, y);
+                layer.set_pixel(x, y, image.get_pixel(ix, iy));
+            }
+        }
+        layer
+    }
+}
@@ -0,0 +1,47 @@
+//! Prusa SL1 / SL1S format (`.sl1`, `.sl1s`).
+//!
+//! A zip archive with each layer as a grayscale PNG image, a `config.ini` read
+//! by the printer, a `prusaslicer.ini` with the full slicer config, and a few
+//! thumbnails. Layer images are written with the custom RLE PNG encoder from
+//! [`common::container::rle::png`].
+//!
+//! ## References
+//!
+//! - <https://github.com/prusa3d/Prusa-Firmware-SL1/blob/master/slafw/project/project.py>
+//! - <https://github.com/prusa3d/PrusaSlicer/blob/master/src/libslic3r/Format/SL1.cpp>
+//! - [UV Tools](https://github.com/sn4k3/UVtools)
+
+use std::io::{Cursor, Read};
+
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat, codecs::png::PngDecoder};
+
+mod file;
+mod ini;
+mod layer;
+mod types;
+mod writer;
+pub use crate::{
+    file::File,
+    layer::{Layer, LayerDecoder, LayerEncoder},
+    types::{Config, Display, Orientation},
+    writer::FileWriter,
+};
+
+fn read_to_bytes<T: Read>(mut reader: T) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+fn decode_png(png: &[u8]) -> Result<DynamicImage> {
+    let decoder = PngDecoder::new(Cursor::new(png))?;
+    let image = DynamicImage::from_decoder(decoder)?;
+    Ok(image)
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+    Ok(bytes)
+}
@@ -0,0 +1,198 @@
+use common::{
+    slice::SliceConfig,
+    units::{Milimeters, Milliliters, Seconds},
+};
+use nalgebra::Vector2;
+
+use crate::ini::Ini;
+
+/// Display resolution of the SL1S, which needs a different printer model in
+/// the config.
+const SL1S_RESOLUTION: Vector2<u32> = Vector2::new(2560, 1620);
+
+/// Contents of `config.ini`, the only config file read by the printer.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Prefix of the layer image file names.
+    pub job_dir: String,
+    pub printer_model: String,
+    pub material_name: String,
+    pub creation_time: String,
+
+    pub layer_height: Milimeters,
+    pub exposure_time: Seconds,
+    pub first_exposure_time: Seconds,
+    /// Number of layers exposed with `first_exposure_time`.
+    pub fade_layers: u32,
+    /// Number of layers printed with fast and slow tilts.
+    pub fast_layers: u32,
+    pub slow_layers: u32,
+
+    pub print_time: Seconds,
+    pub used_material: Milliliters,
+}
+
+/// The printer's display, from `prusaslicer.ini`. The printer shows the layer
+/// images as is, so the slicer is responsible for rotating and mirroring them
+/// to match the display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Display {
+    pub size: Vector2<Milimeters>,
+    pub resolution: Vector2<u32>,
+    pub max_print_height: Milimeters,
+
+    pub orientation: Orientation,
+    pub mirror_x: bool,
+    pub mirror_y: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Landscape,
+    /// Layer images are transposed, so the x resolution is the image height.
+    Portrait,
+}
+
+impl Config {
+    pub fn from_slice_config(config: &SliceConfig, layers: u32, job_dir: String) -> Self {
+        let printer_model = match config.platform_resolution == SL1S_RESOLUTION {
+            true => "SL1S",
+            false => "SL1",
+        };
+
+        Self {
+            job_dir,
+            printer_model: printer_model.into(),
+            material_name: "mslicer".into(),
+            creation_time: chrono::Utc::now()
+                .format("%Y-%m-%d at %H:%M:%S UTC")
+                .to_string(),
+
+            layer_height: config.slice_height,
+            exposure_time: config.exposure_config.exposure_time,
+            first_exposure_time: config.first_exposure_config.exposure_time,
+            fade_layers: config.first_layers,
+            fast_layers: layers,
+            slow_layers: 0,
+
+            print_time: config.print_time(layers),
+            used_material: Milliliters::default(),
+        }
+    }
+
+    pub(crate) fn from_ini(ini: &Ini) -> Self {
+        let seconds = |key| Seconds::new(ini.parse_value(key).unwrap_or_default());
+        Self {
+            job_dir: ini.get("jobDir").unwrap_or_default().to_owned(),
+            printer_model: ini.get("printerModel").unwrap_or("SL1").to_owned(),
+            material_name: ini.get("materialName").unwrap_or_default().to_owned(),
+            creation_time: (ini.get("fileCreationTimestamp"))
+                .unwrap_or_default()
+                .to_owned(),
+
+            layer_height: Milimeters::new(ini.parse_value("layerHeight").unwrap_or(0.05)),
+            exposure_time: seconds("expTime"),
+            first_exposure_time: seconds("expTimeFirst"),
+            fade_layers: ini.parse_value("numFade").unwrap_or_default(),
+            fast_layers: ini.parse_value("numFast").unwrap_or_default(),
+            slow_layers: ini.parse_value("numSlow").unwrap_or_default(),
+
+            print_time: seconds("printTime"),
+            used_material: Milliliters::new(ini.parse_value("usedMaterial").unwrap_or_default()),
+        }
+    }
+
+    pub(crate) fn to_ini(&self) -> Ini {
+        let mut ini = Ini::default();
+        ini.set("action", "print");
+        ini.set("jobDir", &self.job_dir);
+        ini.set("expTime", self.exposure_time.raw());
+        ini.set("expTimeFirst", self.first_exposure_time.raw());
+        ini.set("fileCreationTimestamp", &self.creation_time);
+        ini.set("hollow", 0);
+        ini.set("layerHeight", self.layer_height.raw());
+        ini.set("materialName", &self.material_name);
+        ini.set("numFade", self.fade_layers);
+        ini.set("numFast", self.fast_layers);
+        ini.set("numSlow", self.slow_layers);
+        ini.set("printTime", self.print_time.raw());
+        ini.set("printerModel", &self.printer_model);
+        ini.set(
+            "printerProfile",
+            format!("Original Prusa {}", self.printer_model),
+        );
+        ini.set("printerVariant", "default");
+        ini.set("prusaSlicerVersion", "mslicer");
+        ini.set("usedMaterial", self.used_material.raw());
+        ini
+    }
+}
+
+impl Display {
+    /// Uses the same orientation and mirroring as the PrusaSlicer profiles for
+    /// the SL1 and SL1S.
+    pub fn from_slice_config(config: &SliceConfig) -> Self {
+        let resolution = config.platform_resolution;
+        Self {
+            size: config.platform_size.xy(),
+            resolution,
+            max_print_height: config.platform_size.z,
+
+            orientation: match resolution.x > resolution.y {
+                true => Orientation::Portrait,
+                false => Orientation::Landscape,
+            },
+            mirror_x: true,
+            mirror_y: false,
+        }
+    }
+
+    /// Size of the layer images.
+    pub fn image_size(&self) -> Vector2<u32> {
+        match self.orientation {
+            Orientation::Landscape => self.resolution,
+            Orientation::Portrait => self.resolution.yx(),
+        }
+    }
+
+    /// Missing values fall back to the ones for the original SL1.
+    pub(crate) fn from_ini(ini: &Ini) -> Self {
+        let millimeters = |key, default| Milimeters::new(ini.parse_value(key).unwrap_or(default));
+        let flag = |key, default| ini.get(key).map(|x| x == "1").unwrap_or(default);
+        Self {
+            size: Vector2::new(
+                millimeters("display_width", 120.96),
+                millimeters("display_height", 68.04),
+            ),
+            resolution: Vector2::new(
+                ini.parse_value("display_pixels_x").unwrap_or(2560),
+                ini.parse_value("display_pixels_y").unwrap_or(1440),
+            ),
+            max_print_height: millimeters("max_print_height", 150.0),
+
+            orientation: match ini.get("display_orientation") {
+                Some("landscape") => Orientation::Landscape,
+                _ => Orientation::Portrait,
+            },
+            mirror_x: flag("display_mirror_x", true),
+            mirror_y: flag("display_mirror_y", false),
+        }
+    }
+
+    pub(crate) fn write_ini(&self, ini: &mut Ini) {
+        let orientation = match self.orientation {
+            Orientation::Landscape => "landscape",
+            Orientation::Portrait => "portrait",
+        };
+
+        ini.set("printer_technology", "SLA");
+        ini.set("display_width", self.size.x.raw());
+        ini.set("display_height", self.size.y.raw());
+        ini.set("display_pixels_x", self.resolution.x);
+        ini.set("display_pixels_y", self.resolution.y);
+        ini.set("display_orientation", orientation);
+        ini.set("display_mirror_x", self.mirror_x as u8);
+        ini.set("display_mirror_y", self.mirror_y as u8);
+        ini.set("max_print_height", self.max_print_height.raw());
+    }
+}
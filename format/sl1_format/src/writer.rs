@@ -0,0 +1,54 @@
+use std::io::{Seek, Write};
+
+use anyhow::Result;
+
+use common::slice::{SliceConfig, SliceWriter, SlicedFile};
+use image::RgbaImage;
+use zip::ZipWriter;
+
+use crate::{File, Layer, LayerEncoder};
+
+/// Writes an SL1 file to a stream one layer at a time. The config files and
+/// thumbnails are written after the layers, once the material usage is known.
+pub struct FileWriter<T: Write + Seek> {
+    zip: ZipWriter<T>,
+    file: File,
+    slice_config: SliceConfig,
+    layers: usize,
+}
+
+impl<T: Write + Seek> FileWriter<T> {
+    pub fn new(stream: T, config: &SliceConfig, preview: &RgbaImage) -> Self {
+        let mut file = File::from_config(config, 0, 0);
+        file.set_preview(preview);
+
+        Self {
+            zip: ZipWriter::new(stream),
+            file,
+            slice_config: config.clone(),
+            layers: 0,
+        }
+    }
+}
+
+impl<T: Write + Seek> SliceWriter for FileWriter<T> {
+    type Encoder = LayerEncoder;
+
+    fn write_layer(&mut self, layer: Layer) -> Result<()> {
+        (self.file).serialize_layer(&mut self.zip, self.layers, &layer)?;
+        self.layers += 1;
+        Ok(())
+    }
+
+    fn finish(mut self, voxels: u64) -> Result<()> {
+        let file = File {
+            thumbnails: self.file.thumbnails,
+            ..File::from_config(&self.slice_config, self.layers as u32, voxels)
+        };
+        file.serialize_config(&mut self.zip)?;
+        file.serialize_thumbnails(&mut self.zip)?;
+
+        self.zip.finish()?.flush()?;
+        Ok(())
+    }
+}
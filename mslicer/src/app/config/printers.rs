@@ -1,6 +1,9 @@
 use std::borrow::Cow;
 
-use common::{slice::SliceConfig, units::Milimeters};
+use common::{
+    slice::{SliceConfig, UniformityMask},
+    units::Milimeters,
+};
 use nalgebra::{Vector2, Vector3};
 use serde::{Deserialize, Serialize};
 
@@ -38,6 +41,9 @@ pub struct PrinterProperties {
     pub name: Cow<'static, str>,
     pub resolution: Vector2<u32>,
     pub size: Vector3<Milimeters>,
+    /// Brightness of the display, if it isn't even.
+    #[serde(default)]
+    pub uniformity: Option<UniformityMask>,
 }
 
 impl PrinterProperties {
@@ -50,6 +56,7 @@ impl PrinterProperties {
                 Milimeters::new(sy),
                 Milimeters::new(sz),
             ),
+            uniformity: None,
         }
     }
 }
@@ -60,6 +67,7 @@ impl Default for PrinterProperties {
             name: Cow::Owned("New Printer".into()),
             resolution: Vector2::new(10_000, 5_000),
             size: Vector3::repeat(100.0).map(Milimeters::new),
+            uniformity: None,
         }
     }
 }
@@ -29,7 +29,11 @@ use crate::{
     },
     windows::{self, Tab},
 };
-use common::{progress::CombinedProgress, slice::SliceMode, units::Milimeter};
+use common::{
+    progress::{Cancelled, CombinedProgress},
+    slice::SliceMode,
+    units::Milimeter,
+};
 use slicer::slicer::{Slicer, SlicerModel};
 
 pub mod camera;
@@ -175,23 +179,44 @@ impl App {
         let platform = slice_config.platform_resolution.cast::<f32>();
         let mm_to_px = platform.component_div(&platform_size).push(1.0);
 
-        // Transform models from world-space to platform-space
+        // Transform models and their supports from world-space to
+        // platform-space
         let mut out = Vec::new();
-        for model in meshes.into_iter() {
-            let (mut mesh, exposure) = (model.mesh, model.exposure);
+        let raft = &self.project.support_config.raft;
+        let models = meshes.into_iter().flat_map(|mut model| {
+            let (supports, exposure) = (model.supports.mesh(raft).clone(), model.exposure);
+            let cuts = (model.drain_holes.iter())
+                .map(|x| x.cut(&model.mesh))
+                .collect::<Vec<_>>();
+            let meshes = [Some((model.mesh, cuts)), supports.map(|x| (x, Vec::new()))];
+            (meshes.into_iter().flatten()).map(move |(mesh, cuts)| (mesh, exposure, cuts))
+        });
 
+        for (mut mesh, exposure, cuts) in models {
             let offset = (platform / 2.0).push(-slice_height / 2.0);
             mesh.set_scale_unchecked(mesh.scale().component_mul(&mm_to_px));
             mesh.set_position_unchecked(mesh.position().component_mul(&mm_to_px) + offset);
             mesh.update_transformation_matrix();
 
-            out.push(SlicerModel { mesh, exposure });
+            out.push(SlicerModel {
+                mesh,
+                exposure,
+                cuts,
+            });
+        }
+
+        let mut slicer = Slicer::new(slice_config, out);
+        if slicer.slice_config.mode == SliceMode::Raster {
+            (self.project.post_processing.variable_layer_height).pre_slice(&mut slicer);
         }
 
-        let slicer = Slicer::new(slice_config, out);
         let post_process = CombinedProgress::new();
-        let slice_operation = SliceOperation::new(slicer.progress(), post_process.clone());
-        self.slice_operation.replace(slice_operation);
+        let cancel = slicer.cancellation();
+        let slice_operation =
+            SliceOperation::new(slicer.progress(), post_process.clone(), cancel.clone());
+        if let Some(previous) = self.slice_operation.replace(slice_operation) {
+            previous.cancel.cancel();
+        }
         self.panels.focus_tab(Tab::Sliced, SLICE_PREVIEW_SIZE);
 
         thread::spawn(clone!(
@@ -199,20 +224,29 @@ impl App {
                 { self.slice_operation } as slice_operation,
                 { self.project.post_processing } as post_processing
             ],
-            move || {
+            move || -> Result<(), Cancelled> {
                 let slice_operation = slice_operation.as_ref().unwrap();
 
                 match slicer.slice_config.mode {
                     SliceMode::Raster => {
-                        let mut layers = slicer.slice_raster();
-                        post_processing.process(&slicer.slice_config, &mut layers, post_process);
+                        let mut layers = slicer.slice_raster()?;
+                        let (config, cuts) = (&slicer.slice_config, slicer.cuts());
+                        post_processing.process(
+                            config,
+                            &mut layers,
+                            &cuts,
+                            post_process,
+                            &cancel,
+                        )?;
                         slice_operation.add_raster_result(slicer.slice_config, layers);
                     }
                     SliceMode::Vector => {
-                        let layers = slicer.slice_vector();
+                        let layers = slicer.slice_vector()?;
                         slice_operation.add_vector_result(slicer.slice_config, Arc::new(layers));
                     }
                 }
+
+                Ok(())
             }
         ));
     }
@@ -259,6 +293,6 @@ impl Drop for App {
 pub fn is_slicing(slice_operation: &Option<SliceOperation>) -> bool {
     slice_operation
         .as_ref()
-        .map(|x| !x.progress.complete())
+        .map(|x| !x.progress.complete() && !x.cancel.is_cancelled())
         .unwrap_or_default()
 }
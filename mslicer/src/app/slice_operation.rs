@@ -12,9 +12,9 @@ use std::{
 use common::{
     container::Run,
     misc::{IteratorExt, human_duration},
-    progress::{CombinedProgress, Progress},
-    slice::{DynSlicedFile, Layer, SliceConfig, VectorLayer, format::Format},
-    units::{Miliseconds, Milliliters, Seconds},
+    progress::{CancellationToken, CombinedProgress, Progress},
+    slice::{self, DynSlicedFile, Layer, SliceConfig, VectorLayer, format::Format},
+    units::{Milimeter, Milimeters, Miliseconds, Milliliters, Seconds},
 };
 use egui::Color32;
 use image::RgbaImage;
@@ -33,7 +33,8 @@ pub struct SliceOperation {
 pub struct SliceOperationInner {
     start_time: Instant,
     pub progress: Progress,
-    pub post_processing_progress: CombinedProgress<2>,
+    pub post_processing_progress: CombinedProgress<4>,
+    pub cancel: CancellationToken,
     pub result: Mutex<Option<SliceResult>>,
     pub previews: Mutex<Option<PreviewImage>>,
 }
@@ -59,7 +60,7 @@ pub enum GenericSliceResult {
 
 #[derive(Clone)]
 pub enum GenericSliceData {
-    Raster { data: Vec<Layer>, voxels: u64 },
+    Raster { data: Vec<Layer> },
     Vector { data: Arc<Vec<VectorLayer>> },
 }
 
@@ -67,9 +68,8 @@ pub struct RasterSliceResult {
     pub layers: Vec<Layer>,
     pub annotations: Arc<Annotations>,
     pub detected_islands: bool,
+    pub detected_resin_traps: bool,
 
-    pub voxels: u64,
-    pub volume: Milliliters,
     pub print_time: Seconds,
 }
 
@@ -78,6 +78,7 @@ pub struct VectorSliceResult {
 }
 
 pub const ISLAND_COLOR: Color32 = Color32::from_rgb(159, 44, 54);
+pub const RESIN_TRAP_COLOR: Color32 = Color32::from_rgb(51, 115, 204);
 
 #[derive(Default)]
 pub struct Annotations {
@@ -90,20 +91,26 @@ pub struct LockedAnnotations<'a> {
     updated: &'a AtomicBool,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Annotation {
     None = 0b00,
     Island = 0b01,
+    ResinTrap = 0b10,
 }
 
 impl SliceOperation {
-    pub fn new(slice: Progress, post_process: CombinedProgress<2>) -> Self {
+    pub fn new(
+        slice: Progress,
+        post_process: CombinedProgress<4>,
+        cancel: CancellationToken,
+    ) -> Self {
         Self {
             inner: Arc::new(SliceOperationInner {
                 start_time: Instant::now(),
                 progress: slice,
                 post_processing_progress: post_process,
+                cancel,
                 result: Mutex::new(None),
                 previews: Mutex::new(Default::default()),
             }),
@@ -128,10 +135,6 @@ impl SliceOperationInner {
     }
 
     pub fn add_raster_result(&self, config: SliceConfig, layers: Vec<Layer>) {
-        let voxels = (layers.iter())
-            .flat_map(|x| x.data.iter().filter(|x| x.value != 0).map(|x| x.length))
-            .sum::<u64>();
-
         let elapsed = self.start_time.elapsed();
         info!("Raster slice operation completed in {:?}", elapsed);
 
@@ -141,16 +144,14 @@ impl SliceOperationInner {
             .tuple_windows()
             .map(|(a, b)| b - a)
             .all_equal_float(0.001);
+
         let raster = RasterSliceResult {
-            voxels,
-            // todo: volume calculation currently doesn't respect nonuniform
-            // layer heights.
-            volume: (voxels as f32 * config.voxel_volume()).convert(),
-            print_time: config.print_time(layers.len() as u32),
+            print_time: slice::print_time(&layers),
 
             layers,
             annotations: Arc::new(Annotations::default()),
             detected_islands: false,
+            detected_resin_traps: false,
         };
 
         self.result().replace(SliceResult {
@@ -196,12 +197,12 @@ impl Annotations {
 }
 
 impl<'a> LockedAnnotations<'a> {
-    pub fn contains(&self, layer: usize) -> bool {
-        if let Some(layer) = self.layers.get(&layer) {
-            layer.iter().any(|x| !matches!(x.value, Annotation::None))
-        } else {
-            false
-        }
+    /// Gets the first kind of annotation on a layer, if there are any.
+    pub fn get(&self, layer: usize) -> Option<Annotation> {
+        let layer = self.layers.get(&layer)?;
+        (layer.iter())
+            .map(|x| x.value)
+            .find(|x| *x != Annotation::None)
     }
 
     pub fn get_layer(&self, layer: usize) -> Vec<Run> {
@@ -212,9 +213,27 @@ impl<'a> LockedAnnotations<'a> {
         unsafe { mem::transmute::<Vec<Run<Annotation>>, Vec<Run>>(layer.clone()) }
     }
 
-    pub fn insert_layer(&mut self, annotation: Annotation, layer: usize, runs: &[u64]) {
-        assert!(!self.layers.contains_key(&layer)); // not yet implemented!
+    /// Gets the island annotations of every layer, as alternating lengths of
+    /// unannotated and island pixels.
+    pub fn islands(&self) -> impl Iterator<Item = (usize, Vec<u64>)> {
+        (self.layers.iter())
+            .filter(|(_, runs)| runs.iter().any(|x| matches!(x.value, Annotation::Island)))
+            .map(|(&layer, runs)| {
+                // Other annotations are treated as unannotated, so neighboring
+                // runs need to be joined back up to keep them alternating.
+                let mut lengths = vec![0];
+                for run in runs {
+                    let island = run.value == Annotation::Island;
+                    if (lengths.len() % 2 == 0) != island {
+                        lengths.push(0);
+                    }
+                    *lengths.last_mut().unwrap() += run.length;
+                }
+                (layer, lengths)
+            })
+    }
 
+    pub fn insert_layer(&mut self, annotation: Annotation, layer: usize, runs: &[u64]) {
         let runs = runs
             .iter()
             .enumerate()
@@ -223,11 +242,64 @@ impl<'a> LockedAnnotations<'a> {
                 value: [Annotation::None, annotation][(i % 2 != 0) as usize],
             })
             .collect::<Vec<_>>();
+
+        let runs = match self.layers.remove(&layer) {
+            Some(old) => merge_annotations(&old, &runs),
+            None => runs,
+        };
         self.layers.insert(layer, runs);
         self.updated.store(true, Ordering::Relaxed);
     }
 }
 
+impl Annotation {
+    pub fn color(&self) -> Color32 {
+        match self {
+            Annotation::None => Color32::WHITE,
+            Annotation::Island => ISLAND_COLOR,
+            Annotation::ResinTrap => RESIN_TRAP_COLOR,
+        }
+    }
+}
+
+/// Overlays the new annotation runs over the old ones, keeping the old value
+/// anywhere the new runs are unannotated. Either can end early.
+fn merge_annotations(old: &[Run<Annotation>], new: &[Run<Annotation>]) -> Vec<Run<Annotation>> {
+    let mut out = Vec::<Run<Annotation>>::new();
+    let (mut old, mut new) = (old.iter().copied(), new.iter().copied());
+    let (mut a, mut b) = (old.next(), new.next());
+
+    loop {
+        let length = match (a, b) {
+            (Some(a), Some(b)) => a.length.min(b.length),
+            (Some(x), None) | (None, Some(x)) => x.length,
+            (None, None) => break,
+        };
+
+        let value = match (a, b) {
+            (_, Some(b)) if b.value != Annotation::None => b.value,
+            (Some(a), _) => a.value,
+            _ => Annotation::None,
+        };
+
+        match out.last_mut() {
+            Some(last) if last.value == value => last.length += length,
+            _ => out.push(Run { length, value }),
+        }
+
+        for (run, iter) in [(&mut a, &mut old), (&mut b, &mut new)] {
+            if let Some(x) = run {
+                x.length -= length;
+                if x.length == 0 {
+                    *run = iter.next();
+                }
+            }
+        }
+    }
+
+    out
+}
+
 impl SliceResult {
     pub fn completion(&self) -> String {
         let time = self.elapsed.as_millis() as f32;
@@ -239,7 +311,6 @@ impl SliceResult {
         match &self.inner {
             GenericSliceResult::Raster(result) => GenericSliceData::Raster {
                 data: result.layers.clone(),
-                voxels: result.voxels,
             },
             GenericSliceResult::Vector(result) => GenericSliceData::Vector {
                 data: result.layers.clone(),
@@ -248,6 +319,22 @@ impl SliceResult {
     }
 }
 
+impl RasterSliceResult {
+    /// Resin used by the print, or None if some layers of a loaded file
+    /// haven't been decoded yet.
+    pub fn volume(&self, config: &SliceConfig) -> Option<Milliliters> {
+        // Each layer's area is multiplied by its own thickness, so the volume
+        // stays right after layers are merged or hollowed.
+        let (mut bottom, mut volume) = (0.0, 0.0);
+        for layer in self.layers.iter() {
+            let top = layer.height.get::<Milimeter>();
+            volume += layer.cached_area()? as f32 * (top - mem::replace(&mut bottom, top));
+        }
+
+        Some((config.pixel_area() * Milimeters::new(volume)).convert())
+    }
+}
+
 impl GenericSliceResult {
     pub fn as_raster(&self) -> Option<&RasterSliceResult> {
         match self {
@@ -279,9 +366,10 @@ impl GenericSliceData {
         format: Format,
     ) -> DynSlicedFile {
         match &self {
-            GenericSliceData::Raster { data, voxels } => {
+            GenericSliceData::Raster { data } => {
                 let format = format.as_raster().unwrap();
-                let mut file = util::export_raster(config, data.iter(), *voxels, format);
+                let voxels = data.iter().map(Layer::area).sum();
+                let mut file = util::export_raster(config, data.iter(), voxels, format);
                 file.set_preview(preview_image);
                 file
             }
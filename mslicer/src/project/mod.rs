@@ -6,12 +6,17 @@ use crate::{
 };
 use common::{
     id_type,
-    progress::CombinedProgress,
+    progress::{CancellationToken, Cancelled, CombinedProgress},
     slice::{Layer, SliceConfig},
 };
-use slicer::post_process::{
-    elephant_foot_fixer::ElephantFootFixer, variable_layer_height::VariableLayerHeight,
+use slicer::{
+    post_process::{
+        elephant_foot_fixer::ElephantFootFixer, hollowing::Hollowing, smart_motion::SmartMotion,
+        variable_layer_height::VariableLayerHeight,
+    },
+    slicer::cut::CutPrimitive,
 };
+use tools::supports::SupportConfig;
 
 pub mod model;
 pub mod storage;
@@ -22,6 +27,7 @@ pub struct Project {
     pub path: Option<PathBuf>,
     pub slice_config: SliceConfig,
     pub post_processing: PostProcessing,
+    pub support_config: SupportConfig,
     pub models: Vec<Model>,
     pub collections: Vec<Collection>,
 }
@@ -37,8 +43,10 @@ pub struct Collection {
 
 #[derive(Default, Clone)]
 pub struct PostProcessing {
+    pub hollowing: Hollowing,
     pub variable_layer_height: VariableLayerHeight,
     pub elephant_foot_fixer: ElephantFootFixer,
+    pub smart_motion: SmartMotion,
 }
 
 #[derive(Default, Clone)]
@@ -61,6 +69,7 @@ impl Project {
         self.path = None;
         self.slice_config = default_config.clone();
         self.post_processing = Default::default();
+        self.support_config = Default::default();
         self.models.clear();
         self.collections.clear();
     }
@@ -121,12 +130,21 @@ impl PostProcessing {
         &self,
         config: &SliceConfig,
         layers: &mut Vec<Layer>,
-        progress: CombinedProgress<2>,
-    ) {
+        cuts: &[CutPrimitive],
+        progress: CombinedProgress<4>,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        // Hollowing comes first so similar layers are merged after they have
+        // been hollowed. It works from the height of each layer, so the thin
+        // and thick layers from slope mode still get the right wall thickness.
+        self.hollowing
+            .post_slice(config, layers, cuts, progress[0].clone(), cancel)?;
         self.variable_layer_height
-            .post_slice(config, layers, progress[0].clone());
+            .post_slice(config, layers, progress[1].clone(), cancel)?;
         self.elephant_foot_fixer
-            .post_slice(config, layers, progress[1].clone());
+            .post_slice(config, layers, progress[2].clone(), cancel)?;
+        self.smart_motion
+            .post_slice(config, layers, progress[3].clone(), cancel)
     }
 }
 
@@ -4,13 +4,13 @@ use bitflags::bitflags;
 use common::{
     color::{LinearRgb, START_COLOR},
     id_type,
-    serde::{Deserializer, Serializer},
+    serde::{Deserializer, SerdeExt, Serializer},
     units::{CubicMilimeters, Milimeters},
 };
 use nalgebra::Vector3;
 use wgpu::{Buffer, Device};
 
-use slicer::{geometry::bvh::Bvh, half_edge::HalfEdgeMesh, mesh::Mesh};
+use slicer::{geometry::bvh::Bvh, half_edge::HalfEdgeMesh, mesh::Mesh, slicer::cut::CutPrimitive};
 
 use crate::{
     project::{CollectionId, RenameState, supports::Supports},
@@ -26,6 +26,7 @@ pub struct Model {
     pub bvh: Option<Arc<Bvh>>,
     pub half_edge: Option<Arc<HalfEdgeMesh>>,
     pub supports: Supports,
+    pub drain_holes: Vec<DrainHole>,
     base_volume: CubicMilimeters,
 
     pub unit: MeshUnit,
@@ -40,6 +41,18 @@ pub struct Model {
     pub file: Option<PathBuf>,
 }
 
+/// A hole cut into the surface of a model so resin can drain out of its
+/// hollow interior. The position and normal are relative to the model, so the
+/// hole follows it when moved.
+#[derive(Clone, Copy, PartialEq)]
+pub struct DrainHole {
+    pub position: Vector3<f32>,
+    pub normal: Vector3<f32>,
+    pub radius: f32,
+    /// How far into the model the hole goes.
+    pub depth: f32,
+}
+
 #[derive(Clone)]
 pub struct ModelUi {
     pub rename: RenameState,
@@ -79,6 +92,7 @@ impl Model {
             bvh: None,
             half_edge: None,
             supports: Supports::default(),
+            drain_holes: Vec::new(),
             mesh,
 
             unit: MeshUnit::Millimeters,
@@ -128,6 +142,16 @@ impl Model {
         self
     }
 
+    pub fn with_supports(mut self, supports: Supports) -> Self {
+        self.supports = supports;
+        self
+    }
+
+    pub fn with_drain_holes(mut self, drain_holes: Vec<DrainHole>) -> Self {
+        self.drain_holes = drain_holes;
+        self
+    }
+
     pub fn width_file(mut self, path: Option<PathBuf>) -> Self {
         self.file = path;
         self
@@ -281,6 +305,36 @@ impl MeshUnit {
     }
 }
 
+impl DrainHole {
+    /// Gets the cylinder removed from the layers, in world space. It starts a
+    /// bit outside of the surface so the opening isn't left covered.
+    pub fn cut(&self, mesh: &Mesh) -> CutPrimitive {
+        let position = mesh.transform(&self.position);
+        let normal = mesh.transform_normal(&self.normal).normalize();
+        CutPrimitive::Cylinder {
+            start: position + normal * self.radius,
+            end: position - normal * self.depth,
+            radius: self.radius,
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        self.position.serialize(ser);
+        self.normal.serialize(ser);
+        ser.write_f32_be(self.radius);
+        ser.write_f32_be(self.depth);
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Self {
+        Self {
+            position: Vector3::<f32>::deserialize(des),
+            normal: Vector3::<f32>::deserialize(des),
+            radius: des.read_f32_be(),
+            depth: des.read_f32_be(),
+        }
+    }
+}
+
 impl Clone for Model {
     fn clone(&self) -> Self {
         Self {
@@ -291,7 +345,8 @@ impl Clone for Model {
             mesh: self.mesh.clone(),
             bvh: self.bvh.clone(),
             half_edge: self.half_edge.clone(),
-            supports: Supports::default(),
+            supports: self.supports.clone(),
+            drain_holes: self.drain_holes.clone(),
             base_volume: self.base_volume,
 
             unit: self.unit,
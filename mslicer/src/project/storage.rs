@@ -5,7 +5,8 @@ use nalgebra::Vector3;
 
 use crate::project::{
     Collection, CollectionId, PostProcessing, Project, RenameState,
-    model::{MeshUnit, Model},
+    model::{DrainHole, MeshUnit, Model},
+    supports::Supports,
 };
 use common::{
     progress::Progress,
@@ -15,14 +16,55 @@ use common::{
 use slicer::{
     mesh::{Mesh, MeshInner},
     post_process::{
-        elephant_foot_fixer::ElephantFootFixer, variable_layer_height::VariableLayerHeight,
+        elephant_foot_fixer::ElephantFootFixer, hollowing::Hollowing, smart_motion::SmartMotion,
+        variable_layer_height::VariableLayerHeight,
     },
 };
+use tools::supports::SupportConfig;
 
 /// Project format version. Value should be incremented whenever the save format
 /// changes, even in development so anyone using the dev/prerelease versions
 /// don't ruin their projects.
 ///
+/// ## v25 (v0.9.0)
+/// Added the pause hold time to the slice config.
+///
+/// ## v24 (v0.9.0)
+/// Added the display uniformity mask to the slice config.
+///
+/// ## v23 (v0.9.0)
+/// Added the smart motion post processor.
+///
+/// ## v22 (v0.9.0)
+/// Added exposure overrides for ranges of layer heights to the slice config.
+///
+/// ## v21 (v0.9.0)
+/// Added two stage lifts and retracts and rest times to the exposure config.
+///
+/// ## v20 (v0.9.0)
+/// Added dimensional compensation to the slice config.
+///
+/// ## v19 (v0.9.0)
+/// Added the slope based variable layer height mode.
+///
+/// ## v18 (v0.9.0)
+/// Store model drain holes.
+///
+/// ## v17 (v0.9.0)
+/// Added hollowing post processor.
+///
+/// ## v16 (v0.9.0)
+/// Added raft styles and tapered raft edges to the support config.
+///
+/// ## v15 (v0.9.0)
+/// Store support cross braces and the load scaling and bracing config.
+///
+/// ## v14 (v0.9.0)
+/// Store supports as branching trees and the support routing config.
+///
+/// ## v13 (v0.9.0)
+/// Store model supports and the support config.
+///
 /// ## v12 (v0.9.0)
 /// Store mesh units.
 ///
@@ -58,7 +100,7 @@ use slicer::{
 /// ## v2 (v0.5.0)
 /// A complete rewrite using a custom serilizer/deserilizer because of the
 /// bincode drama...
-const VERSION: u16 = 12;
+const VERSION: u16 = 25;
 
 struct ModelInfo {
     mesh: u32,
@@ -73,6 +115,9 @@ struct ModelInfo {
     position: Vector3<f32>,
     scale: Vector3<f32>,
     rotation: Vector3<f32>,
+
+    supports: Supports,
+    drain_holes: Vec<DrainHole>,
 }
 
 impl ModelInfo {
@@ -88,6 +133,8 @@ impl ModelInfo {
             position: model.mesh.position(),
             scale: model.mesh.scale(),
             rotation: model.mesh.rotation(),
+            supports: model.supports.clone(),
+            drain_holes: model.drain_holes.clone(),
         }
     }
 
@@ -105,6 +152,8 @@ impl ModelInfo {
             .with_hidden(self.hidden)
             .with_collection(self.collection)
             .with_unit(self.unit)
+            .with_supports(self.supports)
+            .with_drain_holes(self.drain_holes)
     }
 
     pub fn serialize<T: Serializer>(&self, ser: &mut T) {
@@ -130,6 +179,10 @@ impl ModelInfo {
         self.position.serialize(ser);
         self.scale.serialize(ser);
         self.rotation.serialize(ser);
+
+        self.supports.serialize(ser);
+        ser.write_u32_be(self.drain_holes.len() as u32);
+        self.drain_holes.iter().for_each(|x| x.serialize(ser));
     }
 
     pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
@@ -157,6 +210,18 @@ impl ModelInfo {
             position: Vector3::<f32>::deserialize(des),
             scale: Vector3::<f32>::deserialize(des),
             rotation: Vector3::<f32>::deserialize(des),
+            supports: if version < 13 {
+                Supports::default()
+            } else {
+                Supports::deserialize(des, version)
+            },
+            drain_holes: if version < 18 {
+                Vec::new()
+            } else {
+                (0..des.read_u32_be())
+                    .map(|_| DrainHole::deserialize(des))
+                    .collect()
+            },
         }
     }
 }
@@ -166,6 +231,7 @@ impl Project {
         ser.write_u16_be(VERSION);
         self.slice_config.serialize(ser);
         self.post_processing.serialize(ser);
+        self.support_config.serialize(ser);
 
         let mut total = 0;
         let mut map = HashMap::new();
@@ -207,6 +273,11 @@ impl Project {
 
         let slice_config = SliceConfig::deserialize(des, version)?;
         let post_processing = PostProcessing::deserialize(des, version);
+        let support_config = if version < 13 {
+            SupportConfig::default()
+        } else {
+            SupportConfig::deserialize(des, version)
+        };
 
         let models = des.read_u32_be();
         let models = (0..models)
@@ -240,6 +311,7 @@ impl Project {
             path: None,
             slice_config,
             post_processing,
+            support_config,
             models,
             collections,
         })
@@ -272,6 +344,8 @@ impl PostProcessing {
     pub fn serialize<T: Serializer>(&self, ser: &mut T) {
         self.variable_layer_height.serialize(ser);
         self.elephant_foot_fixer.serialize(ser);
+        self.hollowing.serialize(ser);
+        self.smart_motion.serialize(ser);
     }
 
     pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
@@ -283,6 +357,16 @@ impl PostProcessing {
                 VariableLayerHeight::deserialize(des, version)
             },
             elephant_foot_fixer: ElephantFootFixer::deserialize(des),
+            hollowing: if version < 17 {
+                Default::default()
+            } else {
+                Hollowing::deserialize(des)
+            },
+            smart_motion: if version < 23 {
+                Default::default()
+            } else {
+                SmartMotion::deserialize(des)
+            },
         }
     }
 }
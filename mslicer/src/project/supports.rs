@@ -1,88 +1,95 @@
-use std::f32::consts::PI;
-
-use nalgebra::{Vector2, Vector3};
+use common::serde::{Deserializer, SerdeExt, Serializer};
+use nalgebra::Vector3;
 use slicer::{builder::MeshBuilder, mesh::Mesh};
-use tools::supports::{SupportConfig, build_raft_mesh};
+use tools::supports::{GeneratedSupports, RaftConfig, SupportTree, build_raft_mesh};
 use wgpu::Device;
 
 use crate::{project::model::RenderedMeshBuffers, render::util::gpu_mesh_buffers};
 
 #[derive(Default)]
 pub struct Supports {
-    auto: Vec<Support>,
-    manual: Vec<Support>,
+    auto: SupportTree,
+    manual: SupportTree,
+    /// Overhang points the last support generation couldn't route to the bed.
+    unsupported: Vec<Vector3<f32>>,
 
+    /// The raft settings the cached mesh was built with.
+    raft: Option<RaftConfig>,
     mesh: Option<Mesh>,
     buffers: Option<RenderedMeshBuffers>,
 }
 
-pub struct Support {
-    points: [Vector3<f32>; 3],
-    tip_radius: f32,
-    radius: f32,
-}
-
 impl Supports {
+    pub fn is_empty(&self) -> bool {
+        self.auto.is_empty() && self.manual.is_empty()
+    }
+
     pub fn invalidate_cache(&mut self) {
+        self.raft.take();
         self.mesh.take();
         self.buffers.take();
     }
 
-    pub fn replace_auto(&mut self, config: &SupportConfig, supports: Vec<[Vector3<f32>; 3]>) {
+    pub fn replace_auto(&mut self, generated: GeneratedSupports) {
         self.invalidate_cache();
-        self.auto = supports
-            .into_iter()
-            .map(|points| Support {
-                points,
-                tip_radius: config.tip_radius,
-                radius: config.support_radius,
-            })
-            .collect();
+        self.auto = generated.tree;
+        self.unsupported = generated.unsupported;
     }
 
-    pub fn add_manual(&mut self, config: &SupportConfig, support: [Vector3<f32>; 3]) {
+    /// Gets the manually placed supports for editing.
+    pub fn manual_mut(&mut self) -> &mut SupportTree {
         self.invalidate_cache();
-        self.manual.push(Support {
-            points: support,
-            tip_radius: config.tip_radius,
-            radius: config.support_radius,
-        });
+        &mut self.manual
+    }
+
+    pub fn unsupported(&self) -> &[Vector3<f32>] {
+        &self.unsupported
     }
 
-    pub fn mesh(&mut self) -> &Option<Mesh> {
-        if self.mesh.is_some() || (self.auto.is_empty() && self.manual.is_empty()) {
+    pub fn mesh(&mut self, raft: &RaftConfig) -> &Option<Mesh> {
+        if self.raft.as_ref() != Some(raft) {
+            self.invalidate_cache();
+        }
+
+        if self.mesh.is_some() || self.is_empty() {
             return &self.mesh;
         }
 
+        let p = 20; // todo: make precision follow actual config...
         let mut builder = MeshBuilder::new();
-        let mut raft_points = Vec::new();
-        for support in self.auto.iter().chain(self.manual.iter()) {
-            let (r, p) = (support.radius, 20); // todo: make precision follow actual config...
-            let points = &support.points;
-
-            builder.add_cylinder((points[0], points[1]), (support.tip_radius, r), p);
-            builder.add_cylinder((points[1], points[2]), (r, r), p);
-            builder.add_cylinder((points[2], points[2].xy().push(0.0)), (r, r), p);
-
-            for i in 0..(p * 2) {
-                let angle = i as f32 / p as f32 * PI;
-                let normal = Vector2::new(angle.cos(), angle.sin());
-                raft_points.push(points[2].xy() + normal * r);
+        let mut roots = Vec::new();
+        for tree in [&self.auto, &self.manual] {
+            for (node, parent) in tree.segments() {
+                let (a, b) = (node.position, parent.position);
+                builder.add_cylinder((a, b), (node.radius, parent.radius), p);
+            }
+
+            for brace in tree.braces.iter() {
+                let [a, b] = brace.nodes.map(|x| tree.nodes[x].position);
+                builder.add_cylinder((a, b), (brace.radius, brace.radius), p);
             }
 
-            builder.add_sphere(points[0], 0.2, p);
-            builder.add_sphere(points[1], r, p);
-            builder.add_sphere(points[2], r, p);
+            for node in tree.nodes.iter() {
+                builder.add_sphere(node.position, node.radius, p);
+            }
+
+            roots.extend(tree.roots().map(|x| (x.position.xy(), x.radius)));
         }
 
-        build_raft_mesh(1.0, 1.0, &raft_points, &mut builder);
+        build_raft_mesh(raft, &roots, &mut builder);
+        self.raft = Some(raft.clone());
         self.mesh = (!builder.is_empty()).then(|| builder.build());
         &self.mesh
     }
 
-    pub fn get_buffers(&mut self, device: &Device) -> &Option<RenderedMeshBuffers> {
+    pub fn get_buffers(
+        &mut self,
+        device: &Device,
+        raft: &RaftConfig,
+    ) -> &Option<RenderedMeshBuffers> {
+        self.mesh(raft);
         if self.buffers.is_none()
-            && let Some(mesh) = self.mesh()
+            && let Some(mesh) = &self.mesh
         {
             let (vertex_buffer, index_buffer) = gpu_mesh_buffers(device, mesh);
             self.buffers = Some(RenderedMeshBuffers {
@@ -99,4 +106,62 @@ impl Supports {
             .as_ref()
             .map(|x| (x, self.mesh.as_ref().unwrap().face_count() as u32 * 3))
     }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        self.auto.serialize(ser);
+        self.manual.serialize(ser);
+        ser.write_u32_be(self.unsupported.len() as u32);
+        self.unsupported.iter().for_each(|x| x.serialize(ser));
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
+        if version < 14 {
+            return Self {
+                auto: deserialize_legacy(des),
+                manual: deserialize_legacy(des),
+                ..Default::default()
+            };
+        }
+
+        Self {
+            auto: SupportTree::deserialize(des, version),
+            manual: SupportTree::deserialize(des, version),
+            unsupported: (0..des.read_u32_be())
+                .map(|_| Vector3::<f32>::deserialize(des))
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Reads the straight three point supports stored before v14, converting
+/// each into its own path down to the bed.
+fn deserialize_legacy<T: Deserializer>(des: &mut T) -> SupportTree {
+    let mut tree = SupportTree::default();
+    for _ in 0..des.read_u32_be() {
+        let points = [(); 3].map(|_| Vector3::<f32>::deserialize(des));
+        let (tip_radius, radius) = (des.read_f32_be(), des.read_f32_be());
+
+        let path = [
+            (points[0], tip_radius),
+            (points[1], radius),
+            (points[2], radius),
+            (points[2].xy().push(0.0), radius),
+        ];
+        tree.add_path(path, None);
+    }
+
+    tree
+}
+
+impl Clone for Supports {
+    fn clone(&self) -> Self {
+        // The cached mesh and GPU buffers are rebuilt when needed.
+        Self {
+            auto: self.auto.clone(),
+            manual: self.manual.clone(),
+            unsupported: self.unsupported.clone(),
+            ..Default::default()
+        }
+    }
 }
@@ -15,7 +15,7 @@ const BACKGROUND_COLOR = vec3f(0.106);
 const ANNOTATION_COLORS = array(
     vec3f(1.000, 1.000, 1.000), // (00) No annotation
     vec3f(0.624, 0.176, 0.212), // (01) Island
-    vec3f(1.000, 1.000, 1.000), // (10) Unused
+    vec3f(0.200, 0.451, 0.800), // (10) Resin Trap
     vec3f(1.000, 1.000, 1.000), // (11) Unused
 );
 
@@ -66,8 +66,10 @@ fn sample(position: vec2f) -> vec3f {
     }
 
     let brightness = index_slice(upos);
-    let color = index_annotation(upos);
-    return vec3f(brightness) * color;
+    let annotation = index_annotation(upos);
+    // Resin traps are empty, so annotated pixels are always shown.
+    let shade = select(brightness, max(brightness, 0.5), annotation != 0u);
+    return vec3f(shade) * ANNOTATION_COLORS[annotation];
 }
 
 struct Index {
@@ -86,8 +88,7 @@ fn index_slice(pos: vec2u) -> f32 {
     return f32(value) / 255.0;
 }
 
-fn index_annotation(pos: vec2u) -> vec3f {
+fn index_annotation(pos: vec2u) -> u32 {
     let index = index(pos);
-    let value = (annotations[index.array_idx] >> index.shift) & 0xFF;
-    return ANNOTATION_COLORS[value];
+    return (annotations[index.array_idx] >> index.shift) & 0xFF;
 }
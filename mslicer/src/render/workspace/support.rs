@@ -110,8 +110,9 @@ impl SupportPipeline {
         gcx.queue
             .write_buffer(&self.uniform_buffer, 0, &buffer.into_inner());
 
+        let raft = &app.project.support_config.raft;
         for model in app.project.models.iter_mut() {
-            model.supports.get_buffers(&gcx.device);
+            model.supports.get_buffers(&gcx.device, raft);
         }
 
         let Some(mesh) = &app.state.support_preview else {
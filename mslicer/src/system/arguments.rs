@@ -8,6 +8,7 @@ use tracing::warn;
 use crate::{
     app::App,
     task::{LoadSliced, MeshLoad, ProjectLoad},
+    ui::popup::{Popup, PopupIcon},
 };
 
 pub struct Args {
@@ -49,8 +50,13 @@ impl OpenInto {
 
         match ext.to_ascii_lowercase().as_str() {
             "mslicer" if self.project.is_none() => self.project = Some(path),
-            "goo" | "ctb" | "nanodlp" if self.sliced.is_none() => self.sliced = Some(path),
-            "stl" | "obj" => self.models.push((ext.into_owned(), path)),
+            "goo" | "ctb" | "cbddlp" | "photon" | "nanodlp" | "sl1" | "sl1s" | "pwmx" | "pwma"
+            | "pm3"
+                if self.sliced.is_none() =>
+            {
+                self.sliced = Some(path)
+            }
+            "stl" | "obj" | "3mf" | "ply" => self.models.push((ext.into_owned(), path)),
             _ => {}
         }
     }
@@ -66,7 +72,14 @@ impl OpenInto {
 
         for (ext, path) in self.models {
             let name = path.file_name().unwrap().to_string_lossy().into_owned();
-            app.tasks.add(MeshLoad::file(path, name, ext).unwrap());
+            match MeshLoad::file(path, name, ext) {
+                Ok(task) => app.tasks.add(task),
+                Err(err) => app.popup.open(Popup::simple(
+                    "Failed to Load Model",
+                    PopupIcon::Error,
+                    err.to_string(),
+                )),
+            }
         }
     }
 }
@@ -79,7 +79,10 @@ fn install() -> Result<()> {
     LOCAL_MACHINE
         .create(r"Software\Classes\mslicer")?
         .set_string("", "mslicer Project")?;
-    for format in [".mslicer", ".stl", ".obj", ".goo", ".ctb", ".nanodlp"] {
+    for format in [
+        ".mslicer", ".stl", ".obj", ".3mf", ".ply", ".goo", ".ctb", ".nanodlp", ".sl1", ".sl1s",
+        ".cbddlp", ".photon", ".pwmx", ".pwma", ".pm3",
+    ] {
         LOCAL_MACHINE
             .create(format!(r"Software\Classes\{format}\OpenWithProgIds"))?
             .set_string("mslicer", "")?;
@@ -53,4 +53,8 @@ impl Task for BuildAccelerationStructures {
             progress: self.progress.progress(),
         })
     }
+
+    fn cancellable(&self) -> bool {
+        false
+    }
 }
@@ -1,5 +1,9 @@
 use clone_macro::clone;
-use common::{progress::Progress, slice::SliceConfig, units::Milimeter};
+use common::{
+    progress::{CancellationToken, Cancelled, Progress},
+    slice::SliceConfig,
+    units::Milimeter,
+};
 use tools::auto_layout::{self, Placement};
 
 use crate::{
@@ -9,8 +13,9 @@ use crate::{
 };
 
 pub struct AutoLayout {
-    handle: TaskThread<Vec<Placement>>,
+    handle: TaskThread<Result<Vec<Placement>, Cancelled>>,
     progress: Progress,
+    cancel: CancellationToken,
 }
 
 impl AutoLayout {
@@ -22,15 +27,20 @@ impl AutoLayout {
         let platform = (slice_config.platform_size.xy()).map(|x| x.get::<Milimeter>());
         let (mut cache, models) = layout_cache(padding, models);
 
-        let progress = Progress::new();
-        let handle = TaskThread::spawn(clone!([progress], move || {
+        let (progress, cancel) = (Progress::new(), CancellationToken::new());
+        let handle = TaskThread::spawn(clone!([progress, cancel], move || {
             auto_layout::AutoLayoutNfp::new(platform, models, &mut cache)
                 .segment_steps(segment_steps)
+                .cancellation(cancel)
                 .layout(progress)
-                .1
+                .map(|x| x.1)
         }));
 
-        Self { handle, progress }
+        Self {
+            handle,
+            progress,
+            cancel,
+        }
     }
 }
 
@@ -39,8 +49,7 @@ impl Task for AutoLayout {
         self.handle
             .poll(app, "Failed to Layout Models")
             .into_poll_result(|x| {
-                x.iter()
-                    .for_each(|x| apply_placement(&mut app.project.models, x));
+                (x.iter().flatten()).for_each(|x| apply_placement(&mut app.project.models, x));
                 PollResult::complete()
             })
     }
@@ -52,4 +61,8 @@ impl Task for AutoLayout {
             progress: self.progress.progress(),
         })
     }
+
+    fn cancel(&mut self) {
+        self.cancel.cancel();
+    }
 }
@@ -0,0 +1,69 @@
+use std::{fs::File, io::BufWriter, path::PathBuf};
+
+use clone_macro::clone;
+use common::{progress::Progress, serde::WriterSerializer};
+use mesh_format::{Object, save_objects};
+use slicer::mesh::Mesh;
+
+use crate::task::{PollResult, Task, TaskApp, TaskStatus, thread::TaskThread};
+
+pub struct ExportMesh {
+    progress: Progress,
+    file_name: String,
+    handle: TaskThread<()>,
+}
+
+impl ExportMesh {
+    /// Writes the meshes, with their transforms applied, to the file. The
+    /// format is chosen from the file's extension.
+    pub fn new(meshes: Vec<(String, Mesh)>, path: PathBuf) -> Self {
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let format = (path.extension())
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let progress = Progress::new();
+        let handle = TaskThread::spawn(clone!([progress], move || {
+            let objects = (meshes.into_iter())
+                .map(|(name, mesh)| {
+                    let inner = mesh_format::Mesh {
+                        verts: mesh.vertices().to_vec(),
+                        faces: mesh.faces().to_vec(),
+                    };
+                    Object::new(Some(name), inner, *mesh.transformation_matrix())
+                })
+                .collect::<Vec<_>>();
+
+            let file = File::create(&path).unwrap();
+            let mut ser = WriterSerializer::new(BufWriter::new(file));
+            save_objects(&mut ser, &objects, &format, progress).unwrap();
+            ser.finish().unwrap();
+        }));
+
+        Self {
+            progress,
+            file_name,
+            handle,
+        }
+    }
+}
+
+impl Task for ExportMesh {
+    fn poll(&mut self, app: &mut TaskApp) -> PollResult {
+        self.handle
+            .poll(app, "Failed to Export Model")
+            .into_poll_result(|_| PollResult::complete())
+    }
+
+    fn status(&self) -> Option<TaskStatus<'_>> {
+        Some(TaskStatus {
+            name: "Exporting Model".into(),
+            details: Some(format!("Saving to {}", self.file_name)),
+            progress: self.progress.progress(),
+        })
+    }
+
+    fn cancellable(&self) -> bool {
+        false
+    }
+}
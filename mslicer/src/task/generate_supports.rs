@@ -0,0 +1,80 @@
+use clone_macro::clone;
+use common::{
+    progress::{CancellationToken, Cancelled, Progress},
+    units::Milimeters,
+};
+use nalgebra::Vector3;
+use tools::supports::{GeneratedSupports, SupportConfig, SupportGenerator};
+
+use crate::{
+    project::model::{Model, ModelId},
+    task::{PollResult, Task, TaskApp, TaskStatus, thread::TaskThread},
+};
+
+pub struct GenerateSupports {
+    model: ModelId,
+    name: String,
+
+    progress: Progress,
+    cancel: CancellationToken,
+    handle: TaskThread<Result<GeneratedSupports, Cancelled>>,
+}
+
+impl GenerateSupports {
+    pub fn new(
+        model: &Model,
+        config: &SupportConfig,
+        platform: Vector3<Milimeters>,
+        islands: Vec<Vector3<f32>>,
+    ) -> Self {
+        let (progress, cancel) = (Progress::new(), CancellationToken::new());
+        let half_edge = model.half_edge.clone().unwrap();
+        let bvh = model.bvh.clone().unwrap();
+        let config = config.clone();
+
+        let handle = TaskThread::spawn(clone!(
+            [progress, cancel, { model.mesh } as mesh],
+            move || {
+                SupportGenerator::new(&config, platform)
+                    .progress(progress)
+                    .cancellation(cancel)
+                    .islands(islands)
+                    .generate_supports(&mesh, &half_edge, &bvh)
+            }
+        ));
+
+        Self {
+            model: model.id,
+            name: model.name.clone(),
+
+            progress,
+            cancel,
+            handle,
+        }
+    }
+}
+
+impl Task for GenerateSupports {
+    fn poll(&mut self, app: &mut TaskApp) -> PollResult {
+        self.handle
+            .poll(app, "Failed to Generate Supports")
+            .into_poll_result(|result| {
+                if let (Ok(supports), Some(model)) = (result, app.project.model(self.model)) {
+                    model.supports.replace_auto(supports);
+                }
+                PollResult::complete()
+            })
+    }
+
+    fn status(&self) -> Option<TaskStatus<'_>> {
+        Some(TaskStatus {
+            name: "Generating Supports".into(),
+            details: Some(format!("Generating supports for `{}`", self.name)),
+            progress: self.progress.progress(),
+        })
+    }
+
+    fn cancel(&mut self) {
+        self.cancel.cancel();
+    }
+}
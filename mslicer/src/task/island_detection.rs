@@ -1,7 +1,10 @@
 use std::sync::Arc;
 
 use clone_macro::clone;
-use common::{progress::Progress, slice::Layer};
+use common::{
+    progress::{CancellationToken, Progress},
+    slice::Layer,
+};
 use nalgebra::Vector2;
 use slicer::post_process::island_detection::detect_islands;
 
@@ -12,6 +15,7 @@ use crate::{
 
 pub struct IslandDetection {
     progress: Progress,
+    cancel: CancellationToken,
     handle: TaskThread<()>,
 }
 
@@ -21,10 +25,13 @@ impl IslandDetection {
         layers: Vec<Layer>,
         annotations: Arc<Annotations>,
     ) -> Self {
-        let progress = Progress::new();
+        let (progress, cancel) = (Progress::new(), CancellationToken::new());
         Self {
-            handle: TaskThread::spawn(clone!([layers, progress], move || {
-                let islands = detect_islands(resolution, &layers, progress, true);
+            handle: TaskThread::spawn(clone!([layers, progress, cancel], move || {
+                let Ok(islands) = detect_islands(resolution, &layers, progress, &cancel, true)
+                else {
+                    return;
+                };
 
                 let mut annotations = annotations.lock();
                 for (layer, runs) in islands
@@ -36,6 +43,7 @@ impl IslandDetection {
                 }
             })),
             progress,
+            cancel,
         }
     }
 }
@@ -54,4 +62,8 @@ impl Task for IslandDetection {
             progress: self.progress.progress(),
         })
     }
+
+    fn cancel(&mut self) {
+        self.cancel.cancel();
+    }
 }
@@ -1,8 +1,9 @@
-use std::{fs, path::PathBuf};
+use std::path::PathBuf;
 
 use clone_macro::clone;
 use common::{
-    progress::{CombinedProgress, Progress},
+    container::SharedBytes,
+    progress::{CancellationToken, CombinedProgress, Progress},
     slice::{Layer, SliceConfig, format::RasterFormat},
 };
 use image::RgbaImage;
@@ -25,7 +26,7 @@ impl LoadSliced {
             let ext = path.extension().unwrap().to_string_lossy();
             let format = RasterFormat::from_extension(&ext).unwrap();
 
-            let data = fs::read(path).unwrap(); // todo:handle
+            let data = SharedBytes::map(path).unwrap(); // todo:handle
             slicer::util::load_sliced(&progress, &format, &data).unwrap() // todo: handle
         }));
         Self { progress, handle }
@@ -40,6 +41,7 @@ impl Task for LoadSliced {
                 let operation = SliceOperation::new(
                     Progress::already_complete(),
                     CombinedProgress::already_complete(),
+                    CancellationToken::new(),
                 );
 
                 operation.add_raster_result(config, layers);
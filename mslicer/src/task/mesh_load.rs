@@ -6,22 +6,26 @@ use common::{
     progress::Progress,
     serde::{ReaderDeserializer, SliceDeserializer},
 };
-use mesh_format::load_mesh;
+use mesh_format::{Object, load_objects};
 
+use nalgebra::Vector3;
 use slicer::mesh::Mesh;
 use tracing::info;
 
 use crate::{
-    project::model::Model,
+    project::{Collection, model::Model},
     task::{
         MeshManifold, PollResult, Task, TaskApp, TaskStatus,
         acceleration_structures::BuildAccelerationStructures, thread::TaskThread,
     },
+    ui::popup::{Popup, PopupIcon},
 };
 
+const FAILURE: &str = "Failed to Load Model";
+
 pub struct MeshLoad {
     progress: Progress,
-    join: TaskThread<Mesh>,
+    join: TaskThread<mesh_format::Result<Vec<(Option<String>, Mesh)>>>,
 
     name: String,
     file: Option<PathBuf>,
@@ -35,8 +39,7 @@ impl MeshLoad {
         let progress = Progress::new();
         Ok(Self {
             join: TaskThread::spawn(clone!([progress], move || {
-                let mesh = load_mesh(des, &format, progress).unwrap();
-                Mesh::new(mesh.verts, mesh.faces)
+                load_objects(des, &format, progress).map(place_objects)
             })),
             progress,
 
@@ -50,8 +53,7 @@ impl MeshLoad {
         let progress = Progress::new();
         Self {
             join: TaskThread::spawn(clone!([progress], move || {
-                let mesh = load_mesh(des, &format, progress).unwrap();
-                Mesh::new(mesh.verts, mesh.faces)
+                load_objects(des, &format, progress).map(place_objects)
             })),
             progress,
 
@@ -63,7 +65,7 @@ impl MeshLoad {
     pub fn complete(name: String, mesh: Mesh) -> Self {
         Self {
             progress: Progress::already_complete(),
-            join: TaskThread::spawn(|| mesh),
+            join: TaskThread::spawn(|| Ok(vec![(None, mesh)])),
 
             name,
             file: None,
@@ -73,22 +75,44 @@ impl MeshLoad {
 
 impl Task for MeshLoad {
     fn poll(&mut self, app: &mut TaskApp) -> PollResult {
-        (self.join.poll(app, "Failed to Load Model")).into_poll_result(|mesh| {
-            info!(
-                "Loaded model `{}` with {} faces",
-                self.name,
-                mesh.face_count()
-            );
-
-            let mut model = Model::from_mesh(mesh)
-                .with_name(mem::take(&mut self.name))
-                .width_file(self.file.take())
-                .with_random_color();
-            model.update_oob(&app.project.slice_config.platform_size);
-            let result = PollResult::complete()
-                .with_task(MeshManifold::new(&model))
-                .with_task(BuildAccelerationStructures::new(&model));
-            app.project.models.push(model);
+        (self.join.poll(app, FAILURE)).into_poll_result(|objects| {
+            let objects = match objects {
+                Ok(objects) => objects,
+                Err(err) => {
+                    app.popup
+                        .open(Popup::simple(FAILURE, PopupIcon::Error, err.to_string()));
+                    return PollResult::complete();
+                }
+            };
+
+            let mut result = PollResult::complete();
+
+            // Files with multiple objects (3MF) get loaded into their own collection.
+            let collection = (objects.len() > 1).then(|| Collection::new(self.name.clone()));
+            for (i, (name, mesh)) in objects.into_iter().enumerate() {
+                let name = match (&collection, name) {
+                    (None, _) => mem::take(&mut self.name),
+                    (Some(_), Some(name)) => name,
+                    (Some(_), None) => format!("Object {i}"),
+                };
+
+                info!("Loaded model `{name}` with {} faces", mesh.face_count());
+
+                // Reloading only makes sense if the model is the whole file.
+                let file = collection.is_none().then(|| self.file.take()).flatten();
+                let mut model = Model::from_mesh(mesh)
+                    .with_name(name)
+                    .width_file(file)
+                    .with_collection(collection.as_ref().map(|x| x.id))
+                    .with_random_color();
+                model.update_oob(&app.project.slice_config.platform_size);
+                result = result
+                    .with_task(MeshManifold::new(&model))
+                    .with_task(BuildAccelerationStructures::new(&model));
+                app.project.models.push(model);
+            }
+
+            app.project.collections.extend(collection);
             result
         })
     }
@@ -101,3 +125,32 @@ impl Task for MeshLoad {
         })
     }
 }
+
+/// Converts the loaded objects into meshes, keeping their placement relative
+/// to each other. The group as a whole is centered on the platform and moved
+/// to rest on the build plate.
+fn place_objects(objects: Vec<Object>) -> Vec<(Option<String>, Mesh)> {
+    // The same origin that `Mesh::new` centers vertices on.
+    fn origin(verts: &[Vector3<f32>]) -> Vector3<f32> {
+        let (min, max) = verts.iter().fold(
+            (Vector3::repeat(f32::MAX), Vector3::repeat(f32::MIN)),
+            |(min, max), v| (min.inf(v), max.sup(v)),
+        );
+        Vector3::new((min.x + max.x) / 2.0, (min.y + max.y) / 2.0, min.z)
+    }
+
+    let objects = (objects.into_iter())
+        .map(|mut x| (x.name.take(), x.into_mesh()))
+        .collect::<Vec<_>>();
+    let all = objects.iter().flat_map(|(_, x)| x.verts.iter().copied());
+    let offset = origin(&all.collect::<Vec<_>>());
+
+    (objects.into_iter())
+        .map(|(name, mesh)| {
+            let position = origin(&mesh.verts) - offset;
+            let mut mesh = Mesh::new(mesh.verts, mesh.faces);
+            mesh.set_position(position);
+            (name, mesh)
+        })
+        .collect()
+}
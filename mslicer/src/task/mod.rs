@@ -12,7 +12,9 @@ use crate::{
 
 mod acceleration_structures;
 mod auto_layout;
+mod export_mesh;
 mod file_dialog;
+mod generate_supports;
 mod island_detection;
 mod load_sliced;
 mod mesh_load;
@@ -21,6 +23,7 @@ mod project;
 mod reconstruct_mesh;
 mod reload_model;
 mod remote_print;
+mod resin_trap_detection;
 mod save_result;
 mod split_bodies;
 mod thread;
@@ -29,7 +32,9 @@ mod webhook;
 pub use self::{
     acceleration_structures::BuildAccelerationStructures,
     auto_layout::AutoLayout,
+    export_mesh::ExportMesh,
     file_dialog::{FileDialog, MultiFileDialog},
+    generate_supports::GenerateSupports,
     island_detection::IslandDetection,
     load_sliced::LoadSliced,
     mesh_load::MeshLoad,
@@ -38,6 +43,7 @@ pub use self::{
     reconstruct_mesh::ReconstructMesh,
     reload_model::ReloadModel,
     remote_print::{PrinterConnect, PrinterScan},
+    resin_trap_detection::ResinTrapDetection,
     save_result::SaveResult,
     split_bodies::SplitBodies,
     update_check::update_check_if_scheduled,
@@ -57,6 +63,17 @@ pub trait Task {
     fn status(&self) -> Option<TaskStatus<'_>> {
         None
     }
+
+    /// Called when the task is cancelled from the tasks panel, right before
+    /// it's removed. Tasks running on another thread should stop their work
+    /// here, otherwise it runs to completion and the result is discarded.
+    fn cancel(&mut self) {}
+
+    /// If the task can be cancelled. Tasks that would leave things broken if
+    /// stopped part way through, like writing a file, should return false.
+    fn cancellable(&self) -> bool {
+        true
+    }
 }
 
 pub struct PollResult {
@@ -116,6 +133,13 @@ impl TaskManager {
         self.tasks.iter()
     }
 
+    /// Cancels the task at `index`, discarding anything it would have done
+    /// once complete.
+    pub fn cancel(&mut self, index: usize) {
+        let mut task = self.tasks.remove(index);
+        task.cancel();
+    }
+
     pub fn any_with_status(&self) -> bool {
         self.iter().any(|x| x.status().is_some())
     }
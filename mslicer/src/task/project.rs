@@ -61,6 +61,7 @@ impl ProjectSave {
             let file = File::create(path).unwrap();
             let mut ser = WriterSerializer::new(BufWriter::new(file));
             project.serialize(&mut ser, progress);
+            ser.finish().unwrap();
         }));
 
         Self {
@@ -121,6 +122,10 @@ impl Task for ProjectSave {
             progress: self.progress.progress(),
         })
     }
+
+    fn cancellable(&self) -> bool {
+        false
+    }
 }
 
 fn file_name(path: &Path) -> String {
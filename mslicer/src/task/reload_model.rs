@@ -14,11 +14,14 @@ use crate::{
         MeshManifold, PollResult, Task, TaskApp, TaskStatus,
         acceleration_structures::BuildAccelerationStructures, thread::TaskThread,
     },
+    ui::popup::{Popup, PopupIcon},
 };
 
+const FAILURE: &str = "Failed to Load Model";
+
 pub struct ReloadModel {
     progress: Progress,
-    join: TaskThread<Mesh>,
+    join: TaskThread<mesh_format::Result<Mesh>>,
 
     model: ModelId,
     path: PathBuf,
@@ -37,8 +40,7 @@ impl ReloadModel {
         let progress = Progress::new();
         Self {
             join: TaskThread::spawn(clone!([progress], move || {
-                let mesh = load_mesh(des, &ext, progress).unwrap();
-                Mesh::new(mesh.verts, mesh.faces)
+                load_mesh(des, &ext, progress).map(|mesh| Mesh::new(mesh.verts, mesh.faces))
             })),
             progress,
 
@@ -51,7 +53,16 @@ impl ReloadModel {
 
 impl Task for ReloadModel {
     fn poll(&mut self, app: &mut TaskApp) -> PollResult {
-        (self.join.poll(app, "Failed to Load Model")).into_poll_result(|mesh| {
+        (self.join.poll(app, FAILURE)).into_poll_result(|mesh| {
+            let mesh = match mesh {
+                Ok(mesh) => mesh,
+                Err(err) => {
+                    app.popup
+                        .open(Popup::simple(FAILURE, PopupIcon::Error, err.to_string()));
+                    return PollResult::complete();
+                }
+            };
+
             info!(
                 "Reloaded model `{}` with {} faces",
                 self.name,
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use clone_macro::clone;
+use common::{
+    progress::{CancellationToken, Progress},
+    slice::Layer,
+};
+use nalgebra::Vector2;
+use slicer::post_process::resin_traps::detect_resin_traps;
+
+use crate::{
+    app::slice_operation::{Annotation, Annotations},
+    task::{PollResult, Task, TaskApp, TaskStatus, thread::TaskThread},
+};
+
+pub struct ResinTrapDetection {
+    progress: Progress,
+    cancel: CancellationToken,
+    handle: TaskThread<()>,
+}
+
+impl ResinTrapDetection {
+    pub fn new(
+        resolution: Vector2<u32>,
+        layers: Vec<Layer>,
+        annotations: Arc<Annotations>,
+    ) -> Self {
+        let (progress, cancel) = (Progress::new(), CancellationToken::new());
+        Self {
+            handle: TaskThread::spawn(clone!([layers, progress, cancel], move || {
+                let Ok(traps) = detect_resin_traps(resolution, &layers, progress, &cancel) else {
+                    return;
+                };
+
+                let mut annotations = annotations.lock();
+                for (layer, runs) in traps
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, runs)| runs.len() > 1)
+                {
+                    annotations.insert_layer(Annotation::ResinTrap, layer, &runs);
+                }
+            })),
+            progress,
+            cancel,
+        }
+    }
+}
+
+impl Task for ResinTrapDetection {
+    fn poll(&mut self, app: &mut TaskApp) -> PollResult {
+        self.handle
+            .poll(app, "Unexpected Error Detecting Resin Traps")
+            .into_poll_result(|_| PollResult::complete())
+    }
+
+    fn status(&self) -> Option<TaskStatus<'_>> {
+        Some(TaskStatus {
+            name: "Detecting Resin Traps".into(),
+            details: None,
+            progress: self.progress.progress(),
+        })
+    }
+
+    fn cancel(&mut self) {
+        self.cancel.cancel();
+    }
+}
@@ -42,4 +42,8 @@ impl Task for SaveResult {
             progress: self.progress.progress(),
         })
     }
+
+    fn cancellable(&self) -> bool {
+        false
+    }
 }
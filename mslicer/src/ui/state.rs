@@ -5,7 +5,6 @@ use egui_tracing::EventCollector;
 use itertools::Either;
 use nalgebra::{Vector2, Vector3};
 use slicer::mesh::Mesh;
-use tools::supports::SupportConfig;
 
 use crate::{
     app::config::peripherals::Webhook,
@@ -16,13 +15,14 @@ use crate::{
 #[derive(Default)]
 pub struct UiState {
     pub event_collector: EventCollector,
-    pub support_config: SupportConfig,
     pub line_support_debug: Vec<[Vector3<f32>; 2]>,
     pub queue_reset_ui: bool,
 
     // support stuff
     pub workspace: WorkspaceHover,
     pub support_placement: bool,
+    pub support_islands: bool,
+    pub drain_holes: DrainHolePlacement,
 
     pub selected: Selected,
     pub selected_printer: SelectedPrinter,
@@ -49,6 +49,12 @@ pub struct UiState {
     pub move_timeout: u32,
 }
 
+pub struct DrainHolePlacement {
+    pub enabled: bool,
+    pub radius: f32,
+    pub depth: f32,
+}
+
 #[derive(Default)]
 pub struct SharedPrintCompletion {
     pub webhook: Webhook,
@@ -61,6 +67,16 @@ pub enum SelectedPrinter {
     Preset(usize, usize),
 }
 
+impl Default for DrainHolePlacement {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            radius: 1.5,
+            depth: 4.0,
+        }
+    }
+}
+
 impl Default for SelectedPrinter {
     fn default() -> Self {
         SelectedPrinter::Preset(0, 0)
@@ -13,7 +13,7 @@ use crate::{
     app::App,
     render::{interface::basis::BasisRenderCallback, workspace::WorkspaceRenderCallback},
     ui::state::WorkspaceHover,
-    windows::supports::manual_support_placement,
+    windows::supports::{drain_hole_placement, manual_support_placement},
 };
 
 mod logs;
@@ -143,6 +143,8 @@ fn viewport(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     if response.clicked() && !is_moving {
         if app.state.support_placement {
             manual_support_placement(app, true);
+        } else if app.state.drain_holes.enabled {
+            drain_hole_placement(app, true);
         } else if let Some(id) = app.hovered_model() {
             app.state
                 .selected
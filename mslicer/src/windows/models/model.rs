@@ -10,10 +10,12 @@ use egui::{
     TextEdit, Ui, UiBuilder, Widget, text::CCursorRange, vec2,
 };
 use egui_phosphor::regular::{
-    ARROW_LINE_DOWN, ARROWS_COUNTER_CLOCKWISE, COPY, CURSOR_TEXT, DICE_THREE, EYE, EYE_SLASH,
-    FOLDER_DASHED, INFO, LINK_BREAK, LINK_SIMPLE, SUBTRACT_SQUARE, SWAP, TRASH, WARNING,
+    ARROW_LINE_DOWN, ARROWS_COUNTER_CLOCKWISE, COPY, CURSOR_TEXT, DICE_THREE, EXPORT, EYE,
+    EYE_SLASH, FOLDER_DASHED, INFO, LINK_BREAK, LINK_SIMPLE, SUBTRACT_SQUARE, SWAP, TRASH, WARNING,
 };
+use mesh_format::SAVE_FORMATS;
 use nalgebra::Vector3;
+use slicer::mesh::Mesh;
 
 use crate::{
     app::{App, history::ModelAction},
@@ -21,7 +23,7 @@ use crate::{
         Collection, RenameState,
         model::{MeshUnit, MeshWarnings},
     },
-    task::{FileDialog, ReloadModel, SplitBodies},
+    task::{ExportMesh, FileDialog, ReloadModel, SplitBodies},
     ui::components::{
         being_edited, grid, history_tracked_model, vec3_dragger, vec3_dragger_proportional,
     },
@@ -181,7 +183,7 @@ pub fn model_properties(app: &mut App, ui: &mut Ui, ctx: &Context, action: &mut
         if ui.button(concatcp!(SWAP, " Replace")).clicked() {
             let (id, name) = (model.id, model.name.clone());
             app.tasks.add(FileDialog::pick_file(
-                ("Mesh", &["stl", "obj"]),
+                ("Mesh", &["stl", "obj", "3mf", "ply"]),
                 move |_app, path, tasks| {
                     let task = ReloadModel::new(id, name, path.to_path_buf());
                     tasks.push(Box::new(task));
@@ -197,6 +199,23 @@ pub fn model_properties(app: &mut App, ui: &mut Ui, ctx: &Context, action: &mut
             let task = ReloadModel::new(model.id, model.name.clone(), file.clone());
             app.tasks.add(task);
         }
+
+        if ui.button(concatcp!(EXPORT, " Export")).clicked() {
+            let meshes = vec![(model.name.clone(), model.mesh.clone())];
+            app.tasks.add(export_dialog(meshes));
+        }
+
+        if !model.supports.is_empty()
+            && ui
+                .button(concatcp!(EXPORT, " Export with Supports"))
+                .clicked()
+        {
+            let mut meshes = vec![(model.name.clone(), model.mesh.clone())];
+            if let Some(supports) = model.supports.mesh(&app.project.support_config.raft) {
+                meshes.push((format!("{} Supports", model.name), supports.clone()));
+            }
+            app.tasks.add(export_dialog(meshes));
+        }
     });
 
     CollapsingHeader::new("Transform")
@@ -345,3 +364,13 @@ fn rad_to_deg(pos: Vector3<f32>) -> Vector3<f32> {
 fn deg_to_rad(pos: Vector3<f32>) -> Vector3<f32> {
     pos.map(|x| x.to_radians())
 }
+
+fn export_dialog(meshes: Vec<(String, Mesh)>) -> FileDialog {
+    FileDialog::save_file(("Mesh", &SAVE_FORMATS), move |_app, path, tasks| {
+        let path = match path.extension() {
+            Some(_) => path.to_path_buf(),
+            None => path.with_extension(SAVE_FORMATS[0]),
+        };
+        tasks.push(Box::new(ExportMesh::new(meshes, path)));
+    })
+}
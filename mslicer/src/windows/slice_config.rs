@@ -1,17 +1,22 @@
 use std::borrow::Cow;
 
 use const_format::concatcp;
-use egui::{Color32, ComboBox, Context, DragValue, Grid, Ui, Widget, emath::OrderedFloat};
+use egui::{
+    Color32, ComboBox, Context, DragValue, Grid, ScrollArea, Ui, Widget, emath::OrderedFloat,
+};
 use egui_extras::{Column, TableBuilder};
 use egui_phosphor::regular::{
-    ARROW_COUNTER_CLOCKWISE, INFO, NOTE_PENCIL, PENCIL, PLUS, TIMER, TRASH, WARNING,
+    ARROW_COUNTER_CLOCKWISE, IMAGE, INFO, NOTE_PENCIL, PENCIL, PLUS, TIMER, TRASH, WARNING,
 };
 use egui_plot::{Line, MarkerShape, Plot, Points};
 use itertools::Itertools;
 use nalgebra::Vector2;
 use num_integer::cbrt;
 use slicer::post_process::{
-    elephant_foot_fixer::ElephantFootFixer, variable_layer_height::VariableLayerHeight,
+    elephant_foot_fixer::ElephantFootFixer,
+    hollowing::{Hollowing, Infill, MIN_HOLLOWING_SIZE},
+    smart_motion::SmartMotion,
+    variable_layer_height::{LayerHeightMode, VariableLayerHeight},
 };
 
 use crate::{
@@ -19,19 +24,28 @@ use crate::{
         App,
         config::{Config, printers::DEFAULT_PRINTERS},
     },
+    project::Project,
+    task::FileDialog,
     ui::{
         components::{collapsing_toggle, grid, vec2_dragger},
-        popup::{Popup, PopupApp},
+        popup::{Popup, PopupApp, PopupIcon},
         state::{SelectedPrinter, UiState},
     },
 };
 use common::{
-    slice::{ExposureConfig, ExposureRemap, SliceMode},
-    units::{Milimeter, Minute, Mircometer},
+    misc::IMAGE_FORMATS,
+    slice::{
+        Compensation, ExposureConfig, ExposureField, ExposureOverride, ExposureRemap, SliceConfig,
+        SliceMode, UniformityMask,
+    },
+    units::{CentimetersPerSecond, Milimeter, Minute, Mircometer},
 };
 
 const ANTI_ALIAS_TOOLTIP: &str = "Uses supersampling anti-aliasing (SSAA) to pick grayscale values that more accurately represent the actual model geometry. The actual value of this setting is the number of effective samples per voxel.";
+const UNIFORMITY_TOOLTIP: &str = "Evens out the exposure across the platform, for displays that are brighter in some places than others.";
+const UNIFORMITY_DESCRIPTION: &str = "Enter the brightness measured over a grid spanning the display, or load a grayscale image of it. Brighter areas are dimmed to match the dimmest one. The brightness can be found by printing copies of the exposure test across the platform and comparing which steps cured.";
 const TRANSITION_LAYER_TOOLTIP: &str = "Transition layers interpolate between the first exposure settings and the normal exposure settings.";
+const PAUSE_HOLD_TOOLTIP: &str = "ChituBox (ctb and cbddlp) files can't pause the print. Instead, layers marked to pause lift the platform to the top and hold it there for this long, then the print carries on by itself. Goo and NanoDLP files pause until resumed on the printer.";
 
 pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     ui.heading("Slice Config");
@@ -109,14 +123,41 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
                 let printer = &app.config.printers[idx];
                 slice_config.platform_resolution = printer.resolution;
                 *platform = printer.size;
+                if slice_config.uniformity != printer.uniformity {
+                    slice_config.uniformity = printer.uniformity.clone();
+                }
             }
             SelectedPrinter::Preset(brand, model) => {
                 let printer = &DEFAULT_PRINTERS[brand].1[model];
                 slice_config.platform_resolution = printer.resolution;
                 *platform = printer.size;
+                slice_config.uniformity = None;
             }
         }
 
+        // The mask belongs to the printer if a custom one is selected.
+        let target = match app.state.selected_printer {
+            SelectedPrinter::Project => Some(None),
+            SelectedPrinter::Custom(idx) => Some(Some(idx)),
+            SelectedPrinter::Preset(..) => None,
+        };
+        if let Some(target) = target {
+            ui.horizontal(|ui| {
+                ui.label("Uniformity Mask");
+                ui.label(INFO).on_hover_text(UNIFORMITY_TOOLTIP);
+            });
+            ui.horizontal(|ui| {
+                ui.label(match &slice_config.uniformity {
+                    Some(mask) => format!("{}×{} grid", mask.size.x, mask.size.y),
+                    None => "None".into(),
+                });
+                if ui.button(PENCIL).clicked() {
+                    app.popup.open(uniformity_popup(target));
+                }
+            });
+            ui.end_row();
+        }
+
         if *platform != prev {
             (app.project.models.iter_mut())
                 .for_each(|model| model.update_oob(&slice_config.platform_size));
@@ -159,6 +200,16 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
         });
         DragValue::new(&mut slice_config.transition_layers).ui(ui);
         ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Pause Hold");
+            ui.label(INFO).on_hover_text(PAUSE_HOLD_TOOLTIP);
+        });
+        DragValue::new(slice_config.pause_hold.raw_mut())
+            .suffix(" s")
+            .range(0.0..=f32::MAX)
+            .ui(ui);
+        ui.end_row();
     });
 
     ui.add_space(8.0);
@@ -170,6 +221,14 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
         exposure_config(ui, &mut slice_config.first_exposure_config);
     });
 
+    ui.collapsing("Exposure Overrides", |ui| {
+        exposure_overrides(ui, slice_config);
+    });
+
+    ui.collapsing("Dimensional Compensation", |ui| {
+        compensation(ui, &mut slice_config.compensation);
+    });
+
     ui.add_space(8.0);
     ui.heading("Post Processing");
 
@@ -183,6 +242,14 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     });
 
     let post_processing = &mut app.project.post_processing;
+    post_processing.hollowing.enabled = collapsing_toggle(
+        "Hollowing",
+        post_processing.hollowing.enabled,
+        |ui| hollowing(&mut post_processing.hollowing, ui),
+        false,
+        ui,
+    );
+
     post_processing.variable_layer_height.enabled = collapsing_toggle(
         "Variable Layer Height",
         post_processing.variable_layer_height.enabled,
@@ -198,6 +265,14 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
         false,
         ui,
     );
+
+    post_processing.smart_motion.enabled = collapsing_toggle(
+        "Smart Motion",
+        post_processing.smart_motion.enabled,
+        |ui| smart_motion(&mut post_processing.smart_motion, ui),
+        false,
+        ui,
+    );
 }
 
 fn printer_presets(ui: &mut Ui, config: &mut Config, state: &mut UiState) {
@@ -250,6 +325,170 @@ fn printer_presets(ui: &mut Ui, config: &mut Config, state: &mut UiState) {
     }
 }
 
+fn compensation(ui: &mut Ui, this: &mut Compensation) {
+    const SCALE_TOOLTIP: &str = "Scales every layer around the center of the platform, to counteract the resin shrinking as it cures.";
+    const OFFSET_TOOLTIP: &str = "Moves the edges of every layer outward, or inward with negative values. Use this to counteract over curing making parts too large.";
+    const HOLE_OFFSET_TOOLTIP: &str = "Additionally grows holes by this distance, as they tend to close up more than the outside of a part.";
+
+    ui.label("Corrects the size of printed parts. Print a calibration model to find the right values for your printer and resin.");
+    ui.add_space(8.0);
+
+    grid("compensation").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Scale");
+            ui.label(INFO).on_hover_text(SCALE_TOOLTIP);
+        });
+        ui.horizontal(|ui| {
+            for value in this.scale.iter_mut() {
+                DragValue::new(value)
+                    .custom_formatter(|val, _| format!("{:.2}", val * 100.0))
+                    .custom_parser(|val| val.parse::<f64>().ok().map(|x| x / 100.0))
+                    .suffix("%")
+                    .speed(0.0001)
+                    .range(0.5..=1.5)
+                    .ui(ui);
+            }
+        });
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Offset");
+            ui.label(INFO).on_hover_text(OFFSET_TOOLTIP);
+        });
+        DragValue::new(this.offset.raw_mut())
+            .speed(0.01)
+            .suffix(" mm")
+            .ui(ui);
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Hole Offset");
+            ui.label(INFO).on_hover_text(HOLE_OFFSET_TOOLTIP);
+        });
+        DragValue::new(this.hole_offset.raw_mut())
+            .speed(0.01)
+            .suffix(" mm")
+            .ui(ui);
+        ui.end_row();
+    });
+}
+
+fn exposure_overrides(ui: &mut Ui, config: &mut SliceConfig) {
+    ui.label("Replaces some exposure settings for the layers within a range of heights, like slowing the lifts over a fragile section. Later overrides take priority.");
+    ui.add_space(8.0);
+
+    let mut delete = None;
+    for (i, range) in config.exposure_overrides.iter_mut().enumerate() {
+        ui.push_id(i, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("From");
+                DragValue::new(range.start.raw_mut())
+                    .suffix(" mm")
+                    .speed(0.1)
+                    .range(0.0..=f32::MAX)
+                    .ui(ui);
+                ui.label("to");
+                let start = range.start.raw();
+                DragValue::new(range.end.raw_mut())
+                    .suffix(" mm")
+                    .speed(0.1)
+                    .range(start..=f32::MAX)
+                    .ui(ui);
+
+                ui.visuals_mut().button_frame = false;
+                (ui.button(TRASH).on_hover_text("Delete override").clicked())
+                    .then(|| delete = Some(i));
+            });
+
+            let mut remove = None;
+            grid("exposure_override").show(ui, |ui| {
+                for (j, &field) in range.fields.iter().enumerate() {
+                    ui.label(field.name());
+                    ui.horizontal(|ui| {
+                        exposure_field(ui, field, &mut range.exposure);
+                        ui.visuals_mut().button_frame = false;
+                        ui.button(TRASH).clicked().then(|| remove = Some(j));
+                    });
+                    ui.end_row();
+                }
+            });
+            if let Some(j) = remove {
+                range.fields.remove(j);
+            }
+
+            let missing = (ExposureField::ALL.into_iter())
+                .filter(|x| !range.fields.contains(x))
+                .collect::<Vec<_>>();
+            ui.add_enabled_ui(!missing.is_empty(), |ui| {
+                ComboBox::from_id_salt("add_field")
+                    .selected_text(concatcp!(PLUS, " Add Setting"))
+                    .show_ui(ui, |ui| {
+                        for field in missing {
+                            (ui.selectable_label(false, field.name()).clicked())
+                                .then(|| range.fields.push(field));
+                        }
+                    });
+            });
+        });
+        ui.separator();
+    }
+
+    if let Some(i) = delete {
+        config.exposure_overrides.remove(i);
+    }
+
+    if ui.button(concatcp!(PLUS, " Add Override")).clicked() {
+        config.exposure_overrides.push(ExposureOverride {
+            exposure: config.exposure_config.clone(),
+            ..Default::default()
+        });
+    }
+}
+
+/// Editor for a single field of an exposure config.
+fn exposure_field(ui: &mut Ui, field: ExposureField, config: &mut ExposureConfig) {
+    let speed = |ui: &mut Ui, speed: &mut CentimetersPerSecond| {
+        speed.with::<Milimeter, Minute>(|val| {
+            DragValue::new(val)
+                .suffix(" mm/min")
+                .speed(0.1)
+                .range(0.0..=f32::MAX)
+                .ui(ui);
+        });
+    };
+
+    let (value, suffix) = match field {
+        ExposureField::ExposureTime => (config.exposure_time.raw_mut(), " s"),
+        ExposureField::ExposureDelay => (config.exposure_delay.raw_mut(), " s"),
+        ExposureField::RestBeforeLift => (config.rest_before_lift.raw_mut(), " s"),
+        ExposureField::RestAfterLift => (config.rest_after_lift.raw_mut(), " s"),
+        ExposureField::LiftDistance => (config.lift_distance.raw_mut(), " mm"),
+        ExposureField::SecondLiftDistance => (config.second_lift_distance.raw_mut(), " mm"),
+        ExposureField::RetractDistance => (config.retract_distance.raw_mut(), " mm"),
+        ExposureField::SecondRetractDistance => (config.second_retract_distance.raw_mut(), " mm"),
+        ExposureField::LiftSpeed => return speed(ui, &mut config.lift_speed),
+        ExposureField::SecondLiftSpeed => return speed(ui, &mut config.second_lift_speed),
+        ExposureField::RetractSpeed => return speed(ui, &mut config.retract_speed),
+        ExposureField::SecondRetractSpeed => return speed(ui, &mut config.second_retract_speed),
+        ExposureField::Pwm => {
+            let mut pwm = config.pwm as f32 / 2.55;
+            DragValue::new(&mut pwm)
+                .max_decimals(0)
+                .suffix('%')
+                .range(0.0..=100.0)
+                .ui(ui);
+            config.pwm = (pwm * 2.55).round() as u8;
+            return;
+        }
+    };
+
+    DragValue::new(value)
+        .suffix(suffix)
+        .speed(0.1)
+        .range(0.0..=f32::MAX)
+        .ui(ui);
+}
+
 pub fn exposure_config(ui: &mut Ui, config: &mut ExposureConfig) -> bool {
     let mut changed = false;
     TableBuilder::new(ui)
@@ -297,63 +536,86 @@ pub fn exposure_config(ui: &mut Ui, config: &mut ExposureConfig) -> bool {
             });
         })
         .body(|mut body| {
-            body.row(16.0, |mut row| {
-                row.col(|ui| {
-                    ui.label("Lift");
-                });
-
-                row.col(|ui| {
-                    changed |= DragValue::new(config.lift_distance.raw_mut())
-                        .suffix(" mm")
-                        .speed(0.1)
-                        .range(0.0..=f32::MAX)
-                        .ui(ui)
-                        .changed();
-                });
+            let stages = [
+                ("Lift", &mut config.lift_distance, &mut config.lift_speed),
+                (
+                    "Lift 2",
+                    &mut config.second_lift_distance,
+                    &mut config.second_lift_speed,
+                ),
+                (
+                    "Retract",
+                    &mut config.retract_distance,
+                    &mut config.retract_speed,
+                ),
+                (
+                    "Retract 2",
+                    &mut config.second_retract_distance,
+                    &mut config.second_retract_speed,
+                ),
+            ];
 
-                row.col(|ui| {
-                    ui.label("@");
-                });
+            for (label, distance, speed) in stages {
+                body.row(16.0, |mut row| {
+                    row.col(|ui| {
+                        ui.label(label);
+                    });
 
-                row.col(|ui| {
-                    config.lift_speed.with::<Milimeter, Minute>(|val| {
-                        changed |= DragValue::new(val)
-                            .suffix(" mm/min")
+                    row.col(|ui| {
+                        changed |= DragValue::new(distance.raw_mut())
+                            .suffix(" mm")
                             .speed(0.1)
                             .range(0.0..=f32::MAX)
                             .ui(ui)
                             .changed();
                     });
+
+                    row.col(|ui| {
+                        ui.label("@");
+                    });
+
+                    row.col(|ui| {
+                        speed.with::<Milimeter, Minute>(|val| {
+                            changed |= DragValue::new(val)
+                                .suffix(" mm/min")
+                                .speed(0.1)
+                                .range(0.0..=f32::MAX)
+                                .ui(ui)
+                                .changed();
+                        });
+                    });
                 });
-            });
+            }
 
             body.row(16.0, |mut row| {
                 row.col(|ui| {
-                    ui.label("Retract");
+                    ui.label("Rest").on_hover_text(
+                        "Time to wait before lifting, and between lifting and retracting.",
+                    );
                 });
 
                 row.col(|ui| {
-                    changed |= DragValue::new(config.retract_distance.raw_mut())
-                        .suffix(" mm")
+                    changed |= DragValue::new(config.rest_before_lift.raw_mut())
+                        .suffix(" s")
                         .speed(0.1)
                         .range(0.0..=f32::MAX)
                         .ui(ui)
+                        .on_hover_text("Before lift")
                         .changed();
                 });
 
                 row.col(|ui| {
-                    ui.label("@");
+                    ui.label("/");
                 });
 
                 row.col(|ui| {
-                    config.retract_speed.with::<Milimeter, Minute>(|val| {
-                        changed |= DragValue::new(val)
-                            .suffix(" mm/min")
-                            .speed(0.1)
-                            .range(0.0..=f32::MAX)
-                            .ui(ui)
-                            .changed();
-                    });
+                    changed |= DragValue::new(config.rest_after_lift.raw_mut())
+                        .suffix(" s")
+                        .speed(0.1)
+                        .range(0.0..=f32::MAX)
+                        .ui(ui)
+                        .on_hover_text("After lift")
+                        .changed();
                 });
             });
         });
@@ -422,6 +684,95 @@ fn edit_presets(app: &mut PopupApp, ui: &mut Ui) -> bool {
     false
 }
 
+/// Gets the uniformity mask of a custom printer, or of the project if
+/// `printer` is None.
+fn uniformity_mask<'a>(
+    config: &'a mut Config,
+    project: &'a mut Project,
+    printer: Option<usize>,
+) -> Option<&'a mut Option<UniformityMask>> {
+    match printer {
+        Some(idx) => (config.printers.get_mut(idx)).map(|x| &mut x.uniformity),
+        None => Some(&mut project.slice_config.uniformity),
+    }
+}
+
+fn uniformity_popup(printer: Option<usize>) -> Popup {
+    Popup::new("Uniformity Mask", move |app, ui| {
+        ui.label(UNIFORMITY_DESCRIPTION);
+        ui.add_space(8.0);
+
+        let Some(mask) = uniformity_mask(app.config, app.project, printer) else {
+            return true;
+        };
+
+        ui.horizontal(|ui| {
+            if ui.button(concatcp!(IMAGE, " Load Image")).clicked() {
+                let task = FileDialog::pick_file(("Image", &IMAGE_FORMATS), move |app, path, _| {
+                    let image = match image::open(path) {
+                        Ok(image) => image.to_luma8(),
+                        Err(err) => {
+                            app.popup.open(Popup::simple(
+                                "Failed to Load Image",
+                                PopupIcon::Error,
+                                err.to_string(),
+                            ));
+                            return;
+                        }
+                    };
+
+                    if let Some(mask) = uniformity_mask(app.config, app.project, printer) {
+                        *mask = Some(UniformityMask::from_image(&image));
+                    }
+                });
+                app.tasks.add(task);
+            }
+
+            if mask.is_none() && ui.button(concatcp!(PLUS, " New Grid")).clicked() {
+                *mask = Some(UniformityMask::new(Vector2::new(4, 3)));
+            }
+
+            if mask.is_some() && ui.button(concatcp!(TRASH, " Remove")).clicked() {
+                *mask = None;
+            }
+        });
+
+        let Some(mask) = mask else {
+            return false;
+        };
+
+        ui.add_space(8.0);
+        grid("uniformity_size").show(ui, |ui| {
+            ui.label("Grid Size");
+            let mut size = mask.size;
+            vec2_dragger(ui, size.as_mut(), |x| x.range(1..=UniformityMask::MAX_SIZE));
+            if size != mask.size {
+                *mask = mask.resize(size);
+            }
+            ui.end_row();
+        });
+
+        ui.add_space(8.0);
+        ScrollArea::both().max_height(300.0).show(ui, |ui| {
+            Grid::new("uniformity_mask").show(ui, |ui| {
+                for row in mask.intensity.chunks_mut(mask.size.x as usize) {
+                    for intensity in row {
+                        DragValue::new(intensity)
+                            .range(0.01..=f32::MAX)
+                            .speed(0.01)
+                            .fixed_decimals(2)
+                            .ui(ui);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+
+        false
+    })
+    .close_button(true)
+}
+
 fn exposure_remapping(
     remap: &mut ExposureRemap,
     selected_remap_point: &mut Option<u8>,
@@ -492,30 +843,129 @@ fn exposure_remapping(
     }
 }
 
-fn variable_layer_height(this: &mut VariableLayerHeight, ui: &mut Ui) {
-    const VARIABLE_LAYER_HEIGHT_THRESHOLD_TOOLTIP: &str =
-        "Maximum allowed average value deviation between layers (in value/mm²).";
+fn hollowing(this: &mut Hollowing, ui: &mut Ui) {
+    const WALL_THICKNESS_TOOLTIP: &str =
+        "Minimum thickness of the walls left around the hollowed out interior (in mm).";
+    const INFILL_TOOLTIP: &str = "Pattern left inside of hollowed models to support their walls and roofs. Spacing is the size of each gyroid cell or the distance between pillars.";
 
-    ui.label("Merges identical layers into thicker layers to reduce printing time while retaining resolution where needed.");
-    ui.label("Note that with this post processor enabled you won't be able to save as NanoDLP.");
+    ui.label("Removes the inside of models to save resin and reduce internal stress. Make sure to add drain holes so uncured resin can get out.");
     ui.add_space(8.0);
 
-    grid("variable_layer_height").show(ui, |ui| {
+    grid("hollowing").show(ui, |ui| {
         ui.horizontal(|ui| {
-            ui.label("Threshold");
-            ui.label(INFO)
-                .on_hover_text(VARIABLE_LAYER_HEIGHT_THRESHOLD_TOOLTIP);
+            ui.label("Wall Thickness");
+            ui.label(INFO).on_hover_text(WALL_THICKNESS_TOOLTIP);
         });
-        DragValue::new(&mut this.threshold).ui(ui);
+        DragValue::new(&mut this.wall_thickness)
+            .range(MIN_HOLLOWING_SIZE..=f32::MAX)
+            .speed(0.1)
+            .suffix(" mm")
+            .ui(ui);
         ui.end_row();
 
-        ui.label("Max Layers");
-        DragValue::new(&mut this.max_layers).ui(ui);
+        ui.horizontal(|ui| {
+            ui.label("Infill");
+            ui.label(INFO).on_hover_text(INFILL_TOOLTIP);
+        });
+        ComboBox::from_id_salt("hollowing_infill")
+            .selected_text(this.infill.name())
+            .show_ui(ui, |ui| {
+                for infill in Infill::ALL {
+                    ui.selectable_value(&mut this.infill, infill, infill.name());
+                }
+            });
         ui.end_row();
 
-        ui.label("Exposure");
-        DragValue::new(this.exposure.raw_mut()).suffix(" s").ui(ui);
+        if this.infill != Infill::None {
+            ui.label("Infill Spacing");
+            DragValue::new(&mut this.infill_spacing)
+                .range(MIN_HOLLOWING_SIZE..=f32::MAX)
+                .speed(0.1)
+                .suffix(" mm")
+                .ui(ui);
+            ui.end_row();
+
+            ui.label("Infill Thickness");
+            DragValue::new(&mut this.infill_thickness)
+                .range(MIN_HOLLOWING_SIZE..=f32::MAX)
+                .speed(0.1)
+                .suffix(" mm")
+                .ui(ui);
+            ui.end_row();
+        }
+    });
+}
+
+fn variable_layer_height(this: &mut VariableLayerHeight, ui: &mut Ui) {
+    const VARIABLE_LAYER_HEIGHT_THRESHOLD_TOOLTIP: &str =
+        "Maximum allowed average value deviation between layers (in value/mm²).";
+    const DEVIATION_TOOLTIP: &str = "Maximum distance between the corners of the layer steps and sloped surfaces (in mm). Lower values use thinner layers on shallow slopes.";
+
+    match this.mode {
+        LayerHeightMode::Similarity => ui.label("Merges identical layers into thicker layers to reduce printing time while retaining resolution where needed."),
+        LayerHeightMode::Slope => ui.label("Slices gentle slopes with thin layers to reduce stair stepping, and steep walls with thick layers. Exposure times are scaled with the layer thickness."),
+    };
+    ui.label("Note that with this post processor enabled you won't be able to save as NanoDLP or Prusa SL1.");
+    ui.add_space(8.0);
+
+    grid("variable_layer_height").show(ui, |ui| {
+        ui.label("Mode");
+        ComboBox::from_id_salt("variable_layer_height_mode")
+            .selected_text(this.mode.name())
+            .show_ui(ui, |ui| {
+                for mode in LayerHeightMode::ALL {
+                    ui.selectable_value(&mut this.mode, mode, mode.name());
+                }
+            });
         ui.end_row();
+
+        match this.mode {
+            LayerHeightMode::Similarity => {
+                ui.horizontal(|ui| {
+                    ui.label("Threshold");
+                    ui.label(INFO)
+                        .on_hover_text(VARIABLE_LAYER_HEIGHT_THRESHOLD_TOOLTIP);
+                });
+                DragValue::new(&mut this.threshold).ui(ui);
+                ui.end_row();
+
+                ui.label("Max Layers");
+                DragValue::new(&mut this.max_layers).ui(ui);
+                ui.end_row();
+
+                ui.label("Exposure");
+                DragValue::new(this.exposure.raw_mut()).suffix(" s").ui(ui);
+                ui.end_row();
+            }
+            LayerHeightMode::Slope => {
+                ui.label("Min Height");
+                DragValue::new(&mut this.min_height)
+                    .range(0.001..=this.max_height)
+                    .speed(0.001)
+                    .suffix(" mm")
+                    .ui(ui);
+                ui.end_row();
+
+                ui.label("Max Height");
+                DragValue::new(&mut this.max_height)
+                    .range(this.min_height..=f32::MAX)
+                    .speed(0.001)
+                    .suffix(" mm")
+                    .ui(ui);
+                ui.end_row();
+
+                ui.horizontal(|ui| {
+                    ui.label("Deviation");
+                    ui.label(INFO).on_hover_text(DEVIATION_TOOLTIP);
+                });
+                DragValue::new(&mut this.deviation)
+                    .range(0.001..=f32::MAX)
+                    .speed(0.001)
+                    .suffix(" mm")
+                    .ui(ui);
+                ui.end_row();
+            }
+        }
     });
 }
 
@@ -561,3 +1011,94 @@ fn elephant_foot_fixer(this: &mut ElephantFootFixer, ui: &mut Ui) {
             });
         });
 }
+
+fn smart_motion(this: &mut SmartMotion, ui: &mut Ui) {
+    const AREA_TOOLTIP: &str = "Layers with a cured area at the low end get the fastest and shortest lifts and the shortest rest, while layers at the high end get the slowest and longest lifts and the longest rest.";
+    const AREA_CHANGE_TOOLTIP: &str = "A change in cured area from the previous layer this large also gives the slowest motion, as sudden large cross sections are the most likely to fail. Set to zero to ignore.";
+    const REST_TOOLTIP: &str = "Time to wait after retracting, before exposing the next layer.";
+
+    ui.label("Picks the lift speed, lift distance, and rest time of each layer from its cured area, as larger layers pull harder on the film. The first layers keep their own settings, and exposure overrides take priority over smart motion for the fields they set.");
+    ui.add_space(8.0);
+
+    grid("smart_motion").show(ui, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Area");
+            ui.label(INFO).on_hover_text(AREA_TOOLTIP);
+        });
+        ui.horizontal(|ui| {
+            DragValue::new(&mut this.min_area)
+                .range(0.0..=this.max_area)
+                .suffix(" mm²")
+                .ui(ui);
+            ui.label("–");
+            DragValue::new(&mut this.max_area)
+                .range(this.min_area..=f32::MAX)
+                .suffix(" mm²")
+                .ui(ui);
+        });
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Area Change");
+            ui.label(INFO).on_hover_text(AREA_CHANGE_TOOLTIP);
+        });
+        DragValue::new(&mut this.max_area_change)
+            .range(0.0..=f32::MAX)
+            .suffix(" mm²")
+            .ui(ui);
+        ui.end_row();
+
+        ui.label("Lift Speed");
+        ui.horizontal(|ui| {
+            for (i, speed) in [&mut this.max_lift_speed, &mut this.min_lift_speed]
+                .into_iter()
+                .enumerate()
+            {
+                (i > 0).then(|| ui.label("–"));
+                speed.with::<Milimeter, Minute>(|val| {
+                    DragValue::new(val)
+                        .range(0.0..=f32::MAX)
+                        .speed(0.1)
+                        .suffix(" mm/min")
+                        .ui(ui);
+                });
+            }
+        });
+        ui.end_row();
+
+        ui.label("Lift Distance");
+        ui.horizontal(|ui| {
+            for (i, distance) in [&mut this.min_lift_distance, &mut this.max_lift_distance]
+                .into_iter()
+                .enumerate()
+            {
+                (i > 0).then(|| ui.label("–"));
+                DragValue::new(distance.raw_mut())
+                    .range(0.0..=f32::MAX)
+                    .speed(0.1)
+                    .suffix(" mm")
+                    .ui(ui);
+            }
+        });
+        ui.end_row();
+
+        ui.horizontal(|ui| {
+            ui.label("Rest");
+            ui.label(INFO).on_hover_text(REST_TOOLTIP);
+        });
+        ui.horizontal(|ui| {
+            for (i, rest) in [&mut this.min_rest, &mut this.max_rest]
+                .into_iter()
+                .enumerate()
+            {
+                (i > 0).then(|| ui.label("–"));
+                DragValue::new(rest.raw_mut())
+                    .range(0.0..=f32::MAX)
+                    .speed(0.1)
+                    .suffix(" s")
+                    .ui(ui);
+            }
+        });
+        ui.end_row();
+    });
+}
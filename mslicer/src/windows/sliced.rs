@@ -17,7 +17,7 @@ use egui::{
 };
 use egui_phosphor::regular::{
     CAMERA, CARET_DOWN, CARET_UP, CLOCK, CORNERS_IN, CROSSHAIR, CUBE_TRANSPARENT, DROP,
-    FLOPPY_DISK_BACK, PAPER_PLANE_TILT, SIDEBAR, SWAP, TEXT_AA,
+    FLOPPY_DISK_BACK, PAPER_PLANE_TILT, PAUSE, SIDEBAR, SWAP, TEXT_AA, X,
 };
 use egui_plot::{Line, LineStyle, Plot, VLine};
 use egui_wgpu::Callback;
@@ -33,12 +33,14 @@ use crate::{
             sliced::{SlicePreviewCoordinateSpace, SlicePreviewView, SlicedConfig},
         },
         slice_operation::{
-            GenericSliceData, GenericSliceResult, ISLAND_COLOR, PreviewImage, RasterSliceResult,
-            SliceOperation, SliceResult,
+            GenericSliceData, GenericSliceResult, PreviewImage, RasterSliceResult, SliceOperation,
+            SliceResult,
         },
     },
     render::slice_preview::SlicePreviewRenderCallback,
-    task::{FileDialog, IslandDetection, ReconstructMesh, SaveResult, TaskManager},
+    task::{
+        FileDialog, IslandDetection, ReconstructMesh, ResinTrapDetection, SaveResult, TaskManager,
+    },
     ui::{
         components::{collapsing_toggle, grid},
         management::{LazyText, LazyTextureId},
@@ -52,7 +54,7 @@ use common::{
     progress::Progress,
     serde::DynamicSerializer,
     slice::{
-        SliceConfig, SliceMode,
+        self, SliceConfig, SliceMode,
         format::{Format, RasterFormat},
     },
     units::{Centimeter, Milimeter},
@@ -62,9 +64,15 @@ const FILENAME_POPUP_TEXT: &str =
     "To ensure the file name is unique, some extra random characters will be added on the end.";
 const DETECT_ISLANDS_DESC: &str =
     "Will color disconnected chunks of voxels red in the slice preview.";
+const DETECT_RESIN_TRAPS_DESC: &str = "Will color empty areas enclosed by the model blue in the slice preview. Resin can't drain out of these, so they may need a drain hole.";
+const PAUSE_DESC: &str = "Pauses the print at this layer, so the resin can be swapped or parts like magnets and nuts embedded. ChituBox files can't pause, so they only hold the platform at the top for the Pause Hold time from the slice config, then carry on by themselves.";
 const SURFACE_AREA_DESC: &str = "Surface area in cm² of each layer. Layers with higher areas will adhere more to the FEP potentially causing print failures.";
 
 pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
+    if (app.slice_operation.as_ref()).is_some_and(|x| x.cancel.is_cancelled()) {
+        app.slice_operation = None;
+    }
+
     if let Some(slice_operation) = &app.slice_operation {
         let progress = &slice_operation.progress;
 
@@ -134,7 +142,10 @@ pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
                             ui.set_width(150.0);
                             for &format in formats {
                                 let disabled = result.variable_layer_height
-                                    && matches!(format, Format::Raster(RasterFormat::NanoDLP));
+                                    && matches!(
+                                        format,
+                                        Format::Raster(RasterFormat::NanoDLP | RasterFormat::Sl1)
+                                    );
 
                                 if ui
                                     .add_enabled(!disabled, Button::new(format.name()))
@@ -174,6 +185,25 @@ pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
                             slice_preview
                         });
 
+                        let can_detect = (result.inner.as_raster())
+                            .map(|x| !x.detected_resin_traps)
+                            .unwrap_or_default();
+                        ui.add_enabled_ui(can_detect, |ui| {
+                            if ui
+                                .button(concatcp!(DROP, " Detect Resin Traps"))
+                                .on_hover_text(DETECT_RESIN_TRAPS_DESC)
+                                .clicked()
+                                && let GenericSliceResult::Raster(raster) = &mut result.inner
+                            {
+                                raster.detected_resin_traps = true;
+                                app.tasks.add(ResinTrapDetection::new(
+                                    result.config.platform_resolution,
+                                    raster.layers.clone(),
+                                    raster.annotations.clone(),
+                                ));
+                            }
+                        });
+
                         if let GenericSliceResult::Raster(raster) = &mut result.inner {
                             ui.menu_button(
                                 concatcp!(CUBE_TRANSPARENT, " Reconstruct Mesh"),
@@ -234,13 +264,20 @@ pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
                                 state.preview_scale = 1.0;
                             }
 
+                            let layer = state.preview_layer.clamp(1, state.layer_count.0) - 1;
+                            let pause = &mut raster.layers[layer].pause;
+                            ui.toggle_value(pause, concatcp!(PAUSE, " Pause"))
+                                .on_hover_text(PAUSE_DESC);
+
                             ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
                                 let duration = human_duration(raster.print_time.convert());
                                 ui.label(format!("{CLOCK} {duration}"));
 
-                                ui.separator();
-                                let volume = raster.volume.get::<Centimeter>(); // cm³ = ml
-                                ui.label(format!("{DROP} {volume:.2} ml"));
+                                if let Some(volume) = raster.volume(&result.config) {
+                                    ui.separator();
+                                    let volume = volume.get::<Centimeter>(); // cm³ = ml
+                                    ui.label(format!("{DROP} {volume:.2} ml"));
+                                }
 
                                 ui.take_available_width();
                             })
@@ -282,7 +319,12 @@ pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
                     let post_process = &slice_operation.post_processing_progress;
                     for i in 0..post_process.count() {
                         let progress = post_process[i].progress();
-                        let name = ["Elephant Foot Fixer", "Variable Layer Heights"][i];
+                        let name = [
+                            "Hollowing",
+                            "Variable Layer Heights",
+                            "Elephant Foot Fixer",
+                            "Smart Motion",
+                        ][i];
                         if progress > 0.0 {
                             ui.label(name);
                             ui.add(ProgressBar::new(progress).show_percentage());
@@ -290,6 +332,11 @@ pub fn ui(app: &mut App, ui: &mut Ui, ctx: &Context) {
                         }
                     }
                 });
+
+            ui.add_space(8.0);
+            if ui.button(concatcp!(X, " Cancel")).clicked() {
+                slice_operation.cancel.cancel();
+            }
         }
     } else {
         ui.horizontal_wrapped(|ui| {
@@ -362,7 +409,7 @@ fn slice_preview(
                     state.last_preview_layer = state.preview_layer;
 
                     let layer_idx = state.preview_layer - 1;
-                    let layer = result.layers[layer_idx].data.clone();
+                    let layer = result.layers[layer_idx].data.runs();
                     let annotations = result.annotations.lock().get_layer(layer_idx);
 
                     Some((layer, annotations))
@@ -412,7 +459,7 @@ fn layer_slider(state: &mut UiState, ui: &mut egui::Ui, result: &mut RasterSlice
 
     let annotations = result.annotations.lock();
     for i in 0..layer_count {
-        if annotations.contains(i) {
+        if let Some(annotation) = annotations.get(i) {
             let t = i as f32 / (layer_count.saturating_sub(1)) as f32;
             let width = if (slider_t - t).abs() < handle_t {
                 handle_inner_r * 2.0 + visuals.expansion
@@ -421,11 +468,18 @@ fn layer_slider(state: &mut UiState, ui: &mut egui::Ui, result: &mut RasterSlice
             };
 
             let rect = Rect::from_center_size(pos(t), Vec2::new(width, slice * 2.0));
-            painter.rect_filled(rect, 0, ISLAND_COLOR);
+            painter.rect_filled(rect, 0, annotation.color());
         }
     }
     drop(annotations);
 
+    let pauses = (result.layers.iter().enumerate()).filter(|(_, layer)| layer.pause);
+    for (i, _) in pauses {
+        let t = i as f32 / (layer_count.saturating_sub(1)) as f32;
+        let rect = Rect::from_center_size(pos(t), Vec2::new(handle_r * 2.0, slice.max(2.0)));
+        painter.rect_filled(rect, 0, visuals.fg_stroke.color);
+    }
+
     let rect = Rect::from_center_size(
         pos(slider_t),
         2.0 * Vec2::splat(handle_r + visuals.expansion),
@@ -485,9 +539,14 @@ fn save_file(
     data: GenericSliceData,
 ) -> FileDialog {
     FileDialog::save_file(
-        (format.name(), &[format.extension()]),
+        (format.name(), format.extensions()),
         move |_app, path, tasks| {
-            let path = path.with_extension(format.extension());
+            // Keep the extension picked by the user if the format has more
+            // than one, as printers can be picky about it.
+            let extension = (path.extension())
+                .map(|x| x.to_string_lossy().to_lowercase())
+                .filter(|x| format.extensions().contains(&x.as_str()));
+            let path = path.with_extension(extension.as_deref().unwrap_or(format.extension()));
             let file_name = path.file_name().unwrap().to_string_lossy();
             let mut out = File::create(&path).unwrap();
 
@@ -700,9 +759,15 @@ fn sidebar(
 
     let raster = result.inner.as_raster_mut().unwrap();
     if exposure_changed {
-        raster.print_time = result.config.print_time(raster.layers.len() as u32);
         for (i, layer) in raster.layers.iter_mut().enumerate() {
-            layer.exposure = result.config.exposure_config(i as u32).into_owned();
+            if layer.unique_exposure {
+                continue;
+            }
+
+            layer.exposure = result
+                .config
+                .layer_exposure(i as u32, layer.height)
+                .into_owned();
         }
     }
 
@@ -712,13 +777,17 @@ fn sidebar(
         layer.unique_exposure,
         |ui| {
             ui.add_enabled_ui(layer.unique_exposure, |ui| {
-                exposure_config(ui, &mut layer.exposure);
+                exposure_changed |= exposure_config(ui, &mut layer.exposure);
             });
         },
         true,
         ui,
     );
 
+    if exposure_changed {
+        raster.print_time = slice::print_time(&raster.layers);
+    }
+
     ui.add_space(8.0);
     ui.heading("Analysis");
     CollapsingHeader::new("Surface Area")
@@ -741,7 +810,7 @@ fn sidebar(
                         .iter()
                         .enumerate()
                         .map(|(x, layer)| {
-                            let area = layer.area as f32 * px_area;
+                            let area = layer.area() as f32 * px_area;
                             [x as f64, area.get::<Centimeter>() as f64]
                         })
                         .collect::<Vec<_>>();
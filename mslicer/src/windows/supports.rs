@@ -1,8 +1,15 @@
-use egui::{CollapsingHeader, Context, Ui};
-use slicer::builder::MeshBuilder;
-use tools::supports::{SupportGenerator, route_support};
+use common::units::Milimeter;
+use egui::{CollapsingHeader, ComboBox, Context, DragValue, Ui};
+use nalgebra::Vector3;
+use slicer::{
+    builder::MeshBuilder, post_process::island_detection::island_centers, slicer::cut::CutPrimitive,
+};
+use tools::supports::{MIN_RAFT_SPACING, RaftStyle, SupportRouter};
 
-use crate::{app::App, ui::components::dragger};
+use crate::{app::App, project::model::DrainHole, task::GenerateSupports, ui::components::dragger};
+
+const SUPPORT_ISLANDS_DESC: &str = "Also adds supports to the islands found with Detect Islands in the last slice. Slice again afterwards to check that none are left.";
+const DRAIN_HOLES_DESC: &str = "Click on a model to cut a hole into it, letting resin drain out of hollowed models. Use Detect Resin Traps on the sliced result to find where they are needed.";
 
 pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     ui.label("This feature is still very early in development.");
@@ -24,6 +31,9 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     ui.heading("Automatic Supports");
 
     ui.add_space(8.0);
+    ui.checkbox(&mut app.state.support_islands, "Support Islands")
+        .on_hover_text(SUPPORT_ISLANDS_DESC);
+
     ui.horizontal(|ui| {
         ui.menu_button("Generate", |ui| {
             ui.style_mut().visuals.button_frame = false;
@@ -43,7 +53,7 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
     });
 
     ui.add_space(8.0);
-    let support = &mut app.state.support_config;
+    let support = &mut app.project.support_config;
 
     CollapsingHeader::new("Support Placement").show(ui, |ui| {
         dragger(ui, "Max Angle", &mut support.max_angle, |x| x.speed(0.01));
@@ -73,8 +83,7 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
             ("Support Radius", &mut support.support_radius),
             ("Tip Length", &mut support.tip_length),
             ("Tip Radius", &mut support.tip_radius),
-            ("Raft Height", &mut support.raft_height),
-            ("Raft Offset", &mut support.raft_offset),
+            ("Load Area", &mut support.load_area),
         ] {
             dragger(ui, name, value, |x| x.speed(0.1));
         }
@@ -82,20 +91,143 @@ pub fn ui(app: &mut App, ui: &mut Ui, _ctx: &Context) {
         dragger(ui, "Support Precision", &mut support.precision, |x| x);
     });
 
-    (app.state.support_placement).then(|| manual_support_placement(app, false));
+    CollapsingHeader::new("Support Routing").show(ui, |ui| {
+        for (name, value) in [
+            ("Branch Angle", &mut support.branch_angle),
+            ("Merge Distance", &mut support.merge_distance),
+            ("Max Trunk Radius", &mut support.max_trunk_radius),
+        ] {
+            dragger(ui, name, value, |x| x.speed(0.1));
+        }
+    });
+
+    CollapsingHeader::new("Raft").show(ui, |ui| {
+        let raft = &mut support.raft;
+        ComboBox::from_id_salt("raft_style")
+            .selected_text(raft.style.name())
+            .show_ui(ui, |ui| {
+                for style in RaftStyle::ALL {
+                    ui.selectable_value(&mut raft.style, style, style.name());
+                }
+            });
+
+        for (name, value) in [
+            ("Height", &mut raft.height),
+            ("Offset", &mut raft.offset),
+            ("Taper", &mut raft.taper),
+        ] {
+            dragger(ui, name, value, |x| x.speed(0.1));
+        }
+
+        // Channels and lattice lines can't be closer together than their width
+        let min_spacing = raft.min_spacing();
+        ui.horizontal(|ui| {
+            ui.add(
+                DragValue::new(&mut raft.spacing)
+                    .speed(0.1)
+                    .range(min_spacing..=f32::MAX),
+            );
+            ui.label("Spacing");
+        });
+        dragger(ui, "Line Width", &mut raft.line_width, |x| {
+            x.speed(0.1).range(MIN_RAFT_SPACING..=f32::MAX)
+        });
+    });
+
+    CollapsingHeader::new("Cross Bracing").show(ui, |ui| {
+        for (name, value) in [
+            ("Brace Height", &mut support.brace_height),
+            ("Brace Distance", &mut support.brace_distance),
+            ("Brace Radius", &mut support.brace_radius),
+        ] {
+            dragger(ui, name, value, |x| x.speed(0.1));
+        }
+    });
+
+    let unsupported = (app.project.models.iter())
+        .map(|x| x.supports.unsupported().len())
+        .sum::<usize>();
+    if unsupported > 0 {
+        ui.add_space(8.0);
+        ui.label(format!("{unsupported} overhangs could not be supported."));
+    }
+
+    ui.add_space(8.0);
+    ui.heading("Drain Holes");
+
+    let drain_holes = &mut app.state.drain_holes;
+    ui.checkbox(&mut drain_holes.enabled, "Drain Hole Placement")
+        .on_hover_text(DRAIN_HOLES_DESC);
+    dragger(ui, "Radius", &mut drain_holes.radius, |x| x.speed(0.1));
+    dragger(ui, "Depth", &mut drain_holes.depth, |x| x.speed(0.1));
+
+    ui.horizontal(|ui| {
+        let count = (app.project.models.iter())
+            .map(|x| x.drain_holes.len())
+            .sum::<usize>();
+        ui.label(format!("{count} drain holes"));
+
+        if ui.button("Clear").clicked() {
+            (app.project.models.iter_mut()).for_each(|x| x.drain_holes.clear());
+        }
+    });
+
+    if app.state.support_placement {
+        manual_support_placement(app, false);
+    } else if app.state.drain_holes.enabled {
+        drain_hole_placement(app, false);
+    } else {
+        app.state.support_preview = None;
+    }
 }
 
 fn generate_support(app: &mut App, model: usize) {
-    let model = &mut app.project.models[model];
-    let half_edge = model.half_edge.as_ref().unwrap();
-    let bvh = model.bvh.as_ref().unwrap();
+    let islands = (app.state.support_islands)
+        .then(|| island_points(app))
+        .unwrap_or_default();
 
-    let support_config = &app.state.support_config;
+    let model = &app.project.models[model];
+    let support_config = &app.project.support_config;
     let platform_size = app.project.slice_config.platform_size.map(|x| x.convert());
 
-    let generator = SupportGenerator::new(support_config, platform_size);
-    let supports = generator.generate_supports(&model.mesh, half_edge, bvh);
-    model.supports.replace_auto(support_config, supports);
+    let task = GenerateSupports::new(model, support_config, platform_size, islands);
+    app.tasks.add(task);
+}
+
+/// Converts the islands detected in the last slice back into world space
+/// points, each inside its island on the layer's slice plane.
+fn island_points(app: &App) -> Vec<Vector3<f32>> {
+    let Some(slice_operation) = &app.slice_operation else {
+        return Vec::new();
+    };
+
+    let result = slice_operation.result();
+    let Some((result, raster)) =
+        (result.as_ref()).and_then(|result| Some((result, result.inner.as_raster()?)))
+    else {
+        return Vec::new();
+    };
+
+    let resolution = result.config.platform_resolution;
+    let platform = resolution.cast::<f32>();
+    let platform_size = (result.config.platform_size.xy()).map(|x| x.get::<Milimeter>());
+    let px_to_mm = platform_size.component_div(&platform);
+
+    let mut points = Vec::new();
+    for (layer, islands) in raster.annotations.lock().islands() {
+        let top = raster.layers[layer].height.get::<Milimeter>();
+        let bottom = (layer > 0)
+            .then(|| raster.layers[layer - 1].height.get::<Milimeter>())
+            .unwrap_or_default();
+
+        let z = (top + bottom) / 2.0;
+        for center in island_centers(resolution, &islands) {
+            let position = (center - platform / 2.0).component_mul(&px_to_mm);
+            points.push(position.push(z));
+        }
+    }
+
+    points
 }
 
 pub fn manual_support_placement(app: &mut App, clicked: bool) {
@@ -118,27 +250,71 @@ pub fn manual_support_placement(app: &mut App, clicked: bool) {
             continue;
         };
 
-        let config = &app.state.support_config;
+        let config = &app.project.support_config;
         let normal = (model.mesh).transform_normal(&model.mesh.normal(intersection.face));
         let intersection = model.mesh.transform(&intersection.position);
-        let start = intersection + normal * config.tip_length;
 
-        if let Some(middle) = route_support(&model.mesh, bvh, start) {
-            let (r, p) = (1.0, 100);
-            builder.add_cylinder((intersection, start), (config.tip_radius, r), p);
-            builder.add_cylinder((start, middle), (r, r), p);
-            builder.add_cylinder((middle, middle.xy().push(0.0)), (r, r), p);
-
-            builder.add_sphere(intersection, 0.2, p);
-            builder.add_sphere(start, r, p);
-            builder.add_sphere(middle, r, p);
+        let router = SupportRouter::new(config, &model.mesh, bvh);
+        if let Some(path) = router.route(intersection, normal) {
+            let (r, p) = (config.support_radius, 100);
+            for (i, segment) in path.windows(2).enumerate() {
+                let tip = if i == 0 { config.tip_radius } else { r };
+                builder.add_cylinder((segment[0], segment[1]), (tip, r), p);
+                builder.add_sphere(segment[1], r, p);
+            }
+            builder.add_sphere(intersection, config.tip_radius, p);
 
             if clicked {
-                let support = [intersection, start, middle];
-                model.supports.add_manual(config, support);
+                let supports = model.supports.manual_mut();
+                router.route_into(supports, intersection, normal, config.support_radius);
             }
         }
     }
 
     app.state.support_preview = (!builder.is_empty()).then(|| builder.build());
 }
+
+/// Previews the drain hole under the cursor along with all existing ones,
+/// adding it to the hovered model if clicked.
+pub fn drain_hole_placement(app: &mut App, clicked: bool) {
+    let workspace = &app.state.workspace;
+    if workspace.is_moving {
+        return;
+    }
+
+    // Only the closest model along the ray gets the hole.
+    let config = &app.state.drain_holes;
+    let hovered = app.hovered_ray().and_then(|(pos, dir)| {
+        (app.project.models.iter().enumerate())
+            .filter_map(|(idx, model)| {
+                let bvh = model.bvh.as_ref()?;
+                Some((idx, bvh.intersect_ray(&model.mesh, pos, dir)?))
+            })
+            .min_by(|(_, a), (_, b)| a.t.total_cmp(&b.t))
+            .map(|(idx, intersection)| {
+                let hole = DrainHole {
+                    position: intersection.position,
+                    normal: app.project.models[idx].mesh.normal(intersection.face),
+                    radius: config.radius,
+                    depth: config.depth,
+                };
+                (idx, hole)
+            })
+    });
+
+    if clicked && let Some((idx, hole)) = hovered {
+        app.project.models[idx].drain_holes.push(hole);
+    }
+
+    let mut builder = MeshBuilder::new();
+    for (idx, model) in app.project.models.iter().enumerate() {
+        let preview = hovered.filter(|(model, _)| *model == idx && !clicked);
+        let holes = (model.drain_holes.iter()).chain(preview.iter().map(|(_, hole)| hole));
+        for hole in holes {
+            let CutPrimitive::Cylinder { start, end, radius } = hole.cut(&model.mesh);
+            builder.add_cylinder((start, end), (radius, radius), 20);
+        }
+    }
+
+    app.state.support_preview = (!builder.is_empty()).then(|| builder.build());
+}
@@ -1,16 +1,17 @@
 use common::units::Milimeter;
 use egui::{Button, DragValue, Ui, Widget};
-use egui_phosphor::regular::{RULER, SQUARES_FOUR};
+use egui_phosphor::regular::{INFO, RULER, SQUARES_FOUR};
 
 use crate::{
     app::App,
     ui::{
-        components::grid,
+        components::{grid, vec2_dragger},
         popup::{Popup, PopupApp},
     },
 };
 
 pub const DESCRIPTION: &str = "Generates a rectangular prism with a gradient of voxel vales across the top layer. Measuring the printed result will allow you get the value to voxel size mapping.";
+const GRID_TOOLTIP: &str = "Prints copies of the test spread across the platform. Differences in which steps cure show where the display is brighter, for making a uniformity mask.";
 
 pub fn open(app: &mut App) {
     app.popup
@@ -56,6 +57,13 @@ fn interface(app: &mut PopupApp, ui: &mut Ui) -> bool {
         });
         ui.end_row();
 
+        ui.horizontal(|ui| {
+            ui.label("Grid");
+            ui.label(INFO).on_hover_text(GRID_TOOLTIP);
+        });
+        vec2_dragger(ui, tool.grid.as_mut(), |x| x.range(1..=16));
+        ui.end_row();
+
         ui.horizontal(|ui| {
             ui.label("Supports");
             ui.checkbox(&mut tool.supports.enabled, "");
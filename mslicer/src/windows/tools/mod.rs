@@ -27,14 +27,18 @@ macro_rules! generator_tool {
         use clone_macro::clone;
         use image::RgbaImage;
 
-        use common::progress::{CombinedProgress, Progress};
+        use common::progress::{CancellationToken, CombinedProgress, Progress};
         use $crate::{
             app::{SLICE_PREVIEW_SIZE, slice_operation::SliceOperation},
             windows::Tab,
         };
 
         let mut config = $app.project.slice_config.clone();
-        let operation = SliceOperation::new(Progress::new(), CombinedProgress::new());
+        let operation = SliceOperation::new(
+            Progress::new(),
+            CombinedProgress::new(),
+            CancellationToken::new(),
+        );
         operation.add_preview(RgbaImage::new(512, 512)); // blank preview image
         let tool = $tool.clone();
         tool.slice_config(&mut config);
@@ -1,13 +1,14 @@
 use std::collections::HashMap;
 use std::f32::consts::TAU;
 
+use common::slice::format::RasterFormat;
 use const_format::concatcp;
 use egui::{
     Align, Align2, Button, Context, FontId, Frame, Grid, Id, Key, KeyboardShortcut, Layout,
     Modifiers, PopupAnchor, ProgressBar, Stroke, StrokeKind, TopBottomPanel, Ui, ViewportCommand,
     vec2,
 };
-use egui_phosphor::regular::{CARDS, FILE_TEXT, GIT_DIFF, HAMMER, HOURGLASS, STACK};
+use egui_phosphor::regular::{CARDS, FILE_TEXT, GIT_DIFF, HAMMER, HOURGLASS, STACK, X};
 
 #[cfg(windows)]
 use crate::system::windows::launch_install;
@@ -16,7 +17,10 @@ use crate::{
     include_asset,
     project::{Collection, Project},
     task::{AutoLayout, FileDialog, LoadSliced, MeshLoad, MultiFileDialog, ProjectLoad},
-    ui::{components::labeled_separator, popup::Popup},
+    ui::{
+        components::labeled_separator,
+        popup::{Popup, PopupIcon},
+    },
     windows::{
         Tab,
         tools::{self, graphics_3d},
@@ -233,17 +237,18 @@ fn tasks_button(app: &mut App, ctx: &Context, ui: &mut Ui) {
         ui.painter().line(points.collect(), stroke);
     }
 
+    let mut cancel = None;
     let anchor = PopupAnchor::Position(response.rect.max + vec2(0.0, 4.0));
     egui::Popup::new(Id::new("tasks"), ctx.clone(), anchor, ui.layer_id())
         .open(app.config.ui.tasks && app.tasks.any_with_status())
         .show(|ui| {
             ui.set_width(300.0);
             Grid::new("slice_config")
-                .num_columns(2)
+                .num_columns(3)
                 .spacing([40.0, 4.0])
                 .striped(true)
                 .show(ui, |ui| {
-                    for task in app.tasks.iter() {
+                    for (i, task) in app.tasks.iter().enumerate() {
                         let Some(status) = task.status() else {
                             continue;
                         };
@@ -255,10 +260,19 @@ fn tasks_button(app: &mut App, ctx: &Context, ui: &mut Ui) {
                             res2.on_hover_text(details);
                         }
 
+                        let button = ui.add_enabled(task.cancellable(), Button::new(X).small());
+                        button
+                            .on_hover_text("Cancel")
+                            .clicked()
+                            .then(|| cancel = Some(i));
                         ui.end_row();
                     }
                 });
         });
+
+    if let Some(i) = cancel {
+        app.tasks.cancel(i);
+    }
 }
 
 fn menu_button(
@@ -275,15 +289,21 @@ fn menu_button(
 
 fn import_model(app: &mut App) {
     app.tasks.add(MultiFileDialog::pick_files(
-        ("Mesh", &["stl", "obj"]),
-        |_app, paths, tasks| {
+        ("Mesh", &["stl", "obj", "3mf", "ply"]),
+        |app, paths, tasks| {
             for path in paths {
                 let name = path.file_name().unwrap().to_str().unwrap().to_string();
                 let ext = path.extension();
                 let format = ext.unwrap_or_default().to_string_lossy();
 
-                let task = MeshLoad::file(path.to_path_buf(), name, format.into()).unwrap();
-                tasks.push(Box::new(task));
+                match MeshLoad::file(path.to_path_buf(), name, format.into()) {
+                    Ok(task) => tasks.push(Box::new(task)),
+                    Err(err) => app.popup.open(Popup::simple(
+                        "Failed to Load Model",
+                        PopupIcon::Error,
+                        err.to_string(),
+                    )),
+                }
             }
         },
     ));
@@ -382,8 +402,12 @@ fn select_all(app: &mut App) {
 }
 
 fn load_sliced(app: &mut App) {
+    let mut extensions = (RasterFormat::ALL.iter())
+        .flat_map(|x| x.extensions())
+        .collect::<Vec<_>>();
+    extensions.dedup();
     app.tasks.add(FileDialog::pick_file(
-        ("Sliced Model", &["goo", "ctb", "nanodlp"]),
+        ("Sliced Model", &extensions),
         |_app, file, tasks| tasks.push(Box::new(LoadSliced::new(file.to_path_buf()))),
     ));
 }
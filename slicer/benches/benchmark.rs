@@ -27,6 +27,7 @@ fn slicer(model: &[u8]) -> Slicer {
         vec![SlicerModel {
             mesh: Mesh::new(mesh.verts, mesh.faces),
             exposure: 255,
+            cuts: Vec::new(),
         }],
     )
 }
@@ -3,7 +3,7 @@ use std::{any::Any, path::PathBuf, str::FromStr};
 use anyhow::{Context, Ok, Result};
 use clap::{ArgMatches, Parser};
 use common::{
-    slice::{ExposureConfig, SliceConfig, SliceMode},
+    slice::{Compensation, ExposureConfig, SliceConfig, SliceMode, UniformityMask},
     units::{Milimeters, MilimetersPerMinute, Seconds},
 };
 use nalgebra::{ArrayStorage, Const, Matrix, Scalar, U1, Vector2, Vector3};
@@ -33,6 +33,22 @@ pub struct Args {
     /// first layer config to the default config.
     pub transition_layers: u32,
 
+    #[arg(long, default_value = "1.0, 1.0", value_parser = vector_value_parser::<f32, 2>)]
+    /// Scale of every layer along the X and Y axes, around the center of the
+    /// platform. Counteracts resin shrinkage.
+    pub xy_scale: Vector2<f32>,
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    /// Distance to move the contours of every layer outward in mm, negative
+    /// values move them inward.
+    pub xy_offset: f32,
+    #[arg(long, default_value_t = 0.0, allow_hyphen_values = true)]
+    /// Extra distance to grow holes by in mm, on top of the XY offset.
+    pub hole_offset: f32,
+    #[arg(long)]
+    /// Path to a grayscale image of the brightness of the printer's display.
+    /// Brighter areas are dimmed to match the dimmest one.
+    pub uniformity_mask: Option<PathBuf>,
+
     #[arg(long, default_value_t = 3.0)]
     /// Layer exposure time in seconds.
     pub exposure_time: f32,
@@ -53,6 +69,27 @@ pub struct Args {
     /// The speed to retract (move down) the platform after exposing each
     /// regular layer, in mm/min.
     pub retract_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Distance to keep lifting the platform at the second lift speed, after
+    /// the first lift of each regular layer, in mm.
+    pub second_lift_distance: f32,
+    #[arg(long, default_value_t = 150.0)]
+    /// The speed of the second lift stage of each regular layer, in mm/min.
+    pub second_lift_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Distance at the end of the retract of each regular layer to move at the
+    /// second retract speed, in mm.
+    pub second_retract_distance: f32,
+    #[arg(long, default_value_t = 65.0)]
+    /// The speed of the second retract stage of each regular layer, in mm/min.
+    pub second_retract_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Time to wait between exposing each regular layer and lifting, in seconds.
+    pub rest_before_lift: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Time to wait between lifting and retracting after each regular layer, in
+    /// seconds.
+    pub rest_after_lift: f32,
 
     #[arg(long, default_value_t = 30.0)]
     /// First layer exposure time in seconds.
@@ -74,16 +111,57 @@ pub struct Args {
     /// The speed to retract (move down) the platform after exposing each first
     /// layer, in mm/min.
     pub first_retract_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Distance to keep lifting the platform at the second lift speed, after
+    /// the first lift of each first layer, in mm.
+    pub first_second_lift_distance: f32,
+    #[arg(long, default_value_t = 150.0)]
+    /// The speed of the second lift stage of each first layer, in mm/min.
+    pub first_second_lift_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Distance at the end of the retract of each first layer to move at the
+    /// second retract speed, in mm.
+    pub first_second_retract_distance: f32,
+    #[arg(long, default_value_t = 65.0)]
+    /// The speed of the second retract stage of each first layer, in mm/min.
+    pub first_second_retract_speed: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Time to wait between exposing each first layer and lifting, in seconds.
+    pub first_rest_before_lift: f32,
+    #[arg(long, default_value_t = 0.0)]
+    /// Time to wait between lifting and retracting after each first layer, in
+    /// seconds.
+    pub first_rest_after_lift: f32,
+
+    #[arg(long, value_delimiter = ',')]
+    /// Heights to pause the print at in mm, to swap the resin or embed parts
+    /// like magnets. Multiple heights are separated by commas.
+    pub pause_at: Vec<f32>,
+    #[arg(long, default_value_t = 300.0)]
+    /// How long ctb and cbddlp files hold the platform at the top for each
+    /// pause, in seconds. These formats can't wait for the user, so the print
+    /// carries on by itself once the time is up.
+    pub pause_hold: f32,
 
     #[arg(long)]
     /// Path to a preview image, will be scaled as needed.
     pub preview: Option<PathBuf>,
 
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(4..=5))]
+    /// Version of .ctb files to write. Version 5 is encrypted, older printer
+    /// boards only take the unencrypted version 4.
+    pub ctb_version: u32,
+
+    #[arg(long)]
+    /// Slice every layer into memory before saving the output file, instead of
+    /// writing layers to it as they are sliced.
+    pub buffered: bool,
+
     #[command(flatten)]
     pub model: ModelArgs,
 
-    /// File to save sliced result to. The extension must be .goo, .ctb, or
-    /// .nanodlp.
+    /// File to save sliced result to. The extension must be .goo, .ctb,
+    /// .cbddlp, .nanodlp, .sl1, .pwmx, .pwma, or .pm3.
     pub output: PathBuf,
 }
 
@@ -122,29 +200,59 @@ impl Args {
             mode: SliceMode::Raster,
             supersample: cbrt(self.supersample) as u8,
             exposure_remap: Default::default(), // todo
+            uniformity: (self.uniformity_mask.as_ref())
+                .map(|path| Ok(UniformityMask::from_image(&image::open(path)?.to_luma8())))
+                .transpose()?,
             platform_resolution: self.platform_resolution,
             platform_size: self.platform_size.map(Milimeters::new),
             slice_height: Milimeters::new(self.layer_height),
+            compensation: Compensation {
+                scale: self.xy_scale,
+                offset: Milimeters::new(self.xy_offset),
+                hole_offset: Milimeters::new(self.hole_offset),
+            },
             exposure_config: ExposureConfig {
                 exposure_time: Seconds::new(self.exposure_time),
                 exposure_delay: Seconds::new(self.exposure_delay),
                 pwm: (self.exposure_pwm.clamp(0.0, 100.0) * 2.55) as u8,
+                rest_before_lift: Seconds::new(self.rest_before_lift),
+                rest_after_lift: Seconds::new(self.rest_after_lift),
                 lift_distance: Milimeters::new(self.lift_distance),
                 lift_speed: MilimetersPerMinute::new(self.lift_speed).convert(),
-                retract_distance: Milimeters::new(self.lift_distance),
+                second_lift_distance: Milimeters::new(self.second_lift_distance),
+                second_lift_speed: MilimetersPerMinute::new(self.second_lift_speed).convert(),
+                retract_distance: Milimeters::new(
+                    (self.lift_distance + self.second_lift_distance - self.second_retract_distance)
+                        .max(0.0),
+                ),
                 retract_speed: MilimetersPerMinute::new(self.retract_speed).convert(),
+                second_retract_distance: Milimeters::new(self.second_retract_distance),
+                second_retract_speed: MilimetersPerMinute::new(self.second_retract_speed).convert(),
             },
             first_exposure_config: ExposureConfig {
                 exposure_time: Seconds::new(self.first_exposure_time),
                 exposure_delay: Seconds::new(self.first_exposure_delay),
                 pwm: (self.first_exposure_pwm.clamp(0.0, 100.0) * 2.55) as u8,
+                rest_before_lift: Seconds::new(self.first_rest_before_lift),
+                rest_after_lift: Seconds::new(self.first_rest_after_lift),
                 lift_distance: Milimeters::new(self.first_lift_distance),
                 lift_speed: MilimetersPerMinute::new(self.first_lift_speed).convert(),
-                retract_distance: Milimeters::new(self.first_lift_distance),
+                second_lift_distance: Milimeters::new(self.first_second_lift_distance),
+                second_lift_speed: MilimetersPerMinute::new(self.first_second_lift_speed).convert(),
+                retract_distance: Milimeters::new(
+                    (self.first_lift_distance + self.first_second_lift_distance
+                        - self.first_second_retract_distance)
+                        .max(0.0),
+                ),
                 retract_speed: MilimetersPerMinute::new(self.first_retract_speed).convert(),
+                second_retract_distance: Milimeters::new(self.first_second_retract_distance),
+                second_retract_speed: MilimetersPerMinute::new(self.first_second_retract_speed)
+                    .convert(),
             },
             first_layers: self.first_layers,
             transition_layers: self.transition_layers,
+            exposure_overrides: Vec::new(),
+            pause_hold: Seconds::new(self.pause_hold),
         })
     }
 
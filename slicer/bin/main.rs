@@ -1,11 +1,11 @@
 use std::{
     fs::{self, File},
-    io::{BufReader, Read, Seek, Write, stdout},
+    io::{BufReader, BufWriter, Read, Seek, Write, stdout},
     thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 
-use anyhow::{Context, Ok, Result};
+use anyhow::{Context, Ok, Result, ensure};
 use args::{Args, Model};
 use clap::{CommandFactory, FromArgMatches};
 use clone_macro::clone;
@@ -20,7 +20,7 @@ use common::{
 use slicer::{
     mesh::Mesh,
     slicer::{Slicer, SlicerModel},
-    util::export_raster,
+    util::{export_raster, stream_raster},
 };
 
 mod args;
@@ -33,7 +33,10 @@ fn main() -> Result<()> {
     let extension = (args.output.extension())
         .context("Output file has no extension")?
         .to_string_lossy();
-    let format = RasterFormat::from_extension(&extension).context("Unknown output format")?;
+    let format = match RasterFormat::from_extension(&extension).context("Unknown output format")? {
+        RasterFormat::Ctb if args.ctb_version < 5 => RasterFormat::CtbLegacy,
+        format => format,
+    };
 
     let slice_config = args.slice_config()?;
     let mm_to_px = args.mm_to_px();
@@ -41,9 +44,15 @@ fn main() -> Result<()> {
     let mut meshes = Vec::new();
     for model in models {
         let ext = model.path.extension().unwrap().to_string_lossy();
-        let buf = BufReader::new(File::open(&model.path)?);
+        let name = model.path.file_name().unwrap().to_string_lossy();
 
-        let mut mesh = load_mesh(buf, &ext)?;
+        // Skip models that can't be loaded rather than failing the whole batch.
+        let mesh = (File::open(&model.path).map_err(Into::into))
+            .and_then(|file| load_mesh(BufReader::new(file), &ext))
+            .inspect_err(|err| eprintln!("Failed to load `{name}`: {err}"));
+        let Some(mut mesh) = mesh.ok() else {
+            continue;
+        };
 
         mesh.set_scale(model.scale);
         mesh.set_rotation(model.rotation.map(f32::to_radians));
@@ -58,8 +67,7 @@ fn main() -> Result<()> {
         mesh.set_scale(model.scale.component_mul(&mm_to_px));
 
         println!(
-            "Loaded `{}`. {{ vert: {}, face: {} }}",
-            model.path.file_name().unwrap().to_string_lossy(),
+            "Loaded `{name}`. {{ vert: {}, face: {} }}",
             mesh.vertex_count(),
             mesh.face_count()
         );
@@ -71,10 +79,13 @@ fn main() -> Result<()> {
         meshes.push(SlicerModel {
             mesh,
             exposure: 255,
+            cuts: Vec::new(),
         });
     }
 
-    let slicer = Slicer::new(slice_config.clone(), meshes);
+    ensure!(!meshes.is_empty(), "No models could be loaded");
+    let mut slicer = Slicer::new(slice_config.clone(), meshes);
+    slicer.set_pauses(args.pause_at);
     let progress = slicer.progress();
     let total = slicer.layer_count();
 
@@ -85,34 +96,45 @@ fn main() -> Result<()> {
         RgbaImage::new(290, 290)
     };
 
-    let file = thread::spawn(move || {
-        export_raster(&slicer.slice_config, slicer.slice_raster(), 0, format)
-    });
-    let mut file = monitor_progress(file, progress, |progress| {
+    if args.buffered {
+        let file = thread::spawn(move || {
+            let layers = slicer.slice_raster()?;
+            let voxels = layers.iter().map(|x| x.area()).sum();
+            Ok(export_raster(&slicer.slice_config, &layers, voxels, format))
+        });
+        let mut file = monitor_progress(file, progress, layer_progress(total))??;
+        file.set_preview(&preview);
+
+        println!();
+        let progress = Progress::new();
+        let handle = thread::spawn(clone!([progress], move || {
+            let mut serializer = DynamicSerializer::new();
+            file.serialize(&mut serializer, progress);
+            fs::write(args.output, serializer.into_inner())
+        }));
+
+        monitor_progress(handle, progress, |progress| {
+            format!("\rSaving {:.1}%", progress.progress() * 100.0)
+        })??;
+    } else {
+        let output = BufWriter::new(File::create(&args.output)?);
+        let handle = thread::spawn(move || stream_raster(&slicer, output, &preview, format));
+        monitor_progress(handle, progress, layer_progress(total))??;
+    }
+
+    println!("\nDone. Elapsed: {:.1}s", now.elapsed().as_secs_f32());
+
+    Ok(())
+}
+
+fn layer_progress(total: u32) -> impl Fn(&Progress) -> String {
+    move |progress| {
         format!(
             "\rLayer: {}/{total}, {:.1}%",
             progress.get_complete(),
             progress.progress() * 100.0
         )
-    })?;
-
-    file.set_preview(&preview);
-
-    println!();
-    let progress = Progress::new();
-    let handle = thread::spawn(clone!([progress], move || {
-        let mut serializer = DynamicSerializer::new();
-        file.serialize(&mut serializer, progress);
-        fs::write(args.output, serializer.into_inner()).unwrap();
-    }));
-
-    monitor_progress(handle, progress, |progress| {
-        format!("\rSaving {:.1}%", progress.progress() * 100.0)
-    })?;
-
-    println!("\nDone. Elapsed: {:.1}s", now.elapsed().as_secs_f32());
-
-    Ok(())
+    }
 }
 
 fn load_mesh<T: Read + Seek + Send + 'static>(reader: T, format: &str) -> Result<Mesh> {
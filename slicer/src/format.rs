@@ -0,0 +1,157 @@
+//! Lookup of the format crate that handles each sliced file format.
+
+use std::{
+    io::{Seek, Write},
+    sync::Arc,
+};
+
+use anyhow::Result;
+
+use common::{
+    container::SharedBytes,
+    slice::{
+        DynSliceDecoder, DynSlicedFile, Layer, SliceConfig, SliceDecoder, format::RasterFormat,
+    },
+};
+use image::RgbaImage;
+
+use crate::{
+    slicer::Slicer,
+    util::{encode_raster_layers, stream_layers},
+};
+
+/// Functions for reading and writing one raster format.
+pub struct RasterHandler {
+    /// Decodes a sliced file. Layers are left encoded until they are
+    /// requested from the returned decoder.
+    pub decode: fn(&SharedBytes) -> Result<DynSliceDecoder>,
+    /// Encodes already sliced layers into a file held in memory.
+    pub export: fn(&SliceConfig, &mut dyn Iterator<Item = &Layer>, u64) -> DynSlicedFile,
+    /// Slices straight to a stream, writing each layer as it's encoded.
+    pub stream: fn(&Slicer, &mut dyn WriteSeek, &RgbaImage) -> Result<()>,
+}
+
+/// Output stream for [`RasterHandler::stream`].
+pub trait WriteSeek: Write + Seek {}
+impl<T: Write + Seek> WriteSeek for T {}
+
+/// Gets the functions for reading and writing a format.
+pub fn handler(format: RasterFormat) -> &'static RasterHandler {
+    match format {
+        RasterFormat::Goo => &GOO,
+        RasterFormat::Ctb => &CTB,
+        RasterFormat::CtbLegacy => &CTB_LEGACY,
+        RasterFormat::Cbddlp => &CBDDLP,
+        RasterFormat::NanoDLP => &NANODLP,
+        RasterFormat::Sl1 => &SL1,
+        RasterFormat::Photon => &PHOTON,
+    }
+}
+
+static GOO: RasterHandler = RasterHandler {
+    decode: decode::<goo_format::File>,
+    export: |config, layers, _voxels| {
+        let layers = encode_raster_layers::<goo_format::LayerEncoder, _, _>(config, layers);
+        Box::new(goo_format::File::from_layers(config, layers))
+    },
+    stream: |slicer, stream, preview| {
+        let (config, layers) = (&slicer.slice_config, slicer.layer_count());
+        stream_layers(
+            slicer,
+            goo_format::FileWriter::new(stream, config, layers, preview),
+        )
+    },
+};
+
+static CTB: RasterHandler = RasterHandler {
+    decode: decode::<ctb_format::File>,
+    export: |config, layers, _voxels| export_ctb(config, layers, ctb_format::Layout::Encrypted),
+    stream: |slicer, stream, preview| {
+        stream_ctb(slicer, stream, preview, ctb_format::Layout::Encrypted)
+    },
+};
+
+/// Older boards only take the unencrypted formats. Version 4 is needed for the
+/// extra print parameters, but CBDDLP is kept at version 2 for the original
+/// printers that use it.
+static CTB_LEGACY: RasterHandler = RasterHandler {
+    decode: decode::<ctb_format::File>,
+    export: |config, layers, _voxels| export_ctb(config, layers, ctb_format::Layout::Ctb(4)),
+    stream: |slicer, stream, preview| {
+        stream_ctb(slicer, stream, preview, ctb_format::Layout::Ctb(4))
+    },
+};
+
+static CBDDLP: RasterHandler = RasterHandler {
+    decode: decode::<ctb_format::File>,
+    export: |config, layers, _voxels| export_ctb(config, layers, ctb_format::Layout::Cbddlp(2)),
+    stream: |slicer, stream, preview| {
+        stream_ctb(slicer, stream, preview, ctb_format::Layout::Cbddlp(2))
+    },
+};
+
+static NANODLP: RasterHandler = RasterHandler {
+    decode: decode::<nanodlp_format::File>,
+    export: |config, layers, voxels| {
+        let layers = encode_raster_layers::<nanodlp_format::LayerEncoder, _, _>(config, layers);
+        Box::new(nanodlp_format::File::from_layers(config, layers, voxels))
+    },
+    stream: |slicer, stream, preview| {
+        let config = &slicer.slice_config;
+        stream_layers(
+            slicer,
+            nanodlp_format::FileWriter::new(stream, config, preview),
+        )
+    },
+};
+
+static SL1: RasterHandler = RasterHandler {
+    decode: decode::<sl1_format::File>,
+    export: |config, layers, voxels| {
+        let layers = encode_raster_layers::<sl1_format::LayerEncoder, _, _>(config, layers);
+        Box::new(sl1_format::File::from_layers(config, layers, voxels))
+    },
+    stream: |slicer, stream, preview| {
+        let config = &slicer.slice_config;
+        stream_layers(slicer, sl1_format::FileWriter::new(stream, config, preview))
+    },
+};
+
+static PHOTON: RasterHandler = RasterHandler {
+    decode: decode::<photon_format::File>,
+    export: |config, layers, voxels| {
+        let layers = encode_raster_layers::<photon_format::LayerEncoder, _, _>(config, layers);
+        Box::new(photon_format::File::from_layers(config, layers, voxels))
+    },
+    stream: |slicer, stream, preview| {
+        let (config, layers) = (&slicer.slice_config, slicer.layer_count());
+        stream_layers(
+            slicer,
+            photon_format::FileWriter::new(stream, config, layers, preview),
+        )
+    },
+};
+
+fn decode<T: SliceDecoder + Send + Sync + 'static>(data: &SharedBytes) -> Result<DynSliceDecoder> {
+    Ok(Arc::new(T::decode(data)?))
+}
+
+fn export_ctb(
+    config: &SliceConfig,
+    layers: &mut dyn Iterator<Item = &Layer>,
+    layout: ctb_format::Layout,
+) -> DynSlicedFile {
+    let layers = encode_raster_layers::<ctb_format::LayerEncoder, _, _>(config, layers);
+    Box::new(ctb_format::File::from_layers(config, layers, layout))
+}
+
+fn stream_ctb(
+    slicer: &Slicer,
+    stream: &mut dyn WriteSeek,
+    preview: &RgbaImage,
+    layout: ctb_format::Layout,
+) -> Result<()> {
+    let (config, layers) = (&slicer.slice_config, slicer.layer_count());
+    let writer = ctb_format::FileWriter::new(stream, config, layers, layout, preview);
+    stream_layers(slicer, writer)
+}
@@ -1,8 +1,8 @@
-use std::{mem, sync::Arc, time::Instant};
+use std::{mem, time::Instant};
 
 use common::{
     container::{Image, rle},
-    progress::Progress,
+    progress::{CancellationToken, Cancelled, Progress},
     serde::{Deserializer, Serializer},
     slice::{Layer, SliceConfig},
     units::Milimeter,
@@ -22,9 +22,15 @@ pub struct ElephantFootFixer {
 }
 
 impl ElephantFootFixer {
-    pub fn post_slice(&self, config: &SliceConfig, layers: &mut [Layer], progress: Progress) {
+    pub fn post_slice(
+        &self,
+        config: &SliceConfig,
+        layers: &mut [Layer],
+        progress: Progress,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
         if !self.enabled {
-            return;
+            return Ok(());
         }
 
         let [width, height] = *config.platform_resolution.as_ref();
@@ -51,8 +57,9 @@ impl ElephantFootFixer {
             .iter_mut()
             .take(config.first_layers as usize)
             .par_bridge()
-            .for_each(|layer| {
-                let inner = rle::decode_vec(&*layer.data);
+            .try_for_each(|layer| {
+                cancel.check()?;
+                let inner = rle::decode_vec(&*layer.data.runs());
                 let mut image = GrayImage::from_raw(width, height, inner).unwrap();
 
                 let erode = imageproc::morphology::grayscale_erode(&image, &mask);
@@ -63,11 +70,13 @@ impl ElephantFootFixer {
                 }
 
                 let image = Image::from_raw(config.platform_resolution.cast(), image.into_raw());
-                layer.data = Arc::new(image.runs().collect());
-            });
+                layer.data = image.runs().collect::<Vec<_>>().into();
+                Ok(())
+            })?;
 
         progress.set_finished();
         info!("Eroded bottom layers in {:?}", start.elapsed());
+        Ok(())
     }
 }
 
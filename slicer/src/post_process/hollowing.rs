@@ -0,0 +1,552 @@
+use std::{
+    f32::consts::{PI, TAU},
+    ops::Range,
+    time::Instant,
+};
+
+use common::{
+    container::Run,
+    progress::{CancellationToken, Cancelled, Progress},
+    serde::{Deserializer, Serializer},
+    slice::{Layer, SliceConfig},
+    units::Milimeter,
+};
+use nalgebra::{Vector2, Vector3};
+use rayon::{
+    current_num_threads,
+    iter::{
+        IndexedParallelIterator, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator,
+    },
+};
+use tracing::info;
+
+use crate::slicer::cut::{CutPrimitive, cut_pixels};
+
+/// Number of voxels across the wall thickness that the distance field is
+/// sampled with. The walls end up accurate to about one voxel.
+const VOXELS_PER_WALL: f32 = 8.0;
+/// Smallest wall thickness and infill size (mm). Thinner walls would need
+/// absurdly small voxels, and the infill pattern divides by its spacing.
+pub const MIN_HOLLOWING_SIZE: f32 = 0.1;
+
+/// Removes the inside of models, leaving walls of a set thickness and an
+/// optional infill pattern to support them.
+#[derive(Clone)]
+pub struct Hollowing {
+    pub enabled: bool,
+
+    /// Minimum distance from any pixel removed to the surface (mm)
+    pub wall_thickness: f32,
+    pub infill: Infill,
+    /// Size of each gyroid cell or distance between pillars (mm)
+    pub infill_spacing: f32,
+    /// Thickness of the gyroid walls or diameter of the pillars (mm)
+    pub infill_thickness: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum Infill {
+    #[default]
+    None,
+    Gyroid,
+    Pillars,
+}
+
+/// How the layers are divided into voxels for the distance field. Each voxel
+/// covers a block of pixels on a run of consecutive layers (a slab).
+struct Grid {
+    resolution: Vector2<u64>,
+    /// Size of each voxel in pixels.
+    cell: Vector2<u64>,
+    /// Number of whole voxels across the platform.
+    size: Vector2<usize>,
+    /// Size of each voxel in mm.
+    spacing: Vector2<f32>,
+    /// Height of each slab in mm.
+    height: f32,
+
+    /// Distance from the surface voxel centers need to be to get removed. This
+    /// is the wall thickness plus half a voxel, so no removed pixel is closer
+    /// than the wall thickness.
+    wall: f32,
+    /// Number of slabs above and below each slab that can be within `wall` of
+    /// it.
+    window: usize,
+}
+
+/// Squared distance (mm²) from each voxel of a slab to the nearest voxel that
+/// isn't completely filled. Only stored within a box around the filled voxels,
+/// everything outside of it has a distance of zero.
+struct Field {
+    min: Vector2<isize>,
+    size: Vector2<usize>,
+    distance: Vec<f32>,
+}
+
+/// Voxels of a slab that are far enough from the surface to be removed.
+struct Hollow {
+    min: Vector2<isize>,
+    size: Vector2<usize>,
+    voxels: Vec<bool>,
+}
+
+impl Hollowing {
+    /// Hollows out the layers. Pixels inside of `cuts` count as filled when
+    /// finding the distance to the surface, so drain holes deeper than the wall
+    /// thickness reach the hollow interior.
+    pub fn post_slice(
+        &self,
+        config: &SliceConfig,
+        layers: &mut [Layer],
+        cuts: &[CutPrimitive],
+        progress: Progress,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        if !self.enabled || layers.is_empty() {
+            return Ok(());
+        }
+
+        // The middle of each layer, and the thickest one. With variable layer
+        // heights the layers can be thinner or thicker than the slice height.
+        let mut bottom = 0.0;
+        let (mut centers, mut thickest) = (Vec::with_capacity(layers.len()), 0.0_f32);
+        for layer in layers.iter() {
+            let top = layer.height.get::<Milimeter>();
+            centers.push((bottom + top) / 2.0);
+            thickest = thickest.max(top - bottom);
+            bottom = top;
+        }
+
+        let grid = Grid::new(config, self.wall_thickness, thickest);
+        let ranges = grid.slabs(&centers);
+        let slabs = ranges.len();
+        info!(
+            "Hollowing with {}x{}px voxels over {} slabs of {:.3}mm",
+            grid.cell.x, grid.cell.y, slabs, grid.height
+        );
+
+        let start = Instant::now();
+        progress.set_total(slabs as u64);
+
+        let mut slab_layers = Vec::with_capacity(slabs);
+        let mut rest = layers;
+        for range in ranges.iter() {
+            let (slab, next) = rest.split_at_mut(range.len());
+            slab_layers.push(slab);
+            rest = next;
+        }
+
+        // The fields are built a batch of slabs at a time, and each slab is
+        // hollowed as soon as every field in its window is ready. This way only
+        // a few fields need to be kept in memory at once.
+        let mut fields = (0..slabs).map(|_| None).collect::<Vec<_>>();
+        let mut hollowed = 0;
+        for batch in (0..slabs).step_by(current_num_threads()) {
+            cancel.check()?;
+            let end = (batch + current_num_threads()).min(slabs);
+            let new = (batch..end)
+                .into_par_iter()
+                .map(|i| grid.field(config, slab_layers[i], &centers[ranges[i].clone()], cuts))
+                .collect::<Vec<_>>();
+            (fields[batch..end].iter_mut())
+                .zip(new)
+                .for_each(|(field, new)| *field = new);
+
+            let ready = if end == slabs {
+                slabs
+            } else {
+                end.saturating_sub(grid.window)
+            };
+            (slab_layers[hollowed..ready].par_iter_mut())
+                .zip(&ranges[hollowed..ready])
+                .enumerate()
+                .try_for_each(|(i, (slab, range))| {
+                    cancel.check()?;
+                    if let Some(hollow) = self.hollow(&grid, &fields, hollowed + i) {
+                        for (layer, &z) in slab.iter_mut().zip(&centers[range.clone()]) {
+                            self.apply(&grid, &hollow, layer, z);
+                        }
+                    }
+
+                    progress.add_complete(1);
+                    Ok(())
+                })?;
+
+            hollowed = ready;
+            let unused = hollowed.saturating_sub(grid.window);
+            fields[..unused].iter_mut().for_each(|x| *x = None);
+        }
+
+        progress.set_finished();
+        info!("Hollowed layers in {:?}", start.elapsed());
+        Ok(())
+    }
+
+    /// Finds the voxels in a slab whose distance to the surface in 3D is at
+    /// least the wall thickness. Slabs past the bottom or top of the print count
+    /// as empty, so every model gets a floor and a roof.
+    fn hollow(&self, grid: &Grid, fields: &[Option<Field>], slab: usize) -> Option<Hollow> {
+        let window = grid.window;
+        if slab < window || slab + window >= fields.len() {
+            return None;
+        }
+
+        let field = fields[slab].as_ref()?;
+        let thickness = grid.wall.powi(2);
+
+        let mut voxels = vec![false; field.size.x * field.size.y];
+        for y in 0..field.size.y {
+            for x in 0..field.size.x {
+                let idx = y * field.size.x + x;
+                if field.distance[idx] < thickness {
+                    continue;
+                }
+
+                let pos = field.min + Vector2::new(x, y).cast::<isize>();
+                voxels[idx] = (1..=window).all(|offset| {
+                    let required = thickness - (offset as f32 * grid.height).powi(2);
+                    [slab - offset, slab + offset]
+                        .iter()
+                        .all(|&i| (fields[i].as_ref()).is_some_and(|x| x.get(pos) >= required))
+                });
+            }
+        }
+
+        (voxels.iter().any(|&x| x)).then_some(Hollow {
+            min: field.min,
+            size: field.size,
+            voxels,
+        })
+    }
+
+    /// Clears the pixels of a layer that are within hollow voxels, except for
+    /// those that are part of the infill.
+    fn apply(&self, grid: &Grid, hollow: &Hollow, layer: &mut Layer, z: f32) {
+        let pixel = grid.spacing.component_div(&grid.cell.cast());
+        let width = grid.resolution.x;
+
+        let mut out = Vec::<Run>::new();
+        let mut push = |length: u64, value: u8| match out.last_mut() {
+            Some(last) if last.value == value => last.length += length,
+            _ => out.push(Run::new(length, value)),
+        };
+
+        let mut pos = 0;
+        for run in layer.data.runs().iter() {
+            let end = pos + run.length;
+            if run.value == 0 {
+                push(run.length, 0);
+                pos = end;
+                continue;
+            }
+
+            // Split the run into spans within a single voxel, so only the
+            // pixels of hollow voxels with infill need to be checked one by one.
+            while pos < end {
+                let (x, y) = (pos % width, pos / width);
+                let voxel = Vector2::new(x / grid.cell.x, y / grid.cell.y);
+                let span_end = (y * width + ((voxel.x + 1) * grid.cell.x).min(width)).min(end);
+
+                if !hollow.contains(voxel.cast()) {
+                    push(span_end - pos, run.value);
+                } else if self.infill == Infill::None {
+                    push(span_end - pos, 0);
+                } else {
+                    for x in x..x + (span_end - pos) {
+                        let position = (Vector2::new(x, y).cast::<f32>().add_scalar(0.5))
+                            .component_mul(&pixel);
+                        let infill = self.is_infill(position.push(z));
+                        push(1, if infill { run.value } else { 0 });
+                    }
+                }
+                pos = span_end;
+            }
+        }
+
+        layer.set_runs(out);
+    }
+
+    /// Checks if a point (mm) is part of the infill pattern.
+    fn is_infill(&self, pos: Vector3<f32>) -> bool {
+        match self.infill {
+            Infill::None => false,
+            Infill::Gyroid => {
+                let pos = pos * TAU / self.infill_spacing;
+                let value = pos.x.sin() * pos.y.cos()
+                    + pos.y.sin() * pos.z.cos()
+                    + pos.z.sin() * pos.x.cos();
+                // The gradient of the gyroid function is roughly 2π/spacing, so
+                // this gives walls of about the right thickness.
+                value.abs() < self.infill_thickness * PI / self.infill_spacing
+            }
+            Infill::Pillars => {
+                let offset = pos
+                    .xy()
+                    .map(|x| x.rem_euclid(self.infill_spacing) - self.infill_spacing / 2.0);
+                offset.magnitude() < self.infill_thickness / 2.0
+            }
+        }
+    }
+}
+
+impl Grid {
+    /// Slabs are a whole number of slice heights tall, but never thinner than
+    /// the `thickest` layer, so every slab has at least one layer in it.
+    fn new(config: &SliceConfig, wall_thickness: f32, thickest: f32) -> Self {
+        let resolution = config.platform_resolution.cast::<u64>();
+        let pixel = (config.platform_size.xy().map(|x| x.get::<Milimeter>()))
+            .component_div(&resolution.cast());
+        let slice_height = config.slice_height.get::<Milimeter>();
+
+        let voxel = wall_thickness / VOXELS_PER_WALL;
+        let cell = pixel.map(|x| (voxel / x).round().max(1.0) as u64);
+        let layers = (voxel / slice_height).round().max(1.0);
+
+        let spacing = pixel.component_mul(&cell.cast());
+        let height = (layers * slice_height).max(thickest);
+        let wall = wall_thickness + spacing.push(height).magnitude() / 2.0;
+
+        Self {
+            resolution,
+            cell,
+            size: resolution.component_div(&cell).cast(),
+            spacing,
+            height,
+
+            wall,
+            window: ((wall / height).ceil() as usize).saturating_sub(1),
+        }
+    }
+
+    /// Splits the layers into slabs by the height of their middles (`centers`),
+    /// giving the range of layers in each one.
+    fn slabs(&self, centers: &[f32]) -> Vec<Range<usize>> {
+        let mut out = Vec::<Range<usize>>::new();
+        let mut current = None;
+        for (i, center) in centers.iter().enumerate() {
+            let slab = (center / self.height).floor() as i64;
+            match out.last_mut() {
+                Some(last) if current == Some(slab) => last.end = i + 1,
+                _ => out.push(i..i + 1),
+            }
+            current = Some(slab);
+        }
+
+        out
+    }
+
+    /// Builds the distance field of a slab. A voxel is only considered filled
+    /// if every one of its pixels is on, in every layer. `centers` are the
+    /// heights of the middle of each layer.
+    fn field(
+        &self,
+        config: &SliceConfig,
+        layers: &[Layer],
+        centers: &[f32],
+        cuts: &[CutPrimitive],
+    ) -> Option<Field> {
+        let mut filled = vec![0; self.size.x * self.size.y];
+        for (layer, &z) in layers.iter().zip(centers) {
+            let mut pos = 0;
+            for run in layer.data.runs().iter() {
+                (run.value > 0).then(|| self.add_run(&mut filled, pos, run.length));
+                pos += run.length;
+            }
+
+            for range in cut_pixels(cuts, config, z) {
+                self.add_run(&mut filled, range.start, range.end - range.start);
+            }
+        }
+
+        let full = self.cell.x * self.cell.y * layers.len() as u64;
+        let (mut min, mut max) = (self.size, Vector2::zeros());
+        for (i, _) in filled.iter().enumerate().filter(|(_, x)| **x == full) {
+            let pos = Vector2::new(i % self.size.x, i / self.size.x);
+            (min, max) = (min.inf(&pos), max.sup(&pos));
+        }
+
+        if min.x > max.x {
+            return None;
+        }
+
+        // Pad the box by one empty voxel on each side, so distances are measured
+        // to its edges too.
+        let (min, size) = (
+            min.cast::<isize>().add_scalar(-1),
+            (max - min).add_scalar(3),
+        );
+        let mut distance = vec![0.0; size.x * size.y];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let pos = min + Vector2::new(x, y).cast::<isize>();
+                let is_full = (pos.x >= 0 && pos.y >= 0)
+                    && (pos.x as usize) < self.size.x
+                    && (pos.y as usize) < self.size.y
+                    && filled[pos.y as usize * self.size.x + pos.x as usize] == full;
+                distance[y * size.x + x] = if is_full { f32::MAX } else { 0.0 };
+            }
+        }
+
+        // The 2D distance transform is separable into a pass along each row,
+        // then one along each column.
+        let mut line = Vec::new();
+        for y in 0..size.y {
+            let row = &mut distance[y * size.x..(y + 1) * size.x];
+            distance_transform(row, self.spacing.x, &mut line);
+        }
+
+        let mut column = vec![0.0; size.y];
+        for x in 0..size.x {
+            (0..size.y).for_each(|y| column[y] = distance[y * size.x + x]);
+            distance_transform(&mut column, self.spacing.y, &mut line);
+            (0..size.y).for_each(|y| distance[y * size.x + x] = column[y]);
+        }
+
+        Some(Field {
+            min,
+            size,
+            distance,
+        })
+    }
+
+    /// Adds the number of pixels of the run within each voxel to `filled`.
+    fn add_run(&self, filled: &mut [u64], start: u64, length: u64) {
+        let (mut pos, end) = (start, start + length);
+        while pos < end {
+            let (x, y) = (pos % self.resolution.x, pos / self.resolution.x);
+            let row_end = (end - y * self.resolution.x).min(self.resolution.x);
+            pos = y * self.resolution.x + row_end;
+
+            let cell_y = (y / self.cell.y) as usize;
+            if cell_y >= self.size.y {
+                continue;
+            }
+
+            let mut x = x;
+            while x < row_end {
+                let cell_x = (x / self.cell.x) as usize;
+                let next = ((cell_x as u64 + 1) * self.cell.x).min(row_end);
+                (cell_x < self.size.x).then(|| filled[cell_y * self.size.x + cell_x] += next - x);
+                x = next;
+            }
+        }
+    }
+}
+
+impl Field {
+    fn get(&self, pos: Vector2<isize>) -> f32 {
+        let pos = pos - self.min;
+        if pos.x < 0 || pos.y < 0 || pos.x >= self.size.x as isize || pos.y >= self.size.y as isize
+        {
+            return 0.0;
+        }
+
+        self.distance[pos.y as usize * self.size.x + pos.x as usize]
+    }
+}
+
+impl Hollow {
+    fn contains(&self, pos: Vector2<isize>) -> bool {
+        let pos = pos - self.min;
+        (pos.x >= 0 && pos.y >= 0)
+            && (pos.x as usize) < self.size.x
+            && (pos.y as usize) < self.size.y
+            && self.voxels[pos.y as usize * self.size.x + pos.x as usize]
+    }
+}
+
+/// One dimensional squared euclidean distance transform, using the lower
+/// envelope of parabolas method from Felzenszwalb and Huttenlocher. Samples are
+/// `spacing` apart and the input is zero at empty samples and infinite
+/// (`f32::MAX`) elsewhere. `scratch` is reused between calls to avoid
+/// allocating.
+fn distance_transform(values: &mut [f32], spacing: f32, scratch: &mut Vec<(usize, f32)>) {
+    // Work in units of samples so the parabolas all have the same shape.
+    let scale = spacing.powi(2);
+    let value = |i: usize, values: &[f32]| values[i] / scale;
+    let intersect = |a: usize, b: usize, values: &[f32]| {
+        let (a_value, b_value) = (value(a, values), value(b, values));
+        ((b_value + (b * b) as f32) - (a_value + (a * a) as f32)) / (2.0 * (b - a) as f32)
+    };
+
+    // Each parabola in the envelope, along with where it starts being the
+    // lowest.
+    scratch.clear();
+    for i in (0..values.len()).filter(|&i| values[i] < f32::MAX) {
+        while let Some(&(last, start)) = scratch.last() {
+            if intersect(last, i, values) > start {
+                break;
+            }
+            scratch.pop();
+        }
+
+        let start = match scratch.last() {
+            Some(&(last, _)) => intersect(last, i, values),
+            None => f32::MIN,
+        };
+        scratch.push((i, start));
+    }
+
+    let mut out = Vec::with_capacity(values.len());
+    let mut parabola = 0;
+    for i in 0..values.len() {
+        while parabola + 1 < scratch.len() && scratch[parabola + 1].1 < i as f32 {
+            parabola += 1;
+        }
+
+        out.push(match scratch.get(parabola) {
+            Some(&(j, _)) => ((i as f32 - j as f32).powi(2) + value(j, values)) * scale,
+            None => f32::MAX,
+        });
+    }
+
+    values.copy_from_slice(&out);
+}
+
+impl Infill {
+    pub const ALL: [Self; 3] = [Self::None, Self::Gyroid, Self::Pillars];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::None => "None",
+            Self::Gyroid => "Gyroid",
+            Self::Pillars => "Pillars",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+}
+
+impl Default for Hollowing {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            wall_thickness: 2.0,
+            infill: Infill::None,
+            infill_spacing: 10.0,
+            infill_thickness: 1.0,
+        }
+    }
+}
+
+impl Hollowing {
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        ser.write_bool(self.enabled);
+        ser.write_f32_be(self.wall_thickness);
+        ser.write_u8(self.infill as u8);
+        ser.write_f32_be(self.infill_spacing);
+        ser.write_f32_be(self.infill_thickness);
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Self {
+        Self {
+            enabled: des.read_bool(),
+            wall_thickness: des.read_f32_be().max(MIN_HOLLOWING_SIZE),
+            infill: Infill::from_u8(des.read_u8()),
+            infill_spacing: des.read_f32_be().max(MIN_HOLLOWING_SIZE),
+            infill_thickness: des.read_f32_be().max(MIN_HOLLOWING_SIZE),
+        }
+    }
+}
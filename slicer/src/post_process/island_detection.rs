@@ -5,7 +5,7 @@ use common::{
         Clusters,
         rle::{self, bits::ClusterRun},
     },
-    progress::Progress,
+    progress::{CancellationToken, Cancelled, Progress},
     slice::Layer,
 };
 use nalgebra::Vector2;
@@ -14,8 +14,9 @@ pub fn detect_islands(
     resolution: Vector2<u32>,
     layers: &[Layer],
     progress: Progress,
+    cancel: &CancellationToken,
     cascade: bool,
-) -> Vec<Vec<u64>> {
+) -> Result<Vec<Vec<u64>>, Cancelled> {
     let [width, rows] = *resolution.cast::<u64>().as_ref();
     progress.set_total(layers.len() as u64);
 
@@ -24,6 +25,7 @@ pub fn detect_islands(
 
     let mut annotations = Vec::new();
     for layer in layers.iter().skip(1) {
+        cancel.check()?;
         // Convert the layer data to a mask of non-zero voxels, split by each row.
         progress.add_complete(1);
         mem::swap(&mut prev, &mut curr);
@@ -66,11 +68,51 @@ pub fn detect_islands(
     }
 
     progress.set_finished();
-    annotations
+    Ok(annotations)
+}
+
+/// Finds a point inside each island of a layer, given in the same format as
+/// the annotations returned by [`detect_islands`]. The point is the pixel of
+/// the island closest to its center, so it's never outside of the island even
+/// if the island isn't convex.
+pub fn island_centers(resolution: Vector2<u32>, islands: &[u64]) -> Vec<Vector2<f32>> {
+    let rows = rle::bits::chunks(islands, resolution.x as u64);
+
+    let mut clusters = Clusters::default();
+    for row in 0..rows.len() {
+        rle::bits::cluster_row_adjacency(&mut clusters, &rows, row.saturating_sub(1), row);
+    }
+
+    let mut centers = Vec::new();
+    for (_, runs) in clusters.clusters() {
+        let run_center = |run: &ClusterRun| {
+            Vector2::new(
+                run.position as f32 + run.size as f32 / 2.0,
+                run.row as f32 + 0.5,
+            )
+        };
+
+        let area = runs.iter().map(|x| x.size as f32).sum::<f32>();
+        let center = (runs.iter()).fold(Vector2::zeros(), |acc, x| {
+            acc + run_center(x) * (x.size as f32 / area)
+        });
+
+        // Clamp the center into each run, and keep the closest of those
+        let closest = (runs.iter())
+            .map(|run| {
+                let min = run.position as f32 + 0.5;
+                let max = (run.position + run.size) as f32 - 0.5;
+                Vector2::new(center.x.clamp(min, max), run.row as f32 + 0.5)
+            })
+            .min_by(|a, b| (a - center).norm().total_cmp(&(b - center).norm()));
+        centers.extend(closest);
+    }
+
+    centers
 }
 
 fn condensed_layer_rows(layer: &Layer, width: u64) -> Vec<Vec<u64>> {
-    let layer = rle::bits::from_runs(&layer.data);
+    let layer = rle::bits::from_runs(&layer.data.runs());
     rle::bits::chunks(&layer, width)
 }
 
@@ -1,3 +1,6 @@
 pub mod elephant_foot_fixer;
+pub mod hollowing;
 pub mod island_detection;
+pub mod resin_traps;
+pub mod smart_motion;
 pub mod variable_layer_height;
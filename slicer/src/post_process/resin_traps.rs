@@ -0,0 +1,170 @@
+use common::{
+    container::{
+        Clusters,
+        rle::{self, bits::ClusterRun},
+    },
+    progress::{CancellationToken, Cancelled, Progress},
+    slice::Layer,
+};
+use nalgebra::Vector2;
+
+/// An empty run in a row of a layer, along with the void it's part of. Runs
+/// connected to the edge of the layer aren't part of any void.
+struct EmptyRun {
+    position: u64,
+    size: u64,
+    void: Option<usize>,
+}
+
+/// Disjoint set of voids, tracking if each has been connected to the outside.
+#[derive(Default)]
+struct Voids {
+    parent: Vec<usize>,
+    vented: Vec<bool>,
+}
+
+/// Finds areas of empty pixels that are enclosed by the model on their layer,
+/// and only connected to the layers above. When printing, resin in these voids
+/// has nowhere to go, so they either become trapped or act like suction cups
+/// pulling the print off of the plate.
+///
+/// Enclosed areas are linked to the ones they overlap on the layer below. A
+/// void is vented once any part of it overlaps empty space that reaches the
+/// edge of a layer, and everything in it from that layer on is fine. The build
+/// plate seals off the first layer.
+///
+/// Returns the trapped pixels of each layer, in the same format as
+/// [`super::island_detection::detect_islands`].
+pub fn detect_resin_traps(
+    resolution: Vector2<u32>,
+    layers: &[Layer],
+    progress: Progress,
+    cancel: &CancellationToken,
+) -> Result<Vec<Vec<u64>>, Cancelled> {
+    let width = resolution.x as u64;
+    progress.set_total(layers.len() as u64);
+
+    let mut voids = Voids::default();
+    let mut prev = Vec::<Vec<EmptyRun>>::new();
+
+    let mut annotations = Vec::new();
+    for layer in layers {
+        cancel.check()?;
+        progress.add_complete(1);
+
+        // Group areas of adjacent empty pixels
+        let rows = empty_layer_rows(layer, width);
+        let mut clusters = Clusters::default();
+        for row in 0..rows.len() {
+            rle::bits::cluster_row_adjacency(&mut clusters, &rows, row.saturating_sub(1), row);
+        }
+
+        let mut curr = (0..rows.len()).map(|_| Vec::new()).collect::<Vec<_>>();
+        let mut enclosed = Vec::new();
+        for (_, runs) in clusters.clusters() {
+            let touches_edge = runs.iter().any(|run| {
+                (run.row == 0 || run.row + 1 == rows.len())
+                    || (run.position == 0 || run.position + run.size >= width)
+            });
+
+            let void = (!touches_edge).then(|| voids.add());
+            for run in runs {
+                curr[run.row].push(EmptyRun {
+                    position: run.position,
+                    size: run.size,
+                    void,
+                });
+            }
+
+            let Some(void) = void else {
+                continue;
+            };
+
+            // Link up with the voids below, or vent if it opens up into
+            // empty space.
+            for run in runs {
+                let below = (prev.get(run.row).into_iter().flatten())
+                    .filter(|x| x.position < run.position + run.size)
+                    .filter(|x| run.position < x.position + x.size);
+                for other in below {
+                    match other.void {
+                        Some(other) => voids.union(void, other),
+                        None => voids.vent(void),
+                    }
+                }
+            }
+
+            enclosed.push((void, runs));
+        }
+
+        let mut trapped = (enclosed.into_iter())
+            .filter(|(void, _)| !voids.is_vented(*void))
+            .flat_map(|(_, runs)| runs.iter().copied())
+            .collect::<Vec<ClusterRun>>();
+        trapped.sort_by(|a, b| a.row.cmp(&b.row).then(a.position.cmp(&b.position)));
+
+        let mut annotation = Vec::new();
+        let mut pos = 0;
+        for run in trapped {
+            let start = run.row as u64 * width + run.position;
+            annotation.push(start - pos);
+            annotation.push(run.size);
+            pos = start + run.size;
+        }
+        annotations.push(annotation);
+
+        curr.iter_mut().for_each(|x| x.sort_by_key(|x| x.position));
+        prev = curr;
+    }
+
+    progress.set_finished();
+    Ok(annotations)
+}
+
+/// Converts the layer data to a mask of empty pixels, split by each row.
+fn empty_layer_rows(layer: &Layer, width: u64) -> Vec<Vec<u64>> {
+    let mut mask = rle::bits::from_runs(&layer.data.runs());
+    // Invert the mask by shifting the alternating runs over by one.
+    if mask.first() == Some(&0) {
+        mask.remove(0);
+    } else {
+        mask.insert(0, 0);
+    }
+
+    rle::bits::chunks(&mask, width)
+}
+
+impl Voids {
+    fn add(&mut self) -> usize {
+        self.parent.push(self.parent.len());
+        self.vented.push(false);
+        self.parent.len() - 1
+    }
+
+    fn find(&mut self, mut void: usize) -> usize {
+        while self.parent[void] != void {
+            self.parent[void] = self.parent[self.parent[void]];
+            void = self.parent[void];
+        }
+
+        void
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parent[a] = b;
+            self.vented[b] |= self.vented[a];
+        }
+    }
+
+    fn vent(&mut self, void: usize) {
+        let root = self.find(void);
+        self.vented[root] = true;
+    }
+
+    fn is_vented(&mut self, void: usize) -> bool {
+        let root = self.find(void);
+        self.vented[root]
+    }
+}
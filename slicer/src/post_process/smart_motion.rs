@@ -0,0 +1,155 @@
+use common::{
+    misc::lerp,
+    progress::{CancellationToken, Cancelled, Progress},
+    serde::{Deserializer, Serializer},
+    slice::{ExposureField, Layer, SliceConfig},
+    units::{CentimetersPerSecond, Milimeters, MilimetersPerMinute, Seconds},
+};
+
+/// Picks the lift speed, lift distance, and rest time of each layer from its
+/// cured area. Larger layers pull harder on the film as they peel, so they
+/// are lifted slower and further, then given longer for the resin to settle.
+#[derive(Clone)]
+pub struct SmartMotion {
+    pub enabled: bool,
+
+    /// Cured area at or below which layers get the lightest motion (mm²)
+    pub min_area: f32,
+    /// Cured area at or above which layers get the heaviest motion (mm²)
+    pub max_area: f32,
+    /// Change in cured area from the previous layer that alone gives the
+    /// heaviest motion (mm²). Zero ignores the change.
+    pub max_area_change: f32,
+
+    pub min_lift_speed: CentimetersPerSecond,
+    pub max_lift_speed: CentimetersPerSecond,
+    pub min_lift_distance: Milimeters,
+    pub max_lift_distance: Milimeters,
+    /// Rest after retracting, before the next exposure.
+    pub min_rest: Seconds,
+    pub max_rest: Seconds,
+}
+
+impl SmartMotion {
+    /// Sets the motion of every layer after the first layers, which keep
+    /// their own settings. Fields set by an exposure override at a layer's
+    /// height are left as the override has them. Changed layers are marked as
+    /// having a unique exposure, so formats write their settings per layer.
+    pub fn post_slice(
+        &self,
+        config: &SliceConfig,
+        layers: &mut [Layer],
+        progress: Progress,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        progress.set_total(layers.len() as u64);
+        let pixel_area = config.pixel_area().raw();
+
+        let mut previous = 0.0;
+        for (i, layer) in layers.iter_mut().enumerate() {
+            cancel.check()?;
+            progress.add_complete(1);
+
+            let area = layer.area() as f32 * pixel_area;
+            let change = (area - previous).abs();
+            previous = area;
+
+            if i < config.first_layers as usize {
+                continue;
+            }
+
+            let overrides = (config.exposure_overrides.iter())
+                .filter(|x| x.contains(layer.height))
+                .flat_map(|x| x.fields.iter().copied())
+                .collect::<Vec<_>>();
+            let free = |field| !overrides.contains(&field);
+
+            let t = self.weight(area, change);
+            let exposure = &mut layer.exposure;
+            if free(ExposureField::LiftDistance) {
+                // Keep the platform coming back down as far as it went up.
+                let lift = lerp(self.min_lift_distance, self.max_lift_distance, t);
+                let retract = exposure.retract_distance.raw() + lift.raw();
+                exposure.retract_distance =
+                    Milimeters::new((retract - exposure.lift_distance.raw()).max(0.0));
+                exposure.lift_distance = lift;
+            }
+            if free(ExposureField::LiftSpeed) {
+                exposure.lift_speed = lerp(self.max_lift_speed, self.min_lift_speed, t);
+            }
+            if free(ExposureField::ExposureDelay) {
+                exposure.exposure_delay = lerp(self.min_rest, self.max_rest, t);
+            }
+            layer.unique_exposure = true;
+        }
+
+        progress.set_finished();
+        Ok(())
+    }
+
+    /// How heavy the motion of a layer should be, from zero to one.
+    fn weight(&self, area: f32, change: f32) -> f32 {
+        let range = (self.max_area - self.min_area).max(f32::EPSILON);
+        let area = ((area - self.min_area) / range).clamp(0.0, 1.0);
+        let change = if self.max_area_change > 0.0 {
+            (change / self.max_area_change).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        area.max(change)
+    }
+}
+
+impl Default for SmartMotion {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+
+            min_area: 100.0,
+            max_area: 5000.0,
+            max_area_change: 1000.0,
+
+            min_lift_speed: MilimetersPerMinute::new(60.0).convert(),
+            max_lift_speed: MilimetersPerMinute::new(180.0).convert(),
+            min_lift_distance: Milimeters::new(4.0),
+            max_lift_distance: Milimeters::new(8.0),
+            min_rest: Seconds::new(0.5),
+            max_rest: Seconds::new(3.0),
+        }
+    }
+}
+
+impl SmartMotion {
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        ser.write_bool(self.enabled);
+        ser.write_f32_be(self.min_area);
+        ser.write_f32_be(self.max_area);
+        ser.write_f32_be(self.max_area_change);
+        ser.write_f32_be(self.min_lift_speed.raw());
+        ser.write_f32_be(self.max_lift_speed.raw());
+        ser.write_f32_be(self.min_lift_distance.raw());
+        ser.write_f32_be(self.max_lift_distance.raw());
+        ser.write_f32_be(self.min_rest.raw());
+        ser.write_f32_be(self.max_rest.raw());
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T) -> Self {
+        Self {
+            enabled: des.read_bool(),
+            min_area: des.read_f32_be(),
+            max_area: des.read_f32_be(),
+            max_area_change: des.read_f32_be(),
+            min_lift_speed: CentimetersPerSecond::new(des.read_f32_be()),
+            max_lift_speed: CentimetersPerSecond::new(des.read_f32_be()),
+            min_lift_distance: Milimeters::new(des.read_f32_be()),
+            max_lift_distance: Milimeters::new(des.read_f32_be()),
+            min_rest: Seconds::new(des.read_f32_be()),
+            max_rest: Seconds::new(des.read_f32_be()),
+        }
+    }
+}
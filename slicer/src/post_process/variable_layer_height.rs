@@ -1,15 +1,18 @@
 use common::{
     container::rle::difference,
-    progress::Progress,
+    progress::{CancellationToken, Cancelled, Progress},
     serde::{Deserializer, Serializer},
     slice::{Layer, SliceConfig},
     units::{Milimeter, Second, Seconds},
 };
 use tracing::info;
 
+use crate::slicer::Slicer;
+
 #[derive(Clone)]
 pub struct VariableLayerHeight {
     pub enabled: bool,
+    pub mode: LayerHeightMode,
 
     /// Maximum allowed value deviation (value/mm²)
     pub threshold: f32,
@@ -17,14 +20,113 @@ pub struct VariableLayerHeight {
     pub max_layers: u8,
     /// Exposure time added per layer (sec)
     pub exposure: Seconds,
+
+    /// Thinnest layer used in slope mode (mm)
+    pub min_height: f32,
+    /// Thickest layer used in slope mode (mm)
+    pub max_height: f32,
+    /// Max distance from the corners of the layer steps to sloped surfaces,
+    /// in slope mode (mm)
+    pub deviation: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayerHeightMode {
+    /// Merges consecutive layers that are nearly the same after slicing.
+    #[default]
+    Similarity,
+    /// Picks the height of each layer from the slope of the surfaces around
+    /// it before slicing.
+    Slope,
+}
+
+/// The height range of a face and the thickest layer that can be used on it.
+#[derive(Clone, Copy)]
+struct FaceSlope {
+    bottom: f32,
+    top: f32,
+    height: f32,
 }
 
 impl VariableLayerHeight {
-    pub fn post_slice(&self, config: &SliceConfig, layers: &mut Vec<Layer>, progress: Progress) {
-        if !self.enabled {
+    /// In slope mode, chooses the height of each layer from the model faces
+    /// crossing it. The stair stepping on a surface is the layer height times
+    /// the vertical part of its normal, so nearly flat surfaces get thin
+    /// layers while walls get thick ones.
+    ///
+    /// The first layers are always left at the slice height, so they stick to
+    /// the plate like normal.
+    pub fn pre_slice(&self, slicer: &mut Slicer) {
+        if !self.enabled || self.mode != LayerHeightMode::Slope {
             return;
         }
 
+        let config = &slicer.slice_config;
+        let slice_height = config.slice_height.get::<Milimeter>();
+        let (min, max) = (self.min_height.max(0.001), self.max_height);
+        let max = max.max(min);
+
+        let mut faces = face_slopes(slicer, self.deviation);
+        faces.sort_by(|a, b| a.bottom.total_cmp(&b.bottom));
+
+        let top = (faces.iter().map(|x| x.top))
+            .fold(0.0, f32::max)
+            .min(slicer.layer_count() as f32 * slice_height);
+
+        let mut heights = Vec::new();
+        let (mut next, mut active) = (0, Vec::new());
+        let mut z = 0.0;
+        while top - z > f32::EPSILON * top.max(1.0) {
+            if heights.len() < config.first_layers as usize {
+                z += slice_height;
+                heights.push(z.min(top));
+                continue;
+            }
+
+            // Track the faces that could be on this layer.
+            while let Some(face) = faces.get(next).filter(|x| x.bottom < z + max) {
+                active.push(*face);
+                next += 1;
+            }
+            active.retain(|x| x.top > z);
+
+            // Thinning the layer can leave out some of the faces that limited
+            // it, so repeat until it stops changing.
+            let mut height = max;
+            loop {
+                let limit = (active.iter())
+                    .filter(|x| x.bottom < z + height)
+                    .fold(max, |height, x| height.min(x.height))
+                    .max(min);
+                if limit >= height {
+                    break;
+                }
+                height = limit;
+            }
+
+            z = (z + height).min(top);
+            heights.push(z);
+        }
+
+        info!(
+            "Picked {} layer heights, from {} uniform layers",
+            heights.len(),
+            slicer.layer_count()
+        );
+        slicer.set_layer_heights(heights);
+    }
+
+    pub fn post_slice(
+        &self,
+        config: &SliceConfig,
+        layers: &mut Vec<Layer>,
+        progress: Progress,
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        if !self.enabled || self.mode != LayerHeightMode::Similarity {
+            return Ok(());
+        }
+
         progress.set_total(layers.len() as _);
 
         let px_per_mm = (config.platform_resolution.cast::<f32>())
@@ -37,14 +139,17 @@ impl VariableLayerHeight {
         let mut counter = 0;
 
         while i < layers.len() - 1 {
+            cancel.check()?;
             // If next layer is identical, remove and merge into current
             if merged < self.max_layers
-                && (difference(&layers[i].data, &layers[i + 1].data) as f32) < threshold
+                && (difference(&layers[i].data.runs(), &layers[i + 1].data.runs()) as f32)
+                    < threshold
             {
                 let old = layers.remove(i + 1);
                 let new = &mut layers[i];
 
                 new.exposure.exposure_time = new.exposure.exposure_time + self.exposure;
+                new.unique_exposure = true;
                 new.height = old.height;
 
                 merged += 1;
@@ -57,6 +162,7 @@ impl VariableLayerHeight {
 
         info!("Merged {counter} layers");
         progress.set_finished();
+        Ok(())
     }
 }
 
@@ -64,9 +170,13 @@ impl Default for VariableLayerHeight {
     fn default() -> Self {
         Self {
             enabled: false,
+            mode: LayerHeightMode::Similarity,
             threshold: 0.5,
             max_layers: 2,
             exposure: Seconds::new(0.5),
+            min_height: 0.025,
+            max_height: 0.1,
+            deviation: 0.02,
         }
     }
 }
@@ -77,14 +187,75 @@ impl VariableLayerHeight {
         ser.write_f32_be(self.threshold);
         ser.write_u8(self.max_layers);
         ser.write_f32_be(self.exposure.get::<Second>());
+        ser.write_u8(self.mode as u8);
+        ser.write_f32_be(self.min_height);
+        ser.write_f32_be(self.max_height);
+        ser.write_f32_be(self.deviation);
     }
 
     pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
-        Self {
+        let mut out = Self {
             enabled: des.read_bool(),
             threshold: if version < 11 { 0.5 } else { des.read_f32_be() },
             max_layers: des.read_u8(),
             exposure: Seconds::new(des.read_f32_be()),
+            ..Default::default()
+        };
+
+        if version >= 19 {
+            out.mode = LayerHeightMode::from_u8(des.read_u8());
+            out.min_height = des.read_f32_be();
+            out.max_height = des.read_f32_be();
+            out.deviation = des.read_f32_be();
+        }
+
+        out
+    }
+}
+
+impl LayerHeightMode {
+    pub const ALL: [Self; 2] = [Self::Similarity, Self::Slope];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Similarity => "Similarity",
+            Self::Slope => "Slope",
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+}
+
+/// Finds the height range and max layer height of every sloped face. Flat
+/// faces are skipped, as they don't cause any stair stepping.
+fn face_slopes(slicer: &Slicer, deviation: f32) -> Vec<FaceSlope> {
+    let config = &slicer.slice_config;
+    let slice_height = config.slice_height.get::<Milimeter>();
+
+    // Undo the platform space transform, so the normals are right.
+    let px_per_mm = (config.platform_resolution.cast::<f32>())
+        .component_div(&config.platform_size.xy().map(|x| x.get::<Milimeter>()));
+    let scale = px_per_mm.push(1.0);
+
+    let mut faces = Vec::new();
+    for model in slicer.models() {
+        for face in 0..model.mesh.face_count() {
+            let [a, b, c] = (model.mesh.face_verts(face)).map(|x| x.component_div(&scale));
+            let (bottom, top) = (a.z.min(b.z).min(c.z), a.z.max(b.z).max(c.z));
+            if top == bottom {
+                continue;
+            }
+
+            let normal = (b - a).cross(&(c - a)).normalize();
+            faces.push(FaceSlope {
+                bottom: bottom + slice_height / 2.0,
+                top: top + slice_height / 2.0,
+                height: deviation / normal.z.abs(),
+            });
         }
     }
+
+    faces
 }
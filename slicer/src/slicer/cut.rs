@@ -0,0 +1,187 @@
+use std::ops::Range;
+
+use common::{container::Run, slice::SliceConfig, units::Milimeter};
+use nalgebra::Vector3;
+
+/// A shape removed from the layers when slicing, like a drain hole. Given in
+/// world space (mm), with the origin at the center of the platform.
+///
+/// Cuts remove everything inside of them, including parts of other models that
+/// they overlap. They are only applied when raster slicing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CutPrimitive {
+    Cylinder {
+        start: Vector3<f32>,
+        end: Vector3<f32>,
+        radius: f32,
+    },
+}
+
+impl CutPrimitive {
+    pub fn bounds(&self) -> (Vector3<f32>, Vector3<f32>) {
+        match *self {
+            Self::Cylinder { start, end, radius } => (
+                start.inf(&end).add_scalar(-radius),
+                start.sup(&end).add_scalar(radius),
+            ),
+        }
+    }
+
+    /// Finds the range of x positions inside of the primitive, along the line
+    /// parallel to the x axis at `y` and `z`.
+    pub fn span(&self, y: f32, z: f32) -> Option<(f32, f32)> {
+        match *self {
+            Self::Cylinder { start, end, radius } => {
+                let length = (end - start).magnitude();
+                if length == 0.0 {
+                    return None;
+                }
+
+                // Both the distance along the axis and the offset from it are
+                // linear in x, so the points within the radius are found by
+                // solving a quadratic.
+                let axis = (end - start) / length;
+                let origin = Vector3::new(0.0, y, z) - start;
+                let (along, along_x) = (origin.dot(&axis), axis.x);
+                let offset = origin - axis * along;
+                let offset_x = Vector3::x() - axis * along_x;
+
+                let a = offset_x.magnitude_squared();
+                let b = 2.0 * offset.dot(&offset_x);
+                let c = offset.magnitude_squared() - radius.powi(2);
+
+                let (mut min, mut max) = if a < f32::EPSILON {
+                    // The line runs parallel to the axis.
+                    (c <= 0.0).then_some((f32::MIN, f32::MAX))?
+                } else {
+                    let discriminant = b.powi(2) - 4.0 * a * c;
+                    let root = (discriminant >= 0.0).then(|| discriminant.sqrt())?;
+                    ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a))
+                };
+
+                // Clip to the ends of the cylinder.
+                if along_x.abs() < f32::EPSILON {
+                    if !(0.0..=length).contains(&along) {
+                        return None;
+                    }
+                } else {
+                    let (a, b) = (-along / along_x, (length - along) / along_x);
+                    (min, max) = (min.max(a.min(b)), max.min(a.max(b)));
+                }
+
+                (min < max).then_some((min, max))
+            }
+        }
+    }
+}
+
+/// Finds the pixels of a layer, whose center is at height `z` (mm), that are
+/// inside of any of the cuts. The ranges index into the whole layer and are
+/// sorted and don't overlap. Cuts are scaled with the layers by the
+/// compensation scale, around the center of the platform.
+pub fn cut_pixels(cuts: &[CutPrimitive], config: &SliceConfig, z: f32) -> Vec<Range<u64>> {
+    let resolution = config.platform_resolution.cast::<u64>();
+    let size = (config.platform_size.xy()).map(|x| x.get::<Milimeter>());
+    let scale = config.compensation.scale;
+    let pixel = size.component_div(&resolution.cast());
+
+    // Pixels are inside a cut if their center is.
+    let to_pixel = |mm: f32, axis: usize| (mm + size[axis] / 2.0) / pixel[axis] - 0.5;
+    let clamp = |x: f32, axis: usize| x.clamp(0.0, resolution[axis] as f32) as u64;
+
+    let mut ranges = Vec::new();
+    for cut in cuts {
+        let (min, max) = cut.bounds();
+        if z < min.z || z > max.z {
+            continue;
+        }
+
+        let (min_y, max_y) = (min.y * scale.y, max.y * scale.y);
+        let rows = clamp(to_pixel(min_y, 1).ceil(), 1)..clamp(to_pixel(max_y, 1).floor() + 1.0, 1);
+        for y in rows {
+            let y_mm = (y as f32 + 0.5) * pixel.y - size.y / 2.0;
+            let Some((start, end)) = cut.span(y_mm / scale.y, z) else {
+                continue;
+            };
+
+            let (start, end) = (
+                clamp(to_pixel(start * scale.x, 0).ceil(), 0),
+                clamp(to_pixel(end * scale.x, 0).floor() + 1.0, 0),
+            );
+            (start < end).then(|| ranges.push(y * resolution.x + start..y * resolution.x + end));
+        }
+    }
+
+    ranges.sort_by_key(|x| x.start);
+    let mut merged = Vec::<Range<u64>>::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if last.end >= range.start => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
+/// Sets every pixel within the ranges to zero.
+pub fn clear_pixels(runs: &[Run], ranges: &[Range<u64>]) -> Vec<Run> {
+    let mut out = Vec::<Run>::with_capacity(runs.len() + ranges.len() * 2);
+    let mut push = |length: u64, value: u8| match out.last_mut() {
+        Some(last) if last.value == value => last.length += length,
+        _ => out.push(Run::new(length, value)),
+    };
+
+    let mut ranges = ranges.iter().peekable();
+    let mut pos = 0;
+    for run in runs {
+        let end = pos + run.length;
+        while pos < end {
+            // Skip past ranges that have already been cleared.
+            while ranges.next_if(|x| x.end <= pos).is_some() {}
+
+            let (length, value) = match ranges.peek() {
+                Some(range) if range.start <= pos => (range.end.min(end) - pos, 0),
+                Some(range) => (range.start.min(end) - pos, run.value),
+                None => (end - pos, run.value),
+            };
+            push(length, value);
+            pos += length;
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{slice::SliceConfig, units::Milimeters};
+    use nalgebra::{Vector2, Vector3};
+
+    use super::{CutPrimitive, cut_pixels};
+
+    #[test]
+    fn scaled_cut() {
+        // One pixel per mm, with the origin between pixels 49 and 50.
+        let mut config = SliceConfig {
+            platform_resolution: Vector2::new(100, 100),
+            platform_size: Vector3::new(100.0, 100.0, 100.0).map(Milimeters::new),
+            ..Default::default()
+        };
+        let cut = CutPrimitive::Cylinder {
+            start: Vector3::new(20.0, 0.0, 0.0),
+            end: Vector3::new(20.0, 0.0, 10.0),
+            radius: 2.0,
+        };
+
+        let row = 50 * 100;
+        let ranges = cut_pixels(&[cut], &config, 5.0);
+        assert!(ranges.contains(&(row + 68..row + 72)));
+
+        // Halving the layers moves the cut halfway to the center, and shrinks it.
+        config.compensation.scale = Vector2::repeat(0.5);
+        let ranges = cut_pixels(&[cut], &config, 5.0);
+        assert!(ranges.contains(&(row + 59..row + 61)));
+        assert_eq!(ranges.len(), 2);
+    }
+}
@@ -1,7 +1,12 @@
-use common::{progress::Progress, slice::SliceConfig};
+use common::{
+    progress::{CancellationToken, Progress},
+    slice::SliceConfig,
+    units::Milimeter,
+};
 
-use crate::mesh::Mesh;
+use crate::{mesh::Mesh, slicer::cut::CutPrimitive};
 
+pub mod cut;
 pub mod raster;
 pub mod vector;
 
@@ -13,12 +18,20 @@ pub struct Slicer {
     models: Vec<SlicerModel>,
 
     layers: u32,
+    /// Top of each layer (mm), if they aren't all the slice height thick.
+    heights: Option<Vec<f32>>,
+    /// Heights (mm) to pause the print at.
+    pauses: Vec<f32>,
     progress: Progress,
+    cancel: CancellationToken,
 }
 
 pub struct SlicerModel {
     pub mesh: Mesh,
     pub exposure: u8,
+    /// Shapes removed from the layers, like drain holes. Unlike the mesh,
+    /// these are in world space.
+    pub cuts: Vec<CutPrimitive>,
 }
 
 impl Slicer {
@@ -42,16 +55,66 @@ impl Slicer {
             models,
 
             layers,
+            heights: None,
+            pauses: Vec::new(),
             progress,
+            cancel: CancellationToken::new(),
         }
     }
 
+    /// Gets the cuts of every model.
+    pub fn cuts(&self) -> Vec<CutPrimitive> {
+        (self.models.iter())
+            .flat_map(|x| x.cuts.iter().copied())
+            .collect()
+    }
+
+    pub fn models(&self) -> &[SlicerModel] {
+        &self.models
+    }
+
     pub fn layer_count(&self) -> u32 {
         self.layers
     }
 
+    /// Slices at the given layer tops (mm) instead of in steps of the slice
+    /// height. Only used when raster slicing, where the exposure time of each
+    /// layer is scaled by its thickness.
+    pub fn set_layer_heights(&mut self, heights: Vec<f32>) {
+        self.layers = heights.len() as u32;
+        self.progress.set_total(self.layers as u64);
+        self.heights = Some(heights);
+    }
+
+    /// Pauses the print at the layers containing the given heights (mm).
+    pub fn set_pauses(&mut self, heights: Vec<f32>) {
+        self.pauses = heights;
+    }
+
+    /// Gets the bottom and top of a layer (mm).
+    fn layer_bounds(&self, layer: u32) -> (f32, f32) {
+        let slice_height = self.slice_config.slice_height.get::<Milimeter>();
+        match &self.heights {
+            Some(heights) => (
+                (layer.checked_sub(1))
+                    .map(|x| heights[x as usize])
+                    .unwrap_or_default(),
+                heights[layer as usize],
+            ),
+            None => (
+                layer as f32 * slice_height,
+                (layer + 1) as f32 * slice_height,
+            ),
+        }
+    }
+
     /// Gets an instance of the slicing [`Progress`] struct.
     pub fn progress(&self) -> Progress {
         self.progress.clone()
     }
+
+    /// Gets a [`CancellationToken`] that stops the slicing operation early.
+    pub fn cancellation(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
 }
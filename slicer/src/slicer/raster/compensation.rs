@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use nalgebra::Vector2;
+
+use crate::slicer::raster::Segment;
+
+/// Limits how far the corners of sharp spikes move, as a multiple of the
+/// offset distance.
+const MITER_LIMIT: f32 = 4.0;
+
+/// Moves the contours of a layer outward by `offset`, and the contours of
+/// holes by `offset - hole_offset` (both mm). `px_per_mm` is the scale of the
+/// segment positions.
+///
+/// Segments are joined into contours by their shared endpoints, then oriented
+/// so the outside of the model is to their right, using the `entering` flag
+/// of each segment. Holes then wind the opposite way to outer contours. Each
+/// corner is moved along the bisector of its two edges, so every edge ends up
+/// offset by the same distance.
+pub fn offset_contours(
+    segments: &mut [Segment],
+    px_per_mm: Vector2<f32>,
+    offset: f32,
+    hole_offset: f32,
+) {
+    let points = segments
+        .iter()
+        .map(|x| x.endpoints.map(|x| x.component_div(&px_per_mm)))
+        .collect::<Vec<_>>();
+
+    let mut connections = HashMap::<[u32; 2], Vec<usize>>::new();
+    for (i, segment) in segments.iter().enumerate() {
+        for point in segment.endpoints {
+            connections.entry(key(point)).or_default().push(i);
+        }
+    }
+
+    let mut visited = vec![false; segments.len()];
+    let mut moved = segments.iter().map(|x| x.endpoints).collect::<Vec<_>>();
+
+    for start in 0..segments.len() {
+        // Start each contour from a segment whose direction is known.
+        let delta = points[start][1] - points[start][0];
+        if visited[start] || delta.y == 0.0 {
+            continue;
+        }
+
+        // The outward normal of an edge going from a to b is (Δy, -Δx).
+        let flip = (delta.y > 0.0) != segments[start].entering;
+        let contour = trace_contour(segments, &connections, &mut visited, start, flip);
+
+        // Positive area means the contour is counter clockwise, with the
+        // inside of the model on its left.
+        let vertices = (contour.iter())
+            .map(|&(i, flip)| points[i][flip as usize])
+            .collect::<Vec<_>>();
+        let area = (0..vertices.len())
+            .map(|i| vertices[i].perp(&vertices[(i + 1) % vertices.len()]))
+            .sum::<f32>();
+        let distance = if area >= 0.0 {
+            offset
+        } else {
+            offset - hole_offset
+        };
+
+        let normals = (contour.iter())
+            .map(|&(i, flip)| {
+                let [a, b] = points[i];
+                let delta = if flip { a - b } else { b - a };
+                Vector2::new(delta.y, -delta.x).try_normalize(0.0)
+            })
+            .collect::<Vec<_>>();
+
+        let closed = contour.len() > 2 && {
+            let (first, last) = (contour[0], contour[contour.len() - 1]);
+            points[first.0][first.1 as usize] == points[last.0][!last.1 as usize]
+        };
+
+        for (j, &(i, flip)) in contour.iter().enumerate() {
+            let [start, end] = [flip as usize, !flip as usize];
+            let previous =
+                (j > 0 || closed).then(|| normals[(j + contour.len() - 1) % contour.len()]);
+            let next = (j + 1 < contour.len() || closed).then(|| normals[(j + 1) % contour.len()]);
+
+            let normal = normals[j];
+            moved[i][start] +=
+                corner(previous.flatten(), normal).component_mul(&px_per_mm) * distance;
+            moved[i][end] += corner(normal, next.flatten()).component_mul(&px_per_mm) * distance;
+        }
+
+        // Keep the scanline winding right for edges that were guessed wrong.
+        for (i, normal) in contour.iter().map(|x| x.0).zip(normals) {
+            if let Some(normal) = normal.filter(|x| x.x != 0.0) {
+                segments[i].entering = normal.x > 0.0;
+            }
+        }
+    }
+
+    for (segment, endpoints) in segments.iter_mut().zip(moved) {
+        segment.endpoints = endpoints;
+    }
+}
+
+/// Follows the segments connected to `start` in both directions. Returns the
+/// index of each segment in order, and if it's reversed.
+fn trace_contour(
+    segments: &[Segment],
+    connections: &HashMap<[u32; 2], Vec<usize>>,
+    visited: &mut [bool],
+    start: usize,
+    flip: bool,
+) -> Vec<(usize, bool)> {
+    visited[start] = true;
+    let mut walk = |mut current: (usize, bool), forward: bool| {
+        let mut out = Vec::new();
+        loop {
+            let (i, flip) = current;
+            let point = segments[i].endpoints[(forward != flip) as usize];
+            let Some(&next) = (connections[&key(point)].iter()).find(|&&x| !visited[x]) else {
+                break;
+            };
+
+            // The shared point is the start of the next segment going forward
+            // and its end going backward.
+            visited[next] = true;
+            let at_end = key(segments[next].endpoints[1]) == key(point);
+            current = (next, at_end == forward);
+            out.push(current);
+        }
+        out
+    };
+
+    let forward = walk((start, flip), true);
+    let backward = walk((start, flip), false);
+
+    (backward.into_iter().rev())
+        .chain([(start, flip)])
+        .chain(forward)
+        .collect()
+}
+
+/// Finds how far a corner moves for each unit of offset, from the outward
+/// normals of the edges before and after it.
+fn corner(a: Option<Vector2<f32>>, b: Option<Vector2<f32>>) -> Vector2<f32> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            let miter = (a + b) / (1.0 + a.dot(&b)).max(f32::EPSILON);
+            miter.cap_magnitude(MITER_LIMIT)
+        }
+        (Some(x), None) | (None, Some(x)) => x,
+        (None, None) => Vector2::zeros(),
+    }
+}
+
+fn key(point: Vector2<f32>) -> [u32; 2] {
+    [point.x.to_bits(), point.y.to_bits()]
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra::Vector2;
+
+    use super::offset_contours;
+    use crate::slicer::raster::Segment;
+
+    /// Two pixels per mm, so an offset of 1mm moves edges by 2px.
+    const PX_PER_MM: Vector2<f32> = Vector2::new(2.0, 2.0);
+
+    fn segment(a: [f32; 2], b: [f32; 2], entering: bool) -> Segment {
+        Segment {
+            endpoints: [Vector2::from(a), Vector2::from(b)],
+            entering,
+            priority: 0,
+            exposure: 0,
+        }
+    }
+
+    /// The edges of a square going counter clockwise. For holes, the model is
+    /// outside of the square instead of inside.
+    fn square(min: f32, max: f32, hole: bool) -> Vec<Segment> {
+        vec![
+            segment([min, min], [max, min], false),
+            segment([max, min], [max, max], !hole),
+            segment([max, max], [min, max], false),
+            segment([min, max], [min, min], hole),
+        ]
+    }
+
+    /// Checks that each endpoint of the square from `min` to `max` moved out
+    /// by `distance` (px) along both axes.
+    fn assert_offset(segments: &[Segment], min: f32, max: f32, distance: f32) {
+        let moved = |x: f32| {
+            if x == min {
+                min - distance
+            } else {
+                max + distance
+            }
+        };
+        for (segment, original) in segments.iter().zip(square(min, max, false)) {
+            for (point, original) in segment.endpoints.iter().zip(original.endpoints) {
+                let expected = original.map(moved);
+                assert!(
+                    (point - expected).magnitude() < 1e-4,
+                    "{original:?} moved to {point:?} instead of {expected:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn square_grows() {
+        let mut segments = square(0.0, 20.0, false);
+        offset_contours(&mut segments, PX_PER_MM, 1.0, 0.0);
+        assert_offset(&segments, 0.0, 20.0, 2.0);
+        assert!(segments[1].entering && !segments[3].entering);
+    }
+
+    #[test]
+    fn square_shrinks() {
+        let mut segments = square(0.0, 20.0, false);
+        offset_contours(&mut segments, PX_PER_MM, -1.0, 0.0);
+        assert_offset(&segments, 0.0, 20.0, -2.0);
+    }
+
+    #[test]
+    fn square_with_hole() {
+        let mut segments = square(0.0, 40.0, false);
+        segments.extend(square(10.0, 30.0, true));
+
+        // The hole shrinks by the offset, then grows by the hole offset.
+        offset_contours(&mut segments, PX_PER_MM, 1.0, 1.5);
+        assert_offset(&segments[..4], 0.0, 40.0, 2.0);
+        assert_offset(&segments[4..], 10.0, 30.0, 1.0);
+        assert!(!segments[5].entering && segments[7].entering);
+    }
+
+    #[test]
+    fn open_contour() {
+        // A square missing its top edge. The loose ends move straight out
+        // from their edges, and the corners along both.
+        let mut segments = vec![
+            segment([0.0, 20.0], [0.0, 0.0], false),
+            segment([0.0, 0.0], [20.0, 0.0], false),
+            segment([20.0, 0.0], [20.0, 20.0], true),
+        ];
+        offset_contours(&mut segments, PX_PER_MM, 1.0, 0.0);
+
+        let expected = [
+            [[-2.0, 20.0], [-2.0, -2.0]],
+            [[-2.0, -2.0], [22.0, -2.0]],
+            [[22.0, -2.0], [22.0, 20.0]],
+        ];
+        for (segment, expected) in segments.iter().zip(expected) {
+            for (point, expected) in segment.endpoints.iter().zip(expected) {
+                let expected = Vector2::from(expected);
+                assert!(
+                    (point - expected).magnitude() < 1e-4,
+                    "{point:?} != {expected:?}"
+                );
+            }
+        }
+    }
+}
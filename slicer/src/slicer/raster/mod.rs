@@ -1,23 +1,32 @@
+use std::{sync::mpsc, thread};
+
+use anyhow::Result;
 use common::{
     container::{
         Run,
         rle::downsample::{downsample, downsample_adjacent},
     },
+    progress::Cancelled,
     slice::Layer,
-    units::Milimeter,
+    units::{Milimeter, Milimeters},
 };
 use itertools::Itertools;
 use nalgebra::Vector2;
-use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use crate::{
     geometry::Segments1D,
     slicer::{
         SEGMENT_LAYERS, Slicer,
-        raster::edge_table::{global_edge_table, update_active_edges},
+        cut::{clear_pixels, cut_pixels},
+        raster::{
+            compensation::offset_contours,
+            edge_table::{global_edge_table, update_active_edges},
+        },
     },
 };
 
+mod compensation;
 mod edge_table;
 
 pub struct Segment {
@@ -29,7 +38,76 @@ pub struct Segment {
 
 impl Slicer {
     /// Actually runs the slicing operation, it is multithreaded.
-    pub fn slice_raster(&self) -> Vec<Layer> {
+    pub fn slice_raster(&self) -> Result<Vec<Layer>, Cancelled> {
+        let segments = self.segments();
+        (0..self.layers)
+            .into_par_iter()
+            .map(|i| {
+                self.cancel.check()?;
+                Ok(self.slice_layer(&segments, i))
+            })
+            .inspect(|_| self.progress.add_complete(1))
+            .collect()
+    }
+
+    /// Slices the layers in batches, passing them on to `write` in order
+    /// through a bounded queue. This way only a few layers are in memory at
+    /// once, no matter how many there are. `map` is run on the slicing threads
+    /// and can be used to encode each layer.
+    pub fn slice_raster_streaming<T: Send>(
+        &self,
+        map: impl Fn(Layer) -> T + Sync,
+        mut write: impl FnMut(T) -> Result<()>,
+    ) -> Result<()> {
+        let (segments, map) = (&self.segments(), &map);
+        let batch = rayon::current_num_threads() as u32;
+        let (tx, rx) = mpsc::sync_channel(batch as usize);
+
+        thread::scope(|s| {
+            s.spawn(move || {
+                for start in (0..self.layers).step_by(batch as usize) {
+                    if self.cancel.is_cancelled() {
+                        break;
+                    }
+
+                    let end = (start + batch).min(self.layers);
+                    let layers = (start..end)
+                        .into_par_iter()
+                        .map(|i| map(self.slice_layer(segments, i)))
+                        .collect::<Vec<_>>();
+
+                    // The receiver is only dropped if writing failed.
+                    if layers.into_iter().try_for_each(|x| tx.send(x)).is_err() {
+                        break;
+                    }
+                }
+            });
+
+            for layer in rx {
+                self.cancel.check()?;
+                write(layer)?;
+                self.progress.add_complete(1);
+            }
+
+            // Slicing stops early if cancelled, leaving the output incomplete.
+            self.cancel.check()?;
+            Ok(())
+        })
+    }
+
+    /// A segment contains a reference to all of the triangles it contains. By
+    /// splitting the mesh into segments, not all triangles need to be tested
+    /// to find all intersections. This massively speeds up the slicing
+    /// operation and actually makes it faster than most other slicers. :p
+    fn segments(&self) -> Vec<Segments1D> {
+        (self.models.iter())
+            .map(|model| Segments1D::from_mesh(&model.mesh, SEGMENT_LAYERS))
+            .collect::<Vec<_>>()
+    }
+
+    /// Slices a single layer, downsampling the supersampled layers that make
+    /// it up.
+    fn slice_layer(&self, segments: &[Segments1D], i: u32) -> Layer {
         let supersample = self.slice_config.supersample;
         let remap = self.slice_config.exposure_remap.table();
 
@@ -37,55 +115,96 @@ impl Slicer {
         let platform = real_platform * supersample as u32;
         let pixels = platform.x as u64 * platform.y as u64;
 
-        // A segment contains a reference to all of the triangles it contains. By
-        // splitting the mesh into segments, not all triangles need to be tested
-        // to find all intersections. This massively speeds up the slicing
-        // operation and actually makes it faster than most other slicers. :p
-        let segments = (self.models.iter())
-            .map(|model| Segments1D::from_mesh(&model.mesh, SEGMENT_LAYERS))
-            .collect::<Vec<_>>();
+        let slice_height = self.slice_config.slice_height.get::<Milimeter>();
+        let (bottom, top) = self.layer_bounds(i);
+        let thickness = top - bottom;
 
-        (0..self.layers * supersample as u32)
-            .into_par_iter()
-            .map(|i| {
-                let height = i as f32 / supersample as f32
-                    * self.slice_config.slice_height.get::<Milimeter>();
+        // Scales around the center of the platform, before offsetting the
+        // contours.
+        let compensation = &self.slice_config.compensation;
+        let (offset, hole_offset) = (
+            compensation.offset.get::<Milimeter>(),
+            compensation.hole_offset.get::<Milimeter>(),
+        );
+        let px_per_mm = self
+            .slice_config
+            .mm_to_px(Vector2::repeat(supersample as f32));
+        let center = platform.cast::<f32>() / 2.0;
+        let unscaled = compensation.scale == Vector2::repeat(1.0);
+        let scale = |x: Vector2<f32>| {
+            if unscaled {
+                x
+            } else {
+                center + (x - center).component_mul(&compensation.scale)
+            }
+        };
+
+        let mut chunk = (0..supersample as u32)
+            .map(|j| {
+                // Models are moved down by half of the slice height, so this
+                // is centered on the layer.
+                let height = bottom
+                    + (thickness - slice_height) / 2.0
+                    + j as f32 / supersample as f32 * thickness;
 
                 // Gets all the intersections between the slice plane and the
                 // model. Because all the faces are triangles, every triangle
                 // intersection will return two points. These can then be
                 // interpreted as line segments making up a polygon.
-                let segments = self.models.iter().enumerate().flat_map(|(idx, model)| {
-                    let intersections = segments[idx].intersect_plane(&model.mesh, height);
-                    intersections.into_iter().map(|(pos, dir)| Segment {
-                        endpoints: pos.map(|x| x * supersample as f32),
-                        entering: dir,
-                        priority: model.exposure,
-                        exposure: model.exposure,
+                let mut segments = (self.models.iter().enumerate())
+                    .flat_map(|(idx, model)| {
+                        let intersections = segments[idx].intersect_plane(&model.mesh, height);
+                        intersections.into_iter().map(|(pos, dir)| Segment {
+                            endpoints: pos.map(|x| scale(x * supersample as f32)),
+                            entering: dir,
+                            priority: model.exposure,
+                            exposure: model.exposure,
+                        })
                     })
-                });
+                    .collect::<Vec<_>>();
 
-                layer(supersample, real_platform, segments)
-            })
-            .chunks(supersample as usize)
-            .enumerate()
-            .map(|(i, mut chunk)| {
-                let mut data = if supersample > 1 {
-                    downsample_to_vec(&chunk, pixels)
-                } else {
-                    chunk.pop().unwrap()
-                };
-
-                data.iter_mut()
-                    .filter(|x| x.value > 0)
-                    .for_each(|x| x.value = remap[x.value as usize]);
-
-                let exposure = self.slice_config.exposure_config(i as u32).into_owned();
-                let height = (i + 1) as f32 * self.slice_config.slice_height;
-                Layer::new(data, height, exposure)
+                if offset != 0.0 || hole_offset != 0.0 {
+                    offset_contours(&mut segments, px_per_mm, offset, hole_offset);
+                }
+
+                layer(supersample, real_platform, segments.into_iter())
             })
-            .inspect(|_| self.progress.add_complete(1))
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>();
+
+        let mut data = if supersample > 1 {
+            downsample_to_vec(&chunk, pixels)
+        } else {
+            chunk.pop().unwrap()
+        };
+
+        data.iter_mut()
+            .filter(|x| x.value > 0)
+            .for_each(|x| x.value = remap[x.value as usize]);
+
+        if let Some(uniformity) = &self.slice_config.uniformity {
+            data = uniformity.apply(&data, real_platform);
+        }
+
+        // Cuts are in world space, where the slice plane is at the middle of
+        // the layer.
+        let cuts = self.cuts();
+        if !cuts.is_empty() {
+            let z = bottom + thickness / 2.0;
+            data = clear_pixels(&data, &cut_pixels(&cuts, &self.slice_config, z));
+        }
+
+        let height = Milimeters::new(top);
+        let mut exposure = self.slice_config.layer_exposure(i, height).into_owned();
+        let scaled = self.heights.is_some() && (thickness - slice_height).abs() > 1e-6;
+        if scaled {
+            exposure.exposure_time = exposure.exposure_time * (thickness / slice_height);
+        }
+
+        let mut layer = Layer::new(data, height, exposure);
+        // Keeps the scaled exposure from being reset when the config is edited.
+        layer.unique_exposure = scaled;
+        layer.pause = (self.pauses.iter()).any(|&z| z > bottom && z <= top);
+        layer
     }
 }
 
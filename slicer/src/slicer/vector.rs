@@ -2,7 +2,7 @@ use std::{collections::HashSet, sync::Arc};
 
 use common::{
     container::{Image, Run},
-    progress::Progress,
+    progress::{Cancelled, Progress},
     serde::{DynamicSerializer, Serializer},
     slice::{SliceInfo, SlicedFile, VectorLayer},
     units::Milimeter,
@@ -26,7 +26,7 @@ pub struct SvgFile {
 }
 
 impl Slicer {
-    pub fn slice_vector(&self) -> Vec<VectorLayer> {
+    pub fn slice_vector(&self) -> Result<Vec<VectorLayer>, Cancelled> {
         let segments = (self.models.iter())
             .map(|x| Segments1D::from_mesh(&x.mesh, SEGMENT_LAYERS))
             .collect::<Vec<_>>();
@@ -35,6 +35,7 @@ impl Slicer {
             .into_par_iter()
             .inspect(|_| self.progress.add_complete(1))
             .map(|layer| {
+                self.cancel.check()?;
                 let height = layer as f32 * self.slice_config.slice_height.get::<Milimeter>();
 
                 let segments = (self.models.iter().enumerate())
@@ -43,12 +44,12 @@ impl Slicer {
                     .map(|x| x.xy())
                     .collect::<Vec<_>>();
 
-                join_segments(&segments)
+                Ok(join_segments(&segments))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, _>>()?;
 
         self.progress.set_finished();
-        layers
+        Ok(layers)
     }
 }
 
@@ -1,45 +1,33 @@
-use std::{borrow::Borrow, io::Cursor, sync::Arc};
+use std::{
+    borrow::Borrow,
+    io::{Seek, Write},
+    sync::Arc,
+};
 
-use anyhow::{Ok, Result};
+use anyhow::Result;
 
 use common::{
-    container::rle::downsample::RunFlattenExt,
+    container::{SharedBytes, rle::downsample::RunFlattenExt},
     progress::Progress,
-    serde::SliceDeserializer,
     slice::{
-        self, DynSlicedFile, EncodableLayer, Layer, SliceConfig, VectorLayer,
+        self, DynSlicedFile, EncodableLayer, Layer, SliceConfig, SliceWriter, VectorLayer,
         format::{RasterFormat, VectorFormat},
     },
 };
 use image::RgbaImage;
 
-use crate::slicer::vector::SvgFile;
+use crate::{
+    format,
+    slicer::{Slicer, vector::SvgFile},
+};
 
-pub fn export_raster<Layers, Layer>(
+pub fn export_raster<'a>(
     config: &SliceConfig,
-    layers: Layers,
+    layers: impl IntoIterator<Item = &'a slice::Layer>,
     voxels: u64,
     format: RasterFormat,
-) -> DynSlicedFile
-where
-    Layers: IntoIterator<Item = Layer>,
-    Layer: Borrow<slice::Layer>,
-{
-    match format {
-        RasterFormat::Goo => Box::new(goo_format::File::from_layers(
-            config,
-            encode_raster_layers::<goo_format::LayerEncoder, _, _>(config, layers),
-        )),
-        RasterFormat::Ctb => Box::new(ctb_format::File::from_layers(
-            config,
-            encode_raster_layers::<ctb_format::LayerEncoder, _, _>(config, layers),
-        )),
-        RasterFormat::NanoDLP => Box::new(nanodlp_format::File::from_layers(
-            config,
-            encode_raster_layers::<nanodlp_format::LayerEncoder, _, _>(config, layers),
-            voxels,
-        )),
-    }
+) -> DynSlicedFile {
+    (format::handler(format).export)(config, &mut layers.into_iter(), voxels)
 }
 
 pub fn export_vector(
@@ -52,6 +40,39 @@ pub fn export_vector(
     }
 }
 
+/// Slices straight to a file in `stream`, writing each layer as soon as it has
+/// been sliced and encoded instead of keeping them all in memory.
+pub fn stream_raster<T: Write + Seek>(
+    slicer: &Slicer,
+    mut stream: T,
+    preview: &RgbaImage,
+    format: RasterFormat,
+) -> Result<()> {
+    (format::handler(format).stream)(slicer, &mut stream, preview)
+}
+
+pub(crate) fn stream_layers<Writer: SliceWriter>(
+    slicer: &Slicer,
+    mut writer: Writer,
+) -> Result<()> {
+    let config = &slicer.slice_config;
+    let mut voxels = 0;
+
+    slicer.slice_raster_streaming(
+        |layer| {
+            (
+                layer.area(),
+                encode_layer::<Writer::Encoder>(config, &layer),
+            )
+        },
+        |(area, layer)| {
+            voxels += area;
+            writer.write_layer(layer)
+        },
+    )?;
+    writer.finish(voxels)
+}
+
 pub fn encode_raster_layers<Encoder, Layers, Layer>(
     config: &SliceConfig,
     layers: Layers,
@@ -61,84 +82,48 @@ where
     Layers: IntoIterator<Item = Layer>,
     Layer: Borrow<slice::Layer>,
 {
-    layers
-        .into_iter()
-        .map(|layer| {
-            let layer = layer.borrow();
-            let mut encoder = Encoder::new(config.platform_resolution);
-
-            // The runs need to be 'flattened' (adjacent runs with the same
-            // value combined) because due to the way anti-aliasing is
-            // implemented no runs (excluding the first and last run) will
-            // continue for multiple scan lines.
-            //
-            // This mainly affects the fully black (value = 0) runs.
-            (layer.data.iter().copied())
-                .run_flatten()
-                .for_each(|run| encoder.add_run(run.length, run.value));
-            encoder.finish(config, &layer.exposure, layer.height)
-        })
+    (layers.into_iter())
+        .map(|layer| encode_layer::<Encoder>(config, layer.borrow()))
         .collect()
 }
 
-// todo: make some kinda generic decoder maybe
+fn encode_layer<Encoder: EncodableLayer>(config: &SliceConfig, layer: &Layer) -> Encoder::Output {
+    let runs = layer.data.runs();
+    let mut encoder = Encoder::new(config.platform_resolution);
+
+    // The runs need to be 'flattened' (adjacent runs with the same
+    // value combined) because due to the way anti-aliasing is
+    // implemented no runs (excluding the first and last run) will
+    // continue for multiple scan lines.
+    //
+    // This mainly affects the fully black (value = 0) runs.
+    (runs.iter().copied())
+        .run_flatten()
+        .for_each(|run| encoder.add_run(run.length, run.value));
+
+    let mut out = encoder.finish(config, &layer.exposure, layer.height);
+    if layer.pause {
+        Encoder::pause(&mut out, config);
+    }
+    out
+}
+
+/// Loads a sliced file, leaving the layers encoded in `data` so only the layers
+/// being used need to be decoded.
 pub fn load_sliced(
     progress: &Progress,
     format: &RasterFormat,
-    data: &[u8],
+    data: &SharedBytes,
 ) -> Result<(SliceConfig, Vec<Layer>, Vec<RgbaImage>)> {
-    match format {
-        RasterFormat::Goo => {
-            let mut des = SliceDeserializer::new(data);
-            let file = goo_format::File::deserialize(&mut des)?;
-            progress.set_total(file.layers.len() as _);
+    let file = (format::handler(*format).decode)(data)?;
+    let layers = file.info().layers as usize;
+    progress.set_total(layers as _);
 
-            let config = file.header.into_slice_config();
-            let layers = (file.layers.iter())
-                .map(|x| {
-                    progress.add_complete(1);
-                    x.into_layer()
-                })
-                .collect();
-            let images = vec![
-                file.header.big_preview.into_image(),
-                file.header.big_preview.into_image(),
-            ];
-
-            Ok((config, layers, images))
-        }
-        RasterFormat::Ctb => {
-            let mut des = SliceDeserializer::new(data);
-            let file = ctb_format::File::deserialize(&mut des)?;
-            progress.set_total(file.layers.len() as _);
-
-            let config = file.into_slice_config();
-            let layers = (file.layers.iter())
-                .map(|x| {
-                    progress.add_complete(1);
-                    x.into_layer()
-                })
-                .collect();
-            let images = [file.large_preview, file.small_preview]
-                .map(|x| x.into_image())
-                .to_vec();
-
-            Ok((config, layers, images))
-        }
-        RasterFormat::NanoDLP => {
-            let file = nanodlp_format::File::deserialize(Cursor::new(data))?;
-            progress.set_total(file.layers.len() as _);
-
-            let config = file.into_slice_config();
-            // todo: optimize since this calls the image crate to load each png
-            // image then converts to runs...
-            let layers = file
-                .into_layers()
-                .inspect(|_| progress.add_complete(1))
-                .collect();
-            let image = file.preview.into_rgba8();
-
-            Ok((config, layers, vec![image]))
-        }
-    }
+    let layers = (0..layers)
+        .map(|layer| {
+            progress.add_complete(1);
+            Layer::encoded(&file, layer)
+        })
+        .collect();
+    Ok((file.slice_config(), layers, file.previews()))
 }
@@ -12,7 +12,7 @@ use std::{
     thread,
 };
 
-use common::progress::Progress;
+use common::progress::{CancellationToken, Cancelled, Progress};
 use nalgebra::Vector2;
 use parking_lot::Mutex;
 use rand::{RngExt, rng, rngs::ThreadRng};
@@ -52,13 +52,13 @@ pub struct Running {
     pub rx: Receiver<Vec<Placement>>,
     pub history: Arc<Mutex<Vec<(u64, f32)>>>,
     pub iteration: Arc<AtomicU64>,
-    stop: mpsc::SyncSender<()>,
+    cancel: CancellationToken,
 }
 
 impl AutoLayoutAnnealing {
     pub fn run(&mut self, mut cache: LayoutCache) {
         let (tx, rx) = mpsc::sync_channel(16);
-        let (stop_tx, stop_rx) = mpsc::sync_channel(1);
+        let cancel = CancellationToken::new();
 
         let history = Arc::new(Mutex::new(Vec::new()));
         let iteration = Arc::new(AtomicU64::new(0));
@@ -66,7 +66,7 @@ impl AutoLayoutAnnealing {
             rx,
             history: history.clone(),
             iteration: iteration.clone(),
-            stop: stop_tx,
+            cancel: cancel.clone(),
         });
 
         let mut models = self.models.clone();
@@ -77,19 +77,20 @@ impl AutoLayoutAnnealing {
                 .objective(config.objective)
                 .segment_steps(config.segment_steps)
                 .bounds_penalty(config.bounds_penalty)
+                .cancellation(cancel.clone())
                 .layout(Progress::new())
         };
 
-        thread::spawn(move || {
+        thread::spawn(move || -> Result<_, Cancelled> {
             let mut temperature = config.start_temp;
-            let (mut best_score, mut best) = score(models.to_vec());
+            let (mut best_score, mut best) = score(models.to_vec())?;
             let mut global_best = f32::MAX;
 
             let mut i = 0;
-            while temperature > config.end_temp && stop_rx.try_recv().is_err() {
+            while temperature > config.end_temp {
                 for _ in 0..config.iters {
                     let iter_models = perturb(config.rotation, &models);
-                    let (iter_score, result) = score(iter_models.clone());
+                    let (iter_score, result) = score(iter_models.clone())?;
 
                     let delta = iter_score - best_score;
 
@@ -111,13 +112,13 @@ impl AutoLayoutAnnealing {
                 temperature *= config.cooling;
             }
 
-            (best_score, best)
+            Ok((best_score, best))
         });
     }
 
     pub fn stop(&mut self) {
         if let Some(running) = mem::take(&mut self.running) {
-            let _ = running.stop.send(());
+            running.cancel.cancel();
         }
     }
 }
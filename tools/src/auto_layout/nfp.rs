@@ -1,6 +1,6 @@
 use std::iter;
 
-use common::progress::Progress;
+use common::progress::{CancellationToken, Cancelled, Progress};
 use itertools::Itertools;
 use nalgebra::Vector2;
 use tracing::warn;
@@ -21,6 +21,7 @@ pub struct AutoLayoutNfp<'a> {
     platform_size: Vector2<f32>,
     cache: &'a mut LayoutCache,
     models: Vec<Model>,
+    cancel: CancellationToken,
 }
 
 impl<'a> AutoLayoutNfp<'a> {
@@ -38,6 +39,7 @@ impl<'a> AutoLayoutNfp<'a> {
             platform_size,
             models,
             cache,
+            cancel: CancellationToken::new(),
         }
     }
 
@@ -59,12 +61,16 @@ impl<'a> AutoLayoutNfp<'a> {
         Self { objective, ..self }
     }
 
+    pub fn cancellation(self, cancel: CancellationToken) -> Self {
+        Self { cancel, ..self }
+    }
+
     fn eval(&self, bounds: Bounds2D) -> f32 {
         self.objective
             .eval(self.platform_size, self.bounds_penalty, bounds)
     }
 
-    pub fn layout(mut self, progress: Progress) -> (f32, Vec<Placement>) {
+    pub fn layout(mut self, progress: Progress) -> Result<(f32, Vec<Placement>), Cancelled> {
         progress.set_total(self.models.len() as _);
 
         let first = &self.models[0];
@@ -72,6 +78,7 @@ impl<'a> AutoLayoutNfp<'a> {
         let mut bounds = first_hull.bounds.offset(first.position);
 
         for i in 1..self.models.len() {
+            self.cancel.check()?;
             progress.add_complete(1);
             let this_entry = self.models[i].entry();
             let this_hull = self.cache.hull(&this_entry);
@@ -123,7 +130,7 @@ impl<'a> AutoLayoutNfp<'a> {
             })
             .collect();
 
-        (self.eval(bounds), models)
+        Ok((self.eval(bounds), models))
     }
 }
 
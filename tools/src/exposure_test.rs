@@ -13,6 +13,9 @@ pub struct ExposureTest {
     pub size: Vector3<f32>,
     pub supports: Supports,
     pub steps: u32,
+    /// Number of copies across and down the platform, each centered in its
+    /// own cell. Comparing them shows how evenly the display exposes.
+    pub grid: Vector2<u32>,
 }
 
 #[derive(Clone)]
@@ -35,24 +38,32 @@ impl ExposureTest {
         };
         progress.set_total(support_layers + self.steps as u64);
 
-        let size = config.mm_to_px(self.size.xy()).map(|x| x.round() as u32);
-        let min = ((config.platform_resolution - size.xy()) / 2).cast();
-        let max = min + size.xy().cast();
+        // Copies are shrunk to fit their cells, so small cells don't make them
+        // overlap or run off the platform.
+        let cell = self.cell(config);
+        let size = (config.mm_to_px(self.size.xy()))
+            .map(|x| x.round() as u32)
+            .inf(&cell);
+        let copies = self.copies(cell, size);
 
         let mut top = Image::blank(config.platform_resolution.cast());
         let mut body = Image::blank(config.platform_resolution.cast());
-        body.rect((min, max), 255);
+        for &(min, max) in &copies {
+            body.rect((min, max), 255);
+        }
         let raft = body.clone();
 
         let strip_width = (size.x / self.steps) as usize;
         for (i, value) in self.steps() {
-            let a = Vector2::new(min.x + strip_width * i, min.y);
-            let b = Vector2::new(a.x + strip_width, max.y);
-            top.rect((a, b), value);
-
-            let a = Vector2::new(min.x + strip_width * i, max.y - 1);
-            let b = Vector2::new(a.x + strip_width, a.y + 1);
-            body.rect((a, b), value);
+            for &(min, max) in &copies {
+                let a = Vector2::new(min.x + strip_width * i, min.y);
+                let b = Vector2::new(a.x + strip_width, max.y);
+                top.rect((a, b), value);
+
+                let a = Vector2::new(min.x + strip_width * i, max.y - 1);
+                let b = Vector2::new(a.x + strip_width, a.y + 1);
+                body.rect((a, b), value);
+            }
             progress.add_complete(1);
         }
 
@@ -76,12 +87,14 @@ impl ExposureTest {
                     .map(|x| x.round() as usize);
                 let r = (config.mm_to_px(Vector2::repeat(r))).map(|x| x.round() as u32);
 
-                let n = (max - min).component_div(&step);
-                let offset = ((max - min) - n.component_mul(&step)) / 2;
-                for y in 0..=n.y {
-                    for x in 0..=n.x {
-                        let pos = min + offset + Vector2::new(x, y).component_mul(&step);
-                        image.circle(pos, r, 255);
+                for &(min, max) in &copies {
+                    let n = (max - min).component_div(&step);
+                    let offset = ((max - min) - n.component_mul(&step)) / 2;
+                    for y in 0..=n.y {
+                        for x in 0..=n.x {
+                            let pos = min + offset + Vector2::new(x, y).component_mul(&step);
+                            image.circle(pos, r, 255);
+                        }
                     }
                 }
 
@@ -101,6 +114,30 @@ impl ExposureTest {
             .collect()
     }
 
+    /// Size (px) of each cell of the grid spanning the platform.
+    fn cell(&self, config: &SliceConfig) -> Vector2<u32> {
+        let grid = self.grid.map(|x| x.max(1));
+        config.platform_resolution.component_div(&grid)
+    }
+
+    /// Gets the bounds (px) of each copy of the test, centered in the cells
+    /// of the grid.
+    fn copies(
+        &self,
+        cell: Vector2<u32>,
+        size: Vector2<u32>,
+    ) -> Vec<(Vector2<usize>, Vector2<usize>)> {
+        let grid = self.grid.map(|x| x.max(1));
+        (0..grid.y)
+            .flat_map(|y| (0..grid.x).map(move |x| Vector2::new(x, y)))
+            .map(|pos| {
+                let center = pos.component_mul(&cell) + cell / 2;
+                let min = center.zip_map(&size, |c, s| c.saturating_sub(s / 2));
+                (min.cast(), (min + size).cast())
+            })
+            .collect()
+    }
+
     fn steps(&self) -> impl Iterator<Item = (usize, u8)> {
         (0..self.steps as usize).map(|i| {
             let t = i as f32 / (self.steps - 1) as f32;
@@ -120,6 +157,7 @@ impl Default for ExposureTest {
                 spacing: 2.0,
             },
             steps: 15,
+            grid: Vector2::repeat(1),
         }
     }
 }
@@ -174,7 +174,7 @@ fn pad_layer(
 }
 
 fn decode(res: Vector2<u32>, factor: u8, layer: &Layer) -> Vec<u8> {
-    let (data, size) = pad_layer(&layer.data, res, factor, 10);
+    let (data, size) = pad_layer(&layer.data.runs(), res, factor, 10);
 
     let mut out = Vec::new();
     downsample_adjacent(factor, &data, &mut out);
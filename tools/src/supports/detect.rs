@@ -3,13 +3,16 @@ use std::collections::HashSet;
 use common::container::ArrayCluster;
 use nalgebra::Vector3;
 use slicer::{
-    geometry::{Ray, primitive, triangle::triangle_intersection},
+    geometry::{Ray, bvh::Bvh, primitive, triangle::triangle_intersection},
     half_edge::HalfEdgeMesh,
     mesh::Mesh,
 };
 
 use crate::supports::{SupportGenerator, SupportPlacement, quazirandom::quazirandom_rect_2d};
 
+/// How far below an island's slice plane its bottom surface is looked for.
+const ISLAND_DEPTH: f32 = 1.0;
+
 impl<'a> SupportGenerator<'a> {
     pub fn overhanging_faces(&self, mesh: &Mesh) -> Vec<(usize, Vector3<f32>)> {
         let max_angle = self.config.max_angle.to_radians();
@@ -193,4 +196,27 @@ impl<'a> SupportGenerator<'a> {
 
         overhangs
     }
+
+    /// Finds the bottom surface of each island, by looking down from the
+    /// point inside of it. Islands that aren't part of this mesh are skipped.
+    /// An island is often found again on the layers above it, so islands that
+    /// end up at the same point are only supported once.
+    pub fn place_island_supports(&self, mesh: &Mesh, bvh: &Bvh) -> Vec<SupportPlacement> {
+        let mut out = Vec::<SupportPlacement>::new();
+        for &island in self.islands.iter() {
+            let bottom = island - Vector3::z() * ISLAND_DEPTH;
+            let Some(hit) = bvh.intersect_segment(mesh, island, bottom) else {
+                continue;
+            };
+
+            let point = mesh.transform(&hit.position);
+            let radius = self.config.support_radius;
+            if out.iter().all(|x| (x.point - point).magnitude() > radius) {
+                let normal = mesh.transform_normal(&mesh.normal(hit.face));
+                out.push(SupportPlacement { point, normal });
+            }
+        }
+
+        out
+    }
 }
@@ -1,15 +1,28 @@
-use common::{geometry::convex_hull, units::Milimeters};
-use nalgebra::Vector2;
+use common::{
+    progress::{CancellationToken, Cancelled, Progress},
+    serde::{Deserializer, Serializer},
+    units::Milimeters,
+};
 use nalgebra::Vector3;
-use slicer::{builder::MeshBuilder, geometry::bvh::Bvh, half_edge::HalfEdgeMesh, mesh::Mesh};
-use tracing::info;
+use ordered_float::OrderedFloat;
+use slicer::{geometry::bvh::Bvh, half_edge::HalfEdgeMesh, mesh::Mesh};
+use tracing::{info, warn};
 
 pub mod detect;
 pub mod quazirandom;
+mod raft;
+mod route;
+mod tree;
+pub use raft::{MIN_RAFT_SPACING, RaftConfig, RaftStyle, build_raft_mesh};
+pub use route::SupportRouter;
+pub use tree::{SupportNode, SupportTree};
 
 pub struct SupportGenerator<'a> {
     config: &'a SupportConfig,
     bed_size: Vector3<Milimeters>,
+    progress: Progress,
+    cancel: CancellationToken,
+    islands: Vec<Vector3<f32>>,
 }
 
 pub struct Support {
@@ -18,6 +31,7 @@ pub struct Support {
     pub radius: f32,
 }
 
+#[derive(Clone)]
 pub struct SupportConfig {
     /// Support generation
     pub support_radius: f32,
@@ -27,13 +41,31 @@ pub struct SupportConfig {
 
     pub min_spacing: f32,
 
-    pub raft_height: f32,
-    pub raft_offset: f32,
+    pub raft: RaftConfig,
 
     pub max_angle: f32,
     pub face_support_spacing: f32,
     pub edge_support_spacing: f32,
     pub edge_angle_delta: f32,
+
+    /// Support routing
+    pub branch_angle: f32,
+    pub merge_distance: f32,
+    pub max_trunk_radius: f32,
+
+    /// Load scaling
+    pub load_area: f32,
+
+    /// Cross bracing
+    pub brace_height: f32,
+    pub brace_distance: f32,
+    pub brace_radius: f32,
+}
+
+pub struct GeneratedSupports {
+    pub tree: SupportTree,
+    /// Overhang points that no support could be routed to.
+    pub unsupported: Vec<Vector3<f32>>,
 }
 
 pub struct SupportPlacement {
@@ -43,7 +75,28 @@ pub struct SupportPlacement {
 
 impl<'a> SupportGenerator<'a> {
     pub fn new(config: &'a SupportConfig, bed_size: Vector3<Milimeters>) -> Self {
-        Self { config, bed_size }
+        Self {
+            config,
+            bed_size,
+            progress: Progress::new(),
+            cancel: CancellationToken::new(),
+            islands: Vec::new(),
+        }
+    }
+
+    /// Tracks how many of the placed supports have been routed.
+    pub fn progress(self, progress: Progress) -> Self {
+        Self { progress, ..self }
+    }
+
+    pub fn cancellation(self, cancel: CancellationToken) -> Self {
+        Self { cancel, ..self }
+    }
+
+    /// Also adds supports to islands found in a sliced layers. Each island is
+    /// given as a point inside of it, on the plane its layer was sliced at.
+    pub fn islands(self, islands: Vec<Vector3<f32>>) -> Self {
+        Self { islands, ..self }
     }
 
     pub fn generate_supports(
@@ -51,108 +104,194 @@ impl<'a> SupportGenerator<'a> {
         mesh: &Mesh,
         half_edge: &HalfEdgeMesh,
         bvh: &Bvh,
-    ) -> Vec<[Vector3<f32>; 3]> {
+    ) -> Result<GeneratedSupports, Cancelled> {
         let mut overhangs = Vec::new();
         let min_dist = self.config.min_spacing;
 
         let overhanging_faces = self.overhanging_faces(mesh);
         let mut faces = self.place_face_supports(mesh, &overhanging_faces);
+        self.cancel.check()?;
         let mut edges = self.place_edge_supports(mesh, half_edge, &overhanging_faces);
+        self.cancel.check()?;
         for overhang in edges.iter() {
             // i know its n²... shut up.
             faces.retain(|x| (x.point - overhang.point).magnitude() > min_dist);
         }
 
         let points = self.place_point_supports(mesh, half_edge);
+        self.cancel.check()?;
         for overhang in points.iter() {
             faces.retain(|x| (x.point - overhang.point).magnitude() > min_dist);
             edges.retain(|x| (x.point - overhang.point).magnitude() > min_dist);
         }
 
+        let islands = self.place_island_supports(mesh, bvh);
         info!(
-            "Placed {} supports. {{ point: {}, face: {}, edge: {} }}",
-            points.len() + faces.len() + edges.len(),
+            "Placed {} supports. {{ point: {}, face: {}, edge: {}, island: {} }}",
+            points.len() + faces.len() + edges.len() + islands.len(),
             points.len(),
             faces.len(),
-            edges.len()
+            edges.len(),
+            islands.len()
         );
-        overhangs.extend([points, faces, edges].into_iter().flatten());
-
-        overhangs
-            .into_iter()
-            .filter_map(|x| {
-                let tip_start = x.point + x.normal * self.config.tip_length;
-                let mid = route_support(mesh, bvh, tip_start);
-                mid.map(|mid| [x.point, tip_start, mid])
-            })
-            .collect()
-    }
+        overhangs.extend([points, faces, edges, islands].into_iter().flatten());
 
-    // let mut builder = MeshBuilder::new();
-    // let raft_points = self.build_support_mesh(mesh, bvh, &overhangs, &mut builder);
-    // self.build_raft_mesh(&raft_points, &mut builder);
-}
+        // Points resting on the bed don't need supports. The rest are routed
+        // from the bottom up, so higher supports can branch off of the trunks
+        // of lower ones.
+        overhangs.retain(|x| x.point.z > self.config.tip_radius);
+        overhangs.sort_by(|a, b| a.point.z.total_cmp(&b.point.z));
+        self.progress.set_total(overhangs.len() as u64);
+
+        let loads = self.support_loads(mesh, &overhanging_faces, &overhangs);
+        self.cancel.check()?;
+
+        let router = SupportRouter::new(self.config, mesh, bvh);
+        let (mut tree, mut unsupported) = (SupportTree::default(), Vec::new());
+        for (overhang, load) in overhangs.iter().zip(loads) {
+            self.cancel.check()?;
+            let radius = self.support_radius(load);
+            if !router.route_into(&mut tree, overhang.point, overhang.normal, radius) {
+                unsupported.push(overhang.point);
+            }
+            self.progress.add_complete(1);
+        }
 
-/// Returns the middle of the three points defining a support. The final point
-/// (that touches the build plate) is just this returned point projected down.
-pub fn route_support(mesh: &Mesh, bvh: &Bvh, position: Vector3<f32>) -> Option<Vector3<f32>> {
-    let mut point = position;
-    let mut momentum = Vector3::zeros();
-    let beta = 0.9;
+        router.brace(&mut tree);
 
-    for _ in 0..100 {
-        let closest = bvh.closest(mesh, point).unwrap();
-        let grad = point - mesh.transform(&closest.position);
+        if !unsupported.is_empty() {
+            warn!("Failed to route {} supports", unsupported.len());
+        }
+
+        self.progress.set_finished();
+        Ok(GeneratedSupports { tree, unsupported })
+    }
 
-        momentum = beta * momentum + (1.0 - beta) * grad;
-        point += momentum.xy().push(momentum.z.min(0.0)).normalize() * closest.t.min(1.0);
+    /// Splits the area of the overhanging faces between the supports, giving
+    /// each face to the closest one.
+    fn support_loads(
+        &self,
+        mesh: &Mesh,
+        faces: &[(usize, Vector3<f32>)],
+        supports: &[SupportPlacement],
+    ) -> Vec<f32> {
+        let mut loads = vec![0.0; supports.len()];
+        for (face, _normal) in faces {
+            let [a, b, c] = mesh
+                .face(*face)
+                .map(|x| mesh.transform(&mesh.vertices()[x as usize]));
+            let (area, center) = ((b - a).cross(&(c - a)).magnitude() / 2.0, (a + b + c) / 3.0);
 
-        if bvh.intersect_ray(mesh, point, -Vector3::z()).is_none() {
-            return Some(point);
+            let closest = (supports.iter().enumerate())
+                .min_by_key(|(_, x)| OrderedFloat((x.point - center).magnitude_squared()));
+            if let Some((i, _)) = closest {
+                loads[i] += area;
+            }
         }
+
+        loads
+    }
+
+    /// Scales the support radius so its cross section grows with the
+    /// overhang area it holds up.
+    fn support_radius(&self, load: f32) -> f32 {
+        let scale = (load / self.config.load_area).max(1.0).sqrt();
+        (self.config.support_radius * scale).min(self.config.max_trunk_radius)
     }
 
-    None
+    // let mut builder = MeshBuilder::new();
+    // let raft_points = self.build_support_mesh(mesh, bvh, &overhangs, &mut builder);
+    // self.build_raft_mesh(&raft_points, &mut builder);
 }
 
-// todo: the config values are stored in support generator struct but not used
-// through it...
-pub fn build_raft_mesh(
-    raft_offset: f32,
-    raft_height: f32,
-    points: &[Vector2<f32>],
-    builder: &mut MeshBuilder,
-) {
-    let hull = convex_hull(points);
-    let idx = builder.next_idx();
-    for i in 0..hull.len() {
-        let point = hull[i];
-        let next = hull[(i + 1) % hull.len()];
-        let prev = hull[(i + hull.len() - 1) % hull.len()];
-
-        let edge_1 = next - point;
-        let edge_2 = point - prev;
-        let offset = (Vector2::new(edge_1.y, -edge_1.x).normalize()
-            + Vector2::new(edge_2.y, -edge_2.x).normalize())
-        .normalize();
-
-        builder.add_vertex(point.push(0.0));
-        builder.add_vertex((point + offset * raft_offset).push(raft_height));
+impl SupportConfig {
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        ser.write_f32_be(self.support_radius);
+        ser.write_f32_be(self.tip_radius);
+        ser.write_f32_be(self.tip_length);
+        ser.write_u32_be(self.precision);
+        ser.write_f32_be(self.min_spacing);
+        ser.write_f32_be(self.raft.height);
+        ser.write_f32_be(self.raft.offset);
+        ser.write_f32_be(self.max_angle);
+        ser.write_f32_be(self.face_support_spacing);
+        ser.write_f32_be(self.edge_support_spacing);
+        ser.write_f32_be(self.edge_angle_delta);
+        ser.write_f32_be(self.branch_angle);
+        ser.write_f32_be(self.merge_distance);
+        ser.write_f32_be(self.max_trunk_radius);
+        ser.write_f32_be(self.load_area);
+        ser.write_f32_be(self.brace_height);
+        ser.write_f32_be(self.brace_distance);
+        ser.write_f32_be(self.brace_radius);
+        ser.write_u8(self.raft.style as u8);
+        ser.write_f32_be(self.raft.taper);
+        ser.write_f32_be(self.raft.spacing);
+        ser.write_f32_be(self.raft.line_width);
     }
 
-    let verts = builder.next_idx() - idx;
-    for i in (0..verts).step_by(2) {
-        if i != 0 && i + 3 < verts {
-            builder.add_face([idx, idx + i + 2, idx + i]);
-            builder.add_face([idx + 1, idx + i + 1, idx + i + 3]);
+    pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
+        let default = Self::default();
+        let mut config = Self {
+            support_radius: des.read_f32_be(),
+            tip_radius: des.read_f32_be(),
+            tip_length: des.read_f32_be(),
+            precision: des.read_u32_be(),
+            min_spacing: des.read_f32_be(),
+            raft: RaftConfig {
+                height: des.read_f32_be(),
+                offset: des.read_f32_be(),
+                ..RaftConfig::default()
+            },
+            max_angle: des.read_f32_be(),
+            face_support_spacing: des.read_f32_be(),
+            edge_support_spacing: des.read_f32_be(),
+            edge_angle_delta: des.read_f32_be(),
+            branch_angle: if version < 14 {
+                default.branch_angle
+            } else {
+                des.read_f32_be()
+            },
+            merge_distance: if version < 14 {
+                default.merge_distance
+            } else {
+                des.read_f32_be()
+            },
+            max_trunk_radius: if version < 14 {
+                default.max_trunk_radius
+            } else {
+                des.read_f32_be()
+            },
+            load_area: if version < 15 {
+                default.load_area
+            } else {
+                des.read_f32_be()
+            },
+            brace_height: if version < 15 {
+                default.brace_height
+            } else {
+                des.read_f32_be()
+            },
+            brace_distance: if version < 15 {
+                default.brace_distance
+            } else {
+                des.read_f32_be()
+            },
+            brace_radius: if version < 15 {
+                default.brace_radius
+            } else {
+                des.read_f32_be()
+            },
+        };
+
+        if version >= 16 {
+            config.raft.style = RaftStyle::from_u8(des.read_u8());
+            config.raft.taper = des.read_f32_be();
+            config.raft.spacing = des.read_f32_be();
+            config.raft.line_width = des.read_f32_be();
         }
 
-        builder.add_quad([
-            idx + i % verts,
-            idx + (i + 1) % verts,
-            idx + (i + 2) % verts,
-            idx + (i + 3) % verts,
-        ]);
+        config
     }
 }
 
@@ -162,14 +301,20 @@ impl Default for SupportConfig {
             support_radius: 1.0,
             tip_radius: 0.2,
             tip_length: 3.0,
-            raft_height: 1.0,
-            raft_offset: 1.0,
+            raft: RaftConfig::default(),
             min_spacing: 5.0,
             precision: 10,
             max_angle: 30.0,
             face_support_spacing: 50.0,
             edge_angle_delta: 0.1,
             edge_support_spacing: 20.0,
+            branch_angle: 30.0,
+            merge_distance: 8.0,
+            max_trunk_radius: 2.0,
+            load_area: 25.0,
+            brace_height: 10.0,
+            brace_distance: 10.0,
+            brace_radius: 0.5,
         }
     }
 }
@@ -0,0 +1,339 @@
+use std::f32::consts::{PI, TAU};
+
+use common::{container::ArrayCluster, geometry::convex_hull};
+use nalgebra::Vector2;
+use slicer::builder::MeshBuilder;
+
+/// Number of points used to approximate the footprint of each support.
+const FOOTPRINT_POINTS: u32 = 40;
+/// Smallest spacing and line width (mm) of the raft, as anything finer would
+/// take forever to build and just come out as a solid slab.
+pub const MIN_RAFT_SPACING: f32 = 0.1;
+
+#[derive(Clone, PartialEq)]
+pub struct RaftConfig {
+    pub style: RaftStyle,
+    pub height: f32,
+    pub offset: f32,
+    /// How much further the bottom of the raft sticks out than the top, making
+    /// a slope that is easier to get a scraper under.
+    pub taper: f32,
+    /// Distance between channels or lattice lines, and the largest gap
+    /// between supports that still get merged into the same pad.
+    pub spacing: f32,
+    /// Width of the drainage channels or lattice lines.
+    pub line_width: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum RaftStyle {
+    /// One solid slab under all the supports.
+    #[default]
+    Solid,
+    /// A slab with drainage channels cut into its bottom half.
+    Crenelated,
+    /// A grid of lines, with a pad under each support.
+    Lattice,
+    /// A separate slab under each cluster of supports.
+    Pads,
+}
+
+/// A convex polygon the raft is cut down to, given as a list of half-planes.
+/// Each plane is a normal and the largest allowed dot product with it.
+type Clip = [(Vector2<f32>, f32)];
+
+impl RaftConfig {
+    /// Smallest allowed spacing. Channels and lattice lines closer than their
+    /// width would just overlap.
+    pub fn min_spacing(&self) -> f32 {
+        match self.style {
+            RaftStyle::Crenelated | RaftStyle::Lattice => self.line_width.max(MIN_RAFT_SPACING),
+            RaftStyle::Solid | RaftStyle::Pads => MIN_RAFT_SPACING,
+        }
+    }
+}
+
+impl RaftStyle {
+    pub const ALL: [Self; 4] = [Self::Solid, Self::Crenelated, Self::Lattice, Self::Pads];
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Solid => "Solid",
+            Self::Crenelated => "Crenelated",
+            Self::Lattice => "Lattice",
+            Self::Pads => "Pads",
+        }
+    }
+
+    pub(super) fn from_u8(value: u8) -> Self {
+        Self::ALL.get(value as usize).copied().unwrap_or_default()
+    }
+}
+
+/// Builds the raft under supports, given the position and radius of where
+/// each one rests on the bed.
+pub fn build_raft_mesh(
+    config: &RaftConfig,
+    roots: &[(Vector2<f32>, f32)],
+    builder: &mut MeshBuilder,
+) {
+    // Channels and lattice lines are placed by stepping by the spacing, which
+    // would take forever if it's tiny.
+    let spacing = config.spacing.max(config.min_spacing());
+    let line_width = config.line_width.max(MIN_RAFT_SPACING);
+    if roots.is_empty() {
+        return;
+    }
+
+    let hull = convex_hull(&footprint(roots));
+    let (height, offset, taper) = (config.height, config.offset, config.taper);
+    match config.style {
+        RaftStyle::Solid => add_slab(builder, &hull, (0.0, height), (offset + taper, offset), &[]),
+        RaftStyle::Crenelated => {
+            // Channels are cut across the bottom half of the slab, along the y
+            // axis, so resin can drain out from under it.
+            let middle = (offset + taper / 2.0, height / 2.0);
+            add_slab(builder, &hull, (middle.1, height), (middle.0, offset), &[]);
+
+            let (min, max) = x_range(&hull, offset + taper);
+            for (start, end) in strips(min, max, spacing, line_width) {
+                let clip = [(-Vector2::x(), -start), (Vector2::x(), end)];
+                add_slab(
+                    builder,
+                    &hull,
+                    (0.0, middle.1),
+                    (offset + taper, middle.0),
+                    &clip,
+                );
+            }
+        }
+        RaftStyle::Lattice => {
+            for &(center, radius) in roots {
+                let pad = convex_hull(&footprint(&[(center, radius)]));
+                add_slab(builder, &pad, (0.0, height), (offset + taper, offset), &[]);
+            }
+
+            let half_width = line_width / 2.0;
+            for axis in [Vector2::x(), Vector2::y()] {
+                let range = |x: Vector2<f32>| x.dot(&axis);
+                let min = hull.iter().map(|&x| range(x)).fold(f32::MAX, f32::min);
+                let max = hull.iter().map(|&x| range(x)).fold(f32::MIN, f32::max);
+
+                let mut line = (min / spacing).ceil() * spacing;
+                while line <= max {
+                    let clip = [(-axis, half_width - line), (axis, line + half_width)];
+                    add_slab(
+                        builder,
+                        &hull,
+                        (0.0, height),
+                        (offset + taper, offset),
+                        &clip,
+                    );
+                    line += spacing;
+                }
+            }
+        }
+        RaftStyle::Pads => {
+            let mut clusters = ArrayCluster::new(roots.len());
+            for (i, (a, a_radius)) in roots.iter().enumerate() {
+                for (j, (b, b_radius)) in roots.iter().enumerate().skip(i + 1) {
+                    let gap = (a - b).magnitude() - a_radius - b_radius - 2.0 * offset;
+                    (gap <= spacing).then(|| clusters.union(i as u32, j as u32));
+                }
+            }
+
+            for cluster in clusters.clusters() {
+                let roots = (cluster.iter())
+                    .map(|&x| roots[x as usize])
+                    .collect::<Vec<_>>();
+                let pad = convex_hull(&footprint(&roots));
+                add_slab(builder, &pad, (0.0, height), (offset + taper, offset), &[]);
+            }
+        }
+    }
+}
+
+/// Points around the edge of each support.
+fn footprint(roots: &[(Vector2<f32>, f32)]) -> Vec<Vector2<f32>> {
+    let mut points = Vec::new();
+    for &(center, radius) in roots {
+        for i in 0..FOOTPRINT_POINTS {
+            let angle = i as f32 / FOOTPRINT_POINTS as f32 * TAU;
+            points.push(center + Vector2::new(angle.cos(), angle.sin()) * radius);
+        }
+    }
+
+    points
+}
+
+/// The x extent of the hull, after being offset.
+fn x_range(hull: &[Vector2<f32>], offset: f32) -> (f32, f32) {
+    let xs = offset_polygon(hull, offset).into_iter().map(|x| x.x);
+    xs.fold((f32::MAX, f32::MIN), |(min, max), x| {
+        (min.min(x), max.max(x))
+    })
+}
+
+/// Splits the range into strips, leaving a gap of `width` every `spacing`.
+fn strips(min: f32, max: f32, spacing: f32, width: f32) -> Vec<(f32, f32)> {
+    let mut out = Vec::new();
+    let mut start = min;
+    let mut channel = (min / spacing).ceil() * spacing;
+    while start < max {
+        let end = (channel - width / 2.0).min(max);
+        (end > start).then(|| out.push((start, end)));
+        start = channel + width / 2.0;
+        channel += spacing;
+    }
+
+    out
+}
+
+/// Adds a slab between the heights in `z`, whose outline is the hull grown by
+/// the offset for the bottom and top respectively, cut down to `clip`.
+fn add_slab(
+    builder: &mut MeshBuilder,
+    hull: &[Vector2<f32>],
+    z: (f32, f32),
+    offset: (f32, f32),
+    clip: &Clip,
+) {
+    let bottom = clip_polygon(offset_polygon(hull, offset.0), clip);
+    let top = clip_polygon(offset_polygon(hull, offset.1), clip);
+    if bottom.len() >= 3 && top.len() >= 3 {
+        add_prismatoid(builder, (&bottom, z.0), (&top, z.1));
+    }
+}
+
+/// Grows a counterclockwise convex polygon by moving each vertex out along
+/// the bisector of its edges.
+fn offset_polygon(hull: &[Vector2<f32>], offset: f32) -> Vec<Vector2<f32>> {
+    (0..hull.len())
+        .map(|i| {
+            let point = hull[i];
+            let next = hull[(i + 1) % hull.len()];
+            let prev = hull[(i + hull.len() - 1) % hull.len()];
+
+            let (edge_1, edge_2) = ((next - point).normalize(), (point - prev).normalize());
+            let normal =
+                (Vector2::new(edge_1.y, -edge_1.x) + Vector2::new(edge_2.y, -edge_2.x)).normalize();
+            // Scale so the edges themselves move out by `offset`.
+            let scale = normal.dot(&Vector2::new(edge_1.y, -edge_1.x)).max(0.1);
+            point + normal * offset / scale
+        })
+        .collect()
+}
+
+/// Clips a convex polygon to a set of half-planes.
+fn clip_polygon(mut polygon: Vec<Vector2<f32>>, clip: &Clip) -> Vec<Vector2<f32>> {
+    for &(normal, distance) in clip {
+        let inside = |x: &Vector2<f32>| normal.dot(x) <= distance;
+
+        let mut out = Vec::new();
+        for i in 0..polygon.len() {
+            let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+            inside(&a).then(|| out.push(a));
+            if inside(&a) != inside(&b) {
+                let t = (distance - normal.dot(&a)) / normal.dot(&(b - a));
+                out.push(a + (b - a) * t);
+            }
+        }
+
+        polygon = out;
+    }
+
+    // Clipping through a vertex can leave duplicate points
+    polygon.dedup_by(|a, b| (*a - *b).magnitude() < 1e-5);
+    while polygon.len() > 1 && (polygon[0] - polygon[polygon.len() - 1]).magnitude() < 1e-5 {
+        polygon.pop();
+    }
+
+    polygon
+}
+
+/// Adds the convex hull of two counterclockwise convex polygons at different
+/// heights. The sides are found by walking around both polygons at once,
+/// always taking the edge that turns the least next.
+fn add_prismatoid(
+    builder: &mut MeshBuilder,
+    (bottom, bottom_z): (&[Vector2<f32>], f32),
+    (top, top_z): (&[Vector2<f32>], f32),
+) {
+    // Start both walks at the lowest (then leftmost) point, so edge angles
+    // only increase from zero as we go around.
+    let start = |polygon: &[Vector2<f32>]| {
+        (0..polygon.len())
+            .min_by(|&a, &b| {
+                let (a, b) = (polygon[a], polygon[b]);
+                a.y.total_cmp(&b.y).then(a.x.total_cmp(&b.x))
+            })
+            .unwrap()
+    };
+
+    let add = |builder: &mut MeshBuilder, polygon: &[Vector2<f32>], z: f32| {
+        let first = builder.next_idx();
+        polygon
+            .iter()
+            .for_each(|x| _ = builder.add_vertex(x.push(z)));
+        first
+    };
+
+    let (bottom_start, top_start) = (start(bottom), start(top));
+    let bottom_idx = add(builder, bottom, bottom_z);
+    let top_idx = add(builder, top, top_z);
+
+    let (n, m) = (bottom.len(), top.len());
+    let vertex = |idx: u32, start: usize, len: usize, i: usize| idx + ((start + i) % len) as u32;
+    let angle = |polygon: &[Vector2<f32>], start: usize, i: usize| {
+        let len = polygon.len();
+        let edge = polygon[(start + i + 1) % len] - polygon[(start + i) % len];
+        let angle = edge.y.atan2(edge.x).rem_euclid(TAU);
+        // The closing edge can come out at zero due to rounding
+        if i + 1 == len && angle < PI {
+            TAU
+        } else {
+            angle
+        }
+    };
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        let a = (i < n).then(|| angle(bottom, bottom_start, i));
+        let b = (j < m).then(|| angle(top, top_start, j));
+
+        let (a_i, b_j) = (
+            vertex(bottom_idx, bottom_start, n, i),
+            vertex(top_idx, top_start, m, j),
+        );
+        if b.is_none_or(|b| a.is_some_and(|a| a <= b)) {
+            let a_next = vertex(bottom_idx, bottom_start, n, i + 1);
+            builder.add_face([a_i, a_next, b_j]);
+            i += 1;
+        } else {
+            let b_next = vertex(top_idx, top_start, m, j + 1);
+            builder.add_face([a_i, b_next, b_j]);
+            j += 1;
+        }
+    }
+
+    for k in 1..(n as u32 - 1) {
+        builder.add_face([bottom_idx, bottom_idx + k + 1, bottom_idx + k]);
+    }
+
+    for k in 1..(m as u32 - 1) {
+        builder.add_face([top_idx, top_idx + k, top_idx + k + 1]);
+    }
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            style: RaftStyle::Solid,
+            height: 1.0,
+            offset: 1.0,
+            taper: 0.0,
+            spacing: 5.0,
+            line_width: 1.0,
+        }
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashSet},
+    f32::consts::TAU,
+};
+
+use nalgebra::Vector3;
+use ordered_float::OrderedFloat;
+use slicer::{geometry::bvh::Bvh, mesh::Mesh};
+
+use crate::supports::{
+    SupportConfig,
+    tree::{SupportBrace, SupportTree},
+};
+
+/// Number of directions tried around a node when looking for the next step.
+const DIRECTIONS: usize = 16;
+/// Number of rays around the edge of a support used to check if it can drop
+/// straight down to the bed.
+const DROP_RAYS: usize = 8;
+/// Number of closest columns each column is braced to.
+const BRACE_NEIGHBOURS: usize = 2;
+/// Smallest distance (mm) between braced columns. Each brace rises by the
+/// distance between its columns, so ones at the same spot would never reach
+/// the top.
+const MIN_BRACE_RISE: f32 = 0.1;
+/// Number of points along each trunk segment that are tried when merging.
+const MERGE_SAMPLES: usize = 8;
+/// Steepest allowed branch angle, so every step makes some progress down.
+const MAX_BRANCH_ANGLE: f32 = 80.0;
+/// Number of nodes explored before giving up on routing a support.
+const MAX_SEARCH_NODES: usize = 4096;
+
+/// Routes supports from overhangs down to the bed, avoiding the mesh.
+pub struct SupportRouter<'a> {
+    config: &'a SupportConfig,
+    mesh: &'a Mesh,
+    bvh: &'a Bvh,
+}
+
+/// Where a routed path ends.
+#[derive(Clone, Copy)]
+enum RouteEnd {
+    Bed,
+    /// Merges into an existing node of the tree.
+    Node(usize),
+    /// Merges into the segment between a node and its parent.
+    Segment(usize, Vector3<f32>),
+}
+
+impl<'a> SupportRouter<'a> {
+    pub fn new(config: &'a SupportConfig, mesh: &'a Mesh, bvh: &'a Bvh) -> Self {
+        Self { config, mesh, bvh }
+    }
+
+    /// Finds a path from the contact `point` on the model down to the bed.
+    /// The first segment is the tip, which points along the surface `normal`.
+    /// Returns `None` if the support can't reach the bed without going
+    /// through the model.
+    pub fn route(&self, point: Vector3<f32>, normal: Vector3<f32>) -> Option<Vec<Vector3<f32>>> {
+        let mut path = vec![point];
+        let radius = self.config.support_radius;
+        self.walk(&mut path, normal, radius, None).map(|_| path)
+    }
+
+    /// Routes a support of `radius` into `tree`, merging into a nearby trunk
+    /// when possible. Trunks are thickened to carry the branches merged into
+    /// them. Returns false if no path to the bed could be found.
+    pub fn route_into(
+        &self,
+        tree: &mut SupportTree,
+        point: Vector3<f32>,
+        normal: Vector3<f32>,
+        radius: f32,
+    ) -> bool {
+        let mut path = vec![point];
+        let Some(end) = self.walk(&mut path, normal, radius, Some(tree)) else {
+            return false;
+        };
+
+        let path = (path.into_iter().enumerate())
+            .map(|(i, point)| (point, [radius, self.config.tip_radius][(i == 0) as usize]));
+
+        let junction = match end {
+            RouteEnd::Bed => None,
+            RouteEnd::Node(node) => Some(node),
+            RouteEnd::Segment(child, position) => Some(tree.split(child, position)),
+        };
+
+        tree.add_path(path, junction);
+        tree.thicken(junction, radius, self.config.max_trunk_radius);
+        true
+    }
+
+    /// Adds diagonal braces between neighbouring columns taller than the
+    /// brace height. Braces zigzag up between each pair of columns at 45°,
+    /// skipping any that would pass through the model.
+    pub fn brace(&self, tree: &mut SupportTree) {
+        // The straight drops from the lowest node of each support to the bed
+        let columns = (tree.nodes.iter().enumerate())
+            .filter_map(|(i, node)| {
+                let root = &tree.nodes[node.parent?];
+                (root.parent.is_none()
+                    && node.position.xy() == root.position.xy()
+                    && node.position.z > self.config.brace_height)
+                    .then_some((i, node.position))
+            })
+            .collect::<Vec<_>>();
+
+        let mut pairs = HashSet::new();
+        for (i, (_, a)) in columns.iter().enumerate() {
+            let mut neighbours = (columns.iter().enumerate())
+                .filter(|(j, _)| *j != i)
+                .map(|(j, (_, b))| (j, (a.xy() - b.xy()).magnitude()))
+                .filter(|(_, distance)| {
+                    *distance <= self.config.brace_distance
+                        && *distance >= self.config.brace_radius.max(MIN_BRACE_RISE)
+                })
+                .collect::<Vec<_>>();
+            neighbours.sort_by(|a, b| a.1.total_cmp(&b.1));
+            (neighbours.iter().take(BRACE_NEIGHBOURS))
+                .for_each(|(j, _)| _ = pairs.insert((i.min(*j), i.max(*j))));
+        }
+
+        for (a, b) in pairs {
+            let (top_a, a) = columns[a];
+            let (top_b, b) = columns[b];
+            let rise = (a.xy() - b.xy()).magnitude();
+            let top = a.z.min(b.z);
+
+            let mut z = self.config.raft.height;
+            let mut ends = [(top_a, a), (top_b, b)];
+            while z + rise <= top {
+                let (lower, upper) = (ends[0].1.xy().push(z), ends[1].1.xy().push(z + rise));
+                if !self.blocked(lower, upper) {
+                    let lower = tree.column_node(ends[0].0, z);
+                    let upper = tree.column_node(ends[1].0, z + rise);
+                    tree.braces.push(SupportBrace {
+                        nodes: [lower, upper],
+                        radius: self.config.brace_radius,
+                    });
+                }
+
+                ends.swap(0, 1);
+                z += rise;
+            }
+        }
+    }
+
+    /// Searches for a path from the tip of a support to the bed or into the
+    /// tree. This is an A* search over steps no steeper than the branch angle,
+    /// using the height above the bed as the heuristic. So supports go
+    /// straight down when they can and only wander sideways to get around
+    /// the model.
+    fn walk(
+        &self,
+        path: &mut Vec<Vector3<f32>>,
+        normal: Vector3<f32>,
+        radius: f32,
+        tree: Option<&SupportTree>,
+    ) -> Option<RouteEnd> {
+        let point = path[0];
+        let tip = point + normal * self.config.tip_length;
+        let tip = tip.xy().push(tip.z.max(0.0));
+        let tip_base = point + normal * self.config.tip_radius;
+        if self.blocked(tip_base, tip) {
+            return None;
+        }
+
+        let targets = tree.map(merge_targets).unwrap_or_default();
+
+        // Each node is its position, the node it was reached from, and the
+        // length of the path so far.
+        let mut nodes = vec![(tip, usize::MAX, 0.0)];
+        let mut queue = BinaryHeap::from([(Reverse(OrderedFloat(tip.z)), 0)]);
+        let mut visited = HashSet::from([self.cell(tip)]);
+
+        while let Some((_, index)) = queue.pop() {
+            let (current, _, cost) = nodes[index];
+            let end = (self.merge(&targets, current))
+                .or_else(|| (current.z <= 0.0).then_some(RouteEnd::Bed));
+            let drop = end.is_none() && self.clear_drop(current, radius);
+
+            if let Some(end) = end.or(drop.then_some(RouteEnd::Bed)) {
+                let start = path.len();
+                let mut node = index;
+                while node != usize::MAX {
+                    path.push(nodes[node].0);
+                    node = nodes[node].1;
+                }
+                path[start..].reverse();
+
+                drop.then(|| path.push(current.xy().push(0.0)));
+                return Some(end);
+            }
+
+            if nodes.len() >= MAX_SEARCH_NODES {
+                break;
+            }
+
+            for next in self.steps(current, radius) {
+                if !visited.insert(self.cell(next)) {
+                    continue;
+                }
+
+                let cost = cost + (next - current).magnitude();
+                queue.push((Reverse(OrderedFloat(cost + next.z)), nodes.len()));
+                nodes.push((next, index, cost));
+            }
+        }
+
+        None
+    }
+
+    /// Possible next nodes of a support, that don't go below the bed, too
+    /// close to the model, or through it.
+    fn steps(&self, current: Vector3<f32>, radius: f32) -> impl Iterator<Item = Vector3<f32>> {
+        let length = radius * 2.0;
+        let angle = (self.config.branch_angle.min(MAX_BRANCH_ANGLE)).to_radians();
+
+        let tilts = [0.0, angle / 2.0, angle].into_iter();
+        let directions = tilts.flat_map(|tilt| {
+            let count = if tilt == 0.0 { 1 } else { DIRECTIONS };
+            (0..count).map(move |i| {
+                let azimuth = i as f32 / DIRECTIONS as f32 * TAU;
+                let horizontal = tilt.sin() * Vector3::new(azimuth.cos(), azimuth.sin(), 0.0);
+                horizontal - Vector3::z() * tilt.cos()
+            })
+        });
+
+        directions
+            .map(move |direction| {
+                // Don't step below the bed
+                let drop = (length * -direction.z).min(current.z);
+                let mut next = current + direction * (drop / -direction.z);
+                next.z = current.z - drop;
+                next
+            })
+            .filter(move |&next| self.clearance(next) >= radius && !self.blocked(current, next))
+    }
+
+    /// Finds the closest of the tree's merge `targets` that `current` can
+    /// branch into, without going up, steeper than the branch angle, or
+    /// through the model.
+    fn merge(
+        &self,
+        targets: &[(Vector3<f32>, RouteEnd)],
+        current: Vector3<f32>,
+    ) -> Option<RouteEnd> {
+        let max_slope = (self.config.branch_angle.min(MAX_BRANCH_ANGLE))
+            .to_radians()
+            .tan();
+        let reachable = |target: Vector3<f32>| {
+            let delta = target - current;
+            let distance = delta.magnitude();
+            (delta.z < 0.0
+                && distance <= self.config.merge_distance
+                && delta.xy().magnitude() <= -delta.z * max_slope)
+                .then_some(distance)
+        };
+
+        let mut candidates = (targets.iter())
+            .filter_map(|&(target, end)| reachable(target).map(|x| (x, target, end)))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+        (candidates.into_iter())
+            .find(|(_, target, _)| !self.blocked(current, *target))
+            .map(|x| x.2)
+    }
+
+    /// Grid cell used to avoid searching the same area twice.
+    fn cell(&self, point: Vector3<f32>) -> Vector3<i32> {
+        (point / self.config.support_radius).map(|x| x.round() as i32)
+    }
+
+    fn blocked(&self, a: Vector3<f32>, b: Vector3<f32>) -> bool {
+        self.bvh.intersect_segment(self.mesh, a, b).is_some()
+    }
+
+    /// Checks if a support of `radius` can go straight down from `point` to
+    /// the bed without touching the model.
+    fn clear_drop(&self, point: Vector3<f32>, radius: f32) -> bool {
+        let offsets = (0..DROP_RAYS).map(|i| {
+            let angle = i as f32 / DROP_RAYS as f32 * TAU;
+            Vector3::new(angle.cos(), angle.sin(), 0.0) * radius
+        });
+
+        (offsets.chain([Vector3::zeros()]))
+            .all(|x| (self.bvh.intersect_ray(self.mesh, point + x, -Vector3::z())).is_none())
+    }
+
+    fn clearance(&self, point: Vector3<f32>) -> f32 {
+        (self.bvh.closest(self.mesh, point)).map_or(f32::MAX, |x| x.t)
+    }
+}
+
+/// Points on the tree that new supports can merge into, along with how the
+/// path would join the tree there. These don't change while a support is
+/// being routed, so are only found once per support.
+fn merge_targets(tree: &SupportTree) -> Vec<(Vector3<f32>, RouteEnd)> {
+    // Tips only touch the model, so the contact nodes and the segments
+    // leading to them can't carry any other supports.
+    let mut has_children = vec![false; tree.nodes.len()];
+    (tree.nodes.iter().filter_map(|x| x.parent)).for_each(|x| has_children[x] = true);
+
+    let mut targets = Vec::new();
+    for (i, node) in tree.nodes.iter().enumerate() {
+        let Some(parent) = node.parent.filter(|_| has_children[i]) else {
+            continue;
+        };
+
+        targets.push((node.position, RouteEnd::Node(i)));
+
+        let parent = tree.nodes[parent].position;
+        for j in 1..MERGE_SAMPLES {
+            let target = node.position.lerp(&parent, j as f32 / MERGE_SAMPLES as f32);
+            targets.push((target, RouteEnd::Segment(i, target)));
+        }
+    }
+
+    targets
+}
@@ -0,0 +1,153 @@
+use common::serde::{Deserializer, SerdeExt, Serializer};
+use nalgebra::Vector3;
+
+/// Supports stored as a forest of nodes. Each node connects down to its
+/// parent, so branches that merge into a trunk share the nodes below the
+/// junction. The leaves are the contact points on the model and the roots
+/// rest on the bed.
+#[derive(Clone, Default)]
+pub struct SupportTree {
+    pub nodes: Vec<SupportNode>,
+    /// Cross braces between the columns of the tree.
+    pub braces: Vec<SupportBrace>,
+}
+
+#[derive(Clone)]
+pub struct SupportNode {
+    pub position: Vector3<f32>,
+    pub radius: f32,
+    /// The next node towards the bed, `None` if this node is on the bed.
+    pub parent: Option<usize>,
+}
+
+#[derive(Clone)]
+pub struct SupportBrace {
+    pub nodes: [usize; 2],
+    pub radius: f32,
+}
+
+impl SupportTree {
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Nodes resting on the bed.
+    pub fn roots(&self) -> impl Iterator<Item = &SupportNode> {
+        self.nodes.iter().filter(|x| x.parent.is_none())
+    }
+
+    /// All the segments of the tree, from each node down to its parent.
+    pub fn segments(&self) -> impl Iterator<Item = (&SupportNode, &SupportNode)> {
+        (self.nodes.iter()).filter_map(|x| x.parent.map(|parent| (x, &self.nodes[parent])))
+    }
+
+    /// Adds a chain of nodes, each one connected to the next. The last node
+    /// connects to `parent`, or rests on the bed if that's `None`. Returns the
+    /// index of the first node.
+    pub fn add_path(
+        &mut self,
+        path: impl IntoIterator<Item = (Vector3<f32>, f32)>,
+        parent: Option<usize>,
+    ) -> usize {
+        let start = self.nodes.len();
+        for (position, radius) in path {
+            let index = self.nodes.len();
+            (index > start).then(|| self.nodes[index - 1].parent = Some(index));
+            self.nodes.push(SupportNode {
+                position,
+                radius,
+                parent: None,
+            });
+        }
+
+        self.nodes.last_mut().unwrap().parent = parent;
+        start
+    }
+
+    /// Inserts a node on the segment between `child` and its parent.
+    pub fn split(&mut self, child: usize, position: Vector3<f32>) -> usize {
+        let (lower, upper) = (self.nodes[child].parent, self.nodes[child].radius);
+        let radius = lower.map_or(upper, |x| self.nodes[x].radius).max(upper);
+
+        let index = self.nodes.len();
+        self.nodes.push(SupportNode {
+            position,
+            radius,
+            parent: lower,
+        });
+        self.nodes[child].parent = Some(index);
+        index
+    }
+
+    /// Gets the node at height `z` on the path from `top` down to the bed,
+    /// splitting the segment that passes through it if needed.
+    pub fn column_node(&mut self, top: usize, z: f32) -> usize {
+        let mut node = top;
+        while let Some(parent) = self.nodes[node].parent {
+            let (upper, lower) = (self.nodes[node].position, self.nodes[parent].position);
+            if (upper.z - z).abs() < f32::EPSILON {
+                return node;
+            }
+
+            if lower.z < z {
+                let t = (upper.z - z) / (upper.z - lower.z);
+                return self.split(node, upper.lerp(&lower, t));
+            }
+
+            node = parent;
+        }
+
+        node
+    }
+
+    /// Thickens the trunk below `node` to carry an extra branch of `radius`,
+    /// keeping the cross-sectional area of the branches that merge into it.
+    pub fn thicken(&mut self, mut node: Option<usize>, radius: f32, max_radius: f32) {
+        while let Some(index) = node {
+            let node_ref = &mut self.nodes[index];
+            let combined = node_ref.radius.hypot(radius).min(max_radius);
+            node_ref.radius = node_ref.radius.max(combined);
+            node = node_ref.parent;
+        }
+    }
+
+    pub fn serialize<T: Serializer>(&self, ser: &mut T) {
+        ser.write_u32_be(self.nodes.len() as u32);
+        for node in self.nodes.iter() {
+            node.position.serialize(ser);
+            ser.write_f32_be(node.radius);
+            ser.write_u32_be(node.parent.map_or(u32::MAX, |x| x as u32));
+        }
+
+        ser.write_u32_be(self.braces.len() as u32);
+        for brace in self.braces.iter() {
+            brace.nodes.iter().for_each(|&x| ser.write_u32_be(x as u32));
+            ser.write_f32_be(brace.radius);
+        }
+    }
+
+    pub fn deserialize<T: Deserializer>(des: &mut T, version: u16) -> Self {
+        let nodes = (0..des.read_u32_be())
+            .map(|_| SupportNode {
+                position: Vector3::<f32>::deserialize(des),
+                radius: des.read_f32_be(),
+                parent: Some(des.read_u32_be())
+                    .filter(|&x| x != u32::MAX)
+                    .map(|x| x as usize),
+            })
+            .collect();
+
+        let braces = if version < 15 {
+            Vec::new()
+        } else {
+            (0..des.read_u32_be())
+                .map(|_| SupportBrace {
+                    nodes: [(); 2].map(|_| des.read_u32_be() as usize),
+                    radius: des.read_f32_be(),
+                })
+                .collect()
+        };
+
+        Self { nodes, braces }
+    }
+}